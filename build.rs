@@ -0,0 +1,17 @@
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    println!("cargo::rerun-if-changed=proto/wolo.proto");
+
+    // Use a vendored protoc so that building doesn't depend on one being
+    // installed.
+    let mut config = prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_prost_build::configure()
+        .build_client(false)
+        .build_transport(false)
+        .compile_with_config(config, &["proto/wolo.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -4,10 +4,19 @@ mod error;
 pub use self::error::Error;
 
 mod pinger;
-pub use self::pinger::{Outcome, Pinger, Response};
+pub use self::pinger::{AnyPinger, Family, Outcome, Pinger, Response};
 
 mod buf;
 pub use self::buf::Buffer;
 
+mod pcap;
+pub use self::pcap::{Dump, PcapWriter};
+
+mod packet;
+pub use self::packet::{PacketSocket, Received};
+
+mod simulator;
+pub use self::simulator::Simulator;
+
 pub mod icmp;
 mod ip;
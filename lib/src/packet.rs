@@ -0,0 +1,110 @@
+use core::mem::{size_of, zeroed};
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+
+/// A frame received on a [`PacketSocket`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct Received {
+    /// The length of the frame.
+    pub len: usize,
+    /// The index of the interface the frame was seen on.
+    pub ifindex: u32,
+    /// Whether the frame was sent by this machine.
+    pub outgoing: bool,
+}
+
+/// A link-layer socket which sends and receives whole Ethernet frames on all
+/// interfaces.
+///
+/// Opening one requires `CAP_NET_RAW`.
+pub struct PacketSocket {
+    socket: AsyncFd<OwnedFd>,
+}
+
+impl PacketSocket {
+    /// Open a socket which receives frames of every protocol.
+    pub fn open() -> io::Result<Self> {
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+
+        let socket = unsafe {
+            let fd = libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                protocol.into(),
+            );
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            OwnedFd::from_raw_fd(fd)
+        };
+
+        Ok(Self {
+            socket: AsyncFd::new(socket)?,
+        })
+    }
+
+    /// Receive a frame into the given buffer, which is truncated if it
+    /// doesn't fit.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<Received> {
+        self.socket
+            .async_io(Interest::READABLE, |socket| unsafe {
+                let mut addr = zeroed::<libc::sockaddr_ll>();
+                let mut addr_len = size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+
+                let n = libc::recvfrom(
+                    socket.as_raw_fd(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    0,
+                    (&mut addr as *mut libc::sockaddr_ll).cast(),
+                    &mut addr_len,
+                );
+
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(Received {
+                    len: (n as usize).min(buf.len()),
+                    ifindex: addr.sll_ifindex as u32,
+                    outgoing: addr.sll_pkttype == libc::PACKET_OUTGOING,
+                })
+            })
+            .await
+    }
+
+    /// Send a frame, including its Ethernet header, on the given interface.
+    pub async fn send(&self, ifindex: u32, frame: &[u8]) -> io::Result<()> {
+        self.socket
+            .async_io(Interest::WRITABLE, |socket| unsafe {
+                let mut addr = zeroed::<libc::sockaddr_ll>();
+                addr.sll_family = libc::AF_PACKET as u16;
+                addr.sll_ifindex = ifindex as i32;
+                addr.sll_halen = 6;
+                addr.sll_addr[..6].copy_from_slice(&frame[..6.min(frame.len())]);
+
+                let n = libc::sendto(
+                    socket.as_raw_fd(),
+                    frame.as_ptr().cast(),
+                    frame.len(),
+                    0,
+                    (&addr as *const libc::sockaddr_ll).cast(),
+                    size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+                );
+
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            })
+            .await
+    }
+}
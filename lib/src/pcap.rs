@@ -0,0 +1,172 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Packets are stored as raw IP packets, so that both IPv4 and IPv6 can be
+/// stored in the same file.
+const LINKTYPE_RAW: u32 = 101;
+/// The largest packet stored.
+const SNAPLEN: u32 = 65535;
+const HOP_LIMIT: u8 = 64;
+
+/// A writer for capture files in the pcap format, as read by Wireshark and
+/// tcpdump.
+///
+/// Since ICMP sockets only give access to the ICMP message, an IP header is
+/// synthesized for every packet written.
+pub struct PcapWriter<W> {
+    out: W,
+    record: Vec<u8>,
+}
+
+impl<W> PcapWriter<W>
+where
+    W: Write,
+{
+    /// Construct a new writer, writing the file header to `out`.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+        // Version 2.4.
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        // Timezone offset and timestamp accuracy, which are always zero.
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        out.write_all(&header)?;
+
+        Ok(Self {
+            out,
+            record: Vec::new(),
+        })
+    }
+
+    /// Write an ICMP message sent from `source` to `dest` at the given time.
+    ///
+    /// Mixing address families between `source` and `dest` is not supported,
+    /// and an unspecified source address of the same family as `dest` is used
+    /// instead.
+    pub fn write_icmp(
+        &mut self,
+        time: SystemTime,
+        source: IpAddr,
+        dest: IpAddr,
+        icmp: &[u8],
+    ) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(40 + icmp.len());
+
+        match dest {
+            IpAddr::V4(dest) => {
+                let source = match source {
+                    IpAddr::V4(source) => source,
+                    IpAddr::V6(..) => Ipv4Addr::UNSPECIFIED,
+                };
+
+                ipv4_header(&mut packet, source, dest, icmp.len());
+            }
+            IpAddr::V6(dest) => {
+                let source = match source {
+                    IpAddr::V6(source) => source,
+                    IpAddr::V4(..) => Ipv6Addr::UNSPECIFIED,
+                };
+
+                ipv6_header(&mut packet, source, dest, icmp.len());
+            }
+        }
+
+        packet.extend_from_slice(icmp);
+
+        let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let len = u32::try_from(packet.len()).unwrap_or(u32::MAX);
+        let captured = len.min(SNAPLEN);
+
+        self.record.clear();
+        self.record
+            .extend_from_slice(&(time.as_secs() as u32).to_le_bytes());
+        self.record
+            .extend_from_slice(&time.subsec_micros().to_le_bytes());
+        self.record.extend_from_slice(&captured.to_le_bytes());
+        self.record.extend_from_slice(&len.to_le_bytes());
+        self.record.extend_from_slice(&packet[..captured as usize]);
+
+        // Records are written and flushed whole, so that the file can be read
+        // while packets are still being captured.
+        self.out.write_all(&self.record)?;
+        self.out.flush()
+    }
+}
+
+/// A capture file shared between pingers.
+#[derive(Clone)]
+pub struct Dump {
+    writer: Arc<Mutex<PcapWriter<File>>>,
+}
+
+impl Dump {
+    /// Create a capture file at the given path, truncating it if it exists.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let writer = PcapWriter::new(File::create(path)?)?;
+
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// Write an ICMP message to the capture file, logging any errors.
+    pub(crate) fn write(&self, source: IpAddr, dest: IpAddr, icmp: &[u8]) {
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+
+        if let Err(error) = writer.write_icmp(SystemTime::now(), source, dest, icmp) {
+            tracing::warn!("Writing packet capture: {error}");
+        }
+    }
+}
+
+fn ipv4_header(out: &mut Vec<u8>, source: Ipv4Addr, dest: Ipv4Addr, len: usize) {
+    let start = out.len();
+    let total = u16::try_from(20 + len).unwrap_or(u16::MAX);
+
+    // Version 4 and a header of five words.
+    out.push(0x45);
+    out.push(0);
+    out.extend_from_slice(&total.to_be_bytes());
+    // Identification, flags and fragment offset.
+    out.extend_from_slice(&[0; 4]);
+    out.push(HOP_LIMIT);
+    out.push(libc::IPPROTO_ICMP as u8);
+    // Checksum, filled in below.
+    out.extend_from_slice(&[0; 2]);
+    out.extend_from_slice(&source.octets());
+    out.extend_from_slice(&dest.octets());
+
+    let mut sum = 0u32;
+
+    for word in out[start..].chunks_exact(2) {
+        sum += u32::from(u16::from_be_bytes([word[0], word[1]]));
+    }
+
+    while (sum >> 16) != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    out[start + 10..start + 12].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+}
+
+fn ipv6_header(out: &mut Vec<u8>, source: Ipv6Addr, dest: Ipv6Addr, len: usize) {
+    let len = u16::try_from(len).unwrap_or(u16::MAX);
+
+    // Version 6 with no traffic class or flow label.
+    out.extend_from_slice(&0x6000_0000u32.to_be_bytes());
+    out.extend_from_slice(&len.to_be_bytes());
+    out.push(libc::IPPROTO_ICMPV6 as u8);
+    out.push(HOP_LIMIT);
+    out.extend_from_slice(&source.octets());
+    out.extend_from_slice(&dest.octets());
+}
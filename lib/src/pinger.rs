@@ -17,6 +17,11 @@ use crate::Buffer;
 use crate::error::{Error, ErrorKind};
 use crate::icmp;
 use crate::ip;
+use crate::pcap::Dump;
+
+const V4_UNSPECIFIED: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+const V6_UNSPECIFIED: SocketAddr =
+    SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0));
 
 macro_rules! rt {
     ($e:expr) => {{
@@ -66,6 +71,15 @@ impl Outcome {
             Outcome::V6(ty) => *ty == icmp::v6::Type::ECHO_REPLY,
         }
     }
+
+    /// Returns true if the outcome is an echo request, which is only received
+    /// by listening sockets.
+    pub fn is_echo_request(&self) -> bool {
+        match self {
+            Outcome::V4(ty) => *ty == icmp::v4::Type::ECHO_REQUEST,
+            Outcome::V6(ty) => *ty == icmp::v6::Type::ECHO_REQUEST,
+        }
+    }
 }
 
 impl fmt::Display for Outcome {
@@ -77,30 +91,115 @@ impl fmt::Display for Outcome {
     }
 }
 
+/// An address family pings can be sent over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Family {
+    /// ICMPv4.
+    V4,
+    /// ICMPv6.
+    V6,
+}
+
+impl fmt::Display for Family {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Family::V4 => write!(f, "ICMPv4"),
+            Family::V6 => write!(f, "ICMPv6"),
+        }
+    }
+}
+
+/// Pingers for whichever address families are available, as constructed by
+/// [`Pinger::any`].
+#[non_exhaustive]
+pub struct AnyPinger {
+    /// The ICMPv4 pinger, if it could be constructed.
+    pub v4: Option<Pinger>,
+    /// The ICMPv6 pinger, if it could be constructed.
+    pub v6: Option<Pinger>,
+    /// Why the families which are unavailable couldn't be constructed.
+    pub errors: Vec<(Family, Error)>,
+}
+
+impl AnyPinger {
+    /// The address families which pings can be sent over.
+    pub fn families(&self) -> impl Iterator<Item = Family> {
+        let v4 = self.v4.as_ref().map(|_| Family::V4);
+        let v6 = self.v6.as_ref().map(|_| Family::V6);
+        v4.into_iter().chain(v6)
+    }
+}
+
 /// A helper structure for sending and handling pings.
 pub struct Pinger {
     socket: AsyncFd<OwnedFd>,
     raw_socket: bool,
     seq: AtomicU16,
+    dump: Option<Dump>,
 }
 
 impl Pinger {
     /// Construct a ICMPv4 pinger.
     pub fn v4() -> Result<Self, Error> {
-        Self::_inner(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)))
+        Self::_inner(V4_UNSPECIFIED, false)
     }
 
     /// Construct a ICMPv6 pinger.
     pub fn v6() -> Result<Self, Error> {
-        Self::_inner(SocketAddr::V6(SocketAddrV6::new(
-            Ipv6Addr::UNSPECIFIED,
-            0,
-            0,
-            0,
-        )))
+        Self::_inner(V6_UNSPECIFIED, false)
+    }
+
+    /// Construct pingers for whichever of ICMPv4 and ICMPv6 are available,
+    /// like on kernels without IPv6 support.
+    ///
+    /// This only fails if neither is available, in which case the error of
+    /// ICMPv4 is returned.
+    pub fn any() -> Result<AnyPinger, Error> {
+        let mut errors = Vec::new();
+
+        let v4 = match Self::v4() {
+            Ok(pinger) => Some(pinger),
+            Err(error) => {
+                errors.push((Family::V4, error));
+                None
+            }
+        };
+
+        let v6 = match Self::v6() {
+            Ok(pinger) => Some(pinger),
+            Err(error) => {
+                errors.push((Family::V6, error));
+                None
+            }
+        };
+
+        if v4.is_none() && v6.is_none() {
+            let (_, error) = errors.swap_remove(0);
+            return Err(error);
+        }
+
+        Ok(AnyPinger { v4, v6, errors })
+    }
+
+    /// Construct a ICMPv4 socket which receives every ICMP packet sent to
+    /// this machine, including echo requests from other machines.
+    ///
+    /// This uses a raw socket, which requires `CAP_NET_RAW`.
+    pub fn listen_v4() -> Result<Self, Error> {
+        Self::_inner(V4_UNSPECIFIED, true)
     }
 
-    fn _inner(addr: SocketAddr) -> Result<Self, Error> {
+    /// Construct a ICMPv6 socket which receives every ICMP packet sent to
+    /// this machine, including echo requests from other machines.
+    ///
+    /// This uses a raw socket, which requires `CAP_NET_RAW`.
+    pub fn listen_v6() -> Result<Self, Error> {
+        Self::_inner(V6_UNSPECIFIED, true)
+    }
+
+    fn _inner(addr: SocketAddr, raw_socket: bool) -> Result<Self, Error> {
         let (domain, protocol, level, recv_err, packet_info) = match addr {
             SocketAddr::V4(..) => (
                 libc::AF_INET,
@@ -119,7 +218,13 @@ impl Pinger {
         };
 
         let socket = unsafe {
-            let fd = libc::socket(domain, libc::SOCK_DGRAM, protocol);
+            let ty = if raw_socket {
+                libc::SOCK_RAW
+            } else {
+                libc::SOCK_DGRAM
+            };
+
+            let fd = libc::socket(domain, ty, protocol);
 
             if fd < 0 {
                 return Err(Error::new(ErrorKind::Socket(io::Error::last_os_error())));
@@ -145,11 +250,17 @@ impl Pinger {
 
         Ok(Self {
             socket: AsyncFd::new(socket).map_err(ErrorKind::AsyncFd)?,
-            raw_socket: false,
+            raw_socket,
             seq: AtomicU16::new(0),
+            dump: None,
         })
     }
 
+    /// Write all packets sent and received to the given capture file.
+    pub fn set_dump(&mut self, dump: Dump) {
+        self.dump = Some(dump);
+    }
+
     /// Send a ping.
     ///
     /// To receive the response, call [`recv`].
@@ -176,6 +287,15 @@ impl Pinger {
         buf.extend_from_slice(header.as_bytes());
         buf.extend_from_slice(data);
 
+        if let Some(dump) = &self.dump {
+            // Fill in the checksum the kernel would calculate, so that the
+            // packet isn't flagged as malformed.
+            let mut packet = buf.as_bytes().to_vec();
+            let checksum = icmp::v4::checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+            dump.write(IpAddr::V4(Ipv4Addr::UNSPECIFIED), dest, &packet);
+        }
+
         self.send_to(buf.as_bytes(), dest).await?;
         Ok(sequence)
     }
@@ -192,6 +312,12 @@ impl Pinger {
         buf.extend_from_slice(header.as_bytes());
         buf.extend_from_slice(data);
 
+        // NOTE: The source address isn't known until the kernel picks it, so
+        // the checksum of sent ICMPv6 packets is left as zero.
+        if let Some(dump) = &self.dump {
+            dump.write(IpAddr::V6(Ipv6Addr::UNSPECIFIED), dest, buf.as_bytes());
+        }
+
         self.send_to(buf.as_bytes(), dest).await?;
         Ok(sequence)
     }
@@ -363,11 +489,17 @@ impl Pinger {
         };
 
         if readable {
+            let packet = self.icmp_packet(buf.as_bytes(), source.ip());
+
+            if let Some(dump) = &self.dump {
+                dump.write(source.ip(), dest, packet);
+            }
+
             let checksum = match (&dest, &source) {
                 (IpAddr::V6(dest), SocketAddr::V6(addr)) => {
-                    icmp::v6::checksum(dest, addr.ip(), buf.as_bytes())
+                    icmp::v6::checksum(dest, addr.ip(), packet)
                 }
-                _ => icmp::v4::checksum(buf.as_bytes()),
+                _ => icmp::v4::checksum(packet),
             };
 
             self.decode_response(buf, source.ip(), dest, checksum)
@@ -392,6 +524,21 @@ impl Pinger {
         }
     }
 
+    /// Get the ICMP packet out of received bytes, which on raw ICMPv4 sockets
+    /// are preceded by the IP header.
+    fn icmp_packet<'a>(&self, bytes: &'a [u8], source: IpAddr) -> &'a [u8] {
+        if !self.raw_socket || !source.is_ipv4() {
+            return bytes;
+        }
+
+        let Some(&first) = bytes.first() else {
+            return bytes;
+        };
+
+        let len = usize::from(first & 0x0f) * 4;
+        bytes.get(len..).unwrap_or_default()
+    }
+
     fn decode_response(
         &self,
         buf: &mut Buffer,
@@ -0,0 +1,163 @@
+use core::net::IpAddr;
+use core::time::Duration;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::time::{self, Instant};
+
+use crate::icmp;
+use crate::{Buffer, Outcome, Response};
+
+/// Share of pings to responding hosts which are lost.
+const LOSS_PERCENT: u64 = 2;
+/// Delay before an unreachable response is generated.
+const UNREACHABLE_DELAY: Duration = Duration::from_millis(3);
+
+/// How a simulated address behaves.
+enum Profile {
+    /// Responds with a base round trip time in milliseconds.
+    Up(u64),
+    /// Never responds.
+    Down,
+    /// Responds with destination unreachable.
+    Unreachable,
+}
+
+struct Pending {
+    due: Instant,
+    dest: IpAddr,
+    sequence: u16,
+    data: Vec<u8>,
+    profile: Profile,
+}
+
+/// A stand-in for [`Pinger`] which doesn't send any packets, but generates
+/// plausible responses instead.
+///
+/// Each address is consistently assigned a behavior based on its value, so
+/// that most addresses respond with a stable round trip time while some are
+/// down or unreachable.
+///
+/// [`Pinger`]: crate::Pinger
+pub struct Simulator {
+    pending: Vec<Pending>,
+    seq: u16,
+    state: u64,
+}
+
+impl Simulator {
+    /// Construct a new simulator.
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        Self {
+            pending: Vec::new(),
+            seq: 0,
+            state: seed | 1,
+        }
+    }
+
+    /// Simulate sending a ping.
+    ///
+    /// To receive the response, call [`recv`].
+    ///
+    /// [`recv`]: Self::recv
+    pub fn ping(&mut self, dest: IpAddr, data: &[u8]) -> u16 {
+        let sequence = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+
+        let profile = profile(dest);
+
+        let delay = match profile {
+            Profile::Up(base) => {
+                if self.next() % 100 < LOSS_PERCENT {
+                    return sequence;
+                }
+
+                // Jitter of up to a quarter of the base round trip time.
+                let jitter = self.next() % (base * 250 + 1);
+                Duration::from_micros(base * 1000 + jitter)
+            }
+            Profile::Down => return sequence,
+            Profile::Unreachable => UNREACHABLE_DELAY,
+        };
+
+        self.pending.push(Pending {
+            due: Instant::now() + delay,
+            dest,
+            sequence,
+            data: data.to_vec(),
+            profile,
+        });
+
+        sequence
+    }
+
+    /// Receive the next simulated response, with its payload stored in `buf`.
+    ///
+    /// This is cancel safe.
+    pub async fn recv(&mut self, buf: &mut Buffer) -> Response {
+        let Some(due) = self.pending.iter().map(|p| p.due).min() else {
+            return std::future::pending().await;
+        };
+
+        time::sleep_until(due).await;
+
+        let Some(index) = self.pending.iter().position(|p| p.due == due) else {
+            return std::future::pending().await;
+        };
+
+        let p = self.pending.swap_remove(index);
+
+        let (outcome, code) = match (p.profile, p.dest) {
+            (Profile::Unreachable, IpAddr::V4(..)) => (Outcome::V4(icmp::v4::Type::UNREACHABLE), 1),
+            (Profile::Unreachable, IpAddr::V6(..)) => (Outcome::V6(icmp::v6::Type::UNREACHABLE), 3),
+            (_, IpAddr::V4(..)) => (Outcome::V4(icmp::v4::Type::ECHO_REPLY), 0),
+            (_, IpAddr::V6(..)) => (Outcome::V6(icmp::v6::Type::ECHO_REPLY), 0),
+        };
+
+        buf.clear();
+        buf.extend_from_slice(&p.data);
+
+        Response {
+            outcome,
+            code,
+            source: p.dest,
+            dest: p.dest,
+            identifier: 0,
+            sequence: p.sequence,
+            checksum: 0,
+            expected_checksum: 0,
+        }
+    }
+
+    /// Generate the next pseudo-random number using xorshift.
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+/// Pick the behavior of an address.
+fn profile(address: IpAddr) -> Profile {
+    let octets = match address {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    };
+
+    // FNV-1a, so that addresses behave the same between runs.
+    let hash = octets.iter().fold(0xcbf29ce484222325u64, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+
+    match hash % 10 {
+        0 => Profile::Down,
+        1 => Profile::Unreachable,
+        _ => Profile::Up(1 + (hash >> 8) % 40),
+    }
+}
@@ -19,10 +19,22 @@ A simple networking utility in Rust.
 
 %build
 cargo build --release
+target/release/wolo completions bash > wolo.bash
+target/release/wolo completions zsh > _wolo
+target/release/wolo completions fish > wolo.fish
+target/release/wolo man > wolo.1
 
 %install
 install -Dm755 target/release/wolo %{buildroot}%{_bindir}/wolo
 install -Dm644 rpm/wolo.service %{buildroot}%{_unitdir}/wolo.service
+%{bash_completions_dir}/wolo
+%{zsh_completions_dir}/_wolo
+%{fish_completions_dir}/wolo.fish
+%{_mandir}/man1/wolo.1*
+install -Dm644 wolo.bash %{buildroot}%{bash_completions_dir}/wolo
+install -Dm644 _wolo %{buildroot}%{zsh_completions_dir}/_wolo
+install -Dm644 wolo.fish %{buildroot}%{fish_completions_dir}/wolo.fish
+install -Dm644 wolo.1 %{buildroot}%{_mandir}/man1/wolo.1
 
 %post
 %systemd_post wolo.service
@@ -38,3 +50,7 @@ install -Dm644 rpm/wolo.service %{buildroot}%{_unitdir}/wolo.service
 %license LICENSE-APACHE
 %{_bindir}/wolo
 %{_unitdir}/wolo.service
+%{bash_completions_dir}/wolo
+%{zsh_completions_dir}/_wolo
+%{fish_completions_dir}/wolo.fish
+%{_mandir}/man1/wolo.1*
@@ -0,0 +1,485 @@
+use core::net::{IpAddr, SocketAddr};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::{ConnectInfo, Query, Request, State};
+use axum::http::{HeaderMap, Method, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum_extra::extract::Form;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+
+use crate::audit;
+use crate::auth::Auth;
+use crate::config::{AdminConfig, Diagnostic, PowerAction};
+use crate::embed::Base64;
+use crate::history::History;
+use crate::hosts;
+use crate::ping_loop;
+use crate::power;
+use crate::reload;
+use crate::state;
+use crate::utils::{Templates, millis};
+use crate::{Error, home};
+
+struct S {
+    prefix: &'static str,
+    templates: Templates,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+    reload: reload::State,
+    home: home::HomePage,
+    audit: audit::Log,
+    history: History,
+    auth: Auth,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn router(
+    prefix: &'static str,
+    templates: Templates,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+    reload: reload::State,
+    home: home::Home,
+    audit: audit::Log,
+    history: History,
+    auth: Auth,
+) -> Router {
+    let home = home.build().await;
+
+    Router::new()
+        .route("/", get(entry))
+        .route("/reload", post(request_reload))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/clear", post(clear))
+        .route("/state", get(export_state))
+        .route("/power", post(change_power))
+        .route_layer(middleware::from_fn_with_state(hosts.clone(), self::auth))
+        .with_state(Arc::new(S {
+            prefix,
+            templates,
+            hosts,
+            ping_state,
+            reload,
+            home,
+            audit,
+            history,
+            auth,
+        }))
+}
+
+/// Require the credentials configured in `[admin]`.
+///
+/// The admin page doesn't exist unless a password or users have been
+/// configured.
+async fn auth(
+    State(hosts): State<hosts::State>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let config = hosts.config().await;
+
+    // Once users are configured, access is granted to admins through
+    // `auth::middleware` instead.
+    if !config.users.is_empty() {
+        return Ok(next.run(req).await);
+    }
+
+    let Some(password) = config.admin.password.as_deref() else {
+        return Err(Error::not_found());
+    };
+
+    let username = config
+        .admin
+        .username
+        .as_deref()
+        .unwrap_or(AdminConfig::DEFAULT_USERNAME);
+
+    if !is_authorized(req.headers(), username, password) {
+        return Ok(unauthorized("wolo admin"));
+    }
+
+    // Browsers attach basic credentials to cross-site form submissions, so
+    // only accept actions which originate from wolo itself.
+    if req.method() != Method::GET && is_cross_site(req.headers()) {
+        return Ok((StatusCode::FORBIDDEN, "Forbidden").into_response());
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Response asking for basic credentials for the given realm.
+pub(crate) fn unauthorized(realm: &str) -> Response {
+    let authenticate = format!("Basic realm=\"{realm}\"");
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, authenticate)],
+        "Unauthorized",
+    )
+        .into_response()
+}
+
+/// Test if the request carries the given basic credentials.
+pub(crate) fn is_authorized(headers: &HeaderMap, username: &str, password: &str) -> bool {
+    let Some((u, p)) = credentials(headers) else {
+        return false;
+    };
+
+    // Evaluate both comparisons to not reveal which one failed.
+    let u = constant_time_eq(u.as_bytes(), username.as_bytes());
+    let p = constant_time_eq(p.as_bytes(), password.as_bytes());
+    u & p
+}
+
+/// Get the user name and password of basic credentials in a request.
+pub(crate) fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?;
+    let encoded = value.to_str().ok()?.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_owned(), password.to_owned()))
+}
+
+/// Compare two byte slices in time which only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Test if the request was made from a different site.
+pub(crate) fn is_cross_site(headers: &HeaderMap) -> bool {
+    let Some(site) = headers.get("sec-fetch-site") else {
+        return false;
+    };
+
+    !matches!(site.as_bytes(), b"same-origin" | b"none")
+}
+
+#[derive(Deserialize)]
+struct Admin {
+    #[serde(default)]
+    done: Option<String>,
+}
+
+async fn entry(
+    State(state): State<Arc<S>>,
+    Query(query): Query<Admin>,
+) -> Result<Html<String>, Error> {
+    #[derive(Serialize)]
+    struct Source {
+        kind: String,
+        path: String,
+        entries: usize,
+        invalid: Vec<String>,
+        error: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Scheduler {
+        paused: bool,
+        sent: u64,
+        per_second: String,
+        scheduled: usize,
+        in_flight: usize,
+    }
+
+    #[derive(Serialize)]
+    struct CacheEntry {
+        host: String,
+        addresses: Vec<IpAddr>,
+        errors: Vec<String>,
+        /// Time since the host was resolved in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct AuditEntry {
+        origin: String,
+        action: String,
+        /// Time since the action was taken in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Attempt {
+        origin: IpAddr,
+        user: String,
+        outcome: String,
+        signed_in: bool,
+        /// Time since the attempt was made in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Lockout {
+        origin: IpAddr,
+        /// Time until the address may sign in again in milliseconds.
+        remaining_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Defined {
+        kind: &'static str,
+        value: String,
+        origins: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct HostOrigins {
+        host: String,
+        defined: Vec<Defined>,
+    }
+
+    #[derive(Serialize)]
+    struct Context {
+        hash: Base64,
+        title: String,
+        prefix: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        done: Option<&'static str>,
+        files: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+        sources: Vec<Source>,
+        conflicts: Vec<String>,
+        scheduler: Scheduler,
+        cache: Vec<CacheEntry>,
+        hosts: Vec<HostOrigins>,
+        audit: Vec<AuditEntry>,
+        users: bool,
+        attempts: Vec<Attempt>,
+        lockouts: Vec<Lockout>,
+        /// Actions which change the power state of this machine.
+        power: Vec<String>,
+    }
+
+    let config = state.hosts.config().await;
+    let stats = *state.ping_state.stats.lock().await;
+
+    let sources = state
+        .hosts
+        .sources()
+        .await
+        .into_iter()
+        .map(|s| Source {
+            kind: s.kind.to_string(),
+            path: s.path.display().to_string(),
+            entries: s.entries,
+            invalid: s.invalid,
+            error: s.error,
+        })
+        .collect();
+
+    let hosts = state.hosts.hosts();
+
+    let names = hosts
+        .iter()
+        .map(|h| (h.id, h.names().next().unwrap_or_default().to_owned()))
+        .collect::<HashMap<_, _>>();
+
+    let mut origins = hosts
+        .iter()
+        .map(|h| {
+            let by_name = h.name_origins.iter().map(|(name, origins)| Defined {
+                kind: "Name",
+                value: name.clone(),
+                origins: origins.iter().map(|o| o.to_string()).collect(),
+            });
+
+            let by_mac = h.mac_origins.iter().map(|(mac, origins)| Defined {
+                kind: "MAC",
+                value: mac.to_string(),
+                origins: origins.iter().map(|o| o.to_string()).collect(),
+            });
+
+            HostOrigins {
+                host: h.names().next().unwrap_or_default().to_owned(),
+                defined: by_name.chain(by_mac).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    drop(hosts);
+    origins.sort_by(|a, b| a.host.cmp(&b.host));
+
+    let mut cache = state
+        .ping_state
+        .cache
+        .entries()
+        .await
+        .into_iter()
+        .map(|(id, results, age)| CacheEntry {
+            host: names.get(&id).cloned().unwrap_or_else(|| id.to_string()),
+            addresses: results.addresses.clone(),
+            errors: results
+                .errors
+                .iter()
+                .map(|e| format!("{}: {} ({})", e.name, e.kind.description(), e.error))
+                .collect(),
+            age_ms: millis(age),
+        })
+        .collect::<Vec<_>>();
+
+    cache.sort_by(|a, b| a.host.cmp(&b.host));
+
+    let context = Context {
+        hash: crate::embed::hash(),
+        title: state.home.title.clone().into_owned(),
+        prefix: state.prefix,
+        done: match query.done.as_deref() {
+            Some("reload") => Some("Reload requested"),
+            Some("pause") => Some("Monitoring paused"),
+            Some("resume") => Some("Monitoring resumed"),
+            Some("clear") => Some("Caches cleared"),
+            Some("suspend") => Some("Suspending this machine"),
+            Some("hibernate") => Some("Hibernating this machine"),
+            Some("power-off") => Some("Powering off this machine"),
+            _ => None,
+        },
+        files: config
+            .files
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect(),
+        diagnostics: state.reload.diagnostics().await,
+        sources,
+        conflicts: state.hosts.conflicts().await,
+        scheduler: Scheduler {
+            paused: state.hosts.is_paused(),
+            sent: stats.sent,
+            per_second: format!("{:.2}", stats.per_second),
+            scheduled: stats.scheduled,
+            in_flight: stats.in_flight,
+        },
+        cache,
+        hosts: origins,
+        audit: state
+            .audit
+            .entries()
+            .await
+            .into_iter()
+            .map(|e| AuditEntry {
+                origin: e.origin,
+                action: e.action,
+                age_ms: millis(e.sampled.elapsed()),
+            })
+            .collect(),
+        users: !config.users.is_empty(),
+        power: config.power.actions.iter().map(|a| a.to_string()).collect(),
+        attempts: state
+            .audit
+            .attempts()
+            .await
+            .into_iter()
+            .map(|a| Attempt {
+                origin: a.origin,
+                user: a.user,
+                outcome: a.outcome.to_string(),
+                signed_in: a.outcome == audit::Outcome::SignedIn,
+                age_ms: millis(a.sampled.elapsed()),
+            })
+            .collect(),
+        lockouts: state
+            .auth
+            .lockouts()
+            .await
+            .into_iter()
+            .map(|(origin, remaining)| Lockout {
+                origin,
+                remaining_ms: millis(remaining),
+            })
+            .collect(),
+    };
+
+    let o = state.templates.render("admin.html", context)?;
+    Ok(Html(o))
+}
+
+fn done(state: &S, action: &str) -> Redirect {
+    Redirect::to(&format!("{}?done={action}", state.prefix))
+}
+
+async fn request_reload(State(state): State<Arc<S>>) -> impl IntoResponse {
+    tracing::info!("Reload requested through admin page");
+    state.reload.request();
+    done(&state, "reload")
+}
+
+async fn pause(State(state): State<Arc<S>>) -> impl IntoResponse {
+    tracing::info!("Monitoring paused through admin page");
+    state.hosts.set_paused(true);
+    done(&state, "pause")
+}
+
+async fn resume(State(state): State<Arc<S>>) -> impl IntoResponse {
+    tracing::info!("Monitoring resumed through admin page");
+    state.hosts.set_paused(false);
+    done(&state, "resume")
+}
+
+async fn clear(State(state): State<Arc<S>>) -> impl IntoResponse {
+    tracing::info!("Caches cleared through admin page");
+    state.ping_state.cache.clear().await;
+    done(&state, "clear")
+}
+
+async fn export_state(State(state): State<Arc<S>>) -> Result<impl IntoResponse, Error> {
+    tracing::info!("State exported through admin page");
+    let config = state.hosts.config().await;
+    let archive = state::export(&config, &state.history).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"wolo-state.zip\"",
+            ),
+        ],
+        archive,
+    ))
+}
+
+#[derive(Deserialize)]
+struct ChangePower {
+    action: String,
+}
+
+/// Suspend, hibernate or power off the machine wolo runs on, if the action is
+/// allowed in `[power]`.
+async fn change_power(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(form): Form<ChangePower>,
+) -> Result<Redirect, Error> {
+    let config = state.hosts.config().await;
+
+    let action = match form.action.parse::<PowerAction>() {
+        Ok(action) if config.power.actions.contains(&action) => action,
+        _ => {
+            return Err(Error::bad_request(format_args!(
+                "{}: not an allowed power action",
+                form.action
+            )));
+        }
+    };
+
+    // Recorded first, since the machine might be gone once it's performed.
+    state
+        .audit
+        .record(peer.ip(), format!("Requested {action} of this machine"))
+        .await;
+
+    power::perform(action).await?;
+    Ok(done(&state, &action.to_string()))
+}
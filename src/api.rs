@@ -0,0 +1,985 @@
+use core::fmt::Write;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::Instant;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+use uuid::Uuid;
+
+use crate::Error;
+use crate::audit;
+use crate::config::SloConfig;
+use crate::discovery;
+use crate::events;
+use crate::hosts;
+use crate::interfaces;
+use crate::mac::Mac;
+use crate::neighbors;
+use crate::ping_loop::{self, HostStatus, PowerState};
+use crate::scripting;
+use crate::showcase;
+use crate::slo;
+use crate::utils;
+use crate::vitals;
+use crate::wake_on_lan;
+use crate::wake_queue;
+
+/// The operations of the API, which are shared by the REST handlers and the
+/// gRPC service.
+pub(crate) struct Service {
+    ping_state: ping_loop::State,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+    wake_queue: wake_queue::State,
+    slo: slo::State,
+    events: events::Log,
+    discovery: discovery::State,
+    audit: audit::Log,
+    scripting: scripting::State,
+    started: Instant,
+}
+
+impl Service {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        ping_state: ping_loop::State,
+        hosts: hosts::State,
+        showcase: showcase::Helper,
+        wake_queue: wake_queue::State,
+        slo: slo::State,
+        events: events::Log,
+        discovery: discovery::State,
+        audit: audit::Log,
+        scripting: scripting::State,
+    ) -> Self {
+        Self {
+            ping_state,
+            hosts,
+            showcase,
+            wake_queue,
+            slo,
+            events,
+            discovery,
+            audit,
+            scripting,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// The OpenAPI document of the API, where paths are relative to `/api/v1`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "wolo"),
+    servers((url = "/api/v1")),
+    paths(
+        status,
+        list_hosts,
+        export_hosts,
+        inventory,
+        list_wakes,
+        wake,
+        list_interfaces,
+        list_events,
+        list_suspicious,
+        get_vitals
+    )
+)]
+struct ApiDoc;
+
+/// Routes serving the OpenAPI document at `/api/v1/openapi.json`, and the
+/// Swagger UI for browsing it at `/api/v1/docs`.
+pub(super) fn docs() -> Router {
+    SwaggerUi::new("/api/v1/docs")
+        .url("/api/v1/openapi.json", ApiDoc::openapi())
+        .into()
+}
+
+pub(super) fn router(service: Arc<Service>) -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/hosts", get(list_hosts))
+        .route("/hosts/export", get(export_hosts))
+        .route("/inventory", get(inventory))
+        .route("/wake", get(list_wakes).post(wake))
+        .route("/interfaces", get(list_interfaces))
+        .route("/events", get(list_events))
+        .route("/devices/suspicious", get(list_suspicious))
+        .route("/vitals", get(get_vitals))
+        .with_state(service)
+}
+
+/// Counts of hosts by their status.
+#[derive(Default, Serialize, Deserialize, ToSchema)]
+pub struct HostCounts {
+    pub total: usize,
+    pub up: usize,
+    pub down: usize,
+    pub unknown: usize,
+    /// Hosts which are unreachable because a host they depend on is down.
+    #[serde(default)]
+    pub unreachable: usize,
+}
+
+/// Summary returned by `GET /api/v1/status`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Status {
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub hosts: HostCounts,
+    pub pings_per_second: f64,
+    pub resolver_errors: usize,
+}
+
+/// Summary of the state of wolo.
+#[utoipa::path(get, path = "/status", responses((status = 200, body = Status)))]
+async fn status(State(state): State<Arc<Service>>) -> Json<Status> {
+    let hosts = state.hosts.hosts();
+    let pinged = state.ping_state.pinged.lock().await;
+    let statuses = ping_loop::statuses(&hosts, &pinged);
+
+    let mut counts = HostCounts {
+        total: hosts.len(),
+        ..HostCounts::default()
+    };
+
+    let mut resolver_errors = 0;
+
+    for host in hosts.iter() {
+        match statuses.get(&host.id).map(|s| s.status) {
+            Some(HostStatus::Up) => counts.up += 1,
+            Some(HostStatus::Down) => counts.down += 1,
+            Some(HostStatus::Unreachable) => counts.unreachable += 1,
+            Some(HostStatus::Unknown) | None => counts.unknown += 1,
+        }
+
+        let Some(pinged) = pinged.get(&host.id) else {
+            continue;
+        };
+
+        resolver_errors += pinged
+            .errors
+            .iter()
+            .filter(|e| e.kind.as_host().is_some())
+            .count();
+    }
+
+    drop(pinged);
+
+    let stats = *state.ping_state.stats.lock().await;
+
+    Json(Status {
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        uptime_seconds: state.started.elapsed().as_secs(),
+        hosts: counts,
+        pings_per_second: stats.per_second,
+        resolver_errors,
+    })
+}
+
+/// A host returned by `GET /api/v1/hosts`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Host {
+    pub id: Uuid,
+    pub names: Vec<String>,
+    pub status: HostStatus,
+    /// Estimate of whether the host is sleeping or gone if it isn't up.
+    pub power: PowerState,
+    /// The host which is down, if this host is unreachable because of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency: Option<Uuid>,
+    /// Whether the host is only up because it responds to ARP, while pings
+    /// go unanswered.
+    #[serde(default)]
+    pub arp_only: bool,
+    /// Whether any reply took longer than the configured `slow` threshold.
+    pub slow: bool,
+    /// Whether quiet hours are in effect for the host, during which it's
+    /// pinged less often and alerts should be suppressed.
+    pub quiet: bool,
+    /// Progress of waking the host, if it's being woken.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waking: Option<Waking>,
+    /// Evaluation of the service level objectives of the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slo: Option<Slo>,
+    pub results: Vec<PingResult>,
+}
+
+/// Evaluation of the service level objectives of a host.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Slo {
+    /// Whether any objective is breached.
+    pub breached: bool,
+    /// The window objectives are evaluated over in seconds.
+    pub window_seconds: u64,
+    /// The number of pings in the window.
+    pub samples: u64,
+    /// Share of answered pings as a percentage.
+    pub availability: Option<f64>,
+    pub availability_objective: Option<f64>,
+    pub availability_breached: bool,
+    /// Round trip time at the percentile in milliseconds.
+    pub rtt_ms: Option<f64>,
+    pub rtt_objective_ms: Option<f64>,
+    pub rtt_breached: bool,
+    pub percentile: u8,
+}
+
+/// Progress of waking a host.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Waking {
+    /// The number of attempts which have been made.
+    pub attempt: u32,
+    /// The number of attempts to make before giving up.
+    pub attempts: u32,
+}
+
+/// The most recent ping of an address of a host.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct PingResult {
+    #[schema(value_type = String)]
+    pub target: IpAddr,
+    pub outcome: String,
+    /// Round trip time in milliseconds.
+    pub rtt_ms: f64,
+    /// Round trip time formatted for display.
+    pub rtt: String,
+    pub slow: bool,
+}
+
+/// All configured hosts and their current state.
+#[utoipa::path(get, path = "/hosts", responses((status = 200, body = Vec<Host>)))]
+async fn list_hosts(State(state): State<Arc<Service>>) -> Json<Vec<Host>> {
+    Json(state.hosts().await)
+}
+
+impl Service {
+    /// All configured hosts and their current state.
+    pub(crate) async fn hosts(&self) -> Vec<Host> {
+        let hosts = self.hosts.hosts();
+        let wakes = self.wake_queue.wakes().await;
+        let neighbors = neighbors::read().await;
+        let slo_results = self.slo.results.lock().await;
+        let pinged = self.ping_state.pinged.lock().await;
+        let statuses = ping_loop::statuses(&hosts, &pinged);
+        let mut showcase = self.showcase.lock().await;
+
+        let time = utils::time_of_day();
+        let now = Instant::now();
+        let mut out = Vec::with_capacity(hosts.len());
+
+        for host in hosts.iter() {
+            let pinged = pinged.get(&host.id);
+            let status = statuses.get(&host.id);
+
+            let results = pinged
+                .into_iter()
+                .flat_map(|p| &p.results)
+                .map(|r| PingResult {
+                    target: showcase.ip(host, r.target),
+                    outcome: r.outcome.to_string(),
+                    rtt_ms: utils::millis(r.rtt),
+                    rtt: utils::duration(r.rtt).to_string(),
+                    slow: r.is_slow(host.ping.slow),
+                })
+                .collect::<Vec<_>>();
+
+            out.push(Host {
+                id: host.id,
+                names: host.names().map(|n| showcase.host_name(host, n)).collect(),
+                status: status.map_or(HostStatus::Unknown, |s| s.status),
+                power: PowerState::estimate(
+                    pinged,
+                    neighbors.iter().any(|n| host.macs.contains(&n.mac)),
+                    now,
+                ),
+                dependency: status.and_then(|s| s.dependency),
+                arp_only: pinged.is_some_and(|p| p.is_arp_only()),
+                slow: results.iter().any(|r| r.slow),
+                quiet: host.quiet_hours.contains(time),
+                waking: wakes
+                    .iter()
+                    .find(|w| w.host == Some(host.id))
+                    .map(|w| Waking {
+                        attempt: w.attempt,
+                        attempts: w.attempts,
+                    }),
+                slo: slo_results.get(&host.id).map(|r| Slo {
+                    breached: r.is_breached(),
+                    window_seconds: r.slo.window.unwrap_or(SloConfig::DEFAULT_WINDOW).as_secs(),
+                    samples: r.samples,
+                    availability: r.availability.map(|a| a * 100.0),
+                    availability_objective: r.slo.availability.map(|a| a.ratio() * 100.0),
+                    availability_breached: r.availability_breached,
+                    rtt_ms: r.rtt.map(utils::millis),
+                    rtt_objective_ms: r.slo.rtt.map(utils::millis),
+                    rtt_breached: r.rtt_breached,
+                    percentile: r.slo.percentile(),
+                }),
+                results,
+            });
+        }
+
+        out
+    }
+}
+
+/// Formats hosts can be exported in.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+enum ExportFormat {
+    /// The format of `/etc/hosts`.
+    #[default]
+    Hosts,
+    /// The format of `/etc/ethers`.
+    Ethers,
+    /// Address records of a BIND zone file, to be included in a zone.
+    DnsZone,
+}
+
+/// Parameters of `GET /api/v1/hosts/export`.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// All configured hosts in a format other infrastructure can consume.
+///
+/// Addresses are the ones hosts most recently resolved to or were seen at in
+/// the neighbor table, so hosts without any are left out of `hosts` and
+/// `dns-zone`.
+#[utoipa::path(
+    get,
+    path = "/hosts/export",
+    params(ExportQuery),
+    responses((status = 200, body = String, content_type = "text/plain")),
+)]
+async fn export_hosts(
+    State(state): State<Arc<Service>>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let out = state.export_hosts(query.format).await;
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], out)
+}
+
+impl Service {
+    /// Render all configured hosts in the given format.
+    async fn export_hosts(&self, format: ExportFormat) -> String {
+        let hosts = self.hosts.hosts();
+        let pinged = self.ping_state.pinged.lock().await;
+        let mut showcase = self.showcase.lock().await;
+        let mut out = String::new();
+
+        if let ExportFormat::DnsZone = format {
+            out.push_str("; Address records of the hosts known to wolo.\n");
+        }
+
+        for host in hosts.iter().filter(|h| !h.ignore) {
+            // Names which are addresses only make sense to wolo.
+            let names = host
+                .preferred_name
+                .iter()
+                .chain(
+                    host.names
+                        .iter()
+                        .filter(|n| host.preferred_name.as_ref() != Some(*n)),
+                )
+                .filter(|n| n.parse::<IpAddr>().is_err())
+                .map(|n| showcase.host_name(host, n))
+                .collect::<Vec<_>>();
+
+            let addresses = addresses(host, pinged.get(&host.id), &mut showcase);
+
+            match format {
+                ExportFormat::Hosts => {
+                    if names.is_empty() {
+                        continue;
+                    }
+
+                    for address in &addresses {
+                        _ = writeln!(out, "{address}\t{}", names.join(" "));
+                    }
+                }
+                ExportFormat::Ethers => {
+                    // Ethers entries take either a name or an address.
+                    let Some(name) = names
+                        .first()
+                        .cloned()
+                        .or_else(|| addresses.first().map(|a| a.to_string()))
+                    else {
+                        continue;
+                    };
+
+                    for &mac in &host.macs {
+                        _ = writeln!(out, "{}\t{name}", showcase.mac(host, mac));
+                    }
+                }
+                ExportFormat::DnsZone => {
+                    for name in &names {
+                        // Names with a dot are taken to be fully qualified,
+                        // and others are relative to the origin of the zone.
+                        let dot = if name.contains('.') { "." } else { "" };
+
+                        for address in &addresses {
+                            let kind = if address.is_ipv4() { "A" } else { "AAAA" };
+                            _ = writeln!(out, "{name}{dot}\tIN\t{kind}\t{address}");
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Addresses a host was last pinged or seen at, with IPv4 addresses first.
+fn addresses(
+    host: &hosts::Host,
+    pinged: Option<&ping_loop::Pinged>,
+    showcase: &mut showcase::LockedHelper<'_>,
+) -> Vec<IpAddr> {
+    let mut addresses = pinged
+        .into_iter()
+        .flat_map(|p| {
+            p.results
+                .iter()
+                .map(|r| r.target)
+                .chain(p.neighbors.iter().copied())
+        })
+        .map(|a| showcase.ip(host, a))
+        .collect::<Vec<_>>();
+
+    addresses.sort();
+    addresses.dedup();
+    addresses
+}
+
+/// Variables of a host in the inventory, which are prefixed so that they
+/// don't collide with those of Ansible.
+#[derive(Serialize)]
+struct HostVars {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ansible_host: Option<IpAddr>,
+    wolo_id: Uuid,
+    wolo_names: Vec<String>,
+    wolo_macs: Vec<String>,
+    wolo_tags: Vec<String>,
+    wolo_depends_on: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wolo_kind: Option<String>,
+    wolo_status: HostStatus,
+}
+
+/// All configured hosts as an Ansible dynamic inventory.
+///
+/// Each tag becomes a group, with characters which aren't allowed in group
+/// names replaced by underscores. Hosts are named after their first name
+/// which isn't an address, and `ansible_host` is the first address they were
+/// last pinged or seen at.
+#[utoipa::path(get, path = "/inventory", responses((status = 200, body = Object)))]
+async fn inventory(State(state): State<Arc<Service>>) -> Json<serde_json::Value> {
+    let hosts = state.hosts.hosts();
+    let pinged = state.ping_state.pinged.lock().await;
+    let statuses = ping_loop::statuses(&hosts, &pinged);
+    let mut showcase = state.showcase.lock().await;
+
+    let mut hostvars = BTreeMap::new();
+    let mut groups = BTreeMap::<String, Vec<String>>::new();
+    let mut ungrouped = Vec::new();
+
+    for host in hosts.iter().filter(|h| !h.ignore) {
+        // Names which are addresses are only used if there are no others.
+        let name = host
+            .names()
+            .find(|n| n.parse::<IpAddr>().is_err())
+            .or_else(|| host.names().next());
+
+        let name = match name {
+            Some(name) => showcase.host_name(host, name),
+            None => host.id.to_string(),
+        };
+
+        let addresses = addresses(host, pinged.get(&host.id), &mut showcase);
+
+        let vars = HostVars {
+            ansible_host: addresses.first().copied(),
+            wolo_id: host.id,
+            wolo_names: host
+                .names
+                .iter()
+                .map(|n| showcase.host_name(host, n))
+                .collect(),
+            wolo_macs: host
+                .macs
+                .iter()
+                .map(|&m| showcase.mac(host, m).to_string())
+                .collect(),
+            wolo_tags: host.tags.iter().cloned().collect(),
+            wolo_depends_on: host.depends_on.iter().cloned().collect(),
+            wolo_kind: host.kind().map(|k| k.to_string()),
+            wolo_status: statuses
+                .get(&host.id)
+                .map_or(HostStatus::Unknown, |s| s.status),
+        };
+
+        if host.tags.is_empty() {
+            ungrouped.push(name.clone());
+        }
+
+        for tag in &host.tags {
+            groups
+                .entry(group_name(tag))
+                .or_default()
+                .push(name.clone());
+        }
+
+        hostvars.insert(name, vars);
+    }
+
+    let mut children = groups.keys().cloned().collect::<Vec<_>>();
+    children.push(String::from("ungrouped"));
+
+    let mut out = serde_json::Map::new();
+    out.insert(String::from("_meta"), json!({ "hostvars": hostvars }));
+    out.insert(String::from("all"), json!({ "children": children }));
+    out.insert(String::from("ungrouped"), json!({ "hosts": ungrouped }));
+
+    for (group, hosts) in groups {
+        out.insert(group, json!({ "hosts": hosts }));
+    }
+
+    Json(serde_json::Value::Object(out))
+}
+
+/// Turn a tag into a valid Ansible group name, which may only contain
+/// letters, digits and underscores, and may not start with a digit or be one
+/// of the names which have a meaning in the inventory.
+fn group_name(tag: &str) -> String {
+    let mut name = tag
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || matches!(name.as_str(), "all" | "ungrouped" | "_meta")
+    {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+/// An interface returned by `GET /api/v1/interfaces`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Interface {
+    pub name: String,
+    pub mac: Option<String>,
+    /// The operational state of the link, like `up` or `down`.
+    pub state: String,
+    pub wireless: bool,
+    pub wakeup: Option<bool>,
+    /// Whether the interface is used for discovery and Wake-on-LAN.
+    pub selected: bool,
+    pub addresses: Vec<Address>,
+}
+
+/// An address of an interface.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Address {
+    #[schema(value_type = String)]
+    pub address: IpAddr,
+    pub prefix: u32,
+    #[schema(value_type = Option<String>)]
+    pub broadcast: Option<IpAddr>,
+}
+
+/// Interfaces of the machine wolo runs on.
+#[utoipa::path(get, path = "/interfaces", responses((status = 200, body = Vec<Interface>)))]
+async fn list_interfaces(State(state): State<Arc<Service>>) -> Json<Vec<Interface>> {
+    let config = state.hosts.config().await;
+    let mut showcase = state.showcase.lock().await;
+
+    let interfaces = interfaces::read()
+        .await
+        .into_iter()
+        .map(|i| Interface {
+            selected: interfaces::is_selected(&config.interfaces, &i.name),
+            mac: i.mac.map(|m| showcase.local_mac(m).to_string()),
+            addresses: i
+                .addresses
+                .iter()
+                .map(|a| Address {
+                    address: showcase.local_ip(a.address),
+                    prefix: a.prefix,
+                    broadcast: a.broadcast.map(|b| showcase.local_ip(IpAddr::V4(b))),
+                })
+                .collect(),
+            name: i.name,
+            state: i.state,
+            wireless: i.wireless,
+            wakeup: i.wakeup,
+        })
+        .collect();
+
+    Json(interfaces)
+}
+
+/// Filters of `GET /api/v1/events`.
+#[derive(Deserialize, IntoParams)]
+struct EventFilter {
+    #[serde(default)]
+    host: Option<Uuid>,
+    #[serde(default)]
+    kind: Option<events::Kind>,
+}
+
+/// An event returned by `GET /api/v1/events`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Event {
+    pub kind: events::Kind,
+    /// The host the event concerns, if any.
+    pub host: Option<Uuid>,
+    /// The name of the host the event concerns.
+    pub name: Option<String>,
+    pub message: String,
+    /// Time since the event in milliseconds.
+    pub age_ms: f64,
+}
+
+/// The timeline of events, most recent first.
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(EventFilter),
+    responses((status = 200, body = Vec<Event>)),
+)]
+async fn list_events(
+    State(state): State<Arc<Service>>,
+    Query(filter): Query<EventFilter>,
+) -> Json<Vec<Event>> {
+    let events = state.events.events().await;
+    let hosts = state.hosts.hosts();
+    let mut showcase = state.showcase.lock().await;
+    let now = SystemTime::now();
+
+    let events = events
+        .into_iter()
+        .filter(|e| filter.host.is_none_or(|id| e.host == Some(id)))
+        .filter(|e| filter.kind.is_none_or(|kind| e.kind == kind))
+        .map(|e| {
+            let host = e.host.and_then(|id| hosts.iter().find(|h| h.id == id));
+
+            let (name, message) = match host {
+                Some(host) => (
+                    host.names().next().map(|n| showcase.host_name(host, n)),
+                    e.message,
+                ),
+                None => (None, showcase.text(&e.message)),
+            };
+
+            Event {
+                kind: e.kind,
+                host: e.host,
+                name,
+                message,
+                age_ms: now.duration_since(e.sampled).map_or(0.0, utils::millis),
+            }
+        })
+        .collect();
+
+    Json(events)
+}
+
+/// A device returned by `GET /api/v1/devices/suspicious`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SuspiciousDevice {
+    pub mac: String,
+    /// The address of the device, if it's in the neighbor table.
+    #[schema(value_type = Option<String>)]
+    pub address: Option<IpAddr>,
+    /// The interface the device was seen on, if it's in the neighbor table.
+    pub device: Option<String>,
+    /// Time since the device was marked in milliseconds.
+    pub marked_ms: f64,
+}
+
+/// Devices which have been marked as suspicious.
+#[utoipa::path(
+    get,
+    path = "/devices/suspicious",
+    responses((status = 200, body = Vec<SuspiciousDevice>)),
+)]
+async fn list_suspicious(State(state): State<Arc<Service>>) -> Json<Vec<SuspiciousDevice>> {
+    let suspicious = state.discovery.suspicious.lock().await.clone();
+    let neighbors = neighbors::read().await;
+    let mut showcase = state.showcase.lock().await;
+    let now = SystemTime::now();
+
+    let devices = suspicious
+        .into_iter()
+        .map(|(mac, marked)| {
+            let neighbor = neighbors.iter().find(|n| n.mac == mac);
+
+            SuspiciousDevice {
+                mac: showcase.local_mac(mac).to_string(),
+                address: neighbor.map(|n| showcase.local_ip(IpAddr::V4(n.address))),
+                device: neighbor.map(|n| n.device.clone()),
+                marked_ms: now.duration_since(marked).map_or(0.0, utils::millis),
+            }
+        })
+        .collect();
+
+    Json(devices)
+}
+
+/// A wake in progress returned by `GET /api/v1/wake`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct QueuedWake {
+    /// The host being woken, unless a MAC address is being woken.
+    pub host: Option<Uuid>,
+    pub macs: Vec<String>,
+    pub targets: Vec<String>,
+    /// The number of attempts which have been made.
+    pub attempt: u32,
+    /// The number of attempts to make before giving up.
+    pub attempts: u32,
+    /// Time until the next attempt, or until giving up, in milliseconds.
+    pub next_ms: f64,
+}
+
+/// Wakes in progress.
+#[utoipa::path(get, path = "/wake", responses((status = 200, body = Vec<QueuedWake>)))]
+async fn list_wakes(State(state): State<Arc<Service>>) -> Json<Vec<QueuedWake>> {
+    let wakes = state.wake_queue.wakes().await;
+    let hosts = state.hosts.hosts();
+    let mut showcase = state.showcase.lock().await;
+    let now = Instant::now();
+
+    let wakes = wakes
+        .into_iter()
+        .map(|w| {
+            let host = hosts.iter().find(|h| Some(h.id) == w.host);
+
+            let macs = w
+                .macs
+                .iter()
+                .map(|&m| match host {
+                    Some(host) => showcase.mac(host, m).to_string(),
+                    None => showcase.local_mac(m).to_string(),
+                })
+                .collect();
+
+            QueuedWake {
+                host: w.host,
+                macs,
+                targets: w.targets.iter().map(|t| t.to_string()).collect(),
+                attempt: w.attempt,
+                attempts: w.attempts,
+                next_ms: utils::millis(w.next.saturating_duration_since(now)),
+            }
+        })
+        .collect();
+
+    Json(wakes)
+}
+
+/// Body of `POST /api/v1/wake`.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum WakeRequest {
+    /// Wake a configured host by its id or one of its names.
+    Host { host: String },
+    /// Wake a MAC address, which doesn't need to belong to a configured host.
+    Mac {
+        mac: String,
+        /// Address to send the magic packet to.
+        #[serde(default)]
+        #[schema(value_type = Option<String>)]
+        target: Option<Ipv4Addr>,
+        /// Port to send the magic packet to.
+        #[serde(default)]
+        port: Option<u16>,
+    },
+}
+
+/// Response of `POST /api/v1/wake`.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Woken {
+    /// MAC addresses magic packets were sent for.
+    pub macs: Vec<String>,
+    /// Where magic packets are sent.
+    pub targets: Vec<String>,
+    /// The number of times magic packets are sent until the host responds.
+    pub attempts: u32,
+}
+
+/// Wake a configured host or a MAC address.
+#[utoipa::path(
+    post,
+    path = "/wake",
+    request_body = WakeRequest,
+    responses(
+        (status = 200, body = Woken),
+        (status = 400, description = "Waking is disabled, or the request is invalid"),
+        (status = 403, description = "The instance is read-only, or a script cancelled the wake"),
+        (status = 404, description = "The host doesn't exist"),
+    ),
+)]
+async fn wake(
+    State(state): State<Arc<Service>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(request): Json<WakeRequest>,
+) -> Result<Json<Woken>, Error> {
+    Ok(Json(state.wake(peer.ip(), request).await?))
+}
+
+impl Service {
+    /// Queue a wake on behalf of the given peer.
+    pub(crate) async fn wake(&self, peer: IpAddr, request: WakeRequest) -> Result<Woken, Error> {
+        let hosts = self.hosts.hosts();
+
+        let (host, name, macs, config) = match request {
+            WakeRequest::Host { host } => {
+                let found = hosts.iter().find(|h| {
+                    h.id.to_string() == host
+                        || h.names.contains(&host)
+                        || h.preferred_name.as_deref() == Some(host.as_str())
+                });
+
+                let Some(found) = found else {
+                    return Err(Error::not_found());
+                };
+
+                if found.wake.enabled == Some(false) {
+                    return Err(Error::bad_request(format_args!(
+                        "{host}: waking is disabled for this host"
+                    )));
+                }
+
+                if found.macs.is_empty() {
+                    return Err(Error::bad_request(format_args!(
+                        "{host}: host has no MAC addresses"
+                    )));
+                }
+
+                let name = format!("host `{}`", found.names().next().unwrap_or_default());
+                let macs = found.macs.iter().copied().collect::<Vec<_>>();
+                (Some(found.id), name, macs, found.wake.clone())
+            }
+            WakeRequest::Mac { mac, target, port } => {
+                let Mac(mac) = mac.parse::<Mac>().map_err(Error::bad_request)?;
+                let mut config = wake_on_lan::config_for(&hosts, mac);
+                config.target = target.or(config.target);
+                config.port = port.or(config.port);
+                (None, mac.to_string(), vec![mac], config)
+            }
+        };
+
+        let broadcasts = interfaces::broadcasts(&self.hosts.config().await.interfaces).await;
+        let targets = wake_on_lan::targets(&config, &broadcasts);
+
+        let description = wake_on_lan::describe(&targets);
+
+        let wake = wake_queue::Wake::new(host, name, macs, config, targets);
+
+        if let Err(reason) = self.scripting.before_wake(&wake).await {
+            return Err(Error::forbidden(format!(
+                "Waking {} was cancelled by a script: {reason}",
+                wake.name
+            )));
+        }
+
+        self.audit
+            .record(
+                peer,
+                format!("Woke {} through {description} using the API", wake.name),
+            )
+            .await;
+
+        let woken = Woken {
+            macs: wake.macs.iter().map(|m| m.to_string()).collect(),
+            targets: wake.targets.iter().map(|t| t.to_string()).collect(),
+            attempts: wake.attempts,
+        };
+
+        self.wake_queue.push(wake).await;
+        Ok(woken)
+    }
+}
+
+/// Vitals of the machine wolo runs on, returned by `GET /api/v1/vitals`.
+///
+/// Vitals which can't be read are left out.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Vitals {
+    /// Load averages over 1, 5 and 15 minutes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_total_bytes: Option<u64>,
+    /// Memory available for starting new applications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_available_bytes: Option<u64>,
+    /// Disk usage of the history database, if one is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Storage>,
+    /// Temperature of the warmest thermal zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_celsius: Option<f64>,
+    /// File descriptors open by wolo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_descriptors: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_descriptor_limit: Option<u64>,
+    /// Open file descriptors which are sockets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sockets: Option<usize>,
+}
+
+/// Disk usage of a file and the file system it's stored on.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Storage {
+    pub size_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+}
+
+/// Vitals of the machine wolo runs on, like its load, memory and temperature.
+#[utoipa::path(get, path = "/vitals", responses((status = 200, body = Vitals)))]
+async fn get_vitals(State(state): State<Arc<Service>>) -> Json<Vitals> {
+    let config = state.hosts.config().await;
+    let v = vitals::read(config.history.path.as_deref()).await;
+
+    Json(Vitals {
+        load: v.load.map(Vec::from),
+        memory_total_bytes: v.memory_total,
+        memory_available_bytes: v.memory_available,
+        history: v.history.map(|h| Storage {
+            size_bytes: h.size,
+            available_bytes: h.available,
+            total_bytes: h.total,
+        }),
+        temperature_celsius: v.temperature,
+        file_descriptors: v.file_descriptors,
+        file_descriptor_limit: v.file_descriptor_limit,
+        sockets: v.sockets,
+    })
+}
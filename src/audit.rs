@@ -0,0 +1,133 @@
+//! A log of actions which have been taken through wolo, like waking hosts,
+//! and of attempts to sign in.
+
+use core::fmt;
+use core::net::IpAddr;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// The number of entries kept in the log.
+const CAPACITY: usize = 100;
+
+/// An entry in the audit log.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// When the action was taken.
+    pub sampled: Instant,
+    /// Who took the action, like the address of a client.
+    pub origin: String,
+    /// Description of the action.
+    pub action: String,
+}
+
+/// How an attempt to sign in turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    SignedIn,
+    Failed,
+    /// The attempt failed and caused the address to be locked out.
+    LockedOut,
+}
+
+impl fmt::Display for Outcome {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::SignedIn => write!(f, "signed in"),
+            Outcome::Failed => write!(f, "failed"),
+            Outcome::LockedOut => write!(f, "locked out"),
+        }
+    }
+}
+
+/// An attempt to sign in.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    /// When the attempt was made.
+    pub sampled: Instant,
+    /// The address of the client.
+    pub origin: IpAddr,
+    /// The user name which was given.
+    pub user: String,
+    pub outcome: Outcome,
+}
+
+/// The audit log, which keeps the most recent actions and attempts to sign in
+/// in memory and writes all of them to the log.
+///
+/// Attempts are kept separately so that a flood of them doesn't push out
+/// actions.
+#[derive(Clone)]
+pub struct Log {
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+    attempts: Arc<Mutex<VecDeque<Attempt>>>,
+}
+
+impl Log {
+    /// Construct a new empty audit log.
+    pub fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            attempts: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Record an action taken by the given origin.
+    pub async fn record(&self, origin: impl fmt::Display, action: impl fmt::Display) {
+        let entry = Entry {
+            sampled: Instant::now(),
+            origin: origin.to_string(),
+            action: action.to_string(),
+        };
+
+        tracing::info!(target: "audit", origin = entry.origin, "{}", entry.action);
+        push(&mut *self.entries.lock().await, entry);
+    }
+
+    /// Record an attempt to sign in as the given user.
+    pub async fn record_attempt(&self, origin: IpAddr, user: &str, outcome: Outcome) {
+        let attempt = Attempt {
+            sampled: Instant::now(),
+            origin,
+            user: user.to_owned(),
+            outcome,
+        };
+
+        match outcome {
+            Outcome::SignedIn => {
+                tracing::info!(target: "audit", %origin, "signed in as `{user}`");
+            }
+            Outcome::Failed => {
+                tracing::warn!(target: "audit", %origin, "failed to sign in as `{user}`");
+            }
+            Outcome::LockedOut => {
+                tracing::warn!(target: "audit", %origin, "failed to sign in as `{user}`, locking out");
+            }
+        }
+
+        push(&mut *self.attempts.lock().await, attempt);
+    }
+
+    /// Get the entries in the log, most recent first.
+    pub async fn entries(&self) -> Vec<Entry> {
+        self.entries.lock().await.iter().rev().cloned().collect()
+    }
+
+    /// Get attempts to sign in, most recent first.
+    pub async fn attempts(&self) -> Vec<Attempt> {
+        self.attempts.lock().await.iter().rev().cloned().collect()
+    }
+}
+
+/// Push to a log, removing the oldest item if it's full.
+fn push<T>(log: &mut VecDeque<T>, item: T) {
+    if log.len() == CAPACITY {
+        log.pop_front();
+    }
+
+    log.push_back(item);
+}
@@ -0,0 +1,493 @@
+//! Signing in as one of the users configured in `[users]`.
+//!
+//! Nothing changes unless users are configured. Once they are, every page
+//! requires signing in, either through the login page which keeps a session in
+//! a signed cookie, or with basic credentials which is more convenient for the
+//! API. What a user may do depends on their [`Role`].
+//!
+//! Addresses which fail to sign in too many times are locked out for a while.
+//! Failed attempts and users signing in through the login page are recorded in
+//! the audit log.
+
+use core::net::{IpAddr, SocketAddr};
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHasher, SaltString};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::Router;
+use axum::extract::{ConnectInfo, OriginalUri, Query, Request, State};
+use axum::http::{HeaderMap, Method, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::{Form, SignedCookieJar};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::Error;
+use crate::admin;
+use crate::audit::{self, Outcome};
+use crate::config::{Config, Role};
+use crate::hosts;
+use crate::preferences::Keys;
+use crate::read_only;
+use crate::utils::{Templates, millis};
+
+/// The name of the cookie sessions are stored in.
+const COOKIE: &str = "wolo-session";
+
+/// How long a session lasts before the user has to sign in again.
+const SESSION_LENGTH: Duration = Duration::from_secs(7 * 24 * 3600);
+
+tokio::task_local! {
+    /// The user signed in for the current request.
+    static USER: Option<User>;
+}
+
+/// A signed in user.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct User {
+    pub(crate) name: String,
+    pub(crate) role: Role,
+}
+
+/// What is stored in the session cookie.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    user: String,
+    /// When the session expires in seconds since the Unix epoch.
+    expires: u64,
+}
+
+/// Signing in was refused since the address is locked out.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LockedOut {
+    /// How long until the address may try again.
+    pub(crate) remaining: Duration,
+}
+
+impl IntoResponse for LockedOut {
+    fn into_response(self) -> Response {
+        let retry_after = self.remaining.as_secs().max(1).to_string();
+
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after)],
+            "Too many failed attempts to sign in",
+        )
+            .into_response()
+    }
+}
+
+/// Failed attempts to sign in from an address.
+struct Failures {
+    count: u32,
+    last: Instant,
+}
+
+/// State used to authenticate requests.
+#[derive(Clone)]
+pub(crate) struct Auth {
+    hosts: hosts::State,
+    keys: Keys,
+    audit: audit::Log,
+    failures: Arc<Mutex<HashMap<IpAddr, Failures>>>,
+}
+
+impl Auth {
+    pub(crate) fn new(hosts: hosts::State, audit: audit::Log) -> Self {
+        Self {
+            hosts,
+            keys: Keys::new(),
+            audit,
+            failures: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Whether users are configured, which means that everyone has to sign
+    /// in.
+    pub(crate) async fn is_enabled(&self) -> bool {
+        !self.hosts.config().await.users.is_empty()
+    }
+
+    /// Find the user a request from the given address is made by through its
+    /// session cookie or basic credentials.
+    pub(crate) async fn authenticate(
+        &self,
+        peer: IpAddr,
+        headers: &HeaderMap,
+    ) -> Result<Option<User>, LockedOut> {
+        let config = self.hosts.config().await;
+
+        let jar = SignedCookieJar::from_headers(headers, self.keys.key(&config));
+
+        if let Some(cookie) = jar.get(COOKIE)
+            && let Ok(session) = serde_json::from_str::<Session>(cookie.value())
+            && session.expires > now()
+            && let Some(user) = config.users.get(&session.user)
+        {
+            return Ok(Some(User {
+                name: session.user,
+                role: user.role,
+            }));
+        }
+
+        let Some((username, password)) = admin::credentials(headers) else {
+            return Ok(None);
+        };
+
+        self.sign_in(&config, peer, username, password).await
+    }
+
+    /// Sign in with a user name and password, unless the address is locked
+    /// out.
+    ///
+    /// Failures are recorded in the audit log, but successes are left to the
+    /// caller since basic credentials are verified for every request.
+    async fn sign_in(
+        &self,
+        config: &Config,
+        peer: IpAddr,
+        username: String,
+        password: String,
+    ) -> Result<Option<User>, LockedOut> {
+        // The attempt is reserved before verifying the password, so that
+        // concurrent requests can't make more attempts than allowed.
+        let outcome = self.reserve(config, peer)?;
+
+        if let Some(user) = verify(config, username.clone(), password).await {
+            self.failures.lock().unwrap().remove(&peer);
+            return Ok(Some(user));
+        }
+
+        self.audit.record_attempt(peer, &username, outcome).await;
+        Ok(None)
+    }
+
+    /// Reserve an attempt from the given address, counting it as failed until
+    /// it succeeds.
+    ///
+    /// Returns the outcome of the attempt should it fail, or an error if the
+    /// address is locked out.
+    fn reserve(&self, config: &Config, peer: IpAddr) -> Result<Outcome, LockedOut> {
+        let lockout = config.login.lockout();
+        let max_failures = config.login.max_failures();
+
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, f| f.last.elapsed() < lockout);
+
+        let f = failures.entry(peer).or_insert(Failures {
+            count: 0,
+            last: Instant::now(),
+        });
+
+        if f.count >= max_failures
+            && let Some(remaining) = lockout.checked_sub(f.last.elapsed())
+        {
+            return Err(LockedOut { remaining });
+        }
+
+        f.count += 1;
+        f.last = Instant::now();
+
+        if f.count >= max_failures {
+            Ok(Outcome::LockedOut)
+        } else {
+            Ok(Outcome::Failed)
+        }
+    }
+
+    /// Addresses which are currently locked out and for how long.
+    pub(crate) async fn lockouts(&self) -> Vec<(IpAddr, Duration)> {
+        let config = self.hosts.config().await;
+        let failures = self.failures.lock().unwrap();
+
+        let mut out = failures
+            .iter()
+            .filter(|(_, f)| f.count >= config.login.max_failures())
+            .filter_map(|(&peer, f)| {
+                let remaining = config.login.lockout().checked_sub(f.last.elapsed())?;
+                Some((peer, remaining))
+            })
+            .collect::<Vec<_>>();
+
+        out.sort();
+        out
+    }
+}
+
+/// The user signed in for the current request, if any.
+pub(crate) fn current() -> Option<User> {
+    USER.try_with(Clone::clone).ok().flatten()
+}
+
+/// Require signing in once users are configured, and a role which allows
+/// the request.
+///
+/// Pages under `/admin` require the admin role, while every action requires
+/// the operator role. All actions are submitted with methods other than `GET`
+/// or `HEAD`, so this covers every route without them having to know about
+/// it.
+pub(crate) async fn middleware(
+    State(auth): State<Auth>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    OriginalUri(uri): OriginalUri,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    if !auth.is_enabled().await {
+        return Ok(USER.scope(None, next.run(req)).await);
+    }
+
+    let user = match auth.authenticate(peer.ip(), req.headers()).await {
+        Ok(user) => user,
+        Err(locked_out) => return Ok(locked_out.into_response()),
+    };
+
+    let Some(user) = user else {
+        if *req.method() == Method::GET && accepts_html(req.headers()) {
+            let query = serde_urlencoded::to_string([("next", uri.to_string())])?;
+            return Ok(Redirect::to(&format!("/login?{query}")).into_response());
+        }
+
+        return Ok(admin::unauthorized("wolo"));
+    };
+
+    let safe = !read_only::is_change(req.method(), uri.path());
+
+    let required = if uri.path() == "/admin" || uri.path().starts_with("/admin/") {
+        Role::Admin
+    } else if !safe {
+        Role::Operator
+    } else {
+        Role::Viewer
+    };
+
+    if user.role < required {
+        return Err(Error::forbidden(format_args!(
+            "Signed in as {}, which doesn't have the {required} role",
+            user.name
+        )));
+    }
+
+    // Browsers attach basic credentials to cross-site form submissions, so
+    // only accept actions which originate from wolo itself.
+    if !safe && admin::is_cross_site(req.headers()) {
+        return Err(Error::forbidden("Cross-site requests are not allowed"));
+    }
+
+    req.extensions_mut().insert(user.clone());
+    Ok(USER.scope(Some(user), next.run(req)).await)
+}
+
+struct S {
+    templates: Templates,
+    auth: Auth,
+}
+
+/// Routes of the login and logout pages.
+pub(super) fn router(templates: Templates, auth: Auth) -> Router {
+    Router::new()
+        .route("/login", get(login).post(submit_login))
+        .route("/logout", get(logout).post(submit_logout))
+        .with_state(Arc::new(S { templates, auth }))
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoginContext<'a> {
+    next: &'a str,
+    failed: bool,
+    /// How long until signing in may be attempted again in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked_out_ms: Option<f64>,
+}
+
+async fn login(
+    State(state): State<Arc<S>>,
+    Query(query): Query<LoginQuery>,
+) -> Result<Html<String>, Error> {
+    if !state.auth.is_enabled().await {
+        return Err(Error::not_found());
+    }
+
+    let context = LoginContext {
+        next: next_page(query.next.as_deref()),
+        failed: false,
+        locked_out_ms: None,
+    };
+
+    Ok(Html(state.templates.render("login.html", context)?))
+}
+
+#[derive(Deserialize)]
+struct Login {
+    username: String,
+    password: String,
+    #[serde(default)]
+    next: Option<String>,
+}
+
+async fn submit_login(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(login): Form<Login>,
+) -> Result<Response, Error> {
+    let config = state.auth.hosts.config().await;
+
+    if config.users.is_empty() {
+        return Err(Error::not_found());
+    }
+
+    let next = next_page(login.next.as_deref());
+
+    let result = state
+        .auth
+        .sign_in(&config, peer.ip(), login.username, login.password)
+        .await;
+
+    let (status, locked_out_ms) = match result {
+        Ok(Some(user)) => {
+            state
+                .auth
+                .audit
+                .record_attempt(peer.ip(), &user.name, Outcome::SignedIn)
+                .await;
+
+            let session = Session {
+                user: user.name,
+                expires: now() + SESSION_LENGTH.as_secs(),
+            };
+
+            let cookie = Cookie::build((COOKIE, serde_json::to_string(&session)?))
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .build();
+
+            let jar = SignedCookieJar::new(state.auth.keys.key(&config)).add(cookie);
+            return Ok((jar, Redirect::to(next)).into_response());
+        }
+        Ok(None) => (StatusCode::UNAUTHORIZED, None),
+        Err(locked_out) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Some(millis(locked_out.remaining)),
+        ),
+    };
+
+    let context = LoginContext {
+        next,
+        failed: true,
+        locked_out_ms,
+    };
+
+    let page = state.templates.render("login.html", context)?;
+    Ok((status, Html(page)).into_response())
+}
+
+#[derive(Serialize)]
+struct LogoutContext {
+    signed_out: bool,
+}
+
+async fn logout(State(state): State<Arc<S>>) -> Result<Html<String>, Error> {
+    let context = LogoutContext { signed_out: false };
+    Ok(Html(state.templates.render("logout.html", context)?))
+}
+
+async fn submit_logout(State(state): State<Arc<S>>, headers: HeaderMap) -> Result<Response, Error> {
+    let config = state.auth.hosts.config().await;
+
+    let jar = SignedCookieJar::from_headers(&headers, state.auth.keys.key(&config));
+    let jar = jar.remove(Cookie::build(COOKIE).path("/"));
+
+    let context = LogoutContext { signed_out: true };
+    let page = state.templates.render("logout.html", context)?;
+    Ok((jar, Html(page)).into_response())
+}
+
+/// Verify the password of a user.
+///
+/// Hashing is deliberately slow, so this is done on a blocking thread. A
+/// password is verified even if the user doesn't exist, so that how long it
+/// takes doesn't reveal which users exist.
+async fn verify(config: &Config, username: String, password: String) -> Option<User> {
+    let user = config.users.get(&username);
+
+    let hash = match user {
+        Some(user) => user.password_hash.clone(),
+        None => missing_user_hash().to_owned(),
+    };
+
+    let verified = tokio::task::spawn_blocking(move || {
+        let Ok(hash) = PasswordHash::new(&hash) else {
+            return false;
+        };
+
+        // The comparison of hashes is constant-time.
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+    .await
+    .unwrap_or_default();
+
+    let role = user?.role;
+
+    verified.then_some(User {
+        name: username,
+        role,
+    })
+}
+
+/// A hash which is verified against for users which don't exist.
+fn missing_user_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+
+    HASH.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+
+        Argon2::default()
+            .hash_password(b"", &salt)
+            .map(|hash| hash.to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// Get the page to go to after signing in, which has to be on this site.
+///
+/// Browsers treat both `//` and `/\` as the start of another site.
+fn next_page(next: Option<&str>) -> &str {
+    let Some(next) = next else {
+        return "/";
+    };
+
+    match next.strip_prefix('/') {
+        Some(rest) if !rest.starts_with(['/', '\\']) => next,
+        _ => "/",
+    }
+}
+
+/// Test if the client prefers an HTML page, like a browser does.
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,182 @@
+//! Status badges of hosts as SVG images, which can be embedded in wikis and
+//! READMEs.
+
+use core::fmt::Write;
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+
+use crate::Error;
+use crate::hosts;
+use crate::ping_loop::{self, HostStatus};
+use crate::showcase;
+
+/// How long badges may be cached, which is kept short since they reflect the
+/// current status of a host.
+const CACHE_CONTROL: &str = "public, max-age=30, must-revalidate";
+/// How long badges may be cached once signing in is required, where shared
+/// caches may not store them.
+const CACHE_CONTROL_PRIVATE: &str = "private, max-age=30, must-revalidate";
+
+/// Approximate width of a character in the font used by badges.
+const CHAR_WIDTH: usize = 7;
+/// Horizontal padding on each side of the text of a badge.
+const PADDING: usize = 6;
+
+struct S {
+    ping_state: ping_loop::State,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+}
+
+pub(super) fn router(
+    ping_state: ping_loop::State,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+) -> Router {
+    Router::new()
+        .route("/badge/{file}", get(badge))
+        .with_state(Arc::new(S {
+            ping_state,
+            hosts,
+            showcase,
+        }))
+}
+
+/// Render the badge of a host, which is looked up by one of its names or its
+/// identifier.
+async fn badge(State(state): State<Arc<S>>, Path(file): Path<String>) -> Result<Response, Error> {
+    let Some(name) = file.strip_suffix(".svg") else {
+        return Err(Error::not_found());
+    };
+
+    let hosts = state.hosts.hosts();
+
+    let Some(host) = hosts.iter().find(|h| {
+        h.names.contains(name)
+            || h.preferred_name.as_deref() == Some(name)
+            || h.id.to_string() == name
+    }) else {
+        return Err(Error::not_found());
+    };
+
+    let (status, rtt) = {
+        let pinged = state.ping_state.pinged.lock().await;
+        let statuses = ping_loop::statuses(&hosts, &pinged);
+
+        let status = statuses
+            .get(&host.id)
+            .map_or(HostStatus::Unknown, |s| s.status);
+
+        // The round trip time of the most recent reply.
+        let rtt = pinged
+            .get(&host.id)
+            .into_iter()
+            .flat_map(|p| p.results.iter())
+            .filter(|r| r.outcome.is_echo_reply())
+            .max_by_key(|r| r.sampled)
+            .map(|r| r.rtt);
+
+        (status, rtt)
+    };
+
+    let label = {
+        let mut showcase = state.showcase.lock().await;
+        let name = host.names().next().unwrap_or(name);
+        showcase.host_name(host, name)
+    };
+
+    let (message, color) = match status {
+        HostStatus::Up => match rtt {
+            Some(rtt) => (format!("up {:.1} ms", rtt.as_secs_f64() * 1000.0), "#4c1"),
+            None => (String::from("up"), "#4c1"),
+        },
+        HostStatus::Down => (String::from("down"), "#e05d44"),
+        HostStatus::Unreachable => (String::from("unreachable"), "#fe7d37"),
+        HostStatus::Unknown => (String::from("unknown"), "#9f9f9f"),
+    };
+
+    let svg = render(&label, &message, color);
+
+    let cache_control = if state.hosts.config().await.users.is_empty() {
+        CACHE_CONTROL
+    } else {
+        CACHE_CONTROL_PRIVATE
+    };
+
+    let response = (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        svg,
+    );
+
+    Ok(response.into_response())
+}
+
+/// Render a badge with a label on the left and a colored message on the
+/// right.
+fn render(label: &str, message: &str, color: &str) -> String {
+    let label_width = label.chars().count() * CHAR_WIDTH + PADDING * 2;
+    let message_width = message.chars().count() * CHAR_WIDTH + PADDING * 2;
+    let width = label_width + message_width;
+
+    let label = escape(label);
+    let message = escape(message);
+
+    let mut out = String::new();
+
+    _ = write!(
+        out,
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"##
+    );
+    _ = write!(out, "<title>{label}: {message}</title>");
+    _ = write!(
+        out,
+        r##"<rect width="{label_width}" height="20" fill="#555"/>"##
+    );
+    _ = write!(
+        out,
+        r##"<rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>"##
+    );
+    _ = write!(
+        out,
+        r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">"##
+    );
+    _ = write!(
+        out,
+        r#"<text x="{}" y="14">{label}</text>"#,
+        label_width / 2
+    );
+    _ = write!(
+        out,
+        r#"<text x="{}" y="14">{message}</text>"#,
+        label_width + message_width / 2
+    );
+    out.push_str("</g></svg>");
+    out
+}
+
+/// Escape text for use in SVG.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
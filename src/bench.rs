@@ -0,0 +1,140 @@
+//! A benchmark of the ping scheduler, which drives it with synthetic hosts
+//! and simulated responses and reports how well it keeps up.
+
+use core::net::Ipv4Addr;
+use core::time::Duration;
+
+use std::fs;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use tokio::sync::Mutex;
+use tokio::task;
+use tokio::time::{self, Instant};
+
+use crate::cli;
+use crate::config::{Config, HostConfig, format_duration};
+use crate::history::History;
+use crate::hosts;
+use crate::ping_loop::{self, HostStatus, Transport};
+
+/// How the benchmark is run.
+pub(crate) struct Options {
+    /// Number of synthetic hosts.
+    pub(crate) hosts: u32,
+    /// How long to run for.
+    pub(crate) duration: Duration,
+    /// Ping interval of each host, which uses the default unless specified.
+    pub(crate) interval: Option<Duration>,
+}
+
+/// Run the benchmark, printing statistics every second and a summary at the
+/// end.
+pub(crate) async fn run(options: Options) -> Result<()> {
+    // Addresses are taken from 10.0.0.0/8 without its first address.
+    if options.hosts == 0 || options.hosts >= 1 << 24 {
+        bail!("expected between 1 and {} hosts", (1 << 24) - 1);
+    }
+
+    let mut config = Config::default();
+    config.defaults.ping.interval = options.interval;
+
+    for n in 1..=options.hosts {
+        let addr = Ipv4Addr::from(u32::from(Ipv4Addr::new(10, 0, 0, 0)) + n);
+        config.hosts.push(HostConfig::named(addr.to_string()));
+    }
+
+    let hosts = hosts::State::builder().build(Arc::new(config));
+    let transport = Transport::new(None, true);
+    let state = ping_loop::State::new(&transport);
+
+    let hosts_handle = task::spawn(hosts::spawn(hosts.clone()));
+
+    let mut pinger_handle = task::spawn(ping_loop::new(
+        state.clone(),
+        hosts,
+        History::disabled(),
+        Arc::new(Mutex::new(transport)),
+    ));
+
+    let started = Instant::now();
+    let mut tick = time::interval_at(started + Duration::from_secs(1), Duration::from_secs(1));
+    let mut rows = Vec::new();
+    let mut max_lag = Duration::ZERO;
+
+    while started.elapsed() < options.duration {
+        tokio::select! {
+            _ = tick.tick() => {}
+            result = &mut pinger_handle => {
+                result?.context("pinger")?;
+                bail!("pinger exited");
+            }
+        }
+
+        let stats = *state.stats.lock().await;
+        max_lag = max_lag.max(stats.max_lag);
+
+        rows.push([
+            format!("{}s", started.elapsed().as_secs()),
+            format!("{:.0}", stats.per_second),
+            stats.scheduled.to_string(),
+            stats.in_flight.to_string(),
+            format_duration(stats.lag),
+            format_duration(stats.max_lag),
+            resident_memory().unwrap_or_else(|| String::from("-")),
+        ]);
+    }
+
+    hosts_handle.abort();
+    pinger_handle.abort();
+
+    cli::print_table(
+        [
+            "TIME",
+            "PINGS/S",
+            "SCHEDULED",
+            "IN FLIGHT",
+            "LAG",
+            "MAX LAG",
+            "MEMORY",
+        ],
+        &rows,
+    );
+
+    let elapsed = started.elapsed();
+    let stats = *state.stats.lock().await;
+
+    let (mut up, mut other) = (0, 0);
+
+    for p in state.pinged.lock().await.values() {
+        if p.status() == HostStatus::Up {
+            up += 1;
+        } else {
+            other += 1;
+        }
+    }
+
+    println!();
+    println!("hosts: {} ({up} up, {other} not up)", options.hosts);
+    println!("pings sent: {}", stats.sent);
+    println!(
+        "pings per second: {:.2}",
+        stats.sent as f64 / elapsed.as_secs_f64()
+    );
+    println!("max scheduling lag: {}", format_duration(max_lag));
+
+    if let Some(memory) = resident_memory() {
+        println!("resident memory: {memory}");
+    }
+
+    Ok(())
+}
+
+/// Get the resident memory of the process, which is only available on Linux.
+fn resident_memory() -> Option<String> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find_map(|l| l.strip_prefix("VmRSS:"))?;
+    let kb = line.trim().strip_suffix("kB")?.trim();
+    let kb = kb.parse::<u64>().ok()?;
+    Some(format!("{:.1} MiB", kb as f64 / 1024.0))
+}
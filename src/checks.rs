@@ -0,0 +1,368 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::time::Duration;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{self, TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::config::{CheckConfig, CheckKind, ExtensionConfig};
+use crate::extensions::{self, Input};
+use crate::hosts::{self, Host};
+
+const INTERVAL: Duration = Duration::from_secs(30);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ports web interfaces are usually served on in order of preference, and the
+/// scheme they are served with.
+const WEB_PORTS: [(u16, &str); 3] = [(443, "https"), (80, "http"), (8080, "http")];
+
+/// The result of performing a check.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct CheckResult {
+    /// The configuration of the check.
+    pub check: CheckConfig,
+    /// The outcome of the check.
+    pub outcome: Result<String, String>,
+    /// How long the check took.
+    pub duration: Duration,
+    /// When the check was performed.
+    pub sampled: Instant,
+}
+
+/// State shared between the check task and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// Results of checks indexed by host and the index of the check.
+    pub results: Arc<Mutex<HashMap<Uuid, BTreeMap<usize, CheckResult>>>>,
+}
+
+impl State {
+    /// Construct a new empty state.
+    pub fn new() -> Self {
+        Self {
+            results: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// Spawn the check task.
+pub async fn spawn(state: State, hosts: hosts::State, runner: extensions::Runner) {
+    // When each check was last started.
+    let mut last = HashMap::<(Uuid, usize), Instant>::new();
+    // Checks in flight.
+    let mut tasks = JoinSet::new();
+    let mut tick = time::interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                let now = Instant::now();
+                let paused = hosts.is_paused();
+                let config = hosts.config().await;
+                let hosts = hosts.hosts();
+
+                last.retain(|(id, index), _| {
+                    hosts
+                        .iter()
+                        .any(|h| h.id == *id && *index < h.checks.len())
+                });
+
+                state.results.lock().await.retain(|id, results| {
+                    let Some(host) = hosts.iter().find(|h| h.id == *id) else {
+                        return false;
+                    };
+
+                    // Discard results of checks whose configuration has
+                    // changed, and schedule them to run again right away.
+                    results.retain(|index, result| {
+                        if host.checks.get(*index) == Some(&result.check) {
+                            return true;
+                        }
+
+                        last.remove(&(*id, *index));
+                        false
+                    });
+
+                    !results.is_empty()
+                });
+
+                if paused {
+                    continue;
+                }
+
+                for host in hosts.iter() {
+                    let Some(name) = host.names().next() else {
+                        continue;
+                    };
+
+                    let names = host.names().map(str::to_owned).collect::<Vec<_>>();
+
+                    for (index, check) in host.checks.iter().enumerate() {
+                        let interval = check.interval.unwrap_or(INTERVAL);
+
+                        if let Some(last) = last.get(&(host.id, index))
+                            && now.saturating_duration_since(*last) < interval
+                        {
+                            continue;
+                        }
+
+                        last.insert((host.id, index), now);
+
+                        let id = host.id;
+                        let name = name.to_owned();
+                        let address = host.ping_address;
+                        let names = names.clone();
+                        let check = check.clone();
+                        let runner = runner.clone();
+
+                        let extension = check
+                            .extension
+                            .as_ref()
+                            .and_then(|e| config.extensions.get(e))
+                            .cloned();
+
+                        tasks.spawn(async move {
+                            let timeout = match &extension {
+                                Some(extension) => check.timeout.unwrap_or(extension.timeout()),
+                                None => check.timeout.unwrap_or(TIMEOUT),
+                            };
+
+                            let start = Instant::now();
+
+                            let task = async {
+                                match &extension {
+                                    Some(extension) => exec(&runner, &names, &check, extension).await,
+                                    None => run(&name, address, &check).await,
+                                }
+                            };
+
+                            let outcome = match time::timeout(timeout, task).await {
+                                Ok(Ok(message)) => Ok(message),
+                                Ok(Err(error)) => Err(format!("{error:#}")),
+                                Err(..) => Err(String::from("timeout")),
+                            };
+
+                            let sampled = Instant::now();
+
+                            let result = CheckResult {
+                                check,
+                                outcome,
+                                duration: sampled.saturating_duration_since(start),
+                                sampled,
+                            };
+
+                            (id, index, result)
+                        });
+                    }
+                }
+            }
+            result = tasks.join_next(), if !tasks.is_empty() => {
+                let Some(Ok((id, index, result))) = result else {
+                    continue;
+                };
+
+                state.results.lock().await.entry(id).or_default().insert(index, result);
+            }
+        }
+    }
+}
+
+/// Get the link to the web interface of a host, which unless configured is
+/// detected from tcp or http checks which found a port web interfaces are
+/// usually served on open.
+pub fn web_ui(host: &Host, results: Option<&BTreeMap<usize, CheckResult>>) -> Option<String> {
+    if let Some(url) = &host.display.url {
+        return Some(url.clone());
+    }
+
+    if !host.display.detect_url() {
+        return None;
+    }
+
+    let name = host.names().next()?;
+
+    let is_open = |port| {
+        results.into_iter().flat_map(|r| r.values()).any(|r| {
+            matches!(r.check.kind, CheckKind::Tcp | CheckKind::Http)
+                && r.check.port == port
+                && r.outcome.is_ok()
+        })
+    };
+
+    let (port, scheme) = WEB_PORTS.into_iter().find(|&(port, _)| is_open(port))?;
+
+    match port {
+        80 | 443 => Some(format!("{scheme}://{name}/")),
+        port => Some(format!("{scheme}://{name}:{port}/")),
+    }
+}
+
+/// Run a single check against the given host name, which connects to
+/// `address` instead of what the name resolves to if it's set.
+async fn run(name: &str, address: Option<IpAddr>, check: &CheckConfig) -> Result<String> {
+    match check.kind {
+        CheckKind::Tcp => {
+            connect(name, address, check).await?;
+            Ok(String::from("open"))
+        }
+        CheckKind::Http => {
+            let mut stream = connect(name, address, check).await?;
+
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: {name}\r\nConnection: close\r\nUser-Agent: wolo\r\n\r\n",
+                check.path
+            );
+
+            stream.write_all(request.as_bytes()).await?;
+
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).await?;
+
+            let Some(status) = line.split_ascii_whitespace().nth(1) else {
+                bail!("malformed response");
+            };
+
+            let status = status.parse::<u16>().context("malformed status code")?;
+
+            if !(200..400).contains(&status) {
+                bail!("status {status}");
+            }
+
+            Ok(format!("status {status}"))
+        }
+        CheckKind::Ntp => ntp(name, address, check).await,
+        CheckKind::Exec => match &check.extension {
+            Some(extension) => bail!("unknown extension `{extension}`"),
+            None => bail!("no extension"),
+        },
+    }
+}
+
+/// Run an extension as a check of a host with the given names.
+async fn exec(
+    runner: &extensions::Runner,
+    names: &[String],
+    check: &CheckConfig,
+    extension: &ExtensionConfig,
+) -> Result<String> {
+    let name = check.extension.as_deref().unwrap_or_default();
+
+    let input = Input::Check {
+        host: names.first().map(String::as_str).unwrap_or_default(),
+        names: names.iter().map(String::as_str).collect(),
+        port: (check.port != 0).then_some(check.port),
+    };
+
+    let verdict = runner.check(name, extension, &input).await?;
+    let message = verdict.message.unwrap_or_default();
+
+    if !verdict.ok {
+        if message.is_empty() {
+            bail!("failed");
+        }
+
+        bail!("{message}");
+    }
+
+    if message.is_empty() {
+        return Ok(String::from("ok"));
+    }
+
+    Ok(message)
+}
+
+async fn connect(name: &str, address: Option<IpAddr>, check: &CheckConfig) -> Result<TcpStream> {
+    match address {
+        Some(address) => TcpStream::connect((address, check.port))
+            .await
+            .with_context(|| format!("connecting to {address}:{}", check.port)),
+        None => TcpStream::connect((name, check.port))
+            .await
+            .with_context(|| format!("connecting to {name}:{}", check.port)),
+    }
+}
+
+/// Query an NTP server using SNTP and compare its clock to ours.
+async fn ntp(name: &str, address: Option<IpAddr>, check: &CheckConfig) -> Result<String> {
+    let address = match address {
+        Some(address) => SocketAddr::new(address, check.port),
+        None => match net::lookup_host((name, check.port)).await?.next() {
+            Some(address) => address,
+            None => bail!("{name}: no addresses"),
+        },
+    };
+
+    let bind = match address {
+        SocketAddr::V4(..) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(..) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+
+    let socket = UdpSocket::bind(bind).await?;
+    socket
+        .connect(address)
+        .await
+        .with_context(|| format!("connecting to {address}"))?;
+
+    // Version 4 in client mode, with our transmit timestamp which the server
+    // echoes back as the originate timestamp.
+    let mut request = [0u8; 48];
+    request[0] = 0x23;
+    let sent = ntp_timestamp(SystemTime::now());
+    request[40..].copy_from_slice(&sent.to_be_bytes());
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 48];
+
+    loop {
+        let n = socket.recv(&mut response).await?;
+        let received = SystemTime::now();
+
+        // Responses which don't answer our request are ignored.
+        if n < 48 || response[0] & 0x07 != 4 || response[24..32] != sent.to_be_bytes() {
+            continue;
+        }
+
+        if response[1] == 0 {
+            bail!("server refused the request");
+        }
+
+        let t1 = from_ntp_timestamp(sent);
+        let t2 = from_ntp_timestamp(u64::from_be_bytes(response[32..40].try_into()?));
+        let t3 = from_ntp_timestamp(u64::from_be_bytes(response[40..48].try_into()?));
+        let t4 = from_ntp_timestamp(ntp_timestamp(received));
+
+        // Positive if the clock of the server is ahead of ours.
+        let offset = ((t2 - t1) + (t3 - t4)) / 2.0;
+        let max = check.max_skew().as_secs_f64();
+
+        if offset.abs() > max {
+            bail!("offset {offset:+.3}s exceeds {max}s");
+        }
+
+        return Ok(format!("offset {offset:+.3}s"));
+    }
+}
+
+/// Seconds between the NTP epoch in 1900 and the unix epoch.
+const NTP_EPOCH: u64 = 2_208_988_800;
+
+fn ntp_timestamp(time: SystemTime) -> u64 {
+    let d = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = d.as_secs() + NTP_EPOCH;
+    let fraction = (u64::from(d.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Convert an NTP timestamp into seconds since the NTP epoch.
+fn from_ntp_timestamp(timestamp: u64) -> f64 {
+    (timestamp >> 32) as f64 + (timestamp & 0xffff_ffff) as f64 / 4_294_967_296.0
+}
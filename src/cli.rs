@@ -0,0 +1,415 @@
+use core::net::{IpAddr, Ipv4Addr};
+use core::str;
+use core::time::Duration;
+
+use std::collections::BTreeSet;
+use std::io;
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHasher, SaltString};
+use axum::http::Uri;
+use lib::{Buffer, Pinger};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::{self, JoinSet};
+use tokio::time::{self, Instant};
+
+use crate::api;
+use crate::config::{DnsConfig, WakeConfig, format_duration};
+use crate::embed;
+use crate::host_name_cache;
+use crate::hosts::Host;
+use crate::i18n;
+use crate::mac::Mac;
+use crate::validate;
+use crate::wake_on_lan::{self, BroadcastSocket};
+
+/// Timeout used when pinging from the command line.
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// Timeout used when querying the status of a running instance.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Find a host by one of its names, its preferred name or the address it's
+/// named by.
+fn find_host<'a>(hosts: &'a [Host], name: &str) -> Option<&'a Host> {
+    hosts
+        .iter()
+        .find(|h| h.names.contains(name) || h.names().next() == Some(name))
+}
+
+/// Send magic packets to the given hosts or literal MAC addresses.
+///
+/// Settings in `overrides` take precedence over those configured for each
+/// host, and `broadcasts` are the broadcast addresses of the selected
+/// interfaces.
+pub async fn wake(
+    hosts: &[Host],
+    targets: &[String],
+    overrides: &WakeConfig,
+    broadcasts: &[Ipv4Addr],
+) -> Result<()> {
+    let socket = BroadcastSocket::bind()
+        .await
+        .context("binding broadcast socket")?;
+
+    for target in targets {
+        let (macs, mut config) = if let Some(host) = find_host(hosts, target) {
+            if host.wake.enabled == Some(false) {
+                bail!("{target}: waking is disabled for this host");
+            }
+
+            (host.macs.clone(), host.wake.clone())
+        } else if let Ok(Mac(mac)) = target.parse::<Mac>() {
+            (BTreeSet::from([mac]), wake_on_lan::config_for(hosts, mac))
+        } else {
+            bail!("{target}: no such host, and not a valid MAC address");
+        };
+
+        if macs.is_empty() {
+            bail!("{target}: host has no MAC addresses");
+        }
+
+        config.merge(overrides);
+
+        let addresses = wake_on_lan::targets(&config, broadcasts);
+        let password = if config.password.is_some() {
+            " with SecureOn password"
+        } else {
+            ""
+        };
+
+        for mac in macs {
+            for &to in &addresses {
+                socket
+                    .send(&wake_on_lan::packet(mac, &config), to)
+                    .await
+                    .with_context(|| format!("{target}: sending magic packet to {mac}"))?;
+
+                println!("{target}: sent magic packet for {mac} to {to}{password}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The result of pinging a single address.
+#[derive(Serialize)]
+struct PingRow {
+    host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<IpAddr>,
+    outcome: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rtt_ms: Option<f64>,
+    /// Whether the address responded with an echo reply.
+    #[serde(skip)]
+    ok: bool,
+}
+
+/// Ping all addresses of the given hosts once, printing the results as a
+/// table or as JSON.
+///
+/// Returns `true` if every address responded with an echo reply.
+pub async fn ping(hosts: &[Host], dns: &DnsConfig, names: &[String], json: bool) -> Result<bool> {
+    let mut rows = Vec::new();
+    let mut tasks = JoinSet::new();
+
+    for (index, name) in names.iter().enumerate() {
+        let lookup = match find_host(hosts, name) {
+            Some(host) if host.names.is_empty() => host.names().map(str::to_owned).collect(),
+            Some(host) => host.names.clone(),
+            None => BTreeSet::from([name.to_owned()]),
+        };
+
+        let mut addresses = BTreeSet::new();
+        let mut errors = Vec::new();
+
+        for n in lookup {
+            let dns = dns.clone();
+
+            match task::spawn_blocking(move || (host_name_cache::resolve(&dns, &n), n)).await? {
+                (Ok(found), _) => addresses.extend(found),
+                (Err((_, error)), n) => errors.push(format!("{n}: {error}")),
+            }
+        }
+
+        if addresses.is_empty() {
+            rows.push((
+                index,
+                PingRow {
+                    host: name.clone(),
+                    address: None,
+                    outcome: errors.join(", "),
+                    rtt_ms: None,
+                    ok: false,
+                },
+            ));
+
+            continue;
+        }
+
+        for address in addresses {
+            let name = name.clone();
+
+            tasks.spawn(async move {
+                let (outcome, rtt_ms, ok) = match ping_once(address).await {
+                    Ok(Some((outcome, rtt))) => (
+                        outcome.to_string().trim().to_owned(),
+                        Some(rtt.as_secs_f64() * 1000.0),
+                        outcome.is_echo_reply(),
+                    ),
+                    Ok(None) => (String::from("timeout"), None, false),
+                    Err(error) => (format!("{error:#}"), None, false),
+                };
+
+                let row = PingRow {
+                    host: name,
+                    address: Some(address),
+                    outcome,
+                    rtt_ms,
+                    ok,
+                };
+
+                (index, row)
+            });
+        }
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        rows.push(result?);
+    }
+
+    rows.sort_by(|(a, x), (b, y)| a.cmp(b).then(x.address.cmp(&y.address)));
+    let rows = rows.into_iter().map(|(_, row)| row).collect::<Vec<_>>();
+    let ok = rows.iter().all(|r| r.ok);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(ok);
+    }
+
+    let rows = rows
+        .iter()
+        .map(|r| {
+            [
+                r.host.clone(),
+                r.address.map(|a| a.to_string()).unwrap_or_default(),
+                r.outcome.clone(),
+                r.rtt_ms.map(|ms| format!("{ms:.2}ms")).unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    print_table(["HOST", "ADDRESS", "OUTCOME", "RTT"], &rows);
+    Ok(ok)
+}
+
+/// Send a single ping to the given address and wait for its response.
+async fn ping_once(address: IpAddr) -> Result<Option<(lib::Outcome, Duration)>> {
+    let pinger = match address {
+        IpAddr::V4(..) => Pinger::v4()?,
+        IpAddr::V6(..) => Pinger::v6()?,
+    };
+
+    let id = rand::random::<u64>();
+    let mut buf = Buffer::new();
+
+    let started = Instant::now();
+    pinger.ping(&mut buf, address, &id.to_be_bytes()).await?;
+
+    let deadline = started + PING_TIMEOUT;
+
+    loop {
+        let response = match time::timeout_at(deadline, pinger.recv(&mut buf)).await {
+            Ok(response) => response?,
+            Err(..) => return Ok(None),
+        };
+
+        if buf.read::<[u8; 8]>().ok() != Some(&id.to_be_bytes()) {
+            continue;
+        }
+
+        return Ok(Some((response.outcome, started.elapsed())));
+    }
+}
+
+/// Query the status of a running instance at the given base URL and print a
+/// summary.
+pub async fn status(url: &str) -> Result<()> {
+    let uri = url
+        .parse::<Uri>()
+        .with_context(|| format!("{url}: invalid URL"))?;
+
+    if uri.scheme_str().is_some_and(|s| s != "http") {
+        bail!("{url}: only http URLs are supported");
+    }
+
+    let Some(host) = uri.host() else {
+        bail!("{url}: missing host");
+    };
+
+    let port = uri.port_u16().unwrap_or(80);
+    let path = format!("{}/api/v1/status", uri.path().trim_end_matches('/'));
+
+    let mut stream = time::timeout(STATUS_TIMEOUT, TcpStream::connect((host, port)))
+        .await
+        .with_context(|| format!("connecting to {host}:{port}"))?
+        .with_context(|| format!("connecting to {host}:{port}"))?;
+
+    let request = format!(
+        "GET {path} HTTP/1.0\r\nHost: {host}\r\nAccept: application/json\r\nUser-Agent: wolo\r\n\r\n"
+    );
+
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+
+    time::timeout(STATUS_TIMEOUT, stream.read_to_end(&mut response))
+        .await
+        .context("reading response")??;
+
+    let response = String::from_utf8(response).context("response is not valid UTF-8")?;
+
+    let Some((head, body)) = response.split_once("\r\n\r\n") else {
+        bail!("malformed response");
+    };
+
+    let Some(code) = head.split_ascii_whitespace().nth(1) else {
+        bail!("malformed response");
+    };
+
+    if code != "200" {
+        bail!("{path}: status {code}");
+    }
+
+    let status = serde_json::from_str::<api::Status>(body).context("decoding status")?;
+    let uptime = Duration::from_secs(status.uptime_seconds);
+
+    println!("version: {}", status.version);
+    println!("uptime: {}", format_duration(uptime));
+    println!(
+        "hosts: {} total, {} up, {} down, {} unreachable, {} unknown",
+        status.hosts.total,
+        status.hosts.up,
+        status.hosts.down,
+        status.hosts.unreachable,
+        status.hosts.unknown
+    );
+    println!("pings per second: {:.2}", status.pings_per_second);
+    println!("resolver errors: {}", status.resolver_errors);
+    Ok(())
+}
+
+/// Print a list of all known hosts.
+pub fn list_hosts(hosts: &[Host]) {
+    let rows = hosts
+        .iter()
+        .map(|h| {
+            let name = h.names().next().unwrap_or_default().to_owned();
+            let macs = h.macs.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+            let tags = h.tags.iter().map(String::as_str).collect::<Vec<_>>();
+            [name, macs.join(","), tags.join(",")]
+        })
+        .collect::<Vec<_>>();
+
+    print_table(["NAME", "MACS", "TAGS"], &rows);
+}
+
+/// Hash a password read from stdin and print it in PHC format.
+pub fn hash_password() -> Result<()> {
+    let mut password = String::new();
+    io::stdin()
+        .read_line(&mut password)
+        .context("reading password")?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    if password.is_empty() {
+        bail!("expected a password on stdin");
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|error| anyhow!("hashing password: {error}"))?;
+
+    println!("{hash}");
+    Ok(())
+}
+
+/// Print all embedded assets along with their size and SHA-256 hash.
+pub fn list_assets() {
+    let rows = embed::iter()
+        .filter_map(|name| {
+            let file = embed::get(&name)?;
+            let hash = file
+                .metadata
+                .sha256_hash()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>();
+            Some([name.into_owned(), file.data.len().to_string(), hash])
+        })
+        .collect::<Vec<_>>();
+
+    print_table(["PATH", "SIZE", "SHA256"], &rows);
+}
+
+/// Check that every required asset is embedded, that templates parse and that
+/// translations can be loaded.
+pub fn verify_assets(report: &mut validate::Report) {
+    for name in embed::REQUIRED {
+        if embed::get(name).is_none() {
+            report.error(format_args!("{name}: missing from embedded assets"));
+        }
+    }
+
+    for name in embed::iter() {
+        let Some(file) = embed::get(&name) else {
+            continue;
+        };
+
+        if name.ends_with(".html") {
+            let result = match str::from_utf8(&file.data) {
+                Ok(source) => crate::utils::parse_template(&name, source),
+                Err(error) => Err(error.into()),
+            };
+
+            if let Err(error) = result {
+                report.error(format_args!("{name}: {error:#}"));
+            }
+        } else if name.starts_with("locales/")
+            && name.ends_with(".toml")
+            && let Err(error) = i18n::parse(&file.data)
+        {
+            report.error(format_args!("{name}: {error}"));
+        }
+    }
+}
+
+/// Print rows as a table with aligned columns.
+pub(crate) fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+
+    for row in rows {
+        for (w, column) in widths.iter_mut().zip(row) {
+            *w = (*w).max(column.len());
+        }
+    }
+
+    for row in [header.map(str::to_owned)].iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(column, w)| format!("{column:w$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    }
+}
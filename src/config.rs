@@ -1,15 +1,24 @@
 use core::cell::RefCell;
 use core::fmt::Write;
+use core::ops::Range;
 use core::str::FromStr;
-use core::{fmt, iter};
+use core::time::Duration;
+use core::{fmt, iter, mem};
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
+use argon2::PasswordHash;
 use macaddr::MacAddr6;
-use toml::Value;
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use crate::events;
+use crate::mac::Mac;
 
 trait TakeFlexible
 where
@@ -23,140 +32,2933 @@ where
 /// Loaded configuration file.
 #[derive(Default)]
 pub struct Config {
+    /// Configuration files which have been loaded, in the order they were
+    /// loaded.
+    pub files: Vec<PathBuf>,
     /// Address and port to bind the server to.
     pub bind: Option<String>,
+    /// Reject every request which would change anything.
+    pub read_only: Option<bool>,
     /// Paths to load landing page configuration from.
     pub home: Vec<PathBuf>,
+    /// The locale used for visitors whose preferred languages aren't
+    /// supported.
+    pub locale: Option<String>,
+    /// Interfaces used for discovery and Wake-on-LAN, where empty means all.
+    pub interfaces: Vec<String>,
     /// Loaded hosts.
     pub hosts: Vec<HostConfig>,
     /// Paths to load Mokuro files from.
     pub mokuro: Vec<MokuroConfig>,
+    /// Access to the Mokuro pages.
+    pub mokuro_access: MokuroAccessConfig,
+    /// Settings applied to all hosts.
+    pub defaults: ProfileConfig,
+    /// Named profiles which hosts can refer to through `profile`.
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Settings for the admin page.
+    pub admin: AdminConfig,
+    /// Settings for showcase mode.
+    pub showcase: ShowcaseConfig,
+    /// Settings for the history store.
+    pub history: HistoryConfig,
+    /// Settings for resolving host names.
+    pub dns: DnsConfig,
+    /// Settings for discovering devices.
+    pub discovery: DiscoveryConfig,
+    /// Settings for notifications.
+    pub notify: NotifyConfig,
+    /// Settings for the periodic summary report.
+    pub report: ReportConfig,
+    /// Settings for metrics.
+    pub metrics: MetricsConfig,
+    /// Settings for scripting hooks.
+    pub script: ScriptConfig,
+    /// Settings for the power of the machine wolo runs on.
+    pub power: PowerConfig,
+    /// Settings for restarting background tasks which fail.
+    pub supervisor: SupervisorConfig,
+    /// Settings for the cookies which store preferences and sessions.
+    pub sessions: SessionsConfig,
+    /// Users who can sign in, which requires everyone to sign in if any
+    /// are configured.
+    pub users: BTreeMap<String, UserConfig>,
+    /// Settings for signing in.
+    pub login: LoginConfig,
+    /// Settings for the security headers sent with responses.
+    pub headers: HeadersConfig,
+    /// Named network segments which hosts are grouped into.
+    pub segments: BTreeMap<String, SegmentConfig>,
+    /// External executables which are used as checks or notified about
+    /// events.
+    pub extensions: BTreeMap<String, ExtensionConfig>,
+    /// Ports which wake a host when something connects to them, by the
+    /// address they listen on.
+    pub wake_listeners: BTreeMap<SocketAddr, WakeListenerConfig>,
 }
 
 impl Config {
+    /// Whether requests which would change anything are rejected.
+    pub fn read_only(&self) -> bool {
+        self.read_only.unwrap_or_default()
+    }
+
+    /// Whether any host may be woken on demand.
+    pub fn wakes_on_demand(&self) -> bool {
+        !self.defaults.wake.on_demand.is_empty()
+            || self.profiles.values().any(|p| !p.wake.on_demand.is_empty())
+            || self.hosts.iter().any(|h| !h.wake.on_demand.is_empty())
+    }
+
     /// Push mokuro path.
     pub fn push_mokuro_path(&mut self, path: &Path) {
-        self.mokuro.push(MokuroConfig {
-            path: path.to_owned(),
-        });
+        self.mokuro.push(MokuroConfig::new(path.to_owned()));
+    }
+
+    /// Diagnose problems which can only be detected once all configuration
+    /// files have been loaded.
+    pub fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for host in &self.hosts {
+            if let Some(profile) = &host.profile
+                && !self.profiles.contains_key(profile)
+            {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format_args!("{}: unknown profile `{profile}`", host.label()),
+                ));
+            }
+        }
+
+        let checks = self
+            .hosts
+            .iter()
+            .flat_map(|h| &h.checks)
+            .chain(&self.defaults.checks)
+            .chain(self.profiles.values().flat_map(|p| &p.checks));
+
+        for check in checks {
+            if let Some(extension) = &check.extension
+                && !self.extensions.contains_key(extension)
+            {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    format_args!("unknown extension `{extension}` used by check"),
+                ));
+            }
+        }
+
+        diagnostics
     }
 }
 
-/// Loaded host configuration.
-#[derive(Debug)]
-pub struct HostConfig {
-    /// Loaded host configurations.
-    pub macs: BTreeSet<MacAddr6>,
-    /// Host names.
-    pub names: BTreeSet<String>,
-    /// Preferred host name.
-    pub preferred_name: Option<String>,
-    /// Whether to ignore this host.
-    pub ignore: bool,
+/// Settings which can be shared across hosts, either through `[defaults]`
+/// which apply to all hosts or through named `[profiles.<name>]`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProfileConfig {
+    /// Ping settings.
+    pub ping: PingConfig,
+    /// Wake-on-LAN settings.
+    pub wake: WakeConfig,
+    /// Quiet hours.
+    pub quiet_hours: QuietHoursConfig,
+    /// Service level objectives.
+    pub slo: SloConfig,
+    /// SNMP polling settings.
+    pub snmp: SnmpConfig,
+    /// Throughput probe settings.
+    pub probe: ProbeConfig,
+    /// Checks to perform.
+    pub checks: Vec<CheckConfig>,
+    /// Tags to associate with hosts.
+    pub tags: BTreeSet<String>,
+    /// Names of hosts which hosts depend on to be reachable.
+    pub depends_on: BTreeSet<String>,
+    /// Display settings.
+    pub display: DisplayConfig,
 }
 
-impl TakeFlexible for HostConfig {
-    fn take_table(key: &str, mut parser: Parser<'_>) -> Option<Self> {
+impl ProfileConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
         let out = Self {
-            macs: parser.take_iter("macs"),
-            names: BTreeSet::from([key.to_owned()]),
-            preferred_name: parser.take("preferred_name"),
-            ignore: parser.take_boolean("ignore").unwrap_or(false),
+            display: DisplayConfig::take(&mut parser),
+            ping: parser
+                .take_table("ping", PingConfig::parse)
+                .unwrap_or_default(),
+            wake: WakeConfig::take(&mut parser),
+            quiet_hours: parser
+                .take_table("quiet_hours", QuietHoursConfig::parse)
+                .unwrap_or_default(),
+            slo: parser
+                .take_table("slo", SloConfig::parse)
+                .unwrap_or_default(),
+            snmp: parser
+                .take_table("snmp", SnmpConfig::parse)
+                .unwrap_or_default(),
+            probe: parser
+                .take_table("probe", ProbeConfig::parse)
+                .unwrap_or_default(),
+            checks: parser.take_tables("check", CheckConfig::parse),
+            tags: parser.take_iter("tags"),
+            depends_on: parser.take_iter("depends_on"),
         };
 
         parser.check();
         Some(out)
     }
 
-    fn take_value(parser: Parser<'_>) -> Option<Self> {
-        let names = BTreeSet::from([parser.parse()?]);
+    /// Merge another profile into this one. Settings in `other` take
+    /// precedence, while checks, tags and dependencies are accumulated.
+    pub fn merge(&mut self, other: &ProfileConfig) {
+        self.ping.merge(&other.ping);
+        self.wake.merge(&other.wake);
+        self.quiet_hours.merge(&other.quiet_hours);
+        self.slo.merge(&other.slo);
+        self.snmp.merge(&other.snmp);
+        self.probe.merge(&other.probe);
+        self.checks.extend(other.checks.iter().cloned());
+        self.tags.extend(other.tags.iter().cloned());
+        self.depends_on.extend(other.depends_on.iter().cloned());
+        self.display.merge(&other.display);
+    }
+}
 
-        Some(Self {
-            macs: BTreeSet::new(),
-            names,
-            preferred_name: None,
-            ignore: false,
-        })
+/// The family of an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Test if the given address belongs to this family.
+    pub fn matches(self, address: IpAddr) -> bool {
+        match self {
+            AddressFamily::Ipv4 => address.is_ipv4(),
+            AddressFamily::Ipv6 => address.is_ipv6(),
+        }
     }
 }
 
-/// Loaded mokuro configuration.
-#[derive(Debug)]
-pub struct MokuroConfig {
-    /// Mokuro path.
-    pub path: PathBuf,
+impl fmt::Display for AddressFamily {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "ipv4"),
+            AddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
 }
 
-impl TakeFlexible for MokuroConfig {
-    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+impl FromStr for AddressFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipv4" => Ok(AddressFamily::Ipv4),
+            "ipv6" => Ok(AddressFamily::Ipv6),
+            other => Err(format!(
+                "unknown address family `{other}`, expected ipv4 or ipv6"
+            )),
+        }
+    }
+}
+
+/// The kind of a device, which determines the icon it's displayed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKind {
+    Server,
+    Desktop,
+    Laptop,
+    Phone,
+    Tablet,
+    Printer,
+    Router,
+    Storage,
+    Tv,
+    Camera,
+    Console,
+}
+
+impl HostKind {
+    /// All kinds, in the order they are guessed.
+    const ALL: [Self; 11] = [
+        HostKind::Server,
+        HostKind::Desktop,
+        HostKind::Laptop,
+        HostKind::Phone,
+        HostKind::Tablet,
+        HostKind::Printer,
+        HostKind::Router,
+        HostKind::Storage,
+        HostKind::Tv,
+        HostKind::Camera,
+        HostKind::Console,
+    ];
+
+    /// The icon used for this kind of device.
+    pub fn icon(self) -> &'static str {
+        match self {
+            HostKind::Server => "🗄️",
+            HostKind::Desktop => "🖥️",
+            HostKind::Laptop => "💻",
+            HostKind::Phone => "📱",
+            HostKind::Tablet => "📲",
+            HostKind::Printer => "🖨️",
+            HostKind::Router => "📡",
+            HostKind::Storage => "💾",
+            HostKind::Tv => "📺",
+            HostKind::Camera => "📷",
+            HostKind::Console => "🎮",
+        }
+    }
+
+    /// Words in host names which suggest this kind of device.
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            HostKind::Server => &["server", "srv"],
+            HostKind::Desktop => &["desktop", "pc", "workstation"],
+            HostKind::Laptop => &["laptop", "notebook", "macbook", "thinkpad"],
+            HostKind::Phone => &["phone", "iphone", "android", "pixel"],
+            HostKind::Tablet => &["tablet", "ipad"],
+            HostKind::Printer => &["printer", "laserjet", "officejet"],
+            HostKind::Router => &["router", "gateway", "firewall"],
+            HostKind::Storage => &["nas", "storage", "synology"],
+            HostKind::Tv => &["tv", "chromecast", "roku"],
+            HostKind::Camera => &["camera", "cam", "doorbell"],
+            HostKind::Console => &["playstation", "xbox", "nintendo"],
+        }
+    }
+
+    /// Guess the kind of a device from its name.
+    ///
+    /// A name matches if one of its words is a keyword of the kind, optionally
+    /// followed by a number like in `nas2`.
+    pub fn guess(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+
+        for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+            for kind in Self::ALL {
+                let matches = kind.keywords().iter().any(|keyword| {
+                    word.strip_prefix(keyword)
+                        .is_some_and(|rest| rest.bytes().all(|b| b.is_ascii_digit()))
+                });
+
+                if matches {
+                    return Some(kind);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for HostKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostKind::Server => write!(f, "server"),
+            HostKind::Desktop => write!(f, "desktop"),
+            HostKind::Laptop => write!(f, "laptop"),
+            HostKind::Phone => write!(f, "phone"),
+            HostKind::Tablet => write!(f, "tablet"),
+            HostKind::Printer => write!(f, "printer"),
+            HostKind::Router => write!(f, "router"),
+            HostKind::Storage => write!(f, "storage"),
+            HostKind::Tv => write!(f, "tv"),
+            HostKind::Camera => write!(f, "camera"),
+            HostKind::Console => write!(f, "console"),
+        }
+    }
+}
+
+impl FromStr for HostKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for kind in Self::ALL {
+            if kind.to_string() == s {
+                return Ok(kind);
+            }
+        }
+
+        let expected = Self::ALL.map(|k| k.to_string());
+
+        Err(format!(
+            "unknown host kind `{s}`, expected one of {}",
+            expected.join(", ")
+        ))
+    }
+}
+
+/// Settings for the history store.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistoryConfig {
+    /// Path to the SQLite database ping samples are stored in. History is
+    /// only recorded if this is set.
+    pub path: Option<PathBuf>,
+    /// How long individual samples are kept before they are compacted into
+    /// aggregates.
+    pub raw: Option<Duration>,
+    /// How long aggregates are kept.
+    pub aggregates: Option<Duration>,
+    /// Settings for writing samples to InfluxDB.
+    pub influx: InfluxConfig,
+}
+
+impl HistoryConfig {
+    /// The default time to keep individual samples.
+    pub const DEFAULT_RAW: Duration = Duration::from_secs(7 * 86400);
+
+    /// The default time to keep aggregates.
+    pub const DEFAULT_AGGREGATES: Duration = Duration::from_secs(90 * 86400);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
         let out = Self {
-            path: PathBuf::from(key),
+            path: parser.take("path"),
+            raw: parser.take_duration("raw"),
+            aggregates: parser.take_duration("aggregates"),
+            influx: parser
+                .take_table("influx", InfluxConfig::parse)
+                .unwrap_or_default(),
         };
 
         parser.check();
         Some(out)
     }
 
-    fn take_value(parser: Parser<'_>) -> Option<Self> {
-        Some(Self {
-            path: parser.parse()?,
-        })
+    /// Merge another history configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &HistoryConfig) {
+        self.path = other.path.clone().or(self.path.take());
+        self.raw = other.raw.or(self.raw);
+        self.aggregates = other.aggregates.or(self.aggregates);
+        self.influx.merge(&other.influx);
+    }
+
+    /// How long individual samples are kept.
+    pub fn raw(&self) -> Duration {
+        self.raw.unwrap_or(Self::DEFAULT_RAW)
+    }
+
+    /// How long aggregates are kept.
+    pub fn aggregates(&self) -> Duration {
+        self.aggregates.unwrap_or(Self::DEFAULT_AGGREGATES)
     }
 }
 
-impl Config {
-    pub fn add_host(&mut self, new: HostConfig) {
-        let host = 'found: {
-            for host in &mut self.hosts {
-                if new.names.iter().any(|n| host.names.contains(n)) {
-                    break 'found host;
-                }
+/// Settings for writing ping samples to InfluxDB in its line protocol, in
+/// addition to or instead of the history database.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InfluxConfig {
+    /// The URL of the write endpoint, like
+    /// `http://influx:8086/api/v2/write?org=home&bucket=wolo`. Samples aren't
+    /// written unless this is set.
+    pub url: Option<Webhook>,
+    /// Token sent in the `Authorization` header.
+    pub token: Option<String>,
+    /// The name of the measurement samples are written to.
+    pub measurement: Option<String>,
+}
+
+impl InfluxConfig {
+    /// The default name of the measurement.
+    pub const DEFAULT_MEASUREMENT: &str = "wolo_ping";
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            url: parser.take("url"),
+            token: parser.take("token"),
+            measurement: parser.take("measurement"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another InfluxDB configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &InfluxConfig) {
+        self.url = other.url.clone().or(self.url.take());
+        self.token = other.token.clone().or(self.token.take());
+        self.measurement = other.measurement.clone().or(self.measurement.take());
+    }
+
+    /// The name of the measurement samples are written to.
+    pub fn measurement(&self) -> &str {
+        self.measurement
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_MEASUREMENT)
+    }
+}
+
+/// Settings for resolving host names.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// DNS servers which are queried directly instead of going through the
+    /// system resolver.
+    pub servers: Vec<DnsServer>,
+    /// Domains which are tried for names without a dot.
+    pub search: Vec<String>,
+    /// Timeout for each query.
+    pub timeout: Option<Duration>,
+}
+
+impl DnsConfig {
+    /// The default timeout of each query.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            servers: parser.take_iter("servers"),
+            search: parser.take_iter("search"),
+            timeout: parser.take_duration("timeout"),
+        };
+
+        if out.servers.is_empty() && !out.search.is_empty() {
+            parser.warning_at(
+                "search",
+                "search domains are only used when `servers` are configured",
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another DNS configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &DnsConfig) {
+        if !other.servers.is_empty() {
+            self.servers = other.servers.clone();
+        }
+
+        if !other.search.is_empty() {
+            self.search = other.search.clone();
+        }
+
+        self.timeout = other.timeout.or(self.timeout);
+    }
+
+    /// The timeout of each query.
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(Self::DEFAULT_TIMEOUT)
+    }
+}
+
+/// The address of a DNS server, where the port defaults to 53.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsServer(pub SocketAddr);
+
+impl fmt::Display for DnsServer {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for DnsServer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(address) = s.parse::<SocketAddr>() {
+            return Ok(Self(address));
+        }
+
+        match s.parse::<IpAddr>() {
+            Ok(ip) => Ok(Self(SocketAddr::new(ip, 53))),
+            Err(_) => Err(format!(
+                "invalid DNS server `{s}`, expected an address with an optional port like \"192.168.1.1:53\""
+            )),
+        }
+    }
+}
+
+/// Settings for discovering devices in the neighbor table.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DiscoveryConfig {
+    /// Whether to notify about devices which have never been seen before.
+    pub new_devices: Option<bool>,
+    /// Whether new devices are listed on the network page until they are
+    /// acknowledged.
+    pub acknowledge: Option<bool>,
+    /// Whether echo requests sent to this machine by other machines are
+    /// recorded in the timeline.
+    pub echo_requests: Option<bool>,
+    /// How long hosts which disappear are listed on the network page.
+    pub disappeared: Option<Duration>,
+}
+
+impl DiscoveryConfig {
+    /// The default time hosts which disappear are listed.
+    pub const DEFAULT_DISAPPEARED: Duration = Duration::from_secs(3600);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            new_devices: parser.take_boolean("new_devices"),
+            acknowledge: parser.take_boolean("acknowledge"),
+            echo_requests: parser.take_boolean("echo_requests"),
+            disappeared: parser.take_duration("disappeared"),
+        };
+
+        if out.acknowledge == Some(true) && out.new_devices != Some(true) {
+            parser.warning_at(
+                "acknowledge",
+                "acknowledge has no effect unless new_devices is enabled",
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another discovery configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &DiscoveryConfig) {
+        self.new_devices = other.new_devices.or(self.new_devices);
+        self.acknowledge = other.acknowledge.or(self.acknowledge);
+        self.echo_requests = other.echo_requests.or(self.echo_requests);
+        self.disappeared = other.disappeared.or(self.disappeared);
+    }
+
+    /// Test if new devices are detected.
+    pub fn new_devices(&self) -> bool {
+        self.new_devices.unwrap_or_default()
+    }
+
+    /// Test if new devices need to be acknowledged.
+    pub fn acknowledge(&self) -> bool {
+        self.new_devices() && self.acknowledge.unwrap_or_default()
+    }
+
+    /// Test if echo requests sent to this machine are recorded.
+    pub fn echo_requests(&self) -> bool {
+        self.echo_requests.unwrap_or_default()
+    }
+
+    /// How long hosts which disappear are listed, where zero means that they
+    /// aren't.
+    pub fn disappeared(&self) -> Duration {
+        self.disappeared.unwrap_or(Self::DEFAULT_DISAPPEARED)
+    }
+}
+
+/// Settings for notifications.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NotifyConfig {
+    /// URLs which notifications are posted to.
+    pub webhooks: Vec<Webhook>,
+}
+
+impl NotifyConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            webhooks: parser.take_iter("webhooks"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another notification configuration into this one, where
+    /// settings in `other` take precedence.
+    pub fn merge(&mut self, other: &NotifyConfig) {
+        if !other.webhooks.is_empty() {
+            self.webhooks = other.webhooks.clone();
+        }
+    }
+}
+
+/// Settings for the cookies which store preferences and sessions.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SessionsConfig {
+    /// Secret the cookies are signed with. A random secret is used unless
+    /// this is set, so preferences and sessions are lost when restarting.
+    pub key: Option<String>,
+}
+
+impl SessionsConfig {
+    /// The shortest secret cookies can be signed with.
+    pub const MIN_KEY_LENGTH: usize = 32;
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let mut key = parser.take::<String>("key");
+
+        if key.as_ref().is_some_and(|k| k.len() < Self::MIN_KEY_LENGTH) {
+            parser.warning_at(
+                "key",
+                format_args!(
+                    "must be at least {} bytes long, ignoring",
+                    Self::MIN_KEY_LENGTH
+                ),
+            );
+            key = None;
+        }
+
+        let out = Self { key };
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another sessions configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &SessionsConfig) {
+        self.key = other.key.clone().or(self.key.take());
+    }
+}
+
+/// Settings for scripting hooks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScriptConfig {
+    /// Path to a Rhai script which defines hooks.
+    pub path: Option<PathBuf>,
+}
+
+impl ScriptConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            path: parser.take("path"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another script configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &ScriptConfig) {
+        self.path = other.path.clone().or(self.path.take());
+    }
+}
+
+/// Settings for the power of the machine wolo runs on.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PowerConfig {
+    /// Actions offered on the admin page, which are performed through logind.
+    pub actions: BTreeSet<PowerAction>,
+}
+
+impl PowerConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            actions: parser.take_iter("actions"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another power configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &PowerConfig) {
+        if !other.actions.is_empty() {
+            self.actions = other.actions.clone();
+        }
+    }
+}
+
+/// Settings for restarting background tasks which fail.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SupervisorConfig {
+    /// How many times in a row a task may be restarted before wolo exits.
+    pub restarts: Option<u32>,
+    /// How long to wait before restarting a task the first time, which is
+    /// doubled for every restart in a row.
+    pub backoff: Option<Duration>,
+    /// The longest time to wait before restarting a task.
+    pub max_backoff: Option<Duration>,
+}
+
+impl SupervisorConfig {
+    /// The default number of restarts in a row.
+    pub const DEFAULT_RESTARTS: u32 = 5;
+
+    /// The default time to wait before the first restart.
+    pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+    /// The default longest time to wait before a restart.
+    pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            restarts: parser.take_integer("restarts"),
+            backoff: parser.take_duration("backoff"),
+            max_backoff: parser.take_duration("max_backoff"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another supervisor configuration into this one, where settings
+    /// in `other` take precedence.
+    pub fn merge(&mut self, other: &SupervisorConfig) {
+        self.restarts = other.restarts.or(self.restarts);
+        self.backoff = other.backoff.or(self.backoff);
+        self.max_backoff = other.max_backoff.or(self.max_backoff);
+    }
+
+    /// How many times in a row a task may be restarted.
+    pub fn restarts(&self) -> u32 {
+        self.restarts.unwrap_or(Self::DEFAULT_RESTARTS)
+    }
+
+    /// How long to wait before restarting a task the first time.
+    pub fn backoff(&self) -> Duration {
+        self.backoff.unwrap_or(Self::DEFAULT_BACKOFF)
+    }
+
+    /// The longest time to wait before restarting a task.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff.unwrap_or(Self::DEFAULT_MAX_BACKOFF)
+    }
+}
+
+/// An action which changes the power state of the machine wolo runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PowerAction {
+    Suspend,
+    Hibernate,
+    PowerOff,
+}
+
+impl fmt::Display for PowerAction {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerAction::Suspend => write!(f, "suspend"),
+            PowerAction::Hibernate => write!(f, "hibernate"),
+            PowerAction::PowerOff => write!(f, "power-off"),
+        }
+    }
+}
+
+impl FromStr for PowerAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "suspend" => Ok(PowerAction::Suspend),
+            "hibernate" => Ok(PowerAction::Hibernate),
+            "power-off" => Ok(PowerAction::PowerOff),
+            other => Err(format!(
+                "unknown power action `{other}`, expected suspend, hibernate or power-off"
+            )),
+        }
+    }
+}
+
+/// Settings for the security headers sent with responses.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeadersConfig {
+    /// Replaces the default `Content-Security-Policy`, where an empty string
+    /// leaves the header out.
+    pub content_security_policy: Option<String>,
+    /// The `frame-ancestors` directive of the default policy, which controls
+    /// which sites may embed pages.
+    pub frame_ancestors: Option<String>,
+    /// Replaces the default `Referrer-Policy`, where an empty string leaves
+    /// the header out.
+    pub referrer_policy: Option<String>,
+}
+
+impl HeadersConfig {
+    /// Sources of the default policy, which allows the embedded assets and
+    /// the modules the network page imports from a CDN.
+    pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+        script-src 'self' https://esm.run https://cdn.jsdelivr.net; \
+        style-src 'self' 'unsafe-inline'; \
+        img-src 'self' data:; \
+        object-src 'none'; \
+        base-uri 'self'; \
+        form-action 'self'";
+
+    /// Sources of the policy for volumes generated by Mokuro, which use inline
+    /// scripts and styles and load their viewer from a CDN.
+    pub const MOKURO_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+        script-src 'self' 'unsafe-inline' https:; \
+        style-src 'self' 'unsafe-inline' https:; \
+        img-src 'self' data: blob:; \
+        font-src 'self' data: https:; \
+        object-src 'none'; \
+        base-uri 'self'; \
+        form-action 'self'";
+
+    /// The default `frame-ancestors` directive, which forbids embedding.
+    pub const DEFAULT_FRAME_ANCESTORS: &str = "'none'";
+
+    /// The default `Referrer-Policy`.
+    pub const DEFAULT_REFERRER_POLICY: &str = "same-origin";
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            content_security_policy: parser.take("content_security_policy"),
+            frame_ancestors: parser.take("frame_ancestors"),
+            referrer_policy: parser.take("referrer_policy"),
+        };
+
+        for (key, value) in [
+            ("content_security_policy", &out.content_security_policy),
+            ("frame_ancestors", &out.frame_ancestors),
+            ("referrer_policy", &out.referrer_policy),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|v| v.contains(char::is_control))
+            {
+                parser.error(format_args!("`{key}` may not contain control characters"));
+                parser.check();
+                return None;
+            }
+        }
+
+        if out.content_security_policy.is_some() && out.frame_ancestors.is_some() {
+            parser.warning_at(
+                "frame_ancestors",
+                "frame_ancestors has no effect when content_security_policy is set",
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another headers configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &HeadersConfig) {
+        self.content_security_policy = other
+            .content_security_policy
+            .clone()
+            .or(self.content_security_policy.take());
+        self.frame_ancestors = other
+            .frame_ancestors
+            .clone()
+            .or(self.frame_ancestors.take());
+        self.referrer_policy = other
+            .referrer_policy
+            .clone()
+            .or(self.referrer_policy.take());
+    }
+
+    /// The `Content-Security-Policy` to send.
+    pub fn content_security_policy(&self) -> String {
+        self.policy(Self::DEFAULT_CONTENT_SECURITY_POLICY)
+    }
+
+    /// The `Content-Security-Policy` to send with volumes generated by
+    /// Mokuro.
+    pub fn mokuro_content_security_policy(&self) -> String {
+        self.policy(Self::MOKURO_CONTENT_SECURITY_POLICY)
+    }
+
+    /// The configured policy, or the given sources with the configured
+    /// `frame-ancestors` directive.
+    fn policy(&self, sources: &str) -> String {
+        if let Some(policy) = &self.content_security_policy {
+            return policy.clone();
+        }
+
+        let frame_ancestors = self
+            .frame_ancestors
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_FRAME_ANCESTORS);
+
+        format!("{sources}; frame-ancestors {frame_ancestors}")
+    }
+
+    /// The `Referrer-Policy` to send.
+    pub fn referrer_policy(&self) -> &str {
+        self.referrer_policy
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_REFERRER_POLICY)
+    }
+}
+
+/// Settings for the periodic summary report.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReportConfig {
+    /// Path the report is written to, as markdown if it ends with `.md` and
+    /// as HTML otherwise.
+    pub path: Option<PathBuf>,
+    /// Whether the report is posted to the webhooks in `[notify]`.
+    pub notify: Option<bool>,
+    /// How often a report is generated, which is also the period it covers.
+    pub interval: Option<Duration>,
+}
+
+impl ReportConfig {
+    /// Reports are weekly by default.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(7 * 86400);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            path: parser.take("path"),
+            notify: parser.take_boolean("notify"),
+            interval: parser.take_duration("interval"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another report configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &ReportConfig) {
+        self.path = other.path.clone().or(self.path.take());
+        self.notify = other.notify.or(self.notify);
+        self.interval = other.interval.or(self.interval);
+    }
+
+    /// Whether the report is posted to webhooks.
+    pub fn notify(&self) -> bool {
+        self.notify.unwrap_or_default()
+    }
+
+    /// How often a report is generated.
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Self::DEFAULT_INTERVAL)
+    }
+
+    /// Whether reports are generated at all.
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some() || self.notify()
+    }
+}
+
+/// Settings for metrics.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetricsConfig {
+    /// Settings for pushing metrics, for when they aren't scraped.
+    pub push: MetricsPushConfig,
+}
+
+impl MetricsConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            push: parser
+                .take_table("push", MetricsPushConfig::parse)
+                .unwrap_or_default(),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another metrics configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &MetricsConfig) {
+        self.push.merge(&other.push);
+    }
+}
+
+/// Settings for pushing the metrics served from `/metrics` to a remote
+/// endpoint on an interval.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MetricsPushConfig {
+    /// The URL metrics are posted to. Metrics aren't pushed unless this is
+    /// set.
+    pub url: Option<Webhook>,
+    /// The format metrics are posted in.
+    pub format: Option<PushFormat>,
+    /// How often metrics are pushed.
+    pub interval: Option<Duration>,
+}
+
+impl MetricsPushConfig {
+    /// Metrics are pushed every 15 seconds by default.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+    /// The shortest interval metrics can be pushed at.
+    pub const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let mut interval = parser.take_duration("interval");
+
+        if interval.is_some_and(|i| i < Self::MIN_INTERVAL) {
+            parser.warning_at(
+                "interval",
+                format_args!(
+                    "interval is shorter than {}, which is used instead",
+                    format_duration(Self::MIN_INTERVAL)
+                ),
+            );
+
+            interval = Some(Self::MIN_INTERVAL);
+        }
+
+        let out = Self {
+            url: parser.take("url"),
+            format: parser.take("format"),
+            interval,
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another push configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &MetricsPushConfig) {
+        self.url = other.url.clone().or(self.url.take());
+        self.format = other.format.or(self.format);
+        self.interval = other.interval.or(self.interval);
+    }
+
+    /// The format metrics are posted in.
+    pub fn format(&self) -> PushFormat {
+        self.format.unwrap_or_default()
+    }
+
+    /// How often metrics are pushed.
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Self::DEFAULT_INTERVAL)
+    }
+}
+
+/// The format metrics are pushed in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PushFormat {
+    /// The Prometheus remote-write protocol.
+    #[default]
+    RemoteWrite,
+    /// The InfluxDB line protocol, which VictoriaMetrics also accepts.
+    Influx,
+}
+
+impl fmt::Display for PushFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushFormat::RemoteWrite => write!(f, "remote-write"),
+            PushFormat::Influx => write!(f, "influx"),
+        }
+    }
+}
+
+impl FromStr for PushFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "remote-write" => Ok(PushFormat::RemoteWrite),
+            "influx" => Ok(PushFormat::Influx),
+            other => Err(format!(
+                "unknown push format `{other}`, expected remote-write or influx"
+            )),
+        }
+    }
+}
+
+/// A URL which notifications or metrics are posted to, like
+/// `http://192.168.1.10:8080/hooks/wolo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    /// The host to connect to.
+    pub host: String,
+    /// The port to connect to, which defaults to 80.
+    pub port: u16,
+    /// The path to post to.
+    pub path: String,
+}
+
+impl fmt::Display for Webhook {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "http://[{}]:{}{}", self.host, self.port, self.path)
+        } else {
+            write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+        }
+    }
+}
+
+impl FromStr for Webhook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(rest) = s.strip_prefix("http://") else {
+            return Err(format!(
+                "unsupported URL `{s}`, only http:// URLs are supported"
+            ));
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+
+        // IPv6 addresses are enclosed in brackets, like `[::1]:8080`.
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(rest) => match rest.split_once(']') {
+                Some((host, port)) => (host, port.strip_prefix(':')),
+                None => return Err(format!("invalid address in URL `{s}`")),
+            },
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| format!("invalid port in URL `{s}`"))?,
+            None => 80,
+        };
+
+        if host.is_empty() {
+            return Err(format!("missing host in URL `{s}`"));
+        }
+
+        Ok(Self {
+            host: host.to_owned(),
+            port,
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// Service level objectives for a host, which are evaluated against the
+/// history store.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SloConfig {
+    /// The window objectives are evaluated over.
+    pub window: Option<Duration>,
+    /// The share of pings which should be answered.
+    pub availability: Option<Percent>,
+    /// The round trip time which the given percentile of replies should be
+    /// faster than.
+    pub rtt: Option<Duration>,
+    /// The percentile of replies the round trip time objective applies to.
+    pub percentile: Option<u8>,
+}
+
+impl SloConfig {
+    /// The default window objectives are evaluated over.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(3600);
+    /// The default percentile of the round trip time objective.
+    pub const DEFAULT_PERCENTILE: u8 = 95;
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            window: parser.take_duration("window"),
+            availability: parser.take("availability"),
+            rtt: parser.take_duration("rtt"),
+            percentile: parser.take_integer("percentile"),
+        };
+
+        if out.percentile.is_some_and(|p| !(1..=100).contains(&p)) {
+            parser.warning_at("percentile", "percentile must be between 1 and 100");
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge other objectives into these, where settings in `other` take
+    /// precedence.
+    pub fn merge(&mut self, other: &SloConfig) {
+        self.window = other.window.or(self.window);
+        self.availability = other.availability.or(self.availability);
+        self.rtt = other.rtt.or(self.rtt);
+        self.percentile = other.percentile.or(self.percentile);
+    }
+
+    /// Test if any objectives are configured.
+    pub fn is_configured(&self) -> bool {
+        self.availability.is_some() || self.rtt.is_some()
+    }
+
+    /// Get the percentile of the round trip time objective.
+    pub fn percentile(&self) -> u8 {
+        self.percentile
+            .unwrap_or(Self::DEFAULT_PERCENTILE)
+            .clamp(1, 100)
+    }
+}
+
+/// Settings for polling a host over SNMP.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SnmpConfig {
+    /// The SNMP v2c community, polling is only enabled if this is set.
+    pub community: Option<String>,
+    /// The port to poll.
+    pub port: Option<u16>,
+    /// Interval between polls.
+    pub interval: Option<Duration>,
+}
+
+impl SnmpConfig {
+    /// The default port to poll.
+    pub const DEFAULT_PORT: u16 = 161;
+    /// The default interval between polls.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            community: parser.take("community"),
+            port: parser.take_integer("port"),
+            interval: parser.take_duration("interval"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge other settings into these, where settings in `other` take
+    /// precedence.
+    pub fn merge(&mut self, other: &SnmpConfig) {
+        self.community = other.community.clone().or(self.community.take());
+        self.port = other.port.or(self.port);
+        self.interval = other.interval.or(self.interval);
+    }
+
+    /// Test if polling is enabled.
+    pub fn is_configured(&self) -> bool {
+        self.community.is_some()
+    }
+
+    /// Get the port to poll.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(Self::DEFAULT_PORT)
+    }
+
+    /// Get the interval between polls.
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Self::DEFAULT_INTERVAL)
+    }
+}
+
+/// Settings for probing the throughput to a host.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProbeConfig {
+    /// The TCP port to send data to, probing is only enabled if this is set.
+    pub port: Option<u16>,
+    /// How long to send data for.
+    pub duration: Option<Duration>,
+}
+
+impl ProbeConfig {
+    /// The default time to send data for.
+    pub const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            port: parser.take_integer("port"),
+            duration: parser.take_duration("duration"),
+        };
+
+        if out.duration.is_some_and(|d| d > Duration::from_secs(60)) {
+            parser.warning_at("duration", "probes longer than 1m are cut short");
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge other settings into these, where settings in `other` take
+    /// precedence.
+    pub fn merge(&mut self, other: &ProbeConfig) {
+        self.port = other.port.or(self.port);
+        self.duration = other.duration.or(self.duration);
+    }
+
+    /// Get the time to send data for.
+    pub fn duration(&self) -> Duration {
+        self.duration
+            .unwrap_or(Self::DEFAULT_DURATION)
+            .min(Duration::from_secs(60))
+    }
+}
+
+/// A percentage, like `99.9%`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Percent {
+    /// The percentage in parts per million.
+    ppm: u32,
+}
+
+impl Percent {
+    /// Get the percentage as a ratio between 0 and 1.
+    pub fn ratio(self) -> f64 {
+        f64::from(self.ppm) / 1_000_000.0
+    }
+}
+
+impl fmt::Display for Percent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", f64::from(self.ppm) / 10_000.0)
+    }
+}
+
+impl FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid percentage `{s}`, expected something like \"99.9%\"");
+
+        let value = s
+            .trim()
+            .strip_suffix('%')
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| (0.0..=100.0).contains(v))
+            .ok_or_else(error)?;
+
+        Ok(Self {
+            ppm: (value * 10_000.0).round() as u32,
+        })
+    }
+}
+
+/// Settings for how a host is displayed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DisplayConfig {
+    /// The kind of device, which determines its icon.
+    pub kind: Option<HostKind>,
+    /// The number of addresses to display before the rest are collapsed.
+    pub addresses: Option<usize>,
+    /// The address family to display first.
+    pub prefer: Option<AddressFamily>,
+    /// Link to the web interface of the host.
+    pub url: Option<String>,
+    /// Whether a link to the web interface is detected from checks of the
+    /// ports web interfaces are usually served on.
+    pub detect_url: Option<bool>,
+}
+
+impl DisplayConfig {
+    /// Take display settings, which are specified directly in host and
+    /// profile tables.
+    fn take(parser: &mut Parser<'_>) -> Self {
+        Self {
+            kind: parser.take("kind"),
+            addresses: parser.take_integer("display_addresses"),
+            prefer: parser.take("display_prefer"),
+            url: parser.take("url"),
+            detect_url: parser.take_boolean("detect_url"),
+        }
+    }
+
+    /// Merge another display configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &DisplayConfig) {
+        self.kind = other.kind.or(self.kind);
+        self.addresses = other.addresses.or(self.addresses);
+        self.prefer = other.prefer.or(self.prefer);
+        self.url = other.url.clone().or(self.url.take());
+        self.detect_url = other.detect_url.or(self.detect_url);
+    }
+
+    /// Test if a link to the web interface is detected, which it is by
+    /// default.
+    pub fn detect_url(&self) -> bool {
+        self.detect_url.unwrap_or(true)
+    }
+}
+
+/// Settings for the admin page.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AdminConfig {
+    /// The user name required to access the admin page.
+    pub username: Option<String>,
+    /// The password required to access the admin page. The admin page is
+    /// disabled unless this is set.
+    pub password: Option<String>,
+}
+
+impl AdminConfig {
+    /// The user name used if none is configured.
+    pub const DEFAULT_USERNAME: &str = "admin";
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            username: parser.take("username"),
+            password: parser.take("password"),
+        };
+
+        if out.username.is_some() && out.password.is_none() {
+            parser.warning_at(
+                "username",
+                "username has no effect unless a password is set",
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another admin configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &AdminConfig) {
+        self.username = other.username.clone().or(self.username.take());
+        self.password = other.password.clone().or(self.password.take());
+    }
+}
+
+/// Access to the Mokuro pages, which is configured separately from the rest of
+/// wolo since a library might be private.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MokuroAccessConfig {
+    /// Whether the Mokuro pages are served at all.
+    pub enabled: Option<bool>,
+    /// The user name required to access the Mokuro pages.
+    pub username: Option<String>,
+    /// The password required to access the Mokuro pages. They are public
+    /// unless this is set.
+    pub password: Option<String>,
+}
+
+impl MokuroAccessConfig {
+    /// The user name used if none is configured.
+    pub const DEFAULT_USERNAME: &str = "mokuro";
+
+    /// Parse access settings from the `mokuro` table, leaving the libraries
+    /// which are configured in it in place.
+    fn parse(parser: &mut Parser<'_>) -> Self {
+        let out = Self {
+            enabled: parser.take_boolean("enabled"),
+            username: parser.take("username"),
+            password: parser.take("password"),
+        };
+
+        if out.username.is_some() && out.password.is_none() {
+            parser.warning_at(
+                "username",
+                "username has no effect unless a password is set",
+            );
+        }
+
+        out
+    }
+
+    /// Merge another access configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &MokuroAccessConfig) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.username = other.username.clone().or(self.username.take());
+        self.password = other.password.clone().or(self.password.take());
+    }
+}
+
+/// Settings for showcase mode.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ShowcaseConfig {
+    /// Seed used to derive showcase values, changing which fake values real
+    /// ones are replaced with.
+    pub seed: Option<u64>,
+    /// Names to pick fake host names from.
+    pub names: Vec<String>,
+    /// Domain used for all but the first name of each host.
+    pub domain: Option<String>,
+    /// Names of hosts which should not be anonymized.
+    pub exclude: BTreeSet<String>,
+}
+
+impl ShowcaseConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            seed: parser.take_integer("seed"),
+            names: parser.take_iter("names"),
+            domain: parser.take("domain"),
+            exclude: parser.take_iter("exclude"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another showcase configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &ShowcaseConfig) {
+        self.seed = other.seed.or(self.seed);
+
+        if !other.names.is_empty() {
+            self.names.clone_from(&other.names);
+        }
+
+        self.domain = other.domain.clone().or(self.domain.take());
+        self.exclude.extend(other.exclude.iter().cloned());
+    }
+}
+
+/// A named profile.
+struct NamedProfile(String, ProfileConfig);
+
+impl TakeFlexible for NamedProfile {
+    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+        Some(NamedProfile(key.to_owned(), ProfileConfig::parse(parser)?))
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        parser.error("expected a table of profiles, like `[profiles.laptop]`");
+        parser.check();
+        None
+    }
+}
+
+struct NamedExtension(String, ExtensionConfig);
+
+impl TakeFlexible for NamedExtension {
+    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+        Some(NamedExtension(
+            key.to_owned(),
+            ExtensionConfig::parse(parser)?,
+        ))
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        parser.error("expected a table of extensions, like `[extensions.matrix]`");
+        parser.check();
+        None
+    }
+}
+
+struct NamedWakeListener(SocketAddr, WakeListenerConfig);
+
+impl TakeFlexible for NamedWakeListener {
+    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+        let Ok(bind) = key.parse() else {
+            parser.error(format_args!(
+                "invalid address `{key}`, expected an address and port like \"0.0.0.0:2222\""
+            ));
+            parser.check();
+            return None;
+        };
+
+        Some(NamedWakeListener(bind, WakeListenerConfig::parse(parser)?))
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        parser.error("expected a table of listeners, like `[wake_listeners.\"0.0.0.0:2222\"]`");
+        parser.check();
+        None
+    }
+}
+
+/// A port which wakes a host when something connects to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeListenerConfig {
+    /// The name of the host which is woken.
+    pub host: String,
+    /// The port of the host which connections are forwarded to once it's
+    /// up, where connections are closed once the host is being woken if
+    /// this isn't set.
+    pub forward: Option<u16>,
+    /// How long to wait for the host to accept forwarded connections.
+    pub timeout: Option<Duration>,
+}
+
+impl WakeListenerConfig {
+    /// The default time to wait for the host to accept forwarded
+    /// connections.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    /// How long to wait for the host to accept forwarded connections.
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(Self::DEFAULT_TIMEOUT)
+    }
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let host = parser.take("host");
+        let forward = parser.take_integer("forward");
+        let timeout = parser.take_duration("timeout");
+
+        let Some(host) = host else {
+            parser.error(format_args!("missing `host`"));
+            parser.check();
+            return None;
+        };
+
+        if forward.is_none() && timeout.is_some() {
+            parser.warning_at("timeout", "timeout has no effect without forward");
+        }
+
+        parser.check();
+
+        Some(Self {
+            host,
+            forward,
+            timeout,
+        })
+    }
+}
+
+/// An external executable which is used as a check or notified about events.
+///
+/// The executable is given a JSON object on stdin, and checks are expected
+/// to print a JSON verdict on stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionConfig {
+    /// The program to run followed by its arguments.
+    pub command: Vec<String>,
+    /// Kinds of events in the timeline the extension is notified about.
+    pub events: BTreeSet<events::Kind>,
+    /// How long the executable may run before it's killed.
+    pub timeout: Option<Duration>,
+    /// How many instances of the executable may run at the same time.
+    pub concurrency: Option<usize>,
+}
+
+impl ExtensionConfig {
+    /// The default time an executable may run.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The default number of instances which may run at the same time.
+    pub const DEFAULT_CONCURRENCY: usize = 4;
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let command: Vec<String> = parser.take_iter("command");
+        let events = parser.take_iter("events");
+        let timeout = parser.take_duration("timeout");
+        let concurrency = parser.take_integer("concurrency");
+
+        if command.is_empty() {
+            parser.error(format_args!("missing `command`"));
+            parser.check();
+            return None;
+        }
+
+        if concurrency == Some(0) {
+            parser.error(format_args!("`concurrency` must be at least 1"));
+            parser.check();
+            return None;
+        }
+
+        let out = Self {
+            command,
+            events,
+            timeout,
+            concurrency,
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// How long the executable may run.
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(Self::DEFAULT_TIMEOUT)
+    }
+
+    /// How many instances of the executable may run at the same time.
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(Self::DEFAULT_CONCURRENCY)
+    }
+}
+
+struct NamedUser(String, UserConfig);
+
+impl TakeFlexible for NamedUser {
+    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+        Some(NamedUser(key.to_owned(), UserConfig::parse(parser)?))
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        parser.error("expected a table of users, like `[users.alice]`");
+        parser.check();
+        None
+    }
+}
+
+/// What a user is allowed to do, where each role may do everything the roles
+/// before it may.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May look at every page.
+    #[default]
+    Viewer,
+    /// May also wake hosts and take other actions on the network page.
+    Operator,
+    /// May also use the admin page.
+    Admin,
+}
+
+impl Role {
+    const ALL: [Role; 3] = [Role::Viewer, Role::Operator, Role::Admin];
+}
+
+impl fmt::Display for Role {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for role in Self::ALL {
+            if role.to_string() == s {
+                return Ok(role);
+            }
+        }
+
+        let expected = Self::ALL.map(|r| r.to_string());
+
+        Err(format!(
+            "unknown role `{s}`, expected one of {}",
+            expected.join(", ")
+        ))
+    }
+}
+
+/// A user who can sign in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserConfig {
+    /// Hash of the password of the user in PHC format, as printed by `wolo
+    /// hash-password`.
+    pub password_hash: String,
+    /// What the user is allowed to do.
+    pub role: Role,
+}
+
+impl UserConfig {
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let password_hash = parser.take::<String>("password_hash");
+        let role = parser.take("role").unwrap_or_default();
+
+        let Some(password_hash) = password_hash else {
+            parser.error("missing `password_hash`");
+            parser.check();
+            return None;
+        };
+
+        if PasswordHash::new(&password_hash).is_err() {
+            parser.warning_at(
+                "password_hash",
+                "not a valid password hash, generate one with `wolo hash-password`",
+            );
+            parser.check();
+            return None;
+        }
+
+        let out = Self {
+            password_hash,
+            role,
+        };
+
+        parser.check();
+        Some(out)
+    }
+}
+
+/// Settings for signing in.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoginConfig {
+    /// How many times signing in may fail from an address before it's locked
+    /// out.
+    pub max_failures: Option<u32>,
+    /// How long an address is locked out, which is also how long failures
+    /// are remembered.
+    pub lockout: Option<Duration>,
+}
+
+impl LoginConfig {
+    /// The default number of failures before an address is locked out.
+    pub const DEFAULT_MAX_FAILURES: u32 = 5;
+
+    /// The default time an address is locked out.
+    pub const DEFAULT_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let max_failures = parser.take_integer("max_failures");
+        let lockout = parser.take_duration("lockout");
+
+        if max_failures == Some(0) {
+            parser.error(format_args!("`max_failures` must be at least 1"));
+            parser.check();
+            return None;
+        }
+
+        let out = Self {
+            max_failures,
+            lockout,
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another login configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &LoginConfig) {
+        self.max_failures = other.max_failures.or(self.max_failures);
+        self.lockout = other.lockout.or(self.lockout);
+    }
+
+    /// How many times signing in may fail before an address is locked out.
+    pub fn max_failures(&self) -> u32 {
+        self.max_failures.unwrap_or(Self::DEFAULT_MAX_FAILURES)
+    }
+
+    /// How long an address is locked out.
+    pub fn lockout(&self) -> Duration {
+        self.lockout.unwrap_or(Self::DEFAULT_LOCKOUT)
+    }
+}
+
+struct NamedSegment(String, SegmentConfig);
+
+impl TakeFlexible for NamedSegment {
+    fn take_table(key: &str, parser: Parser<'_>) -> Option<Self> {
+        if !is_segment_name(key) {
+            parser.error("segment names may only contain letters, digits, `-` and `_`");
+            parser.check();
+            return None;
+        }
+
+        Some(NamedSegment(key.to_owned(), SegmentConfig::parse(parser)?))
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        parser.error("expected a table of segments, like `[segments.iot]`");
+        parser.check();
+        None
+    }
+}
+
+/// Test if a name can be used for a segment, which is part of its URL.
+fn is_segment_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+/// A named network segment, which groups hosts by subnet or tag.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SegmentConfig {
+    /// The title of the segment, which defaults to its name.
+    pub title: Option<String>,
+    /// Subnets whose hosts belong to the segment.
+    pub subnets: Vec<Subnet>,
+    /// Tags whose hosts belong to the segment.
+    pub tags: BTreeSet<String>,
+    /// The user name required to access the segment.
+    pub username: Option<String>,
+    /// The password required to access the segment. The segment is public
+    /// unless this is set.
+    pub password: Option<String>,
+}
+
+impl SegmentConfig {
+    /// The user name used if none is configured.
+    pub const DEFAULT_USERNAME: &str = "guest";
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            title: parser.take("title"),
+            subnets: parser.take_iter("subnets"),
+            tags: parser.take_iter("tags"),
+            username: parser.take("username"),
+            password: parser.take("password"),
+        };
+
+        if out.username.is_some() && out.password.is_none() {
+            parser.warning_at(
+                "username",
+                "username has no effect unless a password is set",
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another segment into this one. Settings in `other` take
+    /// precedence, while subnets and tags are accumulated.
+    pub fn merge(&mut self, other: &SegmentConfig) {
+        self.title = other.title.clone().or(self.title.take());
+        self.subnets.extend(other.subnets.iter().copied());
+        self.tags.extend(other.tags.iter().cloned());
+        self.username = other.username.clone().or(self.username.take());
+        self.password = other.password.clone().or(self.password.take());
+    }
+
+    /// Test if a host with the given tags and addresses belongs to the
+    /// segment.
+    pub fn contains(
+        &self,
+        tags: &BTreeSet<String>,
+        mut addresses: impl Iterator<Item = IpAddr>,
+    ) -> bool {
+        !self.tags.is_disjoint(tags)
+            || addresses.any(|a| self.subnets.iter().any(|s| s.contains(a)))
+    }
+}
+
+/// A subnet in CIDR notation, like `192.168.1.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subnet {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl Subnet {
+    /// Construct the subnet of an address with the given prefix length.
+    pub fn new(address: IpAddr, prefix: u8) -> Self {
+        Self { address, prefix }
+    }
+
+    /// Test if the subnet contains the given address.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(net), IpAddr::V4(a)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(a) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(a)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(a) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Subnet {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid subnet `{s}`, expected one like \"192.168.1.0/24\"");
+
+        let (address, prefix) = s.split_once('/').ok_or_else(error)?;
+        let address = address.parse::<IpAddr>().map_err(|_| error())?;
+        let prefix = prefix.parse::<u8>().map_err(|_| error())?;
+
+        let max = if address.is_ipv4() { 32 } else { 128 };
+
+        if prefix > max {
+            return Err(error());
+        }
+
+        Ok(Self { address, prefix })
+    }
+}
+
+/// Loaded host configuration.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HostConfig {
+    /// Loaded host configurations.
+    pub macs: BTreeSet<MacAddr6>,
+    /// Host names.
+    pub names: BTreeSet<String>,
+    /// Preferred host name.
+    pub preferred_name: Option<String>,
+    /// Whether to ignore this host.
+    pub ignore: bool,
+    /// Ping settings for this host.
+    pub ping: PingConfig,
+    /// Address which is pinged and checked instead of the addresses its
+    /// names resolve to.
+    pub ping_address: Option<IpAddr>,
+    /// Checks to perform against this host.
+    pub checks: Vec<CheckConfig>,
+    /// Wake-on-LAN settings for this host.
+    pub wake: WakeConfig,
+    /// Quiet hours for this host.
+    pub quiet_hours: QuietHoursConfig,
+    /// Service level objectives for this host.
+    pub slo: SloConfig,
+    /// SNMP polling settings for this host.
+    pub snmp: SnmpConfig,
+    /// Throughput probe settings for this host.
+    pub probe: ProbeConfig,
+    /// Tags associated with this host.
+    pub tags: BTreeSet<String>,
+    /// Names of hosts this host depends on to be reachable.
+    pub depends_on: BTreeSet<String>,
+    /// The profile this host uses.
+    pub profile: Option<String>,
+    /// Display settings for this host.
+    pub display: DisplayConfig,
+    /// Configuration files the host was defined in.
+    pub files: BTreeSet<PathBuf>,
+}
+
+impl HostConfig {
+    /// A host with the given name and default settings.
+    pub fn named(name: String) -> Self {
+        Self {
+            macs: BTreeSet::new(),
+            names: BTreeSet::from([name]),
+            preferred_name: None,
+            ignore: false,
+            ping: PingConfig::default(),
+            ping_address: None,
+            checks: Vec::new(),
+            wake: WakeConfig::default(),
+            quiet_hours: QuietHoursConfig::default(),
+            slo: SloConfig::default(),
+            snmp: SnmpConfig::default(),
+            probe: ProbeConfig::default(),
+            tags: BTreeSet::new(),
+            depends_on: BTreeSet::new(),
+            profile: None,
+            display: DisplayConfig::default(),
+            files: BTreeSet::new(),
+        }
+    }
+
+    /// A host without names, which is identified by the address it's pinged
+    /// at.
+    pub fn address(address: IpAddr) -> Self {
+        Self {
+            ping_address: Some(address),
+            ..Self::default()
+        }
+    }
+
+    /// The names of the host joined by `/`, or its address if it has none.
+    pub fn label(&self) -> String {
+        match self.ping_address {
+            Some(address) if self.names.is_empty() => address.to_string(),
+            _ => {
+                let names = self.names.iter().map(String::as_str).collect::<Vec<_>>();
+                names.join("/")
+            }
+        }
+    }
+}
+
+impl TakeFlexible for HostConfig {
+    fn take_table(key: &str, mut parser: Parser<'_>) -> Option<Self> {
+        // Hosts keyed by an address have no names.
+        let address = key.parse::<IpAddr>().ok();
+
+        let out = Self {
+            macs: parser
+                .take_iter::<Mac, Vec<_>>("macs")
+                .into_iter()
+                .map(|Mac(mac)| mac)
+                .collect(),
+            names: match address {
+                Some(..) => BTreeSet::new(),
+                None => BTreeSet::from([key.to_owned()]),
+            },
+            preferred_name: parser.take("preferred_name"),
+            ignore: parser.take_boolean("ignore").unwrap_or(false),
+            ping: parser
+                .take_table("ping", PingConfig::parse)
+                .unwrap_or_default(),
+            ping_address: parser.take("ping_address").or(address),
+            checks: parser.take_tables("check", CheckConfig::parse),
+            wake: WakeConfig::take(&mut parser),
+            quiet_hours: parser
+                .take_table("quiet_hours", QuietHoursConfig::parse)
+                .unwrap_or_default(),
+            slo: parser
+                .take_table("slo", SloConfig::parse)
+                .unwrap_or_default(),
+            snmp: parser
+                .take_table("snmp", SnmpConfig::parse)
+                .unwrap_or_default(),
+            probe: parser
+                .take_table("probe", ProbeConfig::parse)
+                .unwrap_or_default(),
+            tags: parser.take_iter("tags"),
+            depends_on: parser.take_iter("depends_on"),
+            profile: parser.take("profile"),
+            display: DisplayConfig::take(&mut parser),
+            files: BTreeSet::new(),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        let name = parser.parse::<String>()?;
+
+        match name.parse::<IpAddr>() {
+            Ok(address) => Some(Self::address(address)),
+            Err(..) => Some(Self::named(name)),
+        }
+    }
+}
+
+/// Ping settings for a host.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PingConfig {
+    /// Whether the host should be pinged.
+    pub enabled: Option<bool>,
+    /// Interval between pings.
+    pub interval: Option<Duration>,
+    /// Timeout after which a ping is considered lost.
+    pub timeout: Option<Duration>,
+    /// Round trip time above which a host is considered slow.
+    pub slow: Option<Duration>,
+    /// Which address families are pinged.
+    pub family: Option<PingFamily>,
+}
+
+impl PingConfig {
+    /// The smallest supported ping interval.
+    pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            enabled: parser.take_boolean("enabled"),
+            interval: parser.take_duration("interval"),
+            timeout: parser.take_duration("timeout"),
+            slow: parser.take_duration("slow"),
+            family: parser.take("family"),
+        };
+
+        if let Some(interval) = out.interval
+            && interval < Self::MIN_INTERVAL
+        {
+            parser.warning_at(
+                "interval",
+                format_args!(
+                    "interval is shorter than {} and will be clamped",
+                    format_duration(Self::MIN_INTERVAL)
+                ),
+            );
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another ping configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &PingConfig) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.interval = other.interval.or(self.interval);
+        self.timeout = other.timeout.or(self.timeout);
+        self.slow = other.slow.or(self.slow);
+        self.family = other.family.or(self.family);
+    }
+}
+
+/// Which address families of a host are pinged.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PingFamily {
+    /// Ping addresses of both families.
+    #[default]
+    Both,
+    /// Only ping IPv4 addresses, unless the host has none.
+    PreferV4,
+    /// Only ping IPv6 addresses, unless the host has none.
+    PreferV6,
+    /// Only ping IPv4 addresses.
+    V4Only,
+    /// Only ping IPv6 addresses.
+    V6Only,
+}
+
+impl PingFamily {
+    /// Select the addresses to ping out of all addresses of a host.
+    pub fn select(self, addresses: &[IpAddr]) -> impl Iterator<Item = IpAddr> + '_ {
+        let family = match self {
+            PingFamily::Both => None,
+            PingFamily::V4Only => Some(AddressFamily::Ipv4),
+            PingFamily::V6Only => Some(AddressFamily::Ipv6),
+            PingFamily::PreferV4 | PingFamily::PreferV6 => {
+                let (preferred, other) = match self {
+                    PingFamily::PreferV4 => (AddressFamily::Ipv4, AddressFamily::Ipv6),
+                    _ => (AddressFamily::Ipv6, AddressFamily::Ipv4),
+                };
+
+                if addresses.iter().any(|&a| preferred.matches(a)) {
+                    Some(preferred)
+                } else {
+                    Some(other)
+                }
+            }
+        };
+
+        addresses
+            .iter()
+            .copied()
+            .filter(move |&a| family.is_none_or(|f| f.matches(a)))
+    }
+}
+
+impl fmt::Display for PingFamily {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingFamily::Both => write!(f, "both"),
+            PingFamily::PreferV4 => write!(f, "prefer-v4"),
+            PingFamily::PreferV6 => write!(f, "prefer-v6"),
+            PingFamily::V4Only => write!(f, "v4-only"),
+            PingFamily::V6Only => write!(f, "v6-only"),
+        }
+    }
+}
+
+impl FromStr for PingFamily {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "both" => Ok(PingFamily::Both),
+            "prefer-v4" => Ok(PingFamily::PreferV4),
+            "prefer-v6" => Ok(PingFamily::PreferV6),
+            "v4-only" => Ok(PingFamily::V4Only),
+            "v6-only" => Ok(PingFamily::V6Only),
+            other => Err(format!(
+                "unknown address family `{other}`, expected both, prefer-v4, prefer-v6, v4-only or v6-only"
+            )),
+        }
+    }
+}
+
+/// Quiet hours during which hosts are pinged less often, for networks where
+/// devices sleep at night.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuietHoursConfig {
+    /// The local time at which quiet hours start.
+    pub start: Option<TimeOfDay>,
+    /// The local time at which quiet hours end, which is the next day if it's
+    /// before `start`.
+    pub end: Option<TimeOfDay>,
+    /// Interval between pings during quiet hours.
+    pub interval: Option<Duration>,
+}
+
+impl QuietHoursConfig {
+    /// The default interval between pings during quiet hours.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            start: parser.take("start"),
+            end: parser.take("end"),
+            interval: parser.take_duration("interval"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another quiet hours configuration into this one, where settings
+    /// in `other` take precedence.
+    pub fn merge(&mut self, other: &QuietHoursConfig) {
+        self.start = other.start.or(self.start);
+        self.end = other.end.or(self.end);
+        self.interval = other.interval.or(self.interval);
+    }
+
+    /// Test if quiet hours are in effect at the given time of day.
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        let (Some(start), Some(end)) = (self.start, self.end) else {
+            return false;
+        };
+
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+/// A time of day with minute precision, like `23:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay {
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    /// Midnight, the start of the day.
+    pub const MIDNIGHT: Self = Self { minutes: 0 };
+
+    /// Construct a time of day from hours and minutes.
+    pub fn new(hours: u16, minutes: u16) -> Option<Self> {
+        if hours >= 24 || minutes >= 60 {
+            return None;
+        }
+
+        Some(Self {
+            minutes: hours * 60 + minutes,
+        })
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.minutes / 60, self.minutes % 60)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_once(':')
+            .and_then(|(h, m)| Self::new(h.parse().ok()?, m.parse().ok()?))
+            .ok_or_else(|| format!("invalid time `{s}`, expected a time of day like \"23:00\""))
+    }
+}
+
+/// Wake-on-LAN settings for a host.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WakeConfig {
+    /// Whether the host can be woken.
+    pub enabled: Option<bool>,
+    /// The address to send magic packets to, like a directed broadcast
+    /// address.
+    pub target: Option<Ipv4Addr>,
+    /// The port to send magic packets to.
+    pub port: Option<u16>,
+    /// SecureOn password to append to magic packets.
+    pub password: Option<[u8; 6]>,
+    /// The number of times magic packets are sent until the host responds.
+    pub attempts: Option<u32>,
+    /// Time between attempts.
+    pub retry: Option<Duration>,
+    /// TCP ports which wake the host on demand when something tries to
+    /// connect to them while it's down.
+    pub on_demand: Vec<u16>,
+}
+
+impl WakeConfig {
+    /// Take wake settings from `wake`, which is either a table of settings or
+    /// a boolean indicating whether the host can be woken.
+    fn take(parser: &mut Parser<'_>) -> Self {
+        let enabled = |enabled| Self {
+            enabled: Some(enabled),
+            ..Self::default()
+        };
+
+        parser
+            .take_table_or_boolean("wake", Self::parse, enabled)
+            .unwrap_or_default()
+    }
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let out = Self {
+            enabled: parser.take_boolean("enabled"),
+            target: parser.take("target"),
+            port: parser.take_integer("port"),
+            password: parser.take::<Mac>("password").map(|Mac(p)| p.into_array()),
+            attempts: parser.take_integer("attempts"),
+            retry: parser.take_duration("retry"),
+            on_demand: parser.take_integers("on_demand"),
+        };
+
+        parser.check();
+        Some(out)
+    }
+
+    /// Merge another wake configuration into this one, where settings in
+    /// `other` take precedence.
+    pub fn merge(&mut self, other: &WakeConfig) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.target = other.target.or(self.target);
+        self.port = other.port.or(self.port);
+        self.password = other.password.or(self.password);
+        self.attempts = other.attempts.or(self.attempts);
+        self.retry = other.retry.or(self.retry);
+
+        if !other.on_demand.is_empty() {
+            self.on_demand = other.on_demand.clone();
+        }
+    }
+}
+
+/// The kind of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckKind {
+    /// Test that a TCP connection can be established.
+    Tcp,
+    /// Test that an HTTP request can be performed.
+    Http,
+    /// Measure the clock offset of an NTP server.
+    Ntp,
+    /// Run an extension which reports a verdict.
+    Exec,
+}
+
+impl fmt::Display for CheckKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckKind::Tcp => write!(f, "tcp"),
+            CheckKind::Http => write!(f, "http"),
+            CheckKind::Ntp => write!(f, "ntp"),
+            CheckKind::Exec => write!(f, "exec"),
+        }
+    }
+}
+
+impl FromStr for CheckKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tcp" => Ok(CheckKind::Tcp),
+            "http" => Ok(CheckKind::Http),
+            "ntp" => Ok(CheckKind::Ntp),
+            "exec" => Ok(CheckKind::Exec),
+            other => Err(format!(
+                "unknown check kind `{other}`, expected tcp, http, ntp or exec"
+            )),
+        }
+    }
+}
+
+/// A check to perform against a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckConfig {
+    /// The kind of the check.
+    pub kind: CheckKind,
+    /// Optional display name of the check.
+    pub name: Option<String>,
+    /// The port to check. For exec checks this is only passed on to the
+    /// extension, and zero if it isn't configured.
+    pub port: u16,
+    /// The path to request for HTTP checks.
+    pub path: String,
+    /// Interval between checks.
+    pub interval: Option<Duration>,
+    /// Timeout after which the check is considered failed.
+    pub timeout: Option<Duration>,
+    /// The largest clock offset allowed for NTP checks.
+    pub max_skew: Option<Duration>,
+    /// The extension run by exec checks.
+    pub extension: Option<String>,
+}
+
+impl CheckConfig {
+    /// The default largest clock offset allowed for NTP checks.
+    pub const DEFAULT_MAX_SKEW: Duration = Duration::from_secs(1);
+
+    /// The largest clock offset allowed for NTP checks.
+    pub fn max_skew(&self) -> Duration {
+        self.max_skew.unwrap_or(Self::DEFAULT_MAX_SKEW)
+    }
+
+    fn parse(mut parser: Parser<'_>) -> Option<Self> {
+        let kind = parser.take::<CheckKind>("kind");
+        let name = parser.take("name");
+        let port = parser.take_integer("port");
+        let path = parser.take("path");
+        let interval = parser.take_duration("interval");
+        let timeout = parser.take_duration("timeout");
+        let max_skew = parser.take_duration("max_skew");
+        let extension = parser.take("extension");
+
+        let Some(kind) = kind else {
+            parser.error(format_args!("missing `kind`"));
+            parser.check();
+            return None;
+        };
+
+        let port = match (kind, port) {
+            (_, Some(port)) => port,
+            (CheckKind::Http, None) => 80,
+            (CheckKind::Ntp, None) => 123,
+            (CheckKind::Exec, None) => 0,
+            (CheckKind::Tcp, None) => {
+                parser.error(format_args!("missing `port`"));
+                parser.check();
+                return None;
+            }
+        };
+
+        if kind != CheckKind::Http && path.is_some() {
+            parser.warning_at("path", format_args!("path has no effect for {kind} checks"));
+        }
+
+        if kind != CheckKind::Ntp && max_skew.is_some() {
+            parser.warning_at(
+                "max_skew",
+                format_args!("max_skew has no effect for {kind} checks"),
+            );
+        }
+
+        if kind == CheckKind::Exec && extension.is_none() {
+            parser.error(format_args!("missing `extension`"));
+            parser.check();
+            return None;
+        }
+
+        if kind != CheckKind::Exec && extension.is_some() {
+            parser.warning_at(
+                "extension",
+                format_args!("extension has no effect for {kind} checks"),
+            );
+        }
+
+        if let (Some(interval), Some(timeout)) = (interval, timeout)
+            && timeout > interval
+        {
+            parser.warning_at("timeout", "timeout is longer than the check interval");
+        }
+
+        let out = Self {
+            kind,
+            name,
+            port,
+            path: path.unwrap_or_else(|| String::from("/")),
+            interval,
+            timeout,
+            max_skew,
+            extension,
+        };
+
+        parser.check();
+        Some(out)
+    }
+}
+
+/// Loaded mokuro configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MokuroConfig {
+    /// Mokuro path.
+    pub path: PathBuf,
+    /// Title of the library, defaults to the name of its directory.
+    pub title: Option<String>,
+    /// Where the library is listed relative to others, lower comes first.
+    pub order: i64,
+    /// Whether the library is served at all.
+    pub enabled: bool,
+    /// Whether volumes can be uploaded to the library.
+    pub upload: bool,
+}
+
+impl MokuroConfig {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            title: None,
+            order: 0,
+            enabled: true,
+            upload: false,
+        }
+    }
+
+    /// The title to show for the library.
+    pub fn title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+
+        match self.path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+impl TakeFlexible for MokuroConfig {
+    fn take_table(key: &str, mut parser: Parser<'_>) -> Option<Self> {
+        let mut out = Self::new(PathBuf::from(key));
+        out.title = parser.take("title");
+
+        if let Some(order) = parser.take_integer("order") {
+            out.order = order;
+        }
+
+        if let Some(enabled) = parser.take_boolean("enabled") {
+            out.enabled = enabled;
+        }
+
+        if let Some(upload) = parser.take_boolean("upload") {
+            out.upload = upload;
+        }
+
+        parser.check();
+        Some(out)
+    }
+
+    fn take_value(parser: Parser<'_>) -> Option<Self> {
+        Some(Self::new(parser.parse()?))
+    }
+}
+
+impl Config {
+    pub fn add_host(&mut self, new: HostConfig) {
+        let host = 'found: {
+            for host in &mut self.hosts {
+                if new.names.iter().any(|n| host.names.contains(n)) {
+                    break 'found host;
+                }
+
+                if new.macs.iter().any(|m| host.macs.contains(m)) {
+                    break 'found host;
+                }
+
+                if new.names.is_empty()
+                    && host.names.is_empty()
+                    && new.ping_address.is_some()
+                    && new.ping_address == host.ping_address
+                {
+                    break 'found host;
+                }
+            }
+
+            self.hosts.push(new);
+            return;
+        };
+
+        for mac in new.macs {
+            host.macs.insert(mac);
+        }
+
+        for name in new.names {
+            host.names.insert(name);
+        }
+
+        host.preferred_name = new.preferred_name.or(host.preferred_name.take());
+        host.ignore |= new.ignore;
+        host.ping.merge(&new.ping);
+        host.ping_address = new.ping_address.or(host.ping_address);
+        host.checks.extend(new.checks);
+        host.wake.merge(&new.wake);
+        host.quiet_hours.merge(&new.quiet_hours);
+        host.slo.merge(&new.slo);
+        host.snmp.merge(&new.snmp);
+        host.probe.merge(&new.probe);
+        host.tags.extend(new.tags);
+        host.depends_on.extend(new.depends_on);
+        host.display.merge(&new.display);
+        host.profile = new.profile.or(host.profile.take());
+        host.files.extend(new.files);
+    }
+
+    /// Add to configuration from the given path.
+    ///
+    /// If the path is a directory, all `.toml` files in it are loaded in
+    /// lexical order. Files referenced through `include` are loaded after the
+    /// file including them, so that settings in them take precedence.
+    pub fn add_from_path(&mut self, path: &Path, diag: &Diagnostics) -> Result<()> {
+        if path.is_dir() {
+            let mut paths = Vec::new();
+
+            for e in fs::read_dir(path).context("reading config directory")? {
+                let path = e?.path();
+
+                if path.extension().and_then(|e| e.to_str()) == Some("toml") && path.is_file() {
+                    paths.push(path);
+                }
+            }
+
+            paths.sort();
+
+            for path in paths {
+                self.add_from_path(&path, diag)
+                    .with_context(|| path.display().to_string())?;
+            }
+
+            return Ok(());
+        }
+
+        let Ok(bytes) = fs::read(path) else {
+            return Ok(());
+        };
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+
+        if self.files.contains(&canonical) {
+            tracing::warn!("{}: already loaded, skipping", path.display());
+            return Ok(());
+        }
+
+        self.files.push(canonical);
+
+        let text = String::from_utf8(bytes).context("config file is not valid UTF-8")?;
+        let table = DeTable::parse(&text).context("failed to parse config file")?;
+
+        let previous = diag.set_source(Some(Source {
+            path: path.to_owned(),
+            text: text.clone(),
+        }));
+
+        // Restore the previous source on every path, so that errors raised
+        // while loading this file don't confuse later diagnostics.
+        let result = self.add_from_value(path, DeValue::Table(table.into_inner()), diag);
+        diag.set_source(previous);
+        result
+    }
+
+    /// Add to configuration from a parsed value loaded from the given path.
+    fn add_from_value<'a>(
+        &mut self,
+        path: &Path,
+        value: DeValue<'a>,
+        diag: &'a Diagnostics,
+    ) -> Result<()> {
+        let mut parser = Parser::new(value, diag);
+
+        if let Some(bind) = parser.take("bind") {
+            self.bind = Some(bind);
+        }
+
+        if let Some(read_only) = parser.take_boolean("read_only") {
+            self.read_only = Some(read_only);
+        }
+
+        let home: Vec<PathBuf> = parser.take_iter("home");
+
+        if !home.is_empty() {
+            self.home = home;
+        }
+
+        if let Some(locale) = parser.take::<String>("locale") {
+            if !crate::i18n::is_supported(&locale) {
+                parser.warning_at(
+                    "locale",
+                    format_args!(
+                        "unsupported locale `{locale}`, expected one of {}",
+                        crate::i18n::supported().join(", ")
+                    ),
+                );
+            }
+
+            self.locale = Some(locale);
+        }
+
+        let interfaces: Vec<String> = parser.take_iter("interfaces");
+
+        if !interfaces.is_empty() {
+            self.interfaces = interfaces;
+        }
+
+        let include: Vec<String> = parser.take_iter("include");
+
+        if let Some(defaults) = parser.take_table("defaults", ProfileConfig::parse) {
+            self.defaults.merge(&defaults);
+        }
+
+        for NamedProfile(name, profile) in parser.take_flexible::<NamedProfile, Vec<_>>("profiles")
+        {
+            self.profiles.entry(name).or_default().merge(&profile);
+        }
+
+        for mut host in parser.take_flexible::<HostConfig, Vec<_>>("hosts") {
+            host.files.insert(path.to_owned());
+            self.add_host(host);
+        }
+
+        if let Some(access) = parser.take_from("mokuro", MokuroAccessConfig::parse) {
+            self.mokuro_access.merge(&access);
+        }
+
+        for mokuro in parser.take_flexible::<MokuroConfig, Vec<_>>("mokuro") {
+            self.mokuro.push(mokuro);
+        }
+
+        if let Some(admin) = parser.take_table("admin", AdminConfig::parse) {
+            self.admin.merge(&admin);
+        }
+
+        if let Some(showcase) = parser.take_table("showcase", ShowcaseConfig::parse) {
+            self.showcase.merge(&showcase);
+        }
+
+        if let Some(history) = parser.take_table("history", HistoryConfig::parse) {
+            self.history.merge(&history);
+        }
+
+        if let Some(dns) = parser.take_table("dns", DnsConfig::parse) {
+            self.dns.merge(&dns);
+        }
+
+        if let Some(discovery) = parser.take_table("discovery", DiscoveryConfig::parse) {
+            self.discovery.merge(&discovery);
+        }
+
+        if let Some(notify) = parser.take_table("notify", NotifyConfig::parse) {
+            self.notify.merge(&notify);
+        }
 
-                if new.macs.iter().any(|m| host.macs.contains(m)) {
-                    break 'found host;
-                }
-            }
+        if let Some(report) = parser.take_table("report", ReportConfig::parse) {
+            self.report.merge(&report);
+        }
 
-            self.hosts.push(new);
-            return;
-        };
+        if let Some(metrics) = parser.take_table("metrics", MetricsConfig::parse) {
+            self.metrics.merge(&metrics);
+        }
 
-        for mac in new.macs {
-            host.macs.insert(mac);
+        if let Some(script) = parser.take_table("script", ScriptConfig::parse) {
+            self.script.merge(&script);
         }
 
-        for name in new.names {
-            host.names.insert(name);
+        if let Some(power) = parser.take_table("power", PowerConfig::parse) {
+            self.power.merge(&power);
         }
 
-        host.preferred_name = new.preferred_name.or(host.preferred_name.take());
-        host.ignore |= new.ignore;
-    }
+        if let Some(supervisor) = parser.take_table("supervisor", SupervisorConfig::parse) {
+            self.supervisor.merge(&supervisor);
+        }
 
-    /// Add to configuration from the given path.
-    pub fn add_from_path(&mut self, path: &Path, diag: &Diagnostics) -> Result<()> {
-        let Ok(bytes) = fs::read(path) else {
-            return Ok(());
-        };
+        if let Some(sessions) = parser.take_table("sessions", SessionsConfig::parse) {
+            self.sessions.merge(&sessions);
+        }
 
-        let value: Value = toml::from_slice(&bytes).context("failed to parse config file")?;
-        let mut parser = Parser::new(value, diag);
+        for NamedUser(name, user) in parser.take_flexible::<NamedUser, Vec<_>>("users") {
+            self.users.insert(name, user);
+        }
 
-        if let Some(bind) = parser.take("bind") {
-            self.bind = Some(bind);
+        if let Some(login) = parser.take_table("login", LoginConfig::parse) {
+            self.login.merge(&login);
         }
 
-        self.home = parser.take_iter("home");
+        if let Some(headers) = parser.take_table("headers", HeadersConfig::parse) {
+            self.headers.merge(&headers);
+        }
 
-        for host in parser.take_flexible::<HostConfig, Vec<_>>("hosts") {
-            self.add_host(host);
+        for NamedSegment(name, segment) in parser.take_flexible::<NamedSegment, Vec<_>>("segments")
+        {
+            self.segments.entry(name).or_default().merge(&segment);
         }
 
-        for mokuro in parser.take_flexible::<MokuroConfig, Vec<_>>("mokuro") {
-            self.mokuro.push(mokuro);
+        for NamedExtension(name, extension) in
+            parser.take_flexible::<NamedExtension, Vec<_>>("extensions")
+        {
+            self.extensions.insert(name, extension);
+        }
+
+        for NamedWakeListener(bind, listener) in
+            parser.take_flexible::<NamedWakeListener, Vec<_>>("wake_listeners")
+        {
+            self.wake_listeners.insert(bind, listener);
         }
 
         parser.check();
+
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        for pattern in include {
+            let paths =
+                glob(base, &pattern).with_context(|| format!("expanding include `{pattern}`"))?;
+
+            if paths.is_empty() && !pattern.contains(['*', '?']) {
+                diag.error(format_args!("included file `{pattern}` does not exist"));
+            }
+
+            for path in paths {
+                self.add_from_path(&path, diag)
+                    .with_context(|| path.display().to_string())?;
+            }
+        }
+
         Ok(())
     }
 
@@ -167,13 +2969,16 @@ impl Config {
                 if host.names.contains(name) {
                     break 'found host;
                 }
+
+                if host.names.is_empty() && host.ping_address.is_some_and(|a| a.to_string() == name)
+                {
+                    break 'found host;
+                }
             }
 
             self.hosts.push(HostConfig {
-                macs: BTreeSet::new(),
-                names: BTreeSet::from([name.to_owned()]),
-                preferred_name: None,
                 ignore: true,
+                ..HostConfig::named(name.to_owned())
             });
 
             return;
@@ -183,15 +2988,96 @@ impl Config {
     }
 }
 
+/// Expand a pattern relative to `base`, where path components may contain
+/// `*` and `?` wildcards. Matching paths are returned in lexical order.
+fn glob(base: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    fn matches(pattern: &[u8], name: &[u8]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, None) => true,
+            (Some((b'*', rest)), _) => {
+                matches(rest, name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some((b'?', rest)), Some((_, name))) => matches(rest, name),
+            (Some((p, rest)), Some((n, name))) if p == n => matches(rest, name),
+            _ => false,
+        }
+    }
+
+    let mut current = vec![if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        base.to_owned()
+    }];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+
+        for path in current {
+            if !component.contains(['*', '?']) {
+                let path = path.join(component);
+
+                if path.exists() {
+                    next.push(path);
+                }
+
+                continue;
+            }
+
+            let Ok(entries) = fs::read_dir(&path) else {
+                continue;
+            };
+
+            for e in entries {
+                let e = e?;
+
+                let Some(name) = e.file_name().to_str().map(str::to_owned) else {
+                    continue;
+                };
+
+                if name.starts_with('.') && !component.starts_with('.') {
+                    continue;
+                }
+
+                if matches(component.as_bytes(), name.as_bytes()) {
+                    next.push(e.path());
+                }
+            }
+        }
+
+        current = next;
+    }
+
+    current.sort();
+    Ok(current)
+}
+
 #[must_use = "Parser must be consumed to maintain diagnostics"]
 struct Parser<'a> {
-    value: Value,
+    value: DeValue<'a>,
     diag: &'a Diagnostics,
+    /// Spans of keys which have been taken from the table being parsed.
+    taken: Vec<(String, Range<usize>)>,
 }
 
 impl<'a> Parser<'a> {
-    fn new(value: Value, diag: &'a Diagnostics) -> Self {
-        Self { value, diag }
+    fn new(value: DeValue<'a>, diag: &'a Diagnostics) -> Self {
+        Self {
+            value,
+            diag,
+            taken: Vec::new(),
+        }
+    }
+
+    /// Remove the value for the given key, keeping track of the span of the
+    /// key so that later diagnostics can refer to it.
+    fn remove(&mut self, key: &str) -> Option<Spanned<DeValue<'a>>> {
+        let DeValue::Table(table) = &mut self.value else {
+            return None;
+        };
+
+        let (key, value) = table.remove_entry(key)?;
+        self.taken.push((key.get_ref().to_string(), key.span()));
+        Some(value)
     }
 
     fn parse<T>(self) -> Option<T>
@@ -199,7 +3085,7 @@ impl<'a> Parser<'a> {
         T: FromStr<Err: fmt::Display>,
     {
         let out = match self.value {
-            Value::String(value) => match value.parse::<T>() {
+            DeValue::String(value) => match value.parse::<T>() {
                 Ok(value) => Some(value),
                 Err(error) => {
                     self.diag.error(format_args!("{error}"));
@@ -217,20 +3103,16 @@ impl<'a> Parser<'a> {
         out
     }
 
-    fn take_any<T>(&mut self, key: &str, parser: impl FnOnce(Value) -> T) -> T
+    fn take_any<T>(&mut self, key: &str, parser: impl FnOnce(DeValue<'a>) -> T) -> T
     where
         T: Default,
     {
-        let Value::Table(table) = &mut self.value else {
-            return T::default();
-        };
-
-        let Some(value) = table.remove(key) else {
+        let Some(value) = self.remove(key) else {
             return T::default();
         };
 
-        self.diag.key(key);
-        let value = parser(value);
+        self.diag.key(key, value.span());
+        let value = parser(value.into_inner());
         self.diag.pop();
         value
     }
@@ -241,22 +3123,22 @@ impl<'a> Parser<'a> {
         U: FromIterator<T> + Default,
     {
         self.take_any(key, |value| match value {
-            Value::String(value) => match value.parse::<T>() {
+            DeValue::String(value) => match value.parse::<T>() {
                 Ok(value) => U::from_iter([value]),
                 Err(error) => {
                     self.diag.error(format_args!("{error}"));
                     U::default()
                 }
             },
-            Value::Array(values) => {
+            DeValue::Array(values) => {
                 let mut iter = values.into_iter().enumerate();
 
                 let it = iter::from_fn(|| {
                     let (index, value) = iter.next()?;
-                    self.diag.index(index);
+                    self.diag.index(index, value.span());
 
-                    let value = match value {
-                        Value::String(value) => match value.parse::<T>() {
+                    let value = match value.into_inner() {
+                        DeValue::String(value) => match value.parse::<T>() {
                             Ok(value) => Some(value),
                             Err(error) => {
                                 self.diag.error(format_args!("{error}"));
@@ -284,12 +3166,63 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Take an integer or an array of integers.
+    fn take_integers<T, U>(&mut self, key: &str) -> U
+    where
+        T: TryFrom<i64>,
+        U: FromIterator<T> + Default,
+    {
+        fn integer<T>(diag: &Diagnostics, value: DeValue<'_>) -> Option<T>
+        where
+            T: TryFrom<i64>,
+        {
+            match value {
+                DeValue::Integer(value) => match i64::from_str_radix(value.as_str(), value.radix())
+                    .ok()
+                    .and_then(|n| T::try_from(n).ok())
+                {
+                    Some(value) => Some(value),
+                    None => {
+                        diag.error(format_args!("integer {value} is out of range"));
+                        None
+                    }
+                },
+                other => {
+                    diag.error(format_args!("expected integer, found {}", other.type_str()));
+                    None
+                }
+            }
+        }
+
+        self.take_any(key, |value| match value {
+            DeValue::Array(values) => {
+                let mut iter = values.into_iter().enumerate();
+
+                let it = iter::from_fn(|| {
+                    loop {
+                        let (index, value) = iter.next()?;
+                        self.diag.index(index, value.span());
+                        let value = integer(self.diag, value.into_inner());
+                        self.diag.pop();
+
+                        if let Some(value) = value {
+                            return Some(value);
+                        }
+                    }
+                });
+
+                U::from_iter(it)
+            }
+            value => U::from_iter(integer(self.diag, value)),
+        })
+    }
+
     fn take<T>(&mut self, key: &str) -> Option<T>
     where
         T: FromStr<Err: fmt::Display>,
     {
         self.take_any(key, |value| match value {
-            Value::String(value) => match value.parse::<T>() {
+            DeValue::String(value) => match value.parse::<T>() {
                 Ok(value) => Some(value),
                 Err(error) => {
                     self.diag.error(format_args!("{error}"));
@@ -304,9 +3237,53 @@ impl<'a> Parser<'a> {
         })
     }
 
+    fn take_integer<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: TryFrom<i64>,
+    {
+        self.take_any(key, |value| match value {
+            DeValue::Integer(value) => match i64::from_str_radix(value.as_str(), value.radix())
+                .ok()
+                .and_then(|n| T::try_from(n).ok())
+            {
+                Some(value) => Some(value),
+                None => {
+                    self.diag
+                        .error(format_args!("integer {value} is out of range"));
+                    None
+                }
+            },
+            other => {
+                self.diag
+                    .error(format_args!("expected integer, found {}", other.type_str()));
+                None
+            }
+        })
+    }
+
+    /// Take a duration, like `"500ms"`, `"2s"` or `"1m30s"`.
+    fn take_duration(&mut self, key: &str) -> Option<Duration> {
+        self.take_any(key, |value| match value {
+            DeValue::String(value) => match parse_duration(&value) {
+                Ok(value) => Some(value),
+                Err(error) => {
+                    self.diag.error(error);
+                    None
+                }
+            },
+            other => {
+                self.diag.error(format_args!(
+                    "expected duration like \"500ms\", \"2s\" or \"5m\", found {}",
+                    other.type_str()
+                ));
+                None
+            }
+        })
+    }
+
     fn take_boolean(&mut self, key: &str) -> Option<bool> {
         self.take_any(key, |value| match value {
-            Value::Boolean(value) => Some(value),
+            DeValue::Boolean(value) => Some(value),
             other => {
                 self.diag
                     .error(format_args!("expected boolean, found {}", other.type_str()));
@@ -321,15 +3298,17 @@ impl<'a> Parser<'a> {
         U: FromIterator<T> + Default,
     {
         self.take_any(key, |value| match value {
-            Value::Table(table) => {
+            DeValue::Table(table) => {
                 let mut it = table.into_iter();
 
                 let it = iter::from_fn(|| {
                     loop {
                         let (key, value) = it.next()?;
-                        self.diag.key(&key);
+                        self.diag.key(key.get_ref(), value.span());
 
-                        let Some(value) = T::take_table(&key, Parser::new(value, self.diag)) else {
+                        let value = Parser::new(value.into_inner(), self.diag);
+
+                        let Some(value) = T::take_table(key.get_ref(), value) else {
                             continue;
                         };
 
@@ -339,15 +3318,16 @@ impl<'a> Parser<'a> {
 
                 U::from_iter(it)
             }
-            Value::Array(values) => {
+            DeValue::Array(values) => {
                 let mut it = values.into_iter().enumerate();
 
                 let it = iter::from_fn(|| {
                     loop {
                         let (index, value) = it.next()?;
-                        self.diag.index(index);
+                        self.diag.index(index, value.span());
 
-                        let Some(value) = T::take_value(Parser::new(value, self.diag)) else {
+                        let Some(value) = T::take_value(Parser::new(value.into_inner(), self.diag))
+                        else {
                             continue;
                         };
 
@@ -368,13 +3348,154 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Take keys from a nested table using the given function, leaving the
+    /// remaining keys in place so that the table can be parsed further.
+    fn take_from<T>(&mut self, key: &str, f: impl FnOnce(&mut Parser<'a>) -> T) -> Option<T> {
+        let DeValue::Table(table) = &mut self.value else {
+            return None;
+        };
+
+        let value = table.get_mut(key)?;
+
+        if !matches!(value.get_ref(), DeValue::Table(..)) {
+            return None;
+        }
+
+        self.diag.key(key, value.span());
+        let inner = mem::replace(value.get_mut(), DeValue::Boolean(false));
+        let mut parser = Parser::new(inner, self.diag);
+        let out = f(&mut parser);
+        *value.get_mut() = parser.value;
+        self.diag.pop();
+        Some(out)
+    }
+
+    /// Take a nested table and parse it using the given function.
+    ///
+    /// The function is responsible for consuming the parser it is provided.
+    fn take_table<T>(&mut self, key: &str, f: impl FnOnce(Parser<'a>) -> Option<T>) -> Option<T> {
+        let value = self.remove(key)?;
+        self.diag.key(key, value.span());
+
+        match value.into_inner() {
+            value @ DeValue::Table(..) => f(Parser::new(value, self.diag)),
+            other => {
+                self.diag
+                    .error(format_args!("expected table, found {}", other.type_str()));
+                self.diag.pop();
+                None
+            }
+        }
+    }
+
+    /// Take a table and parse it using the given function, or a boolean which
+    /// is converted using `from_boolean`.
+    fn take_table_or_boolean<T>(
+        &mut self,
+        key: &str,
+        f: impl FnOnce(Parser<'a>) -> Option<T>,
+        from_boolean: impl FnOnce(bool) -> T,
+    ) -> Option<T> {
+        let value = self.remove(key)?;
+        self.diag.key(key, value.span());
+
+        match value.into_inner() {
+            value @ DeValue::Table(..) => f(Parser::new(value, self.diag)),
+            DeValue::Boolean(value) => {
+                self.diag.pop();
+                Some(from_boolean(value))
+            }
+            other => {
+                self.diag.error(format_args!(
+                    "expected table or boolean, found {}",
+                    other.type_str()
+                ));
+                self.diag.pop();
+                None
+            }
+        }
+    }
+
+    /// Take an array of tables and parse each element using the given
+    /// function. A single table is treated as an array with one element.
+    ///
+    /// The function is responsible for consuming the parser it is provided.
+    fn take_tables<T, U>(&mut self, key: &str, mut f: impl FnMut(Parser<'a>) -> Option<T>) -> U
+    where
+        U: FromIterator<T> + Default,
+    {
+        let diag = self.diag;
+
+        self.take_any(key, |value| match value {
+            value @ DeValue::Table(..) => {
+                diag.index(0, diag.span());
+                U::from_iter(f(Parser::new(value, diag)))
+            }
+            DeValue::Array(values) => {
+                let mut it = values.into_iter().enumerate();
+
+                let it = iter::from_fn(|| {
+                    loop {
+                        let (index, value) = it.next()?;
+                        diag.index(index, value.span());
+
+                        let value = match value.into_inner() {
+                            value @ DeValue::Table(..) => f(Parser::new(value, diag)),
+                            other => {
+                                diag.error(format_args!(
+                                    "expected table, found {}",
+                                    other.type_str()
+                                ));
+                                diag.pop();
+                                None
+                            }
+                        };
+
+                        if let Some(value) = value {
+                            return Some(value);
+                        }
+                    }
+                });
+
+                U::from_iter(it)
+            }
+            other => {
+                diag.error(format_args!(
+                    "expected table or array, found {}",
+                    other.type_str()
+                ));
+                U::default()
+            }
+        })
+    }
+
+    /// Report an error at the current location of the parser.
+    fn error(&self, message: impl fmt::Display) {
+        self.diag.error(message);
+    }
+
+    /// Report a warning for the given key of the current table.
+    fn warning_at(&self, key: &str, message: impl fmt::Display) {
+        let span = self
+            .taken
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, span)| span.clone());
+
+        self.diag.key(key, span.unwrap_or_else(|| self.diag.span()));
+        self.diag.warning(message);
+        self.diag.pop();
+    }
+
     fn check(self) {
         match self.value {
-            Value::Table(table) => {
+            DeValue::Table(table) => {
                 for (key, value) in table {
-                    self.diag.key(&key);
-                    self.diag
-                        .error(format_args!("unexpected key of type {}", value.type_str()));
+                    self.diag.key(key.get_ref(), key.span());
+                    self.diag.warning(format_args!(
+                        "unexpected key of type {}, ignoring",
+                        value.get_ref().type_str()
+                    ));
                     self.diag.pop();
                 }
             }
@@ -390,14 +3511,158 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Parse a duration consisting of one or more numbers with units, like
+/// `"500ms"`, `"2s"`, `"5m"` or `"1h30m"`.
+pub(crate) fn parse_duration(input: &str) -> Result<Duration, String> {
+    const UNITS: &[(&str, Duration)] = &[
+        ("ms", Duration::from_millis(1)),
+        ("s", Duration::from_secs(1)),
+        ("m", Duration::from_secs(60)),
+        ("h", Duration::from_secs(3600)),
+        ("d", Duration::from_secs(86400)),
+    ];
+
+    let invalid = || {
+        format!(
+            "invalid duration `{input}`, expected a number with a unit (ms, s, m, h, d) like \"500ms\", \"2s\" or \"5m\""
+        )
+    };
+
+    let mut rest = input.trim();
+
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total = Duration::ZERO;
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+
+        let (number, tail) = rest.split_at(digits);
+        let number = number.parse::<u32>().map_err(|_| invalid())?;
+
+        let unit = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+
+        let (unit, tail) = tail.split_at(unit);
+
+        let Some((_, scale)) = UNITS.iter().find(|(name, _)| *name == unit.trim()) else {
+            return Err(invalid());
+        };
+
+        total = scale
+            .checked_mul(number)
+            .and_then(|d| total.checked_add(d))
+            .ok_or_else(invalid)?;
+
+        rest = tail.trim_start();
+    }
+
+    Ok(total)
+}
+
+/// Format a duration in the same format as accepted by the configuration.
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: &[(&str, u128)] = &[
+        ("d", 86_400_000),
+        ("h", 3_600_000),
+        ("m", 60_000),
+        ("s", 1_000),
+        ("ms", 1),
+    ];
+
+    let mut millis = duration.as_millis();
+
+    if millis == 0 {
+        return String::from("0s");
+    }
+
+    let mut out = String::new();
+
+    for &(unit, scale) in UNITS {
+        if millis >= scale {
+            _ = write!(out, "{}{unit}", millis / scale);
+            millis %= scale;
+        }
+    }
+
+    out
+}
+
 enum Step {
     Key(String),
     Index(usize),
 }
 
+/// The source of a configuration file being parsed.
+struct Source {
+    path: PathBuf,
+    text: String,
+}
+
+impl Source {
+    /// Translate a byte offset into a one-based line and column.
+    fn location(&self, offset: usize) -> (usize, usize) {
+        let before = self.text.get(..offset).unwrap_or(&self.text);
+        let line = before.matches('\n').count() + 1;
+        let start = before.rfind('\n').map_or(0, |n| n + 1);
+        let column = before[start..].chars().count() + 1;
+        (line, column)
+    }
+}
+
+/// The severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single reported diagnostic.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    /// The severity of the diagnostic.
+    pub severity: Severity,
+    /// The message of the diagnostic, including where it was reported.
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Construct a new diagnostic.
+    pub fn new(severity: Severity, message: impl fmt::Display) -> Self {
+        Self {
+            severity,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
 struct DiagnosticsInner {
-    path: Vec<Step>,
-    errors: Vec<String>,
+    source: Option<Source>,
+    path: Vec<(Step, Range<usize>)>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 /// Collected diagnostics.
@@ -410,25 +3675,47 @@ impl Diagnostics {
     pub fn new() -> Self {
         Self {
             inner: RefCell::new(DiagnosticsInner {
+                source: None,
                 path: Vec::new(),
-                errors: Vec::new(),
+                diagnostics: Vec::new(),
             }),
         }
     }
 
-    /// Convert into errors.
-    pub(crate) fn into_errors(self) -> Vec<String> {
-        self.inner.into_inner().errors
+    /// Convert into reported diagnostics.
+    pub(crate) fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.inner.into_inner().diagnostics
     }
 }
 
 impl Diagnostics {
-    fn index(&self, index: usize) {
-        self.inner.borrow_mut().path.push(Step::Index(index));
+    /// Set the source diagnostics are being reported for, returning the
+    /// previous one.
+    fn set_source(&self, source: Option<Source>) -> Option<Source> {
+        core::mem::replace(&mut self.inner.borrow_mut().source, source)
+    }
+
+    /// The span of the current location.
+    fn span(&self) -> Range<usize> {
+        let this = self.inner.borrow();
+        this.path
+            .last()
+            .map(|(_, span)| span.clone())
+            .unwrap_or(0..0)
+    }
+
+    fn index(&self, index: usize, span: Range<usize>) {
+        self.inner
+            .borrow_mut()
+            .path
+            .push((Step::Index(index), span));
     }
 
-    fn key(&self, key: &str) {
-        self.inner.borrow_mut().path.push(Step::Key(key.to_owned()));
+    fn key(&self, key: &str, span: Range<usize>) {
+        self.inner
+            .borrow_mut()
+            .path
+            .push((Step::Key(key.to_owned()), span));
     }
 
     fn pop(&self) {
@@ -436,10 +3723,31 @@ impl Diagnostics {
     }
 
     fn error(&self, message: impl fmt::Display) {
+        self.report(Severity::Error, message);
+    }
+
+    fn warning(&self, message: impl fmt::Display) {
+        self.report(Severity::Warning, message);
+    }
+
+    fn report(&self, severity: Severity, message: impl fmt::Display) {
         let mut error = String::new();
         let mut this = self.inner.borrow_mut();
 
-        for step in &this.path {
+        if let Some(source) = &this.source {
+            _ = write!(error, "{}", source.path.display());
+
+            if let Some((_, span)) = this.path.last() {
+                let (line, column) = source.location(span.start);
+                _ = write!(error, ":{line}:{column}");
+            }
+
+            error.push_str(": ");
+        }
+
+        let prefix = error.len();
+
+        for (step, _) in &this.path {
             match step {
                 Step::Key(key) => {
                     error.push('.');
@@ -460,11 +3768,15 @@ impl Diagnostics {
             }
         }
 
-        if !error.is_empty() {
+        if error.len() > prefix {
             error.push_str(": ");
         }
 
         _ = write!(error, "{}", message);
-        this.errors.push(error);
+
+        this.diagnostics.push(Diagnostic {
+            severity,
+            message: error,
+        });
     }
 }
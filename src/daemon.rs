@@ -0,0 +1,205 @@
+//! Hardening for init systems which don't do it on behalf of wolo, like BSD rc
+//! scripts: setting the file mode creation mask, writing a pidfile and
+//! dropping privileges once sockets have been created.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Parse a file mode creation mask in octal, like `027`.
+pub(crate) fn parse_umask(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("{s}: expected an octal mode like 027")),
+    }
+}
+
+/// Set the file mode creation mask of the process.
+#[cfg(unix)]
+pub(crate) fn set_umask(mode: u32) -> Result<()> {
+    // SAFETY: umask can't fail.
+    unsafe {
+        libc::umask(mode as libc::mode_t);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_umask(_: u32) -> Result<()> {
+    anyhow::bail!("setting the umask is only supported on unix")
+}
+
+/// A file containing the id of the process, which is removed when dropped.
+pub(crate) struct Pidfile {
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Write the id of the process to the given path.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        fs::write(path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("writing {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => {
+                tracing::warn!("{}: failed to remove pidfile: {error}", self.path.display());
+            }
+        }
+    }
+}
+
+/// Switch to the given user and group.
+///
+/// If only a user is given, its primary group is used. Supplementary groups
+/// are cleared so that none of those of the starting user are kept.
+#[cfg(unix)]
+pub(crate) fn drop_privileges(user: Option<&str>, group: Option<&str>) -> Result<()> {
+    let user = user.map(lookup_user).transpose()?;
+
+    let gid = match group {
+        Some(group) => Some(lookup_group(group)?),
+        None => user.map(|(_, gid)| gid),
+    };
+
+    // The group has to be changed first, since that's no longer permitted once
+    // the user has been changed.
+    if let Some(gid) = gid {
+        // SAFETY: The pointer is to a single valid gid.
+        if unsafe { libc::setgroups(1, &gid) } != 0 {
+            return Err(io::Error::last_os_error()).context("clearing supplementary groups");
+        }
+
+        // SAFETY: setgid has no memory safety requirements.
+        if unsafe { libc::setgid(gid) } != 0 {
+            return Err(io::Error::last_os_error()).with_context(|| format!("setting group {gid}"));
+        }
+    }
+
+    if let Some((uid, _)) = user {
+        // SAFETY: setuid has no memory safety requirements.
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(io::Error::last_os_error()).with_context(|| format!("setting user {uid}"));
+        }
+    }
+
+    // SAFETY: getuid and getgid can't fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    tracing::info!(uid, gid, "Dropped privileges");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn drop_privileges(_: Option<&str>, _: Option<&str>) -> Result<()> {
+    anyhow::bail!("dropping privileges is only supported on unix")
+}
+
+/// Look up the id and primary group of a user by name.
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<(libc::uid_t, libc::gid_t)> {
+    use std::ffi::CString;
+
+    let c_name = CString::new(name).with_context(|| format!("{name}: invalid user name"))?;
+    let mut buf = vec![0u8; 1024];
+
+    loop {
+        // SAFETY: passwd is plain data which is filled in by getpwnam_r.
+        let mut pwd = unsafe { core::mem::zeroed::<libc::passwd>() };
+        let mut result = core::ptr::null_mut();
+
+        // SAFETY: All pointers are valid, and the buffer length is correct.
+        let code = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        match code {
+            0 if result.is_null() => anyhow::bail!("{name}: no such user"),
+            0 => return Ok((pwd.pw_uid, pwd.pw_gid)),
+            libc::ERANGE => buf.resize(buf.len() * 2, 0),
+            code => {
+                return Err(io::Error::from_raw_os_error(code))
+                    .with_context(|| format!("{name}: looking up user"));
+            }
+        }
+    }
+}
+
+/// Look up the id of a group by name.
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Result<libc::gid_t> {
+    use std::ffi::CString;
+
+    let c_name = CString::new(name).with_context(|| format!("{name}: invalid group name"))?;
+    let mut buf = vec![0u8; 1024];
+
+    loop {
+        // SAFETY: group is plain data which is filled in by getgrnam_r.
+        let mut grp = unsafe { core::mem::zeroed::<libc::group>() };
+        let mut result = core::ptr::null_mut();
+
+        // SAFETY: All pointers are valid, and the buffer length is correct.
+        let code = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        match code {
+            0 if result.is_null() => anyhow::bail!("{name}: no such group"),
+            0 => return Ok(grp.gr_gid),
+            libc::ERANGE => buf.resize(buf.len() * 2, 0),
+            code => {
+                return Err(io::Error::from_raw_os_error(code))
+                    .with_context(|| format!("{name}: looking up group"));
+            }
+        }
+    }
+}
+
+/// Wait until the process is asked to terminate, so that it can exit
+/// normally and clean up after itself, like removing the pidfile.
+#[cfg(unix)]
+pub(crate) async fn terminated() -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate()).context("installing SIGTERM handler")?;
+    let mut interrupt = signal(SignalKind::interrupt()).context("installing SIGINT handler")?;
+
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = interrupt.recv() => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn terminated() -> Result<()> {
+    tokio::signal::ctrl_c()
+        .await
+        .context("installing Ctrl-C handler")
+}
@@ -0,0 +1,90 @@
+//! Hosts which recently disappeared from the list of hosts, like when they are
+//! removed from a hosts file, which are kept around with their last known
+//! status for a while before they are forgotten.
+
+use core::time::Duration;
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+use uuid::Uuid;
+
+use crate::events::{self, Event};
+use crate::hosts;
+use crate::ping_loop::HostStatus;
+
+/// How often hosts which have been listed for long enough are forgotten.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// A host which disappeared.
+#[derive(Debug, Clone)]
+pub struct Host {
+    /// The identifier the host had.
+    pub id: Uuid,
+    pub name: String,
+    /// The status of the host when it disappeared.
+    pub status: HostStatus,
+    pub disappeared: SystemTime,
+}
+
+/// State shared between the task keeping track of hosts which disappeared
+/// and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// Hosts which disappeared, most recent last.
+    pub hosts: Arc<Mutex<Vec<Host>>>,
+}
+
+impl State {
+    /// Construct a new empty state.
+    pub fn new() -> Self {
+        Self {
+            hosts: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+/// Spawn the task which keeps track of hosts which disappeared.
+pub async fn spawn(state: State, events: events::Log, hosts: hosts::State) {
+    let mut receiver = events.subscribe();
+    let mut tick = time::interval(INTERVAL);
+
+    loop {
+        let event = tokio::select! {
+            _ = tick.tick() => None,
+            result = receiver.recv() => match result {
+                Ok(event) => Some(event),
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!("Disappeared hosts missed {n} events");
+                    None
+                }
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        let grace = hosts.config().await.discovery.disappeared();
+        let current = hosts.hosts();
+        let mut disappeared = state.hosts.lock().await;
+
+        if let Some(Event::HostDisappeared { id, name, status }) = event {
+            disappeared.retain(|h| h.id != id && h.name != name);
+
+            disappeared.push(Host {
+                id,
+                name,
+                status,
+                disappeared: SystemTime::now(),
+            });
+        }
+
+        // Hosts are forgotten once they've been listed for long enough, or
+        // if they reappear.
+        disappeared.retain(|h| {
+            h.disappeared.elapsed().unwrap_or_default() < grace
+                && !current.iter().any(|c| c.names().any(|n| n == h.name))
+        });
+    }
+}
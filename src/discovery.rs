@@ -0,0 +1,187 @@
+//! Discovery of devices which appear in the neighbor table of the machine
+//! wolo runs on, used to notify about devices which have never been seen
+//! before.
+
+use core::net::Ipv4Addr;
+use core::time::Duration;
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use macaddr::MacAddr6;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use crate::events::{self, Event};
+use crate::history::History;
+use crate::hosts;
+use crate::interfaces;
+use crate::neighbors;
+use crate::scripting::{self, Classification};
+
+/// How often the neighbor table is read.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// A device which has been seen for the first time.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub mac: MacAddr6,
+    pub address: Ipv4Addr,
+    /// The local interface the device was seen on.
+    pub device: String,
+    pub first_seen: SystemTime,
+}
+
+/// State shared between the discovery task and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// New devices which haven't been acknowledged, in the order they were
+    /// seen.
+    pub unacknowledged: Arc<Mutex<Vec<Device>>>,
+    /// Devices which have been marked as suspicious, and when they were
+    /// marked.
+    pub suspicious: Arc<Mutex<BTreeMap<MacAddr6, SystemTime>>>,
+    history: History,
+}
+
+impl State {
+    /// Construct a new state, populated with the devices marked as
+    /// suspicious in history.
+    pub async fn new(history: History) -> Result<Self> {
+        let suspicious = history.suspicious_devices().await?;
+
+        Ok(Self {
+            unacknowledged: Arc::new(Mutex::new(Vec::new())),
+            suspicious: Arc::new(Mutex::new(suspicious.into_iter().collect())),
+            history,
+        })
+    }
+
+    /// Mark a device as suspicious, which also acknowledges it.
+    pub async fn mark_suspicious(&self, mac: MacAddr6) -> Result<()> {
+        let now = SystemTime::now();
+        self.history.set_suspicious(mac, Some(now)).await?;
+        self.suspicious.lock().await.insert(mac, now);
+        self.acknowledge(mac).await;
+        Ok(())
+    }
+
+    /// Remove the suspicious mark of a device, returning `false` if it wasn't
+    /// marked.
+    pub async fn unmark_suspicious(&self, mac: MacAddr6) -> Result<bool> {
+        self.history.set_suspicious(mac, None).await?;
+        Ok(self.suspicious.lock().await.remove(&mac).is_some())
+    }
+
+    /// Acknowledge a new device, returning `false` if it wasn't waiting to be
+    /// acknowledged.
+    pub async fn acknowledge(&self, mac: MacAddr6) -> bool {
+        let mut unacknowledged = self.unacknowledged.lock().await;
+        let len = unacknowledged.len();
+        unacknowledged.retain(|d| d.mac != mac);
+        unacknowledged.len() != len
+    }
+}
+
+/// Spawn the discovery task.
+///
+/// Devices which have been seen are stored in history if it's enabled, so
+/// that they aren't considered new after a restart. Without history, or if
+/// no devices have been stored yet, the devices present when discovery starts
+/// are considered known.
+pub async fn spawn(
+    state: State,
+    hosts: hosts::State,
+    history: History,
+    events: events::Log,
+    scripting: scripting::State,
+) {
+    let mut known = HashSet::new();
+    let mut seeded = false;
+
+    match history.known_devices().await {
+        Ok(devices) => {
+            seeded = !devices.is_empty();
+            known.extend(devices);
+        }
+        Err(error) => {
+            tracing::error!("Loading known devices: {error:#}");
+        }
+    }
+
+    let mut tick = time::interval(INTERVAL);
+
+    loop {
+        tick.tick().await;
+
+        let config = hosts.config().await;
+
+        if !config.discovery.new_devices() {
+            state.unacknowledged.lock().await.clear();
+            continue;
+        }
+
+        let configured = hosts
+            .hosts()
+            .iter()
+            .flat_map(|h| h.macs.iter().copied())
+            .collect::<HashSet<_>>();
+
+        let now = SystemTime::now();
+
+        for n in neighbors::read().await {
+            if !interfaces::is_selected(&config.interfaces, &n.device)
+                || configured.contains(&n.mac)
+                || !known.insert(n.mac)
+            {
+                continue;
+            }
+
+            if let Err(error) = history.record_device(n.mac, now).await {
+                tracing::error!("Recording device {}: {error:#}", n.mac);
+            }
+
+            if !seeded {
+                continue;
+            }
+
+            let classification = scripting.classify_device(n.mac, n.address, &n.device).await;
+
+            if classification == Classification::Ignore {
+                tracing::info!("Ignoring new device {} as classified by script", n.mac);
+                continue;
+            }
+
+            tracing::info!("New device {} at {} on {}", n.mac, n.address, n.device);
+
+            let event = Event::DeviceDiscovered {
+                mac: n.mac,
+                address: n.address,
+                device: n.device.clone(),
+            };
+
+            events.publish(event).await;
+
+            if classification == Classification::Suspicious {
+                if let Err(error) = state.mark_suspicious(n.mac).await {
+                    tracing::error!("Marking device {} as suspicious: {error:#}", n.mac);
+                }
+
+                continue;
+            }
+
+            if config.discovery.acknowledge() {
+                state.unacknowledged.lock().await.push(Device {
+                    mac: n.mac,
+                    address: n.address,
+                    device: n.device,
+                    first_seen: now,
+                });
+            }
+        }
+
+        seeded = true;
+    }
+}
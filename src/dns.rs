@@ -0,0 +1,295 @@
+//! Minimal DNS client, used to query configured DNS servers directly instead
+//! of going through the system resolver.
+//!
+//! Only `A` and `AAAA` queries over UDP are supported, which is all that's
+//! needed to find the addresses of hosts.
+
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::time::Duration;
+
+use std::io;
+use std::net::UdpSocket;
+use std::time::Instant;
+
+use crate::config::DnsConfig;
+use crate::host_name_cache::ResolveErrorKind;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+const RCODE_REFUSED: u8 = 5;
+
+/// The largest response we accept over UDP.
+const MAX_RESPONSE: usize = 4096;
+
+/// Resolve a name by querying the configured servers.
+///
+/// Names without a dot are tried with each search domain before being tried
+/// as they are.
+pub fn resolve(config: &DnsConfig, name: &str) -> Result<Vec<IpAddr>, (ResolveErrorKind, String)> {
+    if let Ok(ip) = name.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+
+    let mut candidates = Vec::new();
+
+    match name.strip_suffix('.') {
+        Some(name) => candidates.push(name.to_owned()),
+        None => {
+            if !name.contains('.') {
+                for domain in &config.search {
+                    candidates.push(format!("{name}.{}", domain.trim_matches('.')));
+                }
+            }
+
+            candidates.push(name.to_owned());
+        }
+    }
+
+    let mut last = None;
+
+    for candidate in &candidates {
+        match resolve_name(config, candidate) {
+            Ok(addresses) => return Ok(addresses),
+            Err(error) => last = Some(error),
+        }
+    }
+
+    Err(last.unwrap_or((
+        ResolveErrorKind::Other,
+        String::from("no DNS servers configured"),
+    )))
+}
+
+/// Resolve a single fully qualified name.
+fn resolve_name(config: &DnsConfig, name: &str) -> Result<Vec<IpAddr>, (ResolveErrorKind, String)> {
+    let mut last = None;
+
+    // Servers are tried in order until one gives an answer, where a name
+    // which doesn't exist is an answer.
+    for server in &config.servers {
+        let mut addresses = Vec::new();
+        let mut not_found = 0;
+
+        let result = [TYPE_A, TYPE_AAAA].into_iter().try_for_each(|qtype| {
+            match query(server.0, name, qtype, config.timeout())? {
+                Response::Addresses(found) => addresses.extend(found),
+                Response::NotFound => not_found += 1,
+            }
+
+            Ok(())
+        });
+
+        match result {
+            Ok(()) if !addresses.is_empty() => return Ok(addresses),
+            Ok(()) if not_found > 0 => {
+                return Err((
+                    ResolveErrorKind::NotFound,
+                    format!("name doesn't exist (NXDOMAIN from {server})"),
+                ));
+            }
+            Ok(()) => {
+                return Err((
+                    ResolveErrorKind::NotFound,
+                    format!("no addresses (from {server})"),
+                ));
+            }
+            Err(error) => last = Some(error),
+        }
+    }
+
+    Err(last.unwrap_or((
+        ResolveErrorKind::Other,
+        String::from("no DNS servers configured"),
+    )))
+}
+
+enum Response {
+    Addresses(Vec<IpAddr>),
+    NotFound,
+}
+
+/// Send a single query and wait for its response.
+fn query(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> Result<Response, (ResolveErrorKind, String)> {
+    let other = |error: io::Error| (ResolveErrorKind::Other, format!("{server}: {error}"));
+
+    let id = rand::random::<u16>();
+    let request = build(id, name, qtype).map_err(|error| (ResolveErrorKind::NotFound, error))?;
+
+    let bind = match server {
+        SocketAddr::V4(..) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(..) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+
+    let socket = UdpSocket::bind(bind).map_err(other)?;
+    socket.connect(server).map_err(other)?;
+    socket.send(&request).map_err(other)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; MAX_RESPONSE];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err((
+                ResolveErrorKind::Unavailable,
+                format!("{server}: timed out after {timeout:?}"),
+            ));
+        }
+
+        socket.set_read_timeout(Some(remaining)).map_err(other)?;
+
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(error) => {
+                return Err((ResolveErrorKind::Unavailable, format!("{server}: {error}")));
+            }
+        };
+
+        // Stray responses, like late answers to earlier queries, are
+        // ignored.
+        let Some(response) = parse(&buf[..n], id, qtype) else {
+            continue;
+        };
+
+        return match response {
+            Ok(addresses) => Ok(Response::Addresses(addresses)),
+            Err(RCODE_NXDOMAIN) => Ok(Response::NotFound),
+            Err(RCODE_SERVFAIL) => Err((
+                ResolveErrorKind::Unavailable,
+                format!("{server}: server failure (SERVFAIL)"),
+            )),
+            Err(RCODE_REFUSED) => Err((
+                ResolveErrorKind::Failed,
+                format!("{server}: query refused (REFUSED)"),
+            )),
+            Err(rcode) => Err((
+                ResolveErrorKind::Failed,
+                format!("{server}: response code {rcode}"),
+            )),
+        };
+    }
+}
+
+/// Build a recursive query for the given name and type.
+fn build(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(18 + name.len());
+    out.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired.
+    out.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question and no other records.
+    out.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(String::from("invalid name"));
+        }
+
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+
+    out.push(0);
+
+    if out.len() - 12 > 255 {
+        return Err(String::from("name is too long"));
+    }
+
+    out.extend_from_slice(&qtype.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(out)
+}
+
+/// Parse a response to the query with the given id and type.
+///
+/// Returns `None` if the response doesn't belong to the query or is
+/// malformed, otherwise the addresses in it or its error response code.
+fn parse(bytes: &[u8], id: u16, qtype: u16) -> Option<Result<Vec<IpAddr>, u8>> {
+    let header = bytes.get(..12)?;
+
+    // Must be a response to our query.
+    if u16_at(header, 0)? != id || header[2] & 0x80 == 0 {
+        return None;
+    }
+
+    let rcode = header[3] & 0x0f;
+
+    if rcode != 0 {
+        return Some(Err(rcode));
+    }
+
+    let questions = u16_at(header, 4)?;
+    let answers = u16_at(header, 6)?;
+    let mut at = 12;
+
+    for _ in 0..questions {
+        at = skip_name(bytes, at)? + 4;
+    }
+
+    let mut addresses = Vec::new();
+
+    for _ in 0..answers {
+        at = skip_name(bytes, at)?;
+        let rtype = u16_at(bytes, at)?;
+        let len = usize::from(u16_at(bytes, at + 8)?);
+        let data = bytes.get(at + 10..at + 10 + len)?;
+        at += 10 + len;
+
+        // Other records, like the CNAMEs leading up to the address, are
+        // skipped.
+        if rtype != qtype {
+            continue;
+        }
+
+        match (rtype, data.len()) {
+            (TYPE_A, 4) => {
+                addresses.push(IpAddr::V4(Ipv4Addr::new(
+                    data[0], data[1], data[2], data[3],
+                )));
+            }
+            (TYPE_AAAA, 16) => {
+                let data = <[u8; 16]>::try_from(data).ok()?;
+                addresses.push(IpAddr::V6(Ipv6Addr::from(data)));
+            }
+            _ => {}
+        }
+    }
+
+    Some(Ok(addresses))
+}
+
+/// Skip over a possibly compressed name, returning the offset after it.
+fn skip_name(bytes: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        let len = *bytes.get(at)?;
+
+        match len {
+            0 => return Some(at + 1),
+            // A pointer to a name elsewhere ends the name.
+            len if len & 0xc0 == 0xc0 => return Some(at + 2),
+            len => at += 1 + usize::from(len),
+        }
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Option<u16> {
+    let b = bytes.get(at..at + 2)?;
+    Some(u16::from_be_bytes([b[0], b[1]]))
+}
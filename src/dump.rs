@@ -0,0 +1,553 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::config::{self, Config, HeadersConfig};
+use crate::hosts::Host;
+use crate::wake_on_lan;
+
+/// The format to dump configuration in.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+/// Sources configuration was loaded from.
+pub struct Sources<'a> {
+    pub ethers: &'a [PathBuf],
+    pub hosts: &'a [PathBuf],
+    pub home: Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct Dump<'a> {
+    bind: String,
+    #[serde(skip_serializing_if = "is_false")]
+    read_only: bool,
+    config_files: &'a [PathBuf],
+    ethers_files: &'a [PathBuf],
+    hosts_files: &'a [PathBuf],
+    home: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<DumpHistory<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dns: Option<DumpDns<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discovery: Option<DumpDiscovery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notify: Option<DumpNotify>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<DumpReport<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<DumpMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script: Option<&'a PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power: Option<DumpPower>,
+    supervisor: DumpSupervisor,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    users: BTreeMap<&'a str, DumpUser>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    login: Option<DumpLogin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<DumpHeaders>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    segments: BTreeMap<&'a str, DumpSegment<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    extensions: BTreeMap<&'a str, DumpExtension<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    wake_listeners: BTreeMap<String, DumpWakeListener<'a>>,
+    mokuro: Vec<DumpMokuro<'a>>,
+    ignored_hosts: Vec<&'a str>,
+    hosts: Vec<DumpHost<'a>>,
+}
+
+#[derive(Serialize)]
+struct DumpHistory<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a PathBuf>,
+    raw: String,
+    aggregates: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    influx: Option<DumpInflux<'a>>,
+}
+
+/// The token and the query of the URL are left out since they contain
+/// secrets.
+#[derive(Serialize)]
+struct DumpInflux<'a> {
+    url: String,
+    measurement: &'a str,
+}
+
+#[derive(Serialize)]
+struct DumpDns<'a> {
+    servers: Vec<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    search: &'a [String],
+    timeout: String,
+}
+
+#[derive(Serialize)]
+struct DumpDiscovery {
+    new_devices: bool,
+    acknowledge: bool,
+    echo_requests: bool,
+    disappeared: String,
+}
+
+/// Paths of webhooks are left out since they often contain secrets.
+#[derive(Serialize)]
+struct DumpNotify {
+    webhooks: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DumpPower {
+    actions: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DumpSupervisor {
+    restarts: u32,
+    backoff: String,
+    max_backoff: String,
+}
+
+#[derive(Serialize)]
+struct DumpReport<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a PathBuf>,
+    notify: bool,
+    interval: String,
+}
+
+#[derive(Serialize)]
+struct DumpMetrics {
+    push: DumpMetricsPush,
+}
+
+/// The path of the URL is left out like for webhooks.
+#[derive(Serialize)]
+struct DumpMetricsPush {
+    url: String,
+    format: String,
+    interval: String,
+}
+
+/// The password hash of a user is left out.
+#[derive(Serialize)]
+struct DumpUser {
+    role: String,
+}
+
+#[derive(Serialize)]
+struct DumpLogin {
+    max_failures: u32,
+    lockout: String,
+}
+
+#[derive(Serialize)]
+struct DumpHeaders {
+    content_security_policy: String,
+    referrer_policy: String,
+}
+
+/// The password of a segment is left out, only whether one is set.
+#[derive(Serialize)]
+struct DumpSegment<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    subnets: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    tags: &'a BTreeSet<String>,
+    protected: bool,
+}
+
+#[derive(Serialize)]
+struct DumpMokuro<'a> {
+    path: &'a PathBuf,
+    title: String,
+    order: i64,
+    enabled: bool,
+    upload: bool,
+}
+
+#[derive(Serialize)]
+struct DumpHost<'a> {
+    id: String,
+    names: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred_name: Option<&'a str>,
+    macs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ping_address: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_addresses: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_prefer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detect_url: Option<bool>,
+    ping: DumpPing,
+    wake: DumpWake,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quiet_hours: Option<DumpQuietHours>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slo: Option<DumpSlo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snmp: Option<DumpSnmp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    probe: Option<DumpProbe>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    check: Vec<DumpCheck<'a>>,
+}
+
+#[derive(Serialize)]
+struct DumpPing {
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    family: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DumpWake {
+    enabled: bool,
+    target: String,
+    attempts: u32,
+    retry: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    on_demand: Vec<u16>,
+}
+
+#[derive(Serialize)]
+struct DumpQuietHours {
+    start: String,
+    end: String,
+    interval: String,
+}
+
+#[derive(Serialize)]
+struct DumpSlo {
+    window: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    availability: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rtt: Option<String>,
+    percentile: u8,
+}
+
+/// The community is left out since it's a secret.
+#[derive(Serialize)]
+struct DumpSnmp {
+    port: u16,
+    interval: String,
+}
+
+#[derive(Serialize)]
+struct DumpProbe {
+    port: u16,
+    duration: String,
+}
+
+#[derive(Serialize)]
+struct DumpCheck<'a> {
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    port: u16,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_skew: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extension: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct DumpExtension<'a> {
+    command: &'a [String],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    events: Vec<String>,
+    timeout: String,
+    concurrency: usize,
+}
+
+#[derive(Serialize)]
+struct DumpWakeListener<'a> {
+    host: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forward: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<String>,
+}
+
+/// Render the effective configuration in the given format.
+pub fn render(
+    format: Format,
+    bind: String,
+    config: &Config,
+    sources: Sources<'_>,
+    hosts: &[Host],
+) -> Result<String> {
+    let dump = Dump {
+        bind,
+        read_only: config.read_only(),
+        config_files: &config.files,
+        ethers_files: sources.ethers,
+        hosts_files: sources.hosts,
+        home: sources.home,
+        history: (config.history.path.is_some() || config.history.influx.url.is_some()).then(
+            || DumpHistory {
+                path: config.history.path.as_ref(),
+                raw: config::format_duration(config.history.raw()),
+                aggregates: config::format_duration(config.history.aggregates()),
+                influx: config.history.influx.url.as_ref().map(|url| {
+                    let mut url = url.clone();
+                    url.path
+                        .truncate(url.path.find('?').unwrap_or(url.path.len()));
+
+                    DumpInflux {
+                        url: url.to_string(),
+                        measurement: config.history.influx.measurement(),
+                    }
+                }),
+            },
+        ),
+        dns: (!config.dns.servers.is_empty()).then(|| DumpDns {
+            servers: config.dns.servers.iter().map(|s| s.to_string()).collect(),
+            search: &config.dns.search,
+            timeout: config::format_duration(config.dns.timeout()),
+        }),
+        discovery: (config.discovery.new_devices()
+            || config.discovery.echo_requests()
+            || config.discovery.disappeared.is_some())
+        .then(|| DumpDiscovery {
+            new_devices: config.discovery.new_devices(),
+            acknowledge: config.discovery.acknowledge(),
+            echo_requests: config.discovery.echo_requests(),
+            disappeared: config::format_duration(config.discovery.disappeared()),
+        }),
+        notify: (!config.notify.webhooks.is_empty()).then(|| DumpNotify {
+            webhooks: config
+                .notify
+                .webhooks
+                .iter()
+                .map(|w| {
+                    let mut w = w.clone();
+                    w.path = String::from("/");
+                    w.to_string()
+                })
+                .collect(),
+        }),
+        report: config.report.is_enabled().then(|| DumpReport {
+            path: config.report.path.as_ref(),
+            notify: config.report.notify(),
+            interval: config::format_duration(config.report.interval()),
+        }),
+        metrics: config.metrics.push.url.as_ref().map(|url| {
+            let mut url = url.clone();
+            url.path = String::from("/");
+
+            DumpMetrics {
+                push: DumpMetricsPush {
+                    url: url.to_string(),
+                    format: config.metrics.push.format().to_string(),
+                    interval: config::format_duration(config.metrics.push.interval()),
+                },
+            }
+        }),
+        script: config.script.path.as_ref(),
+        power: (!config.power.actions.is_empty()).then(|| DumpPower {
+            actions: config.power.actions.iter().map(|a| a.to_string()).collect(),
+        }),
+        supervisor: DumpSupervisor {
+            restarts: config.supervisor.restarts(),
+            backoff: config::format_duration(config.supervisor.backoff()),
+            max_backoff: config::format_duration(config.supervisor.max_backoff()),
+        },
+        users: config
+            .users
+            .iter()
+            .map(|(name, u)| {
+                let user = DumpUser {
+                    role: u.role.to_string(),
+                };
+
+                (name.as_str(), user)
+            })
+            .collect(),
+        login: (!config.users.is_empty()).then(|| DumpLogin {
+            max_failures: config.login.max_failures(),
+            lockout: config::format_duration(config.login.lockout()),
+        }),
+        headers: (config.headers != HeadersConfig::default()).then(|| DumpHeaders {
+            content_security_policy: config.headers.content_security_policy(),
+            referrer_policy: config.headers.referrer_policy().to_owned(),
+        }),
+        segments: config
+            .segments
+            .iter()
+            .map(|(name, s)| {
+                let segment = DumpSegment {
+                    title: s.title.as_deref(),
+                    subnets: s.subnets.iter().map(|s| s.to_string()).collect(),
+                    tags: &s.tags,
+                    protected: s.password.is_some(),
+                };
+
+                (name.as_str(), segment)
+            })
+            .collect(),
+        extensions: config
+            .extensions
+            .iter()
+            .map(|(name, e)| {
+                let extension = DumpExtension {
+                    command: &e.command,
+                    events: e.events.iter().map(|k| k.to_string()).collect(),
+                    timeout: config::format_duration(e.timeout()),
+                    concurrency: e.concurrency(),
+                };
+
+                (name.as_str(), extension)
+            })
+            .collect(),
+        wake_listeners: config
+            .wake_listeners
+            .iter()
+            .map(|(bind, l)| {
+                let listener = DumpWakeListener {
+                    host: &l.host,
+                    forward: l.forward,
+                    timeout: l.forward.map(|_| config::format_duration(l.timeout())),
+                };
+
+                (bind.to_string(), listener)
+            })
+            .collect(),
+        mokuro: config
+            .mokuro
+            .iter()
+            .map(|m| DumpMokuro {
+                path: &m.path,
+                title: m.title(),
+                order: m.order,
+                enabled: m.enabled,
+                upload: m.upload,
+            })
+            .collect(),
+        ignored_hosts: config
+            .hosts
+            .iter()
+            .filter(|h| h.ignore)
+            .flat_map(|h| h.names.iter().map(String::as_str))
+            .collect(),
+        hosts: hosts
+            .iter()
+            .map(|h| DumpHost {
+                id: h.id.to_string(),
+                names: h.names.iter().map(String::as_str).collect(),
+                preferred_name: h.preferred_name.as_deref(),
+                macs: h.macs.iter().map(|m| m.to_string()).collect(),
+                ping_address: h.ping_address.map(|a| a.to_string()),
+                tags: h.tags.iter().map(String::as_str).collect(),
+                depends_on: h.depends_on.iter().map(String::as_str).collect(),
+                kind: h.display.kind.map(|k| k.to_string()),
+                display_addresses: h.display.addresses,
+                display_prefer: h.display.prefer.map(|p| p.to_string()),
+                url: h.display.url.as_deref(),
+                detect_url: h.display.detect_url,
+                wake: DumpWake {
+                    enabled: h.wake.enabled.unwrap_or(true),
+                    target: wake_on_lan::target(&h.wake).to_string(),
+                    attempts: wake_on_lan::attempts(&h.wake),
+                    retry: config::format_duration(wake_on_lan::retry(&h.wake)),
+                    on_demand: h.wake.on_demand.clone(),
+                },
+                ping: DumpPing {
+                    enabled: h.ping.enabled.unwrap_or(true),
+                    interval: h.ping.interval.map(config::format_duration),
+                    timeout: h.ping.timeout.map(config::format_duration),
+                    slow: h.ping.slow.map(config::format_duration),
+                    family: h.ping.family.map(|f| f.to_string()),
+                },
+                quiet_hours: match (h.quiet_hours.start, h.quiet_hours.end) {
+                    (Some(start), Some(end)) => Some(DumpQuietHours {
+                        start: start.to_string(),
+                        end: end.to_string(),
+                        interval: config::format_duration(
+                            h.quiet_hours
+                                .interval
+                                .unwrap_or(config::QuietHoursConfig::DEFAULT_INTERVAL),
+                        ),
+                    }),
+                    _ => None,
+                },
+                slo: h.slo.is_configured().then(|| DumpSlo {
+                    window: config::format_duration(
+                        h.slo.window.unwrap_or(config::SloConfig::DEFAULT_WINDOW),
+                    ),
+                    availability: h.slo.availability.map(|a| a.to_string()),
+                    rtt: h.slo.rtt.map(config::format_duration),
+                    percentile: h.slo.percentile(),
+                }),
+                snmp: h.snmp.is_configured().then(|| DumpSnmp {
+                    port: h.snmp.port(),
+                    interval: config::format_duration(h.snmp.interval()),
+                }),
+                probe: h.probe.port.map(|port| DumpProbe {
+                    port,
+                    duration: config::format_duration(h.probe.duration()),
+                }),
+                check: h
+                    .checks
+                    .iter()
+                    .map(|c| DumpCheck {
+                        kind: c.kind.to_string(),
+                        name: c.name.as_deref(),
+                        port: c.port,
+                        path: &c.path,
+                        interval: c.interval.map(config::format_duration),
+                        timeout: c.timeout.map(config::format_duration),
+                        max_skew: (c.kind == config::CheckKind::Ntp)
+                            .then(|| config::format_duration(c.max_skew())),
+                        extension: c.extension.as_deref(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    let out = match format {
+        Format::Toml => toml::to_string_pretty(&dump)?,
+        Format::Json => serde_json::to_string_pretty(&dump)?,
+    };
+
+    Ok(out)
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
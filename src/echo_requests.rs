@@ -0,0 +1,145 @@
+//! Passive mode, where echo requests which other machines send to the machine
+//! wolo runs on are recorded in the timeline, which helps spot scanners.
+//!
+//! Echo requests are received through raw ICMP sockets, which get a copy of
+//! every ICMP packet the machine receives while the kernel keeps replying to
+//! them as usual.
+
+use core::net::IpAddr;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use lib::{Buffer, Pinger};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::events::{self, Event};
+use crate::hosts;
+use crate::ping_loop;
+
+/// Echo requests from an address which has been recorded within this long are
+/// only counted, so that something pinging continuously doesn't fill the
+/// timeline.
+pub const QUIET: Duration = Duration::from_secs(10 * 60);
+
+/// How long counts of echo requests which weren't recorded are kept.
+const FORGET: Duration = Duration::from_secs(60 * 60);
+
+/// Sockets echo requests are received through.
+pub struct Sockets {
+    v4: Pinger,
+    v6: Pinger,
+}
+
+/// Open the sockets echo requests are received through, which is only done if
+/// they are recorded when starting and `simulate` isn't set.
+pub fn sockets(simulate: bool, config: &Config) -> Result<Option<Arc<Sockets>>> {
+    if !config.discovery.echo_requests() {
+        return Ok(None);
+    }
+
+    if simulate {
+        tracing::info!("Not recording echo requests while simulating");
+        return Ok(None);
+    }
+
+    let v4 = Pinger::listen_v4().context("opening ICMPv4 listening socket")?;
+    let v6 = Pinger::listen_v6().context("opening ICMPv6 listening socket")?;
+    Ok(Some(Arc::new(Sockets { v4, v6 })))
+}
+
+/// Echo requests received from an address.
+struct Seen {
+    /// When echo requests from the address were last recorded.
+    recorded: Instant,
+    /// Echo requests received since then.
+    count: u64,
+}
+
+/// Spawn the task which records echo requests in the timeline.
+pub async fn spawn(
+    sockets: Option<Arc<Sockets>>,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+    events: events::Log,
+) -> Result<()> {
+    let Some(sockets) = sockets else {
+        return std::future::pending().await;
+    };
+
+    let Sockets { v4, v6 } = &*sockets;
+
+    let mut b1 = Buffer::new();
+    let mut b2 = Buffer::new();
+    let mut seen = HashMap::<IpAddr, Seen>::new();
+
+    loop {
+        let result = tokio::select! {
+            r = v4.recv(&mut b1) => r,
+            r = v6.recv(&mut b2) => r,
+        };
+
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                tracing::trace!("Receiving echo request: {error}");
+                continue;
+            }
+        };
+
+        if !response.outcome.is_echo_request() || response.source.is_loopback() {
+            continue;
+        }
+
+        if !hosts.config().await.discovery.echo_requests() {
+            seen.clear();
+            continue;
+        }
+
+        let now = Instant::now();
+        seen.retain(|_, s| now.saturating_duration_since(s.recorded) < FORGET);
+
+        if let Some(s) = seen.get_mut(&response.source)
+            && now.saturating_duration_since(s.recorded) < QUIET
+        {
+            s.count += 1;
+            continue;
+        }
+
+        let previous = seen.insert(
+            response.source,
+            Seen {
+                recorded: now,
+                count: 0,
+            },
+        );
+
+        let unrecorded = previous.map(|s| s.count).unwrap_or_default();
+
+        let host = host_of(&ping_state, response.source).await;
+        tracing::info!("Echo request from {} to {}", response.source, response.dest);
+
+        let event = Event::Pinged {
+            host,
+            source: response.source,
+            dest: response.dest,
+            unrecorded,
+        };
+
+        events.publish(event).await;
+    }
+}
+
+/// Find the host which the given address was last seen replying on.
+async fn host_of(ping_state: &ping_loop::State, address: IpAddr) -> Option<Uuid> {
+    let pinged = ping_state.pinged.lock().await;
+
+    pinged
+        .iter()
+        .find(|(_, p)| p.results.iter().any(|r| r.target == address))
+        .map(|(id, _)| *id)
+}
@@ -30,6 +30,28 @@ impl Serialize for Base64 {
 #[folder = "static/"]
 struct Assets;
 
+/// Assets which are used by name, and which wolo doesn't work without.
+pub(super) const REQUIRED: &[&str] = &[
+    "admin.html",
+    "error.html",
+    "home.html",
+    "home.md",
+    "interfaces.html",
+    "layout.html",
+    "login.html",
+    "logout.html",
+    "mokuro.html",
+    "mokuro_page.html",
+    "mokuro_search.html",
+    "mokuro_upload.html",
+    "network.html",
+    "network.js",
+    "network_report.html",
+    "report.html",
+    "style.css",
+    "timeline.html",
+];
+
 pub(super) fn hash() -> Base64 {
     const FILES: &[&str] = &["style.css", "network.js"];
 
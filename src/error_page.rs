@@ -0,0 +1,179 @@
+use axum::Json;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use serde::Serialize;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::embed::Base64;
+use crate::utils::Templates;
+
+/// Header used to communicate the correlation id of a request.
+static CORRELATION_ID: HeaderName = HeaderName::from_static("x-correlation-id");
+
+/// Details of an error response.
+///
+/// This is attached as an extension to responses produced by [`crate::Error`]
+/// and is rendered into a proper error page by [`middleware`].
+#[derive(Clone)]
+pub(crate) struct ErrorDetails {
+    pub(crate) detail: Option<String>,
+}
+
+/// The format an error should be rendered in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Html,
+    Problem,
+}
+
+impl Format {
+    /// Negotiate the format to use from the `Accept` header.
+    ///
+    /// API routes default to `application/problem+json` unless HTML is
+    /// explicitly preferred, everything else defaults to HTML.
+    fn negotiate(path: &str, headers: &HeaderMap) -> Self {
+        let mut html = None::<f32>;
+        let mut json = None::<f32>;
+
+        let accept = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+
+        for entry in accept {
+            let mut parts = entry.split(';');
+
+            let Some(mime) = parts.next() else {
+                continue;
+            };
+
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let slot = match mime.trim() {
+                "text/html" | "application/xhtml+xml" => &mut html,
+                "application/problem+json" | "application/json" => &mut json,
+                _ => continue,
+            };
+
+            *slot = Some(slot.map_or(q, |existing| existing.max(q)));
+        }
+
+        let api = path.starts_with("/api/");
+
+        match (html, json) {
+            (Some(html), Some(json)) if json > html => Format::Problem,
+            (Some(..), _) => Format::Html,
+            (None, Some(..)) => Format::Problem,
+            (None, None) if api => Format::Problem,
+            (None, None) => Format::Html,
+        }
+    }
+}
+
+/// Middleware which assigns a correlation id to every request and renders
+/// error responses as templated pages or `application/problem+json`.
+pub(crate) async fn middleware(
+    State(templates): State<Templates>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let id = uuid::Builder::from_random_bytes(rand::random()).into_uuid();
+    let format = Format::negotiate(req.uri().path(), req.headers());
+    let instance = req.uri().path().to_owned();
+
+    let span = tracing::info_span!("request", correlation_id = %id);
+    let mut response = next.run(req).instrument(span).await;
+
+    if let Some(details) = response.extensions_mut().remove::<ErrorDetails>() {
+        response = render(
+            &templates,
+            format,
+            response.status(),
+            &instance,
+            id,
+            details,
+        );
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+        response.headers_mut().insert(CORRELATION_ID.clone(), value);
+    }
+
+    response
+}
+
+fn render(
+    templates: &Templates,
+    format: Format,
+    status: StatusCode,
+    instance: &str,
+    id: Uuid,
+    details: ErrorDetails,
+) -> Response {
+    let title = status.canonical_reason().unwrap_or("Error");
+
+    match format {
+        Format::Html => {
+            #[derive(Serialize)]
+            struct Context<'a> {
+                hash: Base64,
+                title: &'a str,
+                status: u16,
+                detail: Option<String>,
+                correlation_id: Uuid,
+            }
+
+            let context = Context {
+                hash: crate::embed::hash(),
+                title,
+                status: status.as_u16(),
+                detail: details.detail,
+                correlation_id: id,
+            };
+
+            match templates.render("error.html", context) {
+                Ok(html) => (status, Html(html)).into_response(),
+                Err(error) => {
+                    tracing::error!("Failed to render error page: {error}");
+                    (status, format!("{} {title} ({id})", status.as_u16())).into_response()
+                }
+            }
+        }
+        Format::Problem => {
+            #[derive(Serialize)]
+            struct Problem<'a> {
+                #[serde(rename = "type")]
+                ty: &'static str,
+                title: &'a str,
+                status: u16,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                detail: Option<String>,
+                instance: &'a str,
+                correlation_id: Uuid,
+            }
+
+            let problem = Problem {
+                ty: "about:blank",
+                title,
+                status: status.as_u16(),
+                detail: details.detail,
+                instance,
+                correlation_id: id,
+            };
+
+            (
+                status,
+                [(header::CONTENT_TYPE, "application/problem+json")],
+                Json(problem),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Events published by subsystems, like hosts going up or down, wakes,
+//! configuration reloads, new devices and pings of this machine.
+//!
+//! Events are broadcast to subscribers like extensions and notifications, and
+//! the most recent ones are kept in a timeline.
+
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr};
+use core::str::FromStr;
+use core::time::Duration;
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use macaddr::MacAddr6;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, broadcast};
+use tokio::time;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::config::format_duration;
+use crate::echo_requests;
+use crate::hosts;
+use crate::ping_loop::{self, HostStatus};
+use crate::scripting;
+use crate::wake_queue;
+
+/// The number of events kept in the timeline.
+const CAPACITY: usize = 1000;
+
+/// The number of events buffered for each subscriber.
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+/// The kind of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A host started responding.
+    Up,
+    /// A host stopped responding or became unreachable.
+    Down,
+    /// A host or MAC address was woken.
+    Wake,
+    /// Configuration was reloaded.
+    Reload,
+    /// A device was seen for the first time.
+    Discovery,
+    /// This machine was pinged by another machine.
+    Ping,
+    /// A host disappeared from the list of hosts.
+    Gone,
+}
+
+impl Kind {
+    /// All kinds of events, in the order they are presented.
+    pub const ALL: [Kind; 7] = [
+        Kind::Up,
+        Kind::Down,
+        Kind::Wake,
+        Kind::Reload,
+        Kind::Discovery,
+        Kind::Ping,
+        Kind::Gone,
+    ];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Up => write!(f, "up"),
+            Kind::Down => write!(f, "down"),
+            Kind::Wake => write!(f, "wake"),
+            Kind::Reload => write!(f, "reload"),
+            Kind::Discovery => write!(f, "discovery"),
+            Kind::Ping => write!(f, "ping"),
+            Kind::Gone => write!(f, "gone"),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(Kind::Up),
+            "down" => Ok(Kind::Down),
+            "wake" => Ok(Kind::Wake),
+            "reload" => Ok(Kind::Reload),
+            "discovery" => Ok(Kind::Discovery),
+            "ping" => Ok(Kind::Ping),
+            "gone" => Ok(Kind::Gone),
+            other => Err(format!(
+                "unknown event kind `{other}`, expected up, down, wake, reload, discovery, ping or gone"
+            )),
+        }
+    }
+}
+
+/// Something which happened, as published by a subsystem.
+///
+/// The description of an event is its [`Display`] implementation, which
+/// doesn't include the name of the host.
+///
+/// [`Display`]: fmt::Display
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    /// A host started responding.
+    HostUp { host: Uuid },
+    /// A host stopped responding.
+    HostDown { host: Uuid },
+    /// A host became unreachable because a dependency is down.
+    HostUnreachable { host: Uuid },
+    /// Magic packets were sent for the first time to wake a host, or a MAC
+    /// address by the given name.
+    WakeSent { host: Option<Uuid>, name: String },
+    /// A host which was being woken responded on the given attempt.
+    WakeSucceeded { host: Option<Uuid>, attempt: u32 },
+    /// Waking a host was given up on after the given attempt.
+    WakeGaveUp { host: Option<Uuid>, attempt: u32 },
+    /// Configuration was reloaded with the given changes, where none means
+    /// that it was unchanged.
+    ConfigReloaded { changes: Vec<String> },
+    /// Reloaded configuration had errors, so the existing configuration was
+    /// kept.
+    ConfigRejected,
+    /// A device was seen for the first time.
+    DeviceDiscovered {
+        mac: MacAddr6,
+        address: Ipv4Addr,
+        /// The local interface the device was seen on.
+        device: String,
+    },
+    /// This machine was pinged by another machine, which was pinged
+    /// `unrecorded` more times since it was last recorded.
+    Pinged {
+        host: Option<Uuid>,
+        source: IpAddr,
+        dest: IpAddr,
+        unrecorded: u64,
+    },
+    /// A host disappeared from the list of hosts, like when it's removed from
+    /// a hosts file, while it had the given status.
+    ///
+    /// The host no longer exists, so it's referred to by name.
+    HostDisappeared {
+        id: Uuid,
+        name: String,
+        status: HostStatus,
+    },
+}
+
+impl Event {
+    /// The kind of the event.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Event::HostUp { .. } => Kind::Up,
+            Event::HostDown { .. } | Event::HostUnreachable { .. } => Kind::Down,
+            Event::WakeSent { .. } | Event::WakeSucceeded { .. } | Event::WakeGaveUp { .. } => {
+                Kind::Wake
+            }
+            Event::ConfigReloaded { .. } | Event::ConfigRejected => Kind::Reload,
+            Event::DeviceDiscovered { .. } => Kind::Discovery,
+            Event::Pinged { .. } => Kind::Ping,
+            Event::HostDisappeared { .. } => Kind::Gone,
+        }
+    }
+
+    /// The host the event concerns, if any.
+    pub fn host(&self) -> Option<Uuid> {
+        match *self {
+            Event::HostUp { host } | Event::HostDown { host } | Event::HostUnreachable { host } => {
+                Some(host)
+            }
+            Event::WakeSent { host, .. }
+            | Event::WakeSucceeded { host, .. }
+            | Event::WakeGaveUp { host, .. }
+            | Event::Pinged { host, .. } => host,
+            Event::ConfigReloaded { .. }
+            | Event::ConfigRejected
+            | Event::DeviceDiscovered { .. }
+            | Event::HostDisappeared { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::HostUp { .. } => write!(f, "Up"),
+            Event::HostDown { .. } => write!(f, "Down"),
+            Event::HostUnreachable { .. } => write!(f, "Unreachable because a dependency is down"),
+            Event::WakeSent { host: Some(..), .. } => write!(f, "{}", wake_queue::SENT),
+            Event::WakeSent { host: None, name } => write!(f, "{} to {name}", wake_queue::SENT),
+            Event::WakeSucceeded { attempt, .. } => write!(f, "Woke up on attempt {attempt}"),
+            Event::WakeGaveUp { attempt, .. } => write!(f, "Gave up after attempt {attempt}"),
+            Event::ConfigReloaded { changes } if changes.is_empty() => {
+                write!(f, "Configuration unchanged")
+            }
+            Event::ConfigReloaded { changes } => {
+                write!(f, "Configuration {}", changes.join(", "))
+            }
+            Event::ConfigRejected => write!(
+                f,
+                "Configuration had errors, keeping existing configuration"
+            ),
+            Event::DeviceDiscovered {
+                mac,
+                address,
+                device,
+            } => write!(f, "New device {mac} at {address} on {device}"),
+            Event::Pinged {
+                source,
+                dest,
+                unrecorded,
+                ..
+            } => {
+                write!(f, "Pinged by {source} at {dest}")?;
+
+                if *unrecorded > 0 {
+                    write!(
+                        f,
+                        ", after {unrecorded} more pings within {} of when it was last recorded",
+                        format_duration(echo_requests::QUIET)
+                    )?;
+                }
+
+                Ok(())
+            }
+            Event::HostDisappeared { name, status, .. } => {
+                let status = match status {
+                    HostStatus::Up => "up",
+                    HostStatus::Down => "down",
+                    HostStatus::Unknown => "unknown",
+                    HostStatus::Unreachable => "unreachable",
+                };
+
+                write!(f, "Host {name} disappeared while {status}")
+            }
+        }
+    }
+}
+
+/// An event in the timeline.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// When the event happened.
+    pub sampled: SystemTime,
+    pub kind: Kind,
+    /// The host the event concerns, if any.
+    pub host: Option<Uuid>,
+    /// Description of the event, which doesn't include the name of the host.
+    pub message: String,
+}
+
+/// Where events are published, which keeps the most recent ones in memory as
+/// the timeline.
+#[derive(Clone)]
+pub struct Log {
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+    sender: broadcast::Sender<Event>,
+}
+
+impl Log {
+    /// Construct a new empty timeline.
+    pub fn new() -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            sender: broadcast::channel(SUBSCRIBER_CAPACITY).0,
+        }
+    }
+
+    /// Subscribe to events as they are published.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Publish an event, which records it in the timeline.
+    pub async fn publish(&self, event: Event) {
+        let entry = Entry {
+            sampled: SystemTime::now(),
+            kind: event.kind(),
+            host: event.host(),
+            message: event.to_string(),
+        };
+
+        // Nobody might be subscribed, which is fine.
+        _ = self.sender.send(event);
+
+        let mut entries = self.entries.lock().await;
+
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+
+        entries.push_back(entry);
+    }
+
+    /// Get the events in the timeline, most recent first.
+    pub async fn events(&self) -> Vec<Entry> {
+        self.entries.lock().await.iter().rev().cloned().collect()
+    }
+}
+
+/// Spawn the task which records hosts going up or down.
+pub async fn spawn(
+    log: Log,
+    ping_state: ping_loop::State,
+    hosts: hosts::State,
+    scripting: scripting::State,
+) {
+    // The last known status of each host.
+    let mut last = HashMap::<Uuid, HostStatus>::new();
+    // The names of each host as of the last tick.
+    let mut known = HashMap::<Uuid, Vec<String>>::new();
+    let mut tick = time::interval(Duration::from_secs(1));
+
+    loop {
+        tick.tick().await;
+
+        let hosts = hosts.hosts();
+
+        let statuses = {
+            let pinged = ping_state.pinged.lock().await;
+            ping_loop::statuses(&hosts, &pinged)
+        };
+
+        let current = hosts
+            .iter()
+            .map(|h| (h.id, h.names().map(str::to_owned).collect::<Vec<_>>()))
+            .collect::<HashMap<_, _>>();
+
+        for (id, names) in mem::replace(&mut known, current) {
+            if known.contains_key(&id) {
+                continue;
+            }
+
+            // A host which is still known by any of its names has changed
+            // identity, like when a MAC address was added to it, rather than
+            // disappeared.
+            if hosts
+                .iter()
+                .any(|h| h.names().any(|n| names.iter().any(|o| o == n)))
+            {
+                continue;
+            }
+
+            let Some(name) = names.into_iter().next() else {
+                continue;
+            };
+
+            let status = last.get(&id).copied().unwrap_or(HostStatus::Unknown);
+            let event = Event::HostDisappeared { id, name, status };
+            log.publish(event).await;
+        }
+
+        last.retain(|id, _| statuses.contains_key(id));
+
+        for (id, status) in statuses {
+            // Hosts which haven't been pinged or can't be resolved haven't
+            // changed state.
+            if status.status == HostStatus::Unknown {
+                continue;
+            }
+
+            let previous = last.insert(id, status.status);
+
+            // The first status of a host, like right after starting, isn't a
+            // change.
+            let Some(previous) = previous else {
+                continue;
+            };
+
+            if previous == status.status {
+                continue;
+            }
+
+            let event = match status.status {
+                HostStatus::Up => Event::HostUp { host: id },
+                HostStatus::Down => Event::HostDown { host: id },
+                HostStatus::Unreachable => Event::HostUnreachable { host: id },
+                HostStatus::Unknown => continue,
+            };
+
+            log.publish(event).await;
+
+            if let Some(host) = hosts.iter().find(|h| h.id == id) {
+                scripting
+                    .on_host_state_change(host, previous, status.status)
+                    .await;
+            }
+        }
+    }
+}
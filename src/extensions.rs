@@ -0,0 +1,213 @@
+//! Extensions, which are external executables used as checks or notified
+//! about events in the timeline.
+//!
+//! Each invocation writes a JSON object to the stdin of the executable, which
+//! has a `hook` field naming why it was invoked. Checks are expected to print
+//! a JSON verdict like `{"ok": true, "message": "…"}` on stdout, while the
+//! output of notifications is ignored. Executables which don't finish in
+//! time are killed.
+
+use std::collections::HashMap;
+use std::io;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+
+use crate::config::ExtensionConfig;
+use crate::events::{self, Kind};
+use crate::hosts;
+
+/// Input given to an extension.
+#[derive(Serialize)]
+#[serde(tag = "hook", rename_all = "snake_case")]
+pub enum Input<'a> {
+    /// The extension is used as a check of a host.
+    Check {
+        host: &'a str,
+        names: Vec<&'a str>,
+        /// The configured port, if any.
+        port: Option<u16>,
+    },
+    /// An event was recorded in the timeline.
+    Event {
+        kind: Kind,
+        /// The name of the host the event concerns, if any.
+        host: Option<&'a str>,
+        message: &'a str,
+    },
+}
+
+/// The verdict of an extension used as a check.
+#[derive(Deserialize)]
+pub struct Verdict {
+    pub ok: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Limits how many instances of an extension run at the same time.
+struct Limit {
+    /// The concurrency the semaphore was created for.
+    concurrency: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+/// Runs extensions, limiting how many instances of each run at the same time.
+#[derive(Clone)]
+pub struct Runner {
+    /// Limits by the name of the extension.
+    limits: Arc<Mutex<HashMap<String, Limit>>>,
+}
+
+impl Runner {
+    /// Construct a new runner.
+    pub fn new() -> Self {
+        Self {
+            limits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Run an extension used as a check and parse its verdict.
+    pub async fn check(
+        &self,
+        name: &str,
+        config: &ExtensionConfig,
+        input: &Input<'_>,
+    ) -> Result<Verdict> {
+        let output = self.run(name, config, input).await?;
+        serde_json::from_slice(&output).context("malformed verdict")
+    }
+
+    /// Run an extension with the given input, returning what it printed on
+    /// stdout.
+    pub async fn run(
+        &self,
+        name: &str,
+        config: &ExtensionConfig,
+        input: &Input<'_>,
+    ) -> Result<Vec<u8>> {
+        let semaphore = self.semaphore(name, config.concurrency());
+        let _permit = semaphore.acquire_owned().await?;
+
+        let input = serde_json::to_vec(input)?;
+
+        let Some((program, args)) = config.command.split_first() else {
+            bail!("no command");
+        };
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("running {program}"))?;
+
+        let mut stdin = child.stdin.take().context("missing stdin")?;
+
+        let run = async move {
+            // Extensions which exit without reading their input still have
+            // their output and exit status collected.
+            match stdin.write_all(&input).await {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error),
+                _ => {}
+            }
+
+            drop(stdin);
+            child.wait_with_output().await
+        };
+
+        let Ok(output) = time::timeout(config.timeout(), run).await else {
+            bail!("timeout");
+        };
+
+        let output = output?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            match stderr.lines().rfind(|l| !l.trim().is_empty()) {
+                Some(line) => bail!("{}: {}", output.status, line.trim()),
+                None => bail!("{}", output.status),
+            }
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn semaphore(&self, name: &str, concurrency: usize) -> Arc<Semaphore> {
+        let mut limits = self.limits.lock().unwrap();
+
+        match limits.get(name) {
+            Some(limit) if limit.concurrency == concurrency => limit.semaphore.clone(),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(concurrency));
+
+                let limit = Limit {
+                    concurrency,
+                    semaphore: semaphore.clone(),
+                };
+
+                limits.insert(name.to_owned(), limit);
+                semaphore
+            }
+        }
+    }
+}
+
+/// Spawn the task which notifies extensions about published events.
+pub async fn spawn(runner: Runner, hosts: hosts::State, events: events::Log) {
+    let mut receiver = events.subscribe();
+
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(n)) => {
+                tracing::warn!("Extensions missed {n} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let config = hosts.config().await;
+
+        let host = event.host().and_then(|id| {
+            let hosts = hosts.hosts();
+            let host = hosts.iter().find(|h| h.id == id)?;
+            Some(host.names().next()?.to_owned())
+        });
+
+        for (name, extension) in &config.extensions {
+            if !extension.events.contains(&event.kind()) {
+                continue;
+            }
+
+            let runner = runner.clone();
+            let name = name.clone();
+            let extension = extension.clone();
+            let kind = event.kind();
+            let message = event.to_string();
+            let host = host.clone();
+
+            tokio::spawn(async move {
+                let input = Input::Event {
+                    kind,
+                    host: host.as_deref(),
+                    message: &message,
+                };
+
+                if let Err(error) = runner.run(&name, &extension, &input).await {
+                    tracing::warn!("Notifying extension `{name}`: {error:#}");
+                }
+            });
+        }
+    }
+}
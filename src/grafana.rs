@@ -0,0 +1,285 @@
+//! An API compatible with the JSON datasource of Grafana, so that dashboards
+//! can chart the round trip time and availability of hosts from history.
+//!
+//! Targets are named `<host>/rtt` for the mean round trip time in
+//! milliseconds, and `<host>/availability` for the share of answered pings
+//! between 0 and 1.
+
+use core::time::Duration;
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::history::History;
+use crate::hosts::{self, Host};
+use crate::showcase;
+
+/// The smallest step between points.
+const MIN_STEP: Duration = Duration::from_secs(1);
+
+struct S {
+    hosts: hosts::State,
+    history: History,
+    showcase: showcase::Helper,
+}
+
+pub(super) fn router(hosts: hosts::State, history: History, showcase: showcase::Helper) -> Router {
+    Router::new()
+        // Grafana tests the connection with the trailing slash.
+        .route("/grafana", get(test))
+        .route("/grafana/", get(test))
+        .route("/grafana/search", post(search))
+        .route("/grafana/query", post(query))
+        .with_state(Arc::new(S {
+            hosts,
+            history,
+            showcase,
+        }))
+}
+
+/// What a target charts.
+#[derive(Clone, Copy)]
+enum Series {
+    Rtt,
+    Availability,
+}
+
+impl Series {
+    const ALL: [Series; 2] = [Series::Rtt, Series::Availability];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Series::Rtt => "rtt",
+            Series::Availability => "availability",
+        }
+    }
+}
+
+/// Used by Grafana to test the connection to the datasource.
+async fn test() -> &'static str {
+    "OK"
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct SearchRequest {
+    target: String,
+}
+
+/// List targets containing the requested text.
+async fn search(
+    State(state): State<Arc<S>>,
+    body: Option<Json<SearchRequest>>,
+) -> Json<Vec<String>> {
+    let Json(request) = body.unwrap_or_default();
+    let hosts = state.hosts.hosts();
+    let mut showcase = state.showcase.lock().await;
+    let mut targets = Vec::new();
+
+    for host in hosts.iter() {
+        let Some(name) = host.names().next() else {
+            continue;
+        };
+
+        let name = showcase.host_name(host, name);
+
+        for series in Series::ALL {
+            let target = format!("{name}/{}", series.as_str());
+
+            if target.contains(&request.target) {
+                targets.push(target);
+            }
+        }
+    }
+
+    Json(targets)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryRequest {
+    range: Range,
+    #[serde(default)]
+    interval_ms: u64,
+    #[serde(default)]
+    max_data_points: u64,
+    targets: Vec<Target>,
+}
+
+#[derive(Deserialize)]
+struct Range {
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    hide: bool,
+}
+
+#[derive(Serialize)]
+struct TimeSeries {
+    target: String,
+    /// Pairs of a value and a time in milliseconds since the unix epoch.
+    datapoints: Vec<(f64, u64)>,
+}
+
+/// Get the points of the requested targets.
+async fn query(
+    State(state): State<Arc<S>>,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<TimeSeries>>, Error> {
+    let Some(from) = parse_rfc3339(&request.range.from) else {
+        return Err(Error::bad_request(format_args!(
+            "{}: expected an RFC 3339 time",
+            request.range.from
+        )));
+    };
+
+    let Some(to) = parse_rfc3339(&request.range.to) else {
+        return Err(Error::bad_request(format_args!(
+            "{}: expected an RFC 3339 time",
+            request.range.to
+        )));
+    };
+
+    let mut step = Duration::from_millis(request.interval_ms).max(MIN_STEP);
+
+    if let (Ok(range), Ok(points)) = (
+        to.duration_since(from),
+        u32::try_from(request.max_data_points),
+    ) && points > 0
+    {
+        step = step.max(range / points);
+    }
+
+    let hosts = state.hosts.hosts();
+    let mut out = Vec::new();
+
+    for target in &request.targets {
+        if target.hide {
+            continue;
+        }
+
+        let Some((name, series)) = target.target.rsplit_once('/') else {
+            return Err(Error::bad_request(format_args!(
+                "{}: expected a target like <host>/rtt",
+                target.target
+            )));
+        };
+
+        let Some(series) = Series::ALL.into_iter().find(|s| s.as_str() == series) else {
+            return Err(Error::bad_request(format_args!(
+                "{series}: expected one of rtt or availability"
+            )));
+        };
+
+        let Some(host) = state.find(&hosts, name).await else {
+            return Err(Error::bad_request(format_args!("{name}: no such host")));
+        };
+
+        let points = state.history.series(host.id, from, to, step).await?;
+
+        let datapoints = points
+            .iter()
+            .filter_map(|p| {
+                let value = match series {
+                    Series::Rtt => p.rtt?.as_secs_f64() * 1000.0,
+                    Series::Availability => p.answered as f64 / p.samples as f64,
+                };
+
+                let time = p.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                Some((value, time.as_millis() as u64))
+            })
+            .collect();
+
+        out.push(TimeSeries {
+            target: target.target.clone(),
+            datapoints,
+        });
+    }
+
+    Ok(Json(out))
+}
+
+impl S {
+    /// Find a host by one of the names it's listed under.
+    async fn find<'a>(&self, hosts: &'a [Host], name: &str) -> Option<&'a Host> {
+        let mut showcase = self.showcase.lock().await;
+
+        hosts
+            .iter()
+            .find(|h| h.names().any(|n| showcase.host_name(h, n) == name))
+    }
+}
+
+/// Parse a time in RFC 3339 format like `2024-05-01T12:00:00.000Z`, which is
+/// how Grafana sends the range of a query.
+fn parse_rfc3339(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+
+    let mut date = date.splitn(3, '-');
+    let year = date.next()?.parse::<i64>().ok()?;
+    let month = date.next()?.parse::<u32>().ok()?;
+    let day = date.next()?.parse::<u32>().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let at = time.rfind(['+', '-'])?;
+        let (time, offset) = time.split_at(at);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        (time, sign * offset)
+    };
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':');
+    let hour = time.next()?.parse::<i64>().ok()?;
+    let minute = time.next()?.parse::<i64>().ok()?;
+    let second = time.next()?.parse::<i64>().ok()?;
+
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        let digits = &fraction[..fraction.len().min(9)];
+        let value = digits.parse::<u32>().ok()?;
+        value * 10u32.pow(9 - digits.len() as u32)
+    };
+
+    let seconds =
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+
+    let seconds = u64::try_from(seconds).ok()?;
+    Some(UNIX_EPOCH + Duration::new(seconds, nanos))
+}
+
+/// The number of days since the unix epoch of a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
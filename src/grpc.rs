@@ -0,0 +1,250 @@
+//! A gRPC service for integrators who prefer typed clients generated from
+//! `proto/wolo.proto`.
+//!
+//! It offers the same operations as the REST API, which it shares the
+//! implementation of through [`api::Service`], and additionally streams
+//! updates to the state of hosts.
+//!
+//! Once users are configured, calls have to carry basic credentials of a user
+//! with the role the same operation requires through the REST API.
+
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::ConnectInfo;
+use tokio::sync::mpsc;
+use tokio::time;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::server::NamedService;
+use tonic::{Request, Response, Status};
+
+use crate::api::{self, WakeRequest};
+use crate::auth::Auth;
+use crate::config::Role;
+use crate::ping_loop::HostStatus;
+use crate::read_only::ReadOnly;
+use crate::{Error, ErrorKind};
+
+use self::proto::wake_request::Target;
+use self::proto::wolo_server::{Wolo, WoloServer};
+
+mod proto {
+    tonic::include_proto!("wolo.v1");
+}
+
+/// How often the state of hosts is checked for changes when watching them.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Grpc {
+    service: Arc<api::Service>,
+    read_only: ReadOnly,
+    auth: Auth,
+}
+
+impl Grpc {
+    /// Require basic credentials of a user with at least the given role once
+    /// users are configured.
+    async fn authorize<T>(&self, request: &Request<T>, role: Role) -> Result<(), Status> {
+        if !self.auth.is_enabled().await {
+            return Ok(());
+        }
+
+        let headers = request.metadata().clone().into_headers();
+
+        let user = match self.auth.authenticate(peer(request), &headers).await {
+            Ok(user) => user,
+            Err(..) => {
+                return Err(Status::resource_exhausted(
+                    "too many failed attempts to sign in",
+                ));
+            }
+        };
+
+        let Some(user) = user else {
+            return Err(Status::unauthenticated("missing or wrong credentials"));
+        };
+
+        if user.role < role {
+            return Err(Status::permission_denied(format!(
+                "signed in as {}, which doesn't have the {role} role",
+                user.name
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Routes of the gRPC service, which is served over HTTP/2 next to
+/// everything else.
+pub(super) fn router(service: Arc<api::Service>, read_only: ReadOnly, auth: Auth) -> Router {
+    let server = WoloServer::new(Grpc {
+        service,
+        read_only,
+        auth,
+    });
+    let path = format!("/{}/{{*rpc}}", WoloServer::<Grpc>::NAME);
+    Router::new().route_service(&path, server)
+}
+
+#[tonic::async_trait]
+impl Wolo for Grpc {
+    async fn list_hosts(
+        &self,
+        request: Request<proto::ListHostsRequest>,
+    ) -> Result<Response<proto::ListHostsResponse>, Status> {
+        self.authorize(&request, Role::Viewer).await?;
+
+        let hosts = self.service.hosts().await;
+
+        Ok(Response::new(proto::ListHostsResponse {
+            hosts: hosts.into_iter().map(host_state).collect(),
+        }))
+    }
+
+    type WatchHostsStream = ReceiverStream<Result<proto::HostState, Status>>;
+
+    async fn watch_hosts(
+        &self,
+        request: Request<proto::WatchHostsRequest>,
+    ) -> Result<Response<Self::WatchHostsStream>, Status> {
+        self.authorize(&request, Role::Viewer).await?;
+
+        let (tx, rx) = mpsc::channel(16);
+        let service = self.service.clone();
+
+        tokio::spawn(async move {
+            let mut last = HashMap::new();
+            let mut tick = time::interval(WATCH_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    _ = tx.closed() => return,
+                }
+
+                for host in service.hosts().await {
+                    let state = host_state(host);
+
+                    if last.get(&state.id) == Some(&state) {
+                        continue;
+                    }
+
+                    last.insert(state.id.clone(), state.clone());
+
+                    if tx.send(Ok(state)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn wake(
+        &self,
+        request: Request<proto::WakeRequest>,
+    ) -> Result<Response<proto::WakeResponse>, Status> {
+        self.authorize(&request, Role::Operator).await?;
+
+        if self.read_only.is_enabled().await {
+            return Err(Status::permission_denied(
+                "This instance is read-only, so nothing can be changed through it",
+            ));
+        }
+
+        let peer = peer(&request);
+        let request = wake_request(request.into_inner())?;
+
+        let woken = self.service.wake(peer, request).await.map_err(status)?;
+
+        Ok(Response::new(proto::WakeResponse {
+            macs: woken.macs,
+            targets: woken.targets,
+            attempts: woken.attempts,
+        }))
+    }
+}
+
+/// The address a request was made from.
+fn peer<T>(request: &Request<T>) -> IpAddr {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |c| c.0.ip())
+}
+
+/// Convert a wake request into the one used by the REST API.
+fn wake_request(request: proto::WakeRequest) -> Result<WakeRequest, Status> {
+    let mac = match request.target {
+        Some(Target::Host(host)) => return Ok(WakeRequest::Host { host }),
+        Some(Target::Mac(mac)) => mac,
+        None => return Err(Status::invalid_argument("missing host or MAC address")),
+    };
+
+    let target = match mac.target {
+        Some(target) => match target.parse() {
+            Ok(target) => Some(target),
+            Err(_) => {
+                let message = format!("{target}: invalid target address");
+                return Err(Status::invalid_argument(message));
+            }
+        },
+        None => None,
+    };
+
+    let port = match mac.port {
+        Some(port) => match u16::try_from(port) {
+            Ok(port) => Some(port),
+            Err(_) => return Err(Status::invalid_argument(format!("{port}: invalid port"))),
+        },
+        None => None,
+    };
+
+    Ok(WakeRequest::Mac {
+        mac: mac.mac,
+        target,
+        port,
+    })
+}
+
+fn host_state(host: api::Host) -> proto::HostState {
+    let status = match host.status {
+        HostStatus::Up => proto::Status::Up,
+        HostStatus::Down => proto::Status::Down,
+        HostStatus::Unreachable => proto::Status::Unreachable,
+        HostStatus::Unknown => proto::Status::Unknown,
+    };
+
+    proto::HostState {
+        id: host.id.to_string(),
+        names: host.names,
+        status: status.into(),
+        dependency: host.dependency.map(|id| id.to_string()),
+        arp_only: host.arp_only,
+        slow: host.slow,
+        quiet: host.quiet,
+        waking: host.waking.map(|w| proto::Waking {
+            attempt: w.attempt,
+            attempts: w.attempts,
+        }),
+    }
+}
+
+/// Convert an error into the closest gRPC status.
+fn status(error: Error) -> Status {
+    match error.kind {
+        ErrorKind::NotFound => Status::not_found("not found"),
+        ErrorKind::BadRequest(detail) => Status::invalid_argument(detail),
+        ErrorKind::Forbidden(detail) => Status::permission_denied(detail),
+        ErrorKind::Other(error) => {
+            tracing::error!("Request failed: {error:#}");
+            Status::internal(format!("{error:#}"))
+        }
+    }
+}
@@ -0,0 +1,845 @@
+//! History of ping samples, probes, wakes and seen devices, stored in SQLite.
+//!
+//! Ping samples can also be forwarded to sinks like InfluxDB.
+
+use core::net::IpAddr;
+use core::time::Duration;
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use macaddr::MacAddr6;
+use rusqlite::{Connection, OptionalExtension, params};
+use tokio::task;
+use tokio::time;
+use uuid::Uuid;
+
+/// How often recorded samples are written to the database.
+const FLUSH: Duration = Duration::from_secs(5);
+
+/// How often old samples are compacted and expired.
+const COMPACT: Duration = Duration::from_secs(3600);
+
+/// The width of the buckets samples are compacted into.
+const BUCKET: Duration = Duration::from_secs(300);
+
+/// The most samples which are kept for sinks, after which the oldest are
+/// dropped, like when a sink is unavailable for a long time.
+const MAX_FORWARDED: usize = 100_000;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS samples (
+    host TEXT NOT NULL,
+    sampled INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    rtt_us INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS samples_host_sampled ON samples (host, sampled);
+
+CREATE TABLE IF NOT EXISTS aggregates (
+    host TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+    answered INTEGER NOT NULL,
+    rtt_sum_us INTEGER NOT NULL,
+    rtt_min_us INTEGER,
+    rtt_max_us INTEGER,
+    PRIMARY KEY (host, bucket)
+);
+
+CREATE TABLE IF NOT EXISTS probes (
+    host TEXT NOT NULL,
+    sampled INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    bytes INTEGER NOT NULL,
+    idle_rtt_us INTEGER,
+    loaded_rtt_us INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS probes_host_sampled ON probes (host, sampled);
+
+CREATE TABLE IF NOT EXISTS wakes (
+    host TEXT NOT NULL,
+    queued INTEGER NOT NULL,
+    attempts INTEGER NOT NULL,
+    woke_ms INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS wakes_host ON wakes (host);
+
+CREATE TABLE IF NOT EXISTS devices (
+    mac TEXT NOT NULL PRIMARY KEY,
+    first_seen INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS suspicious_devices (
+    mac TEXT NOT NULL PRIMARY KEY,
+    marked INTEGER NOT NULL
+);
+"#;
+
+/// The outcome of a single ping.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    /// The host which was pinged.
+    pub host: Uuid,
+    /// The address which was pinged.
+    pub address: IpAddr,
+    /// When the ping was sent.
+    pub sampled: SystemTime,
+    /// The round trip time, unless the ping went unanswered.
+    pub rtt: Option<Duration>,
+}
+
+/// The outcome of a throughput probe.
+#[derive(Debug, Clone, Copy)]
+pub struct Probe {
+    /// The host which was probed.
+    pub host: Uuid,
+    /// When the probe finished.
+    pub sampled: SystemTime,
+    /// How long data was sent for.
+    pub duration: Duration,
+    /// The number of bytes sent.
+    pub bytes: u64,
+    /// Median time to connect before data was sent.
+    pub idle_rtt: Option<Duration>,
+    /// Median time to connect while data was being sent.
+    pub loaded_rtt: Option<Duration>,
+}
+
+impl Probe {
+    /// The throughput of the probe in bytes per second.
+    pub fn throughput(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The outcome of waking a host.
+#[derive(Debug, Clone, Copy)]
+pub struct Wake {
+    /// The host which was woken.
+    pub host: Uuid,
+    /// When the wake was queued.
+    pub queued: SystemTime,
+    /// The number of attempts which were made.
+    pub attempts: u32,
+    /// How long it took until the host responded, unless it never did.
+    pub woke: Option<Duration>,
+}
+
+/// Statistics over the wakes of a host.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WakeStats {
+    /// The number of wakes.
+    pub wakes: u64,
+    /// The number of wakes after which the host responded.
+    pub woke: u64,
+    /// The total time it took until the host responded over wakes which
+    /// succeeded.
+    pub time_to_wake: Duration,
+}
+
+impl WakeStats {
+    /// Add the outcome of a wake.
+    pub fn add(&mut self, wake: &Wake) {
+        self.wakes += 1;
+
+        if let Some(woke) = wake.woke {
+            self.woke += 1;
+            self.time_to_wake += woke;
+        }
+    }
+
+    /// The average time it took until the host responded, if any wake
+    /// succeeded.
+    pub fn average_time_to_wake(&self) -> Option<Duration> {
+        let woke = u32::try_from(self.woke).ok().filter(|n| *n > 0)?;
+        Some(self.time_to_wake / woke)
+    }
+}
+
+/// Statistics over the samples of a host in a window of time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// The number of pings.
+    pub samples: u64,
+    /// The number of pings which were answered.
+    pub answered: u64,
+    /// The round trip time at the requested percentile of answered pings.
+    pub rtt: Option<Duration>,
+}
+
+/// The samples of a host in one step of a series.
+#[derive(Debug, Clone, Copy)]
+pub struct Point {
+    /// Start of the step.
+    pub time: SystemTime,
+    /// The number of pings.
+    pub samples: u64,
+    /// The number of pings which were answered.
+    pub answered: u64,
+    /// The mean round trip time of answered pings.
+    pub rtt: Option<Duration>,
+}
+
+/// Handle to the history store.
+///
+/// History is only stored if a database has been opened, but samples can
+/// still be forwarded to sinks without one, see [`crate::history_sink`].
+#[derive(Clone)]
+pub struct History {
+    inner: Option<Arc<Inner>>,
+    forward: Arc<Forward>,
+}
+
+struct Inner {
+    connection: Mutex<Connection>,
+    /// Samples waiting to be written.
+    pending: Mutex<Vec<Sample>>,
+}
+
+/// Samples waiting to be written to sinks.
+#[derive(Default)]
+struct Forward {
+    /// Whether samples are kept for sinks, which is only the case while any
+    /// sink is configured.
+    enabled: AtomicBool,
+    pending: Mutex<VecDeque<Sample>>,
+}
+
+impl History {
+    /// A history which doesn't store anything.
+    pub fn disabled() -> Self {
+        Self {
+            inner: None,
+            forward: Arc::default(),
+        }
+    }
+
+    /// Open the history database at the given path, creating it if it
+    /// doesn't exist.
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .with_context(|| format!("opening history database {}", path.display()))?;
+
+        connection
+            .execute_batch(SCHEMA)
+            .context("creating history schema")?;
+
+        Ok(Self {
+            inner: Some(Arc::new(Inner {
+                connection: Mutex::new(connection),
+                pending: Mutex::new(Vec::new()),
+            })),
+            forward: Arc::default(),
+        })
+    }
+
+    /// Test if history is being recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Record a sample, which is written to the database and to sinks in the
+    /// background.
+    pub fn record(&self, sample: Sample) {
+        if let Some(inner) = &self.inner {
+            inner.pending.lock().unwrap().push(sample);
+        }
+
+        if self.forward.enabled.load(Ordering::Relaxed) {
+            let mut pending = self.forward.pending.lock().unwrap();
+
+            if pending.len() == MAX_FORWARDED {
+                pending.pop_front();
+            }
+
+            pending.push_back(sample);
+        }
+    }
+
+    /// Set whether samples are kept for sinks, where samples which are kept
+    /// are dropped when disabled.
+    pub fn set_forwarding(&self, enabled: bool) {
+        self.forward.enabled.store(enabled, Ordering::Relaxed);
+
+        if !enabled {
+            self.forward.pending.lock().unwrap().clear();
+        }
+    }
+
+    /// Take the samples kept for sinks.
+    pub fn take_forwarded(&self) -> Vec<Sample> {
+        self.forward.pending.lock().unwrap().drain(..).collect()
+    }
+
+    /// Put back samples which couldn't be written to sinks, so that writing
+    /// them is tried again.
+    pub fn restore_forwarded(&self, samples: Vec<Sample>) {
+        let mut pending = self.forward.pending.lock().unwrap();
+
+        for sample in samples.into_iter().rev() {
+            if pending.len() == MAX_FORWARDED {
+                break;
+            }
+
+            pending.push_front(sample);
+        }
+    }
+
+    /// Get statistics over the samples of a host recorded since the given
+    /// time, with the round trip time at the given percentile.
+    ///
+    /// Samples which have been compacted still count towards the number of
+    /// pings, but the percentile only covers samples which haven't been.
+    pub async fn stats(&self, host: Uuid, since: SystemTime, percentile: u8) -> Result<Stats> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Stats::default());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+            let host = host.to_string();
+            let since = to_millis(since);
+
+            let (raw_samples, raw_answered) = connection.query_row(
+                "SELECT COUNT(*), COUNT(rtt_us) FROM samples WHERE host = ?1 AND sampled >= ?2",
+                params![host, since],
+                |row| Ok((row.get::<_, u64>(0)?, row.get::<_, u64>(1)?)),
+            )?;
+
+            let (samples, answered) = connection.query_row(
+                "SELECT COALESCE(SUM(samples), 0), COALESCE(SUM(answered), 0) FROM aggregates WHERE host = ?1 AND bucket >= ?2",
+                params![host, since],
+                |row| Ok((row.get::<_, u64>(0)?, row.get::<_, u64>(1)?)),
+            )?;
+
+            // Nearest-rank percentile of the answered pings.
+            let rank = (raw_answered * u64::from(percentile))
+                .div_ceil(100)
+                .max(1);
+
+            let rtt = connection
+                .query_row(
+                    "SELECT rtt_us FROM samples WHERE host = ?1 AND sampled >= ?2 AND rtt_us IS NOT NULL ORDER BY rtt_us LIMIT 1 OFFSET ?3",
+                    params![host, since, rank - 1],
+                    |row| row.get::<_, u64>(0),
+                )
+                .optional()?
+                .map(Duration::from_micros);
+
+            Ok(Stats {
+                samples: samples + raw_samples,
+                answered: answered + raw_answered,
+                rtt,
+            })
+        })
+        .await?
+    }
+
+    /// Get the samples of a host between the given times, grouped into points
+    /// which are `step` apart.
+    ///
+    /// Compacted samples are grouped by the start of their bucket, so steps
+    /// smaller than the compaction bucket leave gaps in older points.
+    pub async fn series(
+        &self,
+        host: Uuid,
+        from: SystemTime,
+        to: SystemTime,
+        step: Duration,
+    ) -> Result<Vec<Point>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+            let step = i64::try_from(step.as_millis()).unwrap_or(i64::MAX).max(1);
+
+            let mut select = connection.prepare(
+                "SELECT time - time % ?4 AS point, SUM(samples), SUM(answered), SUM(rtt_sum_us) FROM (
+                    SELECT sampled AS time, 1 AS samples, rtt_us IS NOT NULL AS answered, COALESCE(rtt_us, 0) AS rtt_sum_us FROM samples WHERE host = ?1 AND sampled >= ?2 AND sampled < ?3
+                    UNION ALL
+                    SELECT bucket, samples, answered, rtt_sum_us FROM aggregates WHERE host = ?1 AND bucket >= ?2 AND bucket < ?3
+                ) GROUP BY point ORDER BY point",
+            )?;
+
+            let rows = select.query_map(
+                params![host.to_string(), to_millis(from), to_millis(to), step],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, u64>(1)?,
+                        row.get::<_, u64>(2)?,
+                        row.get::<_, u64>(3)?,
+                    ))
+                },
+            )?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                let (time, samples, answered, rtt_sum) = row?;
+
+                out.push(Point {
+                    time: UNIX_EPOCH + Duration::from_millis(time.max(0) as u64),
+                    samples,
+                    answered,
+                    rtt: rtt_sum
+                        .checked_div(answered)
+                        .map(Duration::from_micros),
+                });
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Store the outcome of a probe.
+    pub async fn record_probe(&self, probe: Probe) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            connection.execute(
+                "INSERT INTO probes (host, sampled, duration_ms, bytes, idle_rtt_us, loaded_rtt_us) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    probe.host.to_string(),
+                    to_millis(probe.sampled),
+                    probe.duration.as_millis() as u64,
+                    probe.bytes,
+                    probe.idle_rtt.map(|rtt| rtt.as_micros() as u64),
+                    probe.loaded_rtt.map(|rtt| rtt.as_micros() as u64),
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Get the most recent probe of each host.
+    pub async fn latest_probes(&self) -> Result<Vec<Probe>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            let mut select = connection.prepare(
+                "SELECT host, MAX(sampled), duration_ms, bytes, idle_rtt_us, loaded_rtt_us FROM probes GROUP BY host",
+            )?;
+
+            let rows = select.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, u64>(3)?,
+                    row.get::<_, Option<u64>>(4)?,
+                    row.get::<_, Option<u64>>(5)?,
+                ))
+            })?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                let (host, sampled, duration, bytes, idle, loaded) = row?;
+
+                let Ok(host) = host.parse() else {
+                    continue;
+                };
+
+                out.push(Probe {
+                    host,
+                    sampled: UNIX_EPOCH + Duration::from_millis(sampled.max(0) as u64),
+                    duration: Duration::from_millis(duration),
+                    bytes,
+                    idle_rtt: idle.map(Duration::from_micros),
+                    loaded_rtt: loaded.map(Duration::from_micros),
+                });
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Store the outcome of a wake.
+    pub async fn record_wake(&self, wake: Wake) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            connection.execute(
+                "INSERT INTO wakes (host, queued, attempts, woke_ms) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    wake.host.to_string(),
+                    to_millis(wake.queued),
+                    wake.attempts,
+                    wake.woke.map(|woke| woke.as_millis() as u64),
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Get statistics over the wakes of each host.
+    pub async fn wake_stats(&self) -> Result<Vec<(Uuid, WakeStats)>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            let mut select = connection.prepare(
+                "SELECT host, COUNT(*), COUNT(woke_ms), COALESCE(SUM(woke_ms), 0) FROM wakes GROUP BY host",
+            )?;
+
+            let rows = select.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, u64>(3)?,
+                ))
+            })?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                let (host, wakes, woke, time_to_wake) = row?;
+
+                let Ok(host) = host.parse() else {
+                    continue;
+                };
+
+                out.push((
+                    host,
+                    WakeStats {
+                        wakes,
+                        woke,
+                        time_to_wake: Duration::from_millis(time_to_wake),
+                    },
+                ));
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Get the MAC addresses of all devices which have been seen.
+    pub async fn known_devices(&self) -> Result<Vec<MacAddr6>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+            let mut select = connection.prepare("SELECT mac FROM devices")?;
+            let rows = select.query_map([], |row| row.get::<_, String>(0))?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                if let Ok(mac) = row?.parse() {
+                    out.push(mac);
+                }
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Get devices which were seen for the first time since the given time,
+    /// and when they were first seen.
+    pub async fn devices_since(&self, since: SystemTime) -> Result<Vec<(MacAddr6, SystemTime)>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            let mut select = connection.prepare(
+                "SELECT mac, first_seen FROM devices WHERE first_seen >= ?1 ORDER BY first_seen",
+            )?;
+
+            let rows = select.query_map(params![to_millis(since)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                let (mac, first_seen) = row?;
+
+                if let Ok(mac) = mac.parse() {
+                    out.push((
+                        mac,
+                        UNIX_EPOCH + Duration::from_millis(first_seen.max(0) as u64),
+                    ));
+                }
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Store that a device has been seen for the first time.
+    pub async fn record_device(&self, mac: MacAddr6, first_seen: SystemTime) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            connection.execute(
+                "INSERT OR IGNORE INTO devices (mac, first_seen) VALUES (?1, ?2)",
+                params![mac.to_string(), to_millis(first_seen)],
+            )?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Get devices which have been marked as suspicious, and when they were
+    /// marked.
+    pub async fn suspicious_devices(&self) -> Result<Vec<(MacAddr6, SystemTime)>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(Vec::new());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+            let mut select = connection.prepare("SELECT mac, marked FROM suspicious_devices")?;
+
+            let rows = select.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+
+            let mut out = Vec::new();
+
+            for row in rows {
+                let (mac, marked) = row?;
+
+                if let Ok(mac) = mac.parse() {
+                    out.push((
+                        mac,
+                        UNIX_EPOCH + Duration::from_millis(marked.max(0) as u64),
+                    ));
+                }
+            }
+
+            Ok(out)
+        })
+        .await?
+    }
+
+    /// Mark a device as suspicious at the given time, or remove the mark if
+    /// `marked` is `None`.
+    pub async fn set_suspicious(&self, mac: MacAddr6, marked: Option<SystemTime>) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            match marked {
+                Some(marked) => connection.execute(
+                    "INSERT OR REPLACE INTO suspicious_devices (mac, marked) VALUES (?1, ?2)",
+                    params![mac.to_string(), to_millis(marked)],
+                )?,
+                None => connection.execute(
+                    "DELETE FROM suspicious_devices WHERE mac = ?1",
+                    params![mac.to_string()],
+                )?,
+            };
+
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Take a consistent copy of the database, including samples which are
+    /// waiting to be written.
+    pub async fn snapshot(&self) -> Result<Option<Vec<u8>>> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(None);
+        };
+
+        self.flush().await?;
+
+        task::spawn_blocking(move || {
+            let connection = inner.connection.lock().unwrap();
+
+            let Some(path) = connection.path().filter(|p| !p.is_empty()) else {
+                bail!("history database has no path");
+            };
+
+            let copy = PathBuf::from(format!("{path}.export-{:016x}", rand::random::<u64>()));
+
+            let result = connection
+                .execute("VACUUM INTO ?1", params![copy.to_string_lossy()])
+                .context("copying history database")
+                .and_then(|_| {
+                    fs::read(&copy).with_context(|| format!("reading {}", copy.display()))
+                });
+
+            _ = fs::remove_file(&copy);
+            result.map(Some)
+        })
+        .await?
+    }
+
+    /// Write pending samples to the database.
+    async fn flush(&self) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let pending = std::mem::take(&mut *inner.pending.lock().unwrap());
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            let mut connection = inner.connection.lock().unwrap();
+            let tx = connection.transaction()?;
+
+            {
+                let mut insert = tx.prepare_cached(
+                    "INSERT INTO samples (host, sampled, address, rtt_us) VALUES (?1, ?2, ?3, ?4)",
+                )?;
+
+                for s in pending {
+                    let rtt = s.rtt.map(|rtt| rtt.as_micros() as u64);
+                    insert.execute(params![
+                        s.host.to_string(),
+                        to_millis(s.sampled),
+                        s.address.to_string(),
+                        rtt
+                    ])?;
+                }
+            }
+
+            tx.commit()?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Compact samples older than `raw` into aggregates over five minute
+    /// buckets, and remove aggregates and probes older than `aggregates`.
+    async fn compact(&self, raw: Duration, aggregates: Duration) -> Result<()> {
+        let Some(inner) = self.inner.clone() else {
+            return Ok(());
+        };
+
+        task::spawn_blocking(move || {
+            let now = SystemTime::now();
+            let bucket = BUCKET.as_millis() as i64;
+
+            // Only compact whole buckets, so that a bucket is never split
+            // between samples and aggregates.
+            let raw = to_millis(now.checked_sub(raw).unwrap_or(UNIX_EPOCH));
+            let raw = raw - raw.rem_euclid(bucket);
+            let aggregates = to_millis(now.checked_sub(aggregates).unwrap_or(UNIX_EPOCH));
+
+            let mut connection = inner.connection.lock().unwrap();
+            let tx = connection.transaction()?;
+
+            tx.execute(
+                "INSERT INTO aggregates (host, bucket, samples, answered, rtt_sum_us, rtt_min_us, rtt_max_us)
+                 SELECT host, sampled - sampled % ?2, COUNT(*), COUNT(rtt_us), COALESCE(SUM(rtt_us), 0), MIN(rtt_us), MAX(rtt_us)
+                 FROM samples WHERE sampled < ?1 GROUP BY host, sampled - sampled % ?2
+                 ON CONFLICT (host, bucket) DO UPDATE SET
+                    samples = samples + excluded.samples,
+                    answered = answered + excluded.answered,
+                    rtt_sum_us = rtt_sum_us + excluded.rtt_sum_us,
+                    rtt_min_us = MIN(COALESCE(rtt_min_us, excluded.rtt_min_us), COALESCE(excluded.rtt_min_us, rtt_min_us)),
+                    rtt_max_us = MAX(COALESCE(rtt_max_us, excluded.rtt_max_us), COALESCE(excluded.rtt_max_us, rtt_max_us))",
+                params![raw, bucket],
+            )?;
+
+            let compacted = tx.execute("DELETE FROM samples WHERE sampled < ?1", params![raw])?;
+            let expired = tx.execute(
+                "DELETE FROM aggregates WHERE bucket < ?1",
+                params![aggregates],
+            )? + tx.execute(
+                "DELETE FROM probes WHERE sampled < ?1",
+                params![aggregates],
+            )?;
+
+            tx.commit()?;
+
+            if compacted > 0 || expired > 0 {
+                tracing::debug!(compacted, expired, "Compacted history");
+            }
+
+            Ok(())
+        })
+        .await?
+    }
+}
+
+/// Spawn the task which writes recorded samples to the database, and
+/// compacts samples older than `raw` and expires aggregates older than
+/// `aggregates`.
+pub async fn spawn(history: History, raw: Duration, aggregates: Duration) -> Result<()> {
+    let mut flush = time::interval(FLUSH);
+    let mut compact = time::interval(COMPACT);
+
+    loop {
+        tokio::select! {
+            _ = flush.tick() => {
+                if let Err(error) = history.flush().await {
+                    tracing::warn!("Writing history: {error:#}");
+                }
+            }
+            _ = compact.tick() => {
+                if let Err(error) = history.compact(raw, aggregates).await {
+                    tracing::warn!("Compacting history: {error:#}");
+                }
+            }
+        }
+    }
+}
+
+/// Convert a time into milliseconds since the unix epoch.
+fn to_millis(time: SystemTime) -> i64 {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
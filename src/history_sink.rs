@@ -0,0 +1,142 @@
+//! Sinks which ping samples are written to in addition to, or instead of, the
+//! history database, so that they can flow into an existing time-series
+//! stack.
+//!
+//! Each kind of sink is a variant of [`Sink`], which is set up from `[history]`
+//! in configuration.
+
+use core::fmt::Write;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Result, anyhow};
+use tokio::time;
+use uuid::Uuid;
+
+use crate::config::{HistoryConfig, InfluxConfig, Webhook};
+use crate::history::{History, Sample};
+use crate::hosts;
+use crate::metrics::InfluxEscape;
+use crate::notify;
+
+/// How often samples are written to sinks.
+const FLUSH: Duration = Duration::from_secs(5);
+/// How long to wait for a sink to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A destination samples are written to.
+enum Sink {
+    /// InfluxDB, or anything else which accepts its line protocol.
+    Influx(Influx),
+}
+
+impl Sink {
+    /// Set up the sink configured in `[history]`, if any.
+    fn new(config: &HistoryConfig) -> Option<Self> {
+        Influx::new(&config.influx).map(Sink::Influx)
+    }
+
+    /// Write samples, where hosts are named after the given names.
+    async fn write(&self, samples: &[Sample], names: &HashMap<Uuid, &str>) -> Result<()> {
+        match self {
+            Sink::Influx(influx) => influx.write(samples, names).await,
+        }
+    }
+}
+
+struct Influx {
+    url: Webhook,
+    authorization: Option<String>,
+    measurement: String,
+}
+
+impl Influx {
+    fn new(config: &InfluxConfig) -> Option<Self> {
+        Some(Self {
+            url: config.url.clone()?,
+            authorization: config.token.as_ref().map(|token| format!("Token {token}")),
+            measurement: config.measurement().to_owned(),
+        })
+    }
+
+    /// Write samples with an `answered` field, and the round trip time in
+    /// seconds as `rtt` if the ping was answered.
+    async fn write(&self, samples: &[Sample], names: &HashMap<Uuid, &str>) -> Result<()> {
+        let mut body = String::new();
+
+        for s in samples {
+            _ = write!(
+                body,
+                "{},host_id={},address={}",
+                InfluxEscape(&self.measurement),
+                s.host,
+                s.address
+            );
+
+            if let Some(name) = names.get(&s.host).filter(|n| !n.is_empty()) {
+                _ = write!(body, ",host={}", InfluxEscape(name));
+            }
+
+            _ = write!(body, " answered={}i", u8::from(s.rtt.is_some()));
+
+            if let Some(rtt) = s.rtt {
+                _ = write!(body, ",rtt={}", rtt.as_secs_f64());
+            }
+
+            let timestamp = s.sampled.duration_since(UNIX_EPOCH).unwrap_or_default();
+            _ = writeln!(body, " {}", timestamp.as_nanos());
+        }
+
+        let mut headers = vec![("Content-Type", "text/plain; charset=utf-8")];
+
+        if let Some(authorization) = &self.authorization {
+            headers.push(("Authorization", authorization.as_str()));
+        }
+
+        notify::post(&self.url, &headers, body.as_bytes()).await
+    }
+}
+
+/// Spawn the task which writes samples to the sinks configured in `[history]`.
+///
+/// Samples which can't be written are tried again on the next flush.
+pub async fn spawn(history: History, hosts: hosts::State) {
+    let mut flush = time::interval(FLUSH);
+
+    loop {
+        flush.tick().await;
+
+        let config = hosts.config().await;
+        let sink = Sink::new(&config.history);
+        history.set_forwarding(sink.is_some());
+
+        let Some(sink) = sink else {
+            continue;
+        };
+
+        let samples = history.take_forwarded();
+
+        if samples.is_empty() {
+            continue;
+        }
+
+        let hosts = hosts.hosts();
+
+        let names = hosts
+            .iter()
+            .filter_map(|h| Some((h.id, h.names().next()?)))
+            .collect::<HashMap<_, _>>();
+
+        let result = match time::timeout(TIMEOUT, sink.write(&samples, &names)).await {
+            Ok(result) => result,
+            Err(..) => Err(anyhow!("timeout")),
+        };
+
+        if let Err(error) = result {
+            tracing::warn!("Writing {} samples to sink: {error:#}", samples.len());
+            history.restore_forwarded(samples);
+        }
+    }
+}
@@ -10,6 +10,7 @@ use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
 use crate::embed;
 use crate::embed::Base64;
+use crate::showcase::LockedHelper;
 
 /// Construct a new home handle.
 pub fn new(paths: Vec<PathBuf>) -> Home {
@@ -86,6 +87,23 @@ impl HomePage {
         }
     }
 
+    /// Anonymize the contents of the home page for showcase mode.
+    ///
+    /// Relative links point to wolo itself and are kept so that the page can
+    /// still be navigated.
+    pub fn showcase(&mut self, showcase: &mut LockedHelper<'_>) {
+        self.title = Cow::Owned(showcase.text(&self.title));
+        self.text = showcase.text(&self.text);
+
+        for link in &mut self.links {
+            link.title = showcase.text(&link.title);
+
+            if link.href.contains("://") {
+                link.href = showcase.path(&link.href);
+            }
+        }
+    }
+
     /// Populate the home page from an asynchronous reader.
     async fn populate(&mut self, reader: impl AsyncRead) {
         let mut reader = pin!(BufReader::new(reader));
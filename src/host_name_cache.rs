@@ -1,8 +1,10 @@
+use core::ffi::CStr;
+use core::fmt;
 use core::net::IpAddr;
 use core::time::Duration;
 use std::collections::{BTreeSet, HashMap};
-use std::net::ToSocketAddrs;
-use std::sync::Arc;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use anyhow::Result;
@@ -10,11 +12,27 @@ use tokio::sync::RwLock;
 use tokio::task::{self, JoinError, JoinHandle};
 use uuid::Uuid;
 
+use crate::config::DnsConfig;
+use crate::dns;
 use crate::hosts::Host;
+use crate::interfaces;
+
+/// How long to wait before resolving a name which doesn't exist again. This
+/// doubles with every consecutive failure up to [`NOT_FOUND_MAX`].
+const NOT_FOUND_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The longest time to wait before resolving a name which doesn't exist
+/// again.
+const NOT_FOUND_MAX: Duration = Duration::from_secs(30 * 60);
 
 /// A cache of looked up host names.
+#[derive(Clone)]
 pub struct HostNameCache {
     map: Arc<RwLock<HashMap<Uuid, HostNameEntry>>>,
+    /// Names which don't exist, and when they are resolved again.
+    backoff: Arc<Mutex<HashMap<String, Backoff>>>,
+    /// Settings for resolving names.
+    dns: Arc<Mutex<Arc<DnsConfig>>>,
 }
 
 impl HostNameCache {
@@ -22,7 +40,25 @@ impl HostNameCache {
     pub fn new() -> Self {
         Self {
             map: Arc::new(RwLock::new(HashMap::new())),
+            backoff: Arc::new(Mutex::new(HashMap::new())),
+            dns: Arc::new(Mutex::new(Arc::new(DnsConfig::default()))),
+        }
+    }
+
+    /// Update the settings used to resolve names, causing all names to be
+    /// resolved again if they changed.
+    pub async fn set_dns(&self, dns: &DnsConfig) {
+        {
+            let mut current = self.dns.lock().unwrap();
+
+            if **current == *dns {
+                return;
+            }
+
+            *current = Arc::new(dns.clone());
         }
+
+        self.clear().await;
     }
 
     /// Get an entry from the cache.
@@ -40,24 +76,60 @@ impl HostNameCache {
         }
 
         let names = host.names.clone();
+        let backoff = self.backoff.clone();
+        let dns = self.dns.lock().unwrap().clone();
 
         let handle = task::spawn_blocking(move || {
             let mut errors = Vec::new();
             let mut results = BTreeSet::new();
 
             for name in names {
-                match (name.as_str(), 0).to_socket_addrs() {
-                    Err(error) => {
-                        errors.push(NameError {
-                            name,
-                            error: format!("{error}"),
-                        });
-                        continue;
+                let now = Instant::now();
+
+                // Names which don't exist keep their error until they are
+                // due to be resolved again.
+                if let Some(b) = backoff.lock().unwrap().get(&name)
+                    && now < b.retry
+                {
+                    errors.push(b.error.clone());
+                    continue;
+                }
+
+                match resolve(&dns, &name) {
+                    Ok(addresses) => {
+                        backoff.lock().unwrap().remove(&name);
+                        results.extend(addresses);
                     }
-                    Ok(result) => {
-                        for addr in result {
-                            results.insert(addr.ip());
+                    Err((kind, error)) => {
+                        let error = NameError {
+                            name: name.clone(),
+                            kind,
+                            error,
+                        };
+
+                        let mut backoff = backoff.lock().unwrap();
+
+                        if kind == ResolveErrorKind::NotFound {
+                            let failures = backoff.get(&name).map_or(0, |b| b.failures) + 1;
+                            let delay = NOT_FOUND_BACKOFF
+                                .saturating_mul(1 << (failures - 1).min(16))
+                                .min(NOT_FOUND_MAX);
+
+                            tracing::debug!(name, ?delay, "Name not found, backing off");
+
+                            backoff.insert(
+                                name,
+                                Backoff {
+                                    failures,
+                                    retry: now + delay,
+                                    error: error.clone(),
+                                },
+                            );
+                        } else {
+                            backoff.remove(&name);
                         }
+
+                        errors.push(error);
                     }
                 }
             }
@@ -74,6 +146,26 @@ impl HostNameCache {
         }
     }
 
+    /// Get a snapshot of all entries in the cache, along with how long ago
+    /// they were looked up.
+    pub async fn entries(&self) -> Vec<(Uuid, Arc<CacheNameResult>, Duration)> {
+        let now = Instant::now();
+        let map = self.map.read().await;
+
+        map.iter()
+            .map(|(id, entry)| {
+                let age = now.saturating_duration_since(entry.last);
+                (*id, entry.results.clone(), age)
+            })
+            .collect()
+    }
+
+    /// Clear all entries, causing host names to be looked up again.
+    pub async fn clear(&self) {
+        self.map.write().await.clear();
+        self.backoff.lock().unwrap().clear();
+    }
+
     /// Evict old entries.
     pub async fn evict_old(&mut self) {
         const DURATION: Duration = Duration::from_secs(15);
@@ -97,10 +189,129 @@ pub struct CacheNameResult {
 pub struct NameError {
     /// The name that was looked up.
     pub name: String,
+    /// The kind of error.
+    pub kind: ResolveErrorKind,
     /// The error during name resolution.
     pub error: String,
 }
 
+/// The kind of error encountered when resolving a name, as far as the system
+/// resolver lets us tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResolveErrorKind {
+    /// The name doesn't exist (NXDOMAIN) or has no addresses.
+    NotFound,
+    /// The DNS server failed (SERVFAIL) or didn't respond in time.
+    Unavailable,
+    /// The DNS server refused to answer or the answer was invalid.
+    Failed,
+    /// Any other error, like the system running out of resources.
+    Other,
+}
+
+impl ResolveErrorKind {
+    /// A short description of the kind of error.
+    pub fn description(self) -> &'static str {
+        match self {
+            ResolveErrorKind::NotFound => "Name doesn't exist",
+            ResolveErrorKind::Unavailable => "DNS server failed or timed out",
+            ResolveErrorKind::Failed => "DNS server refused",
+            ResolveErrorKind::Other => "Resolver error",
+        }
+    }
+}
+
+impl fmt::Display for ResolveErrorKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveErrorKind::NotFound => write!(f, "not-found"),
+            ResolveErrorKind::Unavailable => write!(f, "unavailable"),
+            ResolveErrorKind::Failed => write!(f, "failed"),
+            ResolveErrorKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Backoff for a name which doesn't exist.
+struct Backoff {
+    /// Number of consecutive times the name wasn't found.
+    failures: u32,
+    /// When the name is resolved again.
+    retry: Instant,
+    /// The error the name was last resolved with.
+    error: NameError,
+}
+
+/// Resolve a name, using the configured DNS servers if there are any and
+/// otherwise the system resolver.
+pub(crate) fn resolve(
+    dns: &DnsConfig,
+    name: &str,
+) -> Result<Vec<IpAddr>, (ResolveErrorKind, String)> {
+    if dns.servers.is_empty() {
+        resolve_system(name)
+    } else {
+        dns::resolve(dns, name)
+    }
+}
+
+/// Resolve a name using the system resolver.
+///
+/// This calls `getaddrinfo` directly rather than going through the standard
+/// library, since its error codes tell us why resolution failed.
+fn resolve_system(name: &str) -> Result<Vec<IpAddr>, (ResolveErrorKind, String)> {
+    let Ok(c_name) = CString::new(name) else {
+        return Err((
+            ResolveErrorKind::NotFound,
+            String::from("name contains a nul byte"),
+        ));
+    };
+
+    // SAFETY: An all-zero `addrinfo` is a valid set of hints.
+    let mut hints = unsafe { core::mem::zeroed::<libc::addrinfo>() };
+    hints.ai_socktype = libc::SOCK_STREAM;
+
+    let mut head = core::ptr::null_mut::<libc::addrinfo>();
+
+    // SAFETY: The arguments are valid for the duration of the call, and on
+    // success `head` is a linked list which stays valid until it's passed to
+    // `freeaddrinfo`.
+    unsafe {
+        let code = libc::getaddrinfo(c_name.as_ptr(), core::ptr::null(), &hints, &mut head);
+
+        if code != 0 {
+            let error = if code == libc::EAI_SYSTEM {
+                std::io::Error::last_os_error().to_string()
+            } else {
+                CStr::from_ptr(libc::gai_strerror(code))
+                    .to_string_lossy()
+                    .into_owned()
+            };
+
+            let kind = match code {
+                libc::EAI_NONAME | libc::EAI_NODATA => ResolveErrorKind::NotFound,
+                libc::EAI_AGAIN => ResolveErrorKind::Unavailable,
+                libc::EAI_FAIL => ResolveErrorKind::Failed,
+                _ => ResolveErrorKind::Other,
+            };
+
+            return Err((kind, error));
+        }
+
+        let mut addresses = Vec::new();
+        let mut current = head;
+
+        while let Some(ai) = current.as_ref() {
+            current = ai.ai_next;
+            addresses.extend(interfaces::to_ip(ai.ai_addr));
+        }
+
+        libc::freeaddrinfo(head);
+        Ok(addresses)
+    }
+}
+
 /// A cache lookup.
 pub struct HostNameCacheLookup {
     kind: InnerKind,
@@ -1,18 +1,26 @@
+use core::fmt;
+use core::mem;
 use core::time::Duration;
-use std::collections::{BTreeSet, HashMap, btree_set};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, btree_set};
+use std::io;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use macaddr::MacAddr6;
 use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::sync::{RwLock, RwLockReadGuard};
+use tokio::sync::{Notify, RwLock, watch};
 use tokio::time;
 use twox_hash::xxhash3_128;
 use uuid::Uuid;
 
-use crate::config::Config;
+use crate::config::{
+    CheckConfig, Config, DisplayConfig, HostKind, PingConfig, ProbeConfig, ProfileConfig,
+    QuietHoursConfig, SloConfig, SnmpConfig, WakeConfig,
+};
+use crate::mac::Mac;
 
 /// Builder for the host monitoring state.
 pub struct Builder {
@@ -31,12 +39,20 @@ impl Builder {
         self.host_paths.push(path.to_owned());
     }
 
-    /// Build the host monitoring state.
-    pub fn build(self) -> State {
+    /// Build the host monitoring state using the given configuration.
+    pub fn build(self, config: Arc<Config>) -> State {
         let inner = Inner {
             ether_paths: self.ether_paths,
             host_paths: self.host_paths,
-            hosts: RwLock::new(Vec::new()),
+            config: RwLock::new(config),
+            hosts: watch::Sender::new(Hosts {
+                generation: 0,
+                hosts: Arc::from([]),
+            }),
+            sources: RwLock::new(Vec::new()),
+            conflicts: RwLock::new(Vec::new()),
+            paused: AtomicBool::new(false),
+            reload: Notify::new(),
         };
 
         State {
@@ -48,7 +64,27 @@ impl Builder {
 struct Inner {
     ether_paths: Vec<PathBuf>,
     host_paths: Vec<PathBuf>,
-    hosts: RwLock<Vec<Host>>,
+    config: RwLock<Arc<Config>>,
+    /// The current list of hosts, which subscribers are notified about when it
+    /// changes.
+    hosts: watch::Sender<Hosts>,
+    /// The status of each source hosts were last collected from.
+    sources: RwLock<Vec<SourceStatus>>,
+    /// Conflicts between sources found when hosts were last collected.
+    conflicts: RwLock<Vec<String>>,
+    /// Whether monitoring of hosts is paused.
+    paused: AtomicBool,
+    /// Notified when the configuration has been replaced.
+    reload: Notify,
+}
+
+/// A snapshot of the list of hosts.
+#[derive(Clone)]
+pub struct Hosts {
+    /// Incremented every time the list of hosts changes.
+    pub generation: u64,
+    /// The hosts.
+    pub hosts: Arc<[Host]>,
 }
 
 /// Representation of a host on the network.
@@ -59,13 +95,62 @@ pub struct Host {
     pub macs: BTreeSet<MacAddr6>,
     pub preferred_name: Option<String>,
     pub ignore: bool,
+    pub ping: PingConfig,
+    /// Address which is pinged and checked instead of the addresses the
+    /// names of the host resolve to.
+    pub ping_address: Option<IpAddr>,
+    pub checks: Vec<CheckConfig>,
+    pub wake: WakeConfig,
+    pub quiet_hours: QuietHoursConfig,
+    pub slo: SloConfig,
+    pub snmp: SnmpConfig,
+    pub probe: ProbeConfig,
+    pub tags: BTreeSet<String>,
+    /// Names of hosts this host depends on to be reachable.
+    pub depends_on: BTreeSet<String>,
+    pub display: DisplayConfig,
+    /// Where each name of the host was defined.
+    pub name_origins: BTreeMap<String, BTreeSet<Origin>>,
+    /// Where each MAC address of the host was defined.
+    pub mac_origins: BTreeMap<MacAddr6, BTreeSet<Origin>>,
+    /// The address of a host without names, which is used as its name.
+    address_name: Option<String>,
+}
+
+/// Where a name or MAC address of a host was defined.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Origin {
+    /// An ethers file.
+    Ethers(PathBuf),
+    /// A hosts file.
+    Hosts(PathBuf),
+    /// A configuration file.
+    Config(PathBuf),
+    /// The command line, like `--ignore-host`.
+    CommandLine,
+}
+
+impl fmt::Display for Origin {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Ethers(path) => write!(f, "ethers {}", path.display()),
+            Origin::Hosts(path) => write!(f, "hosts {}", path.display()),
+            Origin::Config(path) => write!(f, "config {}", path.display()),
+            Origin::CommandLine => write!(f, "command line"),
+        }
+    }
 }
 
 impl Host {
     /// Get an iterator over the host names.
+    ///
+    /// A host without names is named by the address it's pinged at.
     pub fn names(&self) -> impl Iterator<Item = &str> {
         let (head, tail) = if let Some(preferred) = &self.preferred_name {
             (Some(preferred.as_str()), btree_set::Iter::default())
+        } else if self.names.is_empty() {
+            (self.address_name.as_deref(), btree_set::Iter::default())
         } else {
             (None, self.names.iter())
         };
@@ -73,16 +158,49 @@ impl Host {
         head.into_iter().chain(tail.map(|n| n.as_str()))
     }
 
+    /// Get the kind of the host, which is guessed from its names unless it
+    /// has been configured.
+    pub fn kind(&self) -> Option<HostKind> {
+        self.display
+            .kind
+            .or_else(|| self.names().find_map(HostKind::guess))
+    }
+
+    /// Apply shared settings to this host, where settings in `profile` take
+    /// precedence.
+    fn apply(&mut self, profile: &ProfileConfig) {
+        self.ping.merge(&profile.ping);
+        self.wake.merge(&profile.wake);
+        self.quiet_hours.merge(&profile.quiet_hours);
+        self.slo.merge(&profile.slo);
+        self.snmp.merge(&profile.snmp);
+        self.probe.merge(&profile.probe);
+        self.checks.extend(profile.checks.iter().cloned());
+        self.tags.extend(profile.tags.iter().cloned());
+        self.depends_on.extend(profile.depends_on.iter().cloned());
+        self.display.merge(&profile.display);
+    }
+
     pub fn build_id(&mut self) {
         const NAME: u8 = 0x01;
         const MAC: u8 = 0x02;
 
         let mut hasher = xxhash3_128::Hasher::default();
 
-        let bytes = (self.names.len() as u64).to_be_bytes();
+        // A host without names is hashed as if its address was its only
+        // name, which is how it was configured before hosts could be
+        // without names so its identifier stays the same.
+        let address = match &self.address_name {
+            Some(address) if self.names.is_empty() => Some(address),
+            _ => None,
+        };
+
+        let names = address.into_iter().chain(&self.names);
+
+        let bytes = (names.clone().count() as u64).to_be_bytes();
         hasher.write(&bytes);
 
-        for name in &self.names {
+        for name in names {
             hasher.write(&[NAME]);
             hasher.write(name.as_bytes());
         }
@@ -106,13 +224,82 @@ pub struct State {
 }
 
 impl State {
-    /// Get a read lock on the current list of hosts.
-    pub async fn hosts(&self) -> RwLockReadGuard<'_, [Host]> {
-        let hosts = self.inner.hosts.read().await;
-        RwLockReadGuard::map(hosts, |v| v.as_slice())
+    /// Get the current list of hosts.
+    pub fn hosts(&self) -> Arc<[Host]> {
+        self.inner.hosts.borrow().hosts.clone()
+    }
+
+    /// Subscribe to the list of hosts, which can be used to wait for it to
+    /// change.
+    pub fn subscribe(&self) -> watch::Receiver<Hosts> {
+        self.inner.hosts.subscribe()
+    }
+
+    /// Get the current configuration.
+    pub async fn config(&self) -> Arc<Config> {
+        self.inner.config.read().await.clone()
+    }
+
+    /// Replace the configuration, causing hosts to be collected again.
+    pub async fn set_config(&self, config: Arc<Config>) {
+        *self.inner.config.write().await = config;
+        self.inner.reload.notify_one();
+    }
+
+    /// Get the status of each source hosts were last collected from.
+    pub async fn sources(&self) -> Vec<SourceStatus> {
+        self.inner.sources.read().await.clone()
+    }
+
+    /// Get descriptions of conflicts between sources found when hosts were
+    /// last collected.
+    pub async fn conflicts(&self) -> Vec<String> {
+        self.inner.conflicts.read().await.clone()
+    }
+
+    /// Test if monitoring of hosts is paused.
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pause or resume monitoring of hosts.
+    pub fn set_paused(&self, paused: bool) {
+        self.inner.paused.store(paused, Ordering::Relaxed);
     }
 }
 
+/// The kind of a source hosts are collected from.
+#[derive(Debug, Clone, Copy)]
+pub enum SourceKind {
+    Ethers,
+    Hosts,
+}
+
+impl fmt::Display for SourceKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceKind::Ethers => write!(f, "ethers"),
+            SourceKind::Hosts => write!(f, "hosts"),
+        }
+    }
+}
+
+/// The status of a source hosts are collected from.
+#[derive(Debug, Clone)]
+pub struct SourceStatus {
+    /// The kind of the source.
+    pub kind: SourceKind,
+    /// The path of the source.
+    pub path: PathBuf,
+    /// The number of entries read.
+    pub entries: usize,
+    /// Descriptions of lines which could not be read.
+    pub invalid: Vec<String>,
+    /// The error raised when opening the source, if any.
+    pub error: Option<String>,
+}
+
 impl State {
     /// Create a new builder for the host monitoring state.
     pub fn builder() -> Builder {
@@ -123,6 +310,15 @@ impl State {
     }
 }
 
+/// The contents of an ethers file.
+#[derive(Default)]
+pub struct Ethers {
+    /// Entries which were successfully read.
+    pub entries: Vec<(MacAddr6, String)>,
+    /// Descriptions of lines which could not be read.
+    pub invalid: Vec<String>,
+}
+
 #[derive(Default)]
 struct Reader {
     line: String,
@@ -130,13 +326,12 @@ struct Reader {
 
 impl Reader {
     /// Read an ethers file from the given path.
-    async fn read_ethers(&mut self, path: &Path) -> Vec<(MacAddr6, String)> {
-        let Ok(f) = File::open(path).await else {
-            return Vec::new();
-        };
+    async fn read_ethers(&mut self, path: &Path) -> io::Result<Ethers> {
+        let mut ethers = Ethers::default();
+        let f = File::open(path).await?;
 
         let mut reader = BufReader::new(f);
-        let mut ethers = Vec::new();
+        let mut number = 0usize;
 
         loop {
             self.line.clear();
@@ -149,28 +344,44 @@ impl Reader {
                 break;
             }
 
+            number += 1;
+
             let line = self.line.trim();
 
-            let Some((mac, name)) = line.split_once(' ') else {
+            if line.is_empty() || line.starts_with('#') {
                 continue;
-            };
+            }
 
-            let Ok(mac) = mac.parse::<MacAddr6>() else {
+            let Some((mac, name)) = line.split_once(char::is_whitespace) else {
+                ethers.invalid.push(format!(
+                    "{}:{number}: expected a MAC address followed by a host name",
+                    path.display()
+                ));
                 continue;
             };
 
+            let mac = match mac.parse::<Mac>() {
+                Ok(Mac(mac)) => mac,
+                Err(error) => {
+                    ethers
+                        .invalid
+                        .push(format!("{}:{number}: {error}", path.display()));
+                    continue;
+                }
+            };
+
             let name = name.trim();
-            ethers.push((mac, name.to_owned()));
+            ethers.entries.push((mac, name.to_owned()));
         }
 
-        ethers
+        Ok(ethers)
     }
 
     /// Read a hosts file from the given path.
-    async fn read_hosts(&mut self, path: &Path) -> Vec<String> {
-        let Ok(f) = File::open(path).await else {
-            return Vec::new();
-        };
+    ///
+    /// Returns each address with the names on the same line.
+    async fn read_hosts(&mut self, path: &Path) -> io::Result<Vec<(IpAddr, Vec<String>)>> {
+        let f = File::open(path).await?;
 
         let mut reader = BufReader::new(f);
         let mut hosts = Vec::new();
@@ -204,12 +415,17 @@ impl Reader {
                 continue;
             }
 
-            for name in names.split_ascii_whitespace() {
-                hosts.push(name.to_owned());
+            let names = names
+                .split_ascii_whitespace()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+
+            if !names.is_empty() {
+                hosts.push((ip, names));
             }
         }
 
-        hosts
+        Ok(hosts)
     }
 }
 
@@ -217,18 +433,224 @@ struct Service {
     by_mac: HashMap<MacAddr6, usize>,
     by_name: HashMap<String, usize>,
     reader: Reader,
+    /// Invalid ethers lines which have already been reported.
+    reported: HashSet<String>,
+    /// The status of each source as of the last collection.
+    sources: Vec<SourceStatus>,
+    /// Conflicts between sources found in the last collection.
+    conflicts: Vec<String>,
+    /// Conflicts which have already been reported.
+    reported_conflicts: HashSet<String>,
 }
 
 impl Service {
+    fn new() -> Self {
+        Self {
+            by_mac: HashMap::new(),
+            by_name: HashMap::new(),
+            reader: Reader::default(),
+            reported: HashSet::new(),
+            sources: Vec::new(),
+            conflicts: Vec::new(),
+            reported_conflicts: HashSet::new(),
+        }
+    }
+
+    /// Collect hosts from all sources.
+    async fn collect(
+        &mut self,
+        hosts: &mut Vec<Host>,
+        ether_paths: &[PathBuf],
+        host_paths: &[PathBuf],
+        config: &Config,
+    ) {
+        hosts.clear();
+
+        self.by_mac.clear();
+        self.by_name.clear();
+        self.sources.clear();
+        self.conflicts.clear();
+
+        let mut invalid = HashSet::new();
+
+        for path in ether_paths {
+            let (ethers, error) = match self.reader.read_ethers(path).await {
+                Ok(ethers) => (ethers, None),
+                Err(error) => (Ethers::default(), Some(error.to_string())),
+            };
+
+            self.sources.push(SourceStatus {
+                kind: SourceKind::Ethers,
+                path: path.clone(),
+                entries: ethers.entries.len(),
+                invalid: ethers.invalid.clone(),
+                error,
+            });
+
+            let origin = [Origin::Ethers(path.clone())];
+
+            for (mac, name) in ethers.entries {
+                self.add(hosts, [mac], [name.as_str()], None, false, &origin);
+            }
+
+            invalid.extend(ethers.invalid);
+        }
+
+        // Only report invalid lines once, so that they don't spam the log
+        // every time hosts are collected.
+        for line in invalid.difference(&self.reported) {
+            tracing::warn!("{line}");
+        }
+
+        self.reported = invalid;
+
+        let mut addresses = Vec::new();
+
+        for path in host_paths {
+            let (found, error) = match self.reader.read_hosts(path).await {
+                Ok(found) => (found, None),
+                Err(error) => (Vec::new(), Some(error.to_string())),
+            };
+
+            self.sources.push(SourceStatus {
+                kind: SourceKind::Hosts,
+                path: path.clone(),
+                entries: found.len(),
+                invalid: Vec::new(),
+                error,
+            });
+
+            let origin = [Origin::Hosts(path.clone())];
+
+            for (address, names) in found {
+                self.add(hosts, [], &names, None, false, &origin);
+                addresses.push((address, names));
+            }
+        }
+
+        self.add_from_config(hosts, config);
+        self.apply_defaults(hosts, &config.defaults);
+        self.address_conflicts(hosts, &addresses);
+
+        let conflicts = self.conflicts.iter().cloned().collect::<HashSet<_>>();
+
+        // Like invalid lines, only report conflicts once.
+        for conflict in conflicts.difference(&self.reported_conflicts) {
+            tracing::warn!("{conflict}");
+        }
+
+        self.reported_conflicts = conflicts;
+
+        hosts.retain(|h| !h.ignore);
+
+        for host in hosts.iter_mut() {
+            host.address_name = match host.ping_address {
+                Some(address) if host.names.is_empty() => Some(address.to_string()),
+                _ => None,
+            };
+
+            host.build_id();
+        }
+
+        hosts.sort_by_key(|h| h.id);
+    }
+
     fn add_from_config(&mut self, hosts: &mut Vec<Host>, config: &Config) {
         for h in &config.hosts {
-            self.add(
+            let mut origins = h
+                .files
+                .iter()
+                .cloned()
+                .map(Origin::Config)
+                .collect::<Vec<_>>();
+
+            if origins.is_empty() {
+                origins.push(Origin::CommandLine);
+            }
+
+            let indexes = self.add(
                 hosts,
                 h.macs.iter().copied(),
                 &h.names,
                 h.preferred_name.as_deref(),
                 h.ignore,
+                &origins,
             );
+
+            let profile = h.profile.as_ref().and_then(|p| config.profiles.get(p));
+
+            for index in indexes {
+                let host = &mut hosts[index];
+
+                if let Some(profile) = profile {
+                    host.apply(profile);
+                }
+
+                host.ping.merge(&h.ping);
+                host.ping_address = h.ping_address.or(host.ping_address);
+                host.checks.extend(h.checks.iter().cloned());
+                host.wake.merge(&h.wake);
+                host.quiet_hours.merge(&h.quiet_hours);
+                host.slo.merge(&h.slo);
+                host.snmp.merge(&h.snmp);
+                host.probe.merge(&h.probe);
+                host.tags.extend(h.tags.iter().cloned());
+                host.depends_on.extend(h.depends_on.iter().cloned());
+                host.display.merge(&h.display);
+            }
+        }
+    }
+
+    /// Apply defaults to all hosts. Defaults have the lowest precedence, so
+    /// they are applied underneath whatever has been configured for each host.
+    fn apply_defaults(&mut self, hosts: &mut [Host], defaults: &ProfileConfig) {
+        for host in hosts.iter_mut() {
+            let mut settings = defaults.clone();
+
+            settings.merge(&ProfileConfig {
+                ping: mem::take(&mut host.ping),
+                wake: mem::take(&mut host.wake),
+                quiet_hours: mem::take(&mut host.quiet_hours),
+                slo: mem::take(&mut host.slo),
+                snmp: mem::take(&mut host.snmp),
+                probe: mem::take(&mut host.probe),
+                checks: mem::take(&mut host.checks),
+                tags: mem::take(&mut host.tags),
+                depends_on: mem::take(&mut host.depends_on),
+                display: mem::take(&mut host.display),
+            });
+
+            host.apply(&settings);
+        }
+    }
+
+    /// Record conflicts for addresses which are claimed by more than one host.
+    fn address_conflicts(&mut self, hosts: &[Host], addresses: &[(IpAddr, Vec<String>)]) {
+        let mut claimed = BTreeMap::<IpAddr, BTreeSet<usize>>::new();
+
+        for (address, names) in addresses {
+            for name in names {
+                if let Some(&index) = self.by_name.get(name)
+                    && !hosts[index].ignore
+                {
+                    claimed.entry(*address).or_default().insert(index);
+                }
+            }
+        }
+
+        for (address, indexes) in claimed {
+            if indexes.len() < 2 {
+                continue;
+            }
+
+            let names = indexes
+                .iter()
+                .map(|&i| hosts[i].names().next().unwrap_or_default());
+
+            self.conflicts.push(format!(
+                "address {address} is claimed by hosts {}",
+                quoted(names)
+            ));
         }
     }
 
@@ -239,12 +661,21 @@ impl Service {
         names: impl IntoIterator<Item: AsRef<str>> + Clone,
         preferred_name: Option<&str>,
         ignore: bool,
-    ) {
+        origins: &[Origin],
+    ) -> BTreeSet<usize> {
         let mut indexes = BTreeSet::new();
 
         // Try to find existing indexes first.
         for mac in macs.clone() {
-            indexes.extend(self.by_mac.get(&mac).copied());
+            let Some(&index) = self.by_mac.get(&mac) else {
+                continue;
+            };
+
+            indexes.insert(index);
+
+            if let Some(conflict) = mac_conflict(&hosts[index], mac, names.clone(), origins) {
+                self.conflicts.push(conflict);
+            }
         }
 
         for name in names.clone() {
@@ -264,13 +695,27 @@ impl Service {
                 preferred_name: preferred_name.map(|n| n.to_owned()),
                 id: Uuid::nil(),
                 ignore,
+                ping: PingConfig::default(),
+                ping_address: None,
+                checks: Vec::new(),
+                wake: WakeConfig::default(),
+                quiet_hours: QuietHoursConfig::default(),
+                slo: SloConfig::default(),
+                snmp: SnmpConfig::default(),
+                probe: ProbeConfig::default(),
+                tags: BTreeSet::new(),
+                depends_on: BTreeSet::new(),
+                display: DisplayConfig::default(),
+                name_origins: BTreeMap::new(),
+                mac_origins: BTreeMap::new(),
+                address_name: None,
             });
 
             indexes.insert(index);
         } else {
             for &index in &indexes {
                 let host = &mut hosts[index];
-                host.macs.extend(macs.clone().into_iter());
+                host.macs.extend(macs.clone());
                 host.names
                     .extend(names.clone().into_iter().map(|n| n.as_ref().to_owned()));
                 host.preferred_name = preferred_name
@@ -283,76 +728,172 @@ impl Service {
         for mac in macs {
             for &index in &indexes {
                 self.by_mac.insert(mac, index);
+
+                let origin = hosts[index].mac_origins.entry(mac).or_default();
+                origin.extend(origins.iter().cloned());
             }
         }
 
         for name in names {
             for &index in &indexes {
                 self.by_name.insert(name.as_ref().to_owned(), index);
+
+                let origin = hosts[index]
+                    .name_origins
+                    .entry(name.as_ref().to_owned())
+                    .or_default();
+                origin.extend(origins.iter().cloned());
             }
         }
+
+        indexes
     }
 }
 
-/// Spawn the host monitoring task.
-pub async fn spawn(state: State, config: Arc<Config>) {
-    let mut hosts = Vec::new();
+/// Describe the conflict if a MAC address of a host is given names which the
+/// host doesn't already have.
+fn mac_conflict(
+    host: &Host,
+    mac: MacAddr6,
+    names: impl IntoIterator<Item: AsRef<str>>,
+    origins: &[Origin],
+) -> Option<String> {
+    let names = names
+        .into_iter()
+        .map(|n| n.as_ref().to_owned())
+        .collect::<Vec<_>>();
+
+    if names.is_empty() || names.iter().any(|n| host.names.contains(n)) {
+        return None;
+    }
 
-    let mut service = Service {
-        by_mac: HashMap::new(),
-        by_name: HashMap::new(),
-        reader: Reader::default(),
-    };
+    Some(format!(
+        "MAC {mac} is {} in {}, but {} in {}",
+        quoted(&host.names),
+        describe(host.mac_origins.get(&mac).into_iter().flatten()),
+        quoted(&names),
+        describe(origins),
+    ))
+}
 
-    loop {
-        hosts.clear();
+/// Join names in quotes.
+fn quoted(names: impl IntoIterator<Item: fmt::Display>) -> String {
+    let names = names
+        .into_iter()
+        .map(|n| format!("`{n}`"))
+        .collect::<Vec<_>>();
 
-        service.by_mac.clear();
-        service.by_name.clear();
+    names.join(", ")
+}
 
-        for path in &state.inner.ether_paths {
-            let ethers = service.reader.read_ethers(path).await;
+/// Describe where something was defined.
+fn describe<'a>(origins: impl IntoIterator<Item = &'a Origin>) -> String {
+    let origins = origins
+        .into_iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<_>>();
 
-            for (mac, name) in ethers {
-                service.add(&mut hosts, [mac], [name.as_str()], None, false);
-            }
-        }
+    if origins.is_empty() {
+        return String::from("an unknown source");
+    }
 
-        for path in &state.inner.host_paths {
-            let found = service.reader.read_hosts(path).await;
+    origins.join(", ")
+}
 
-            for name in found {
-                service.add(&mut hosts, [], [name.as_str()], None, false);
-            }
-        }
+/// Read the entries of an ethers file.
+///
+/// Files which cannot be opened have no entries.
+pub async fn read_ethers(path: &Path) -> Ethers {
+    Reader::default()
+        .read_ethers(path)
+        .await
+        .unwrap_or_default()
+}
 
-        service.add_from_config(&mut hosts, &config);
+/// Load hosts from all sources once.
+pub async fn load(ether_paths: &[PathBuf], host_paths: &[PathBuf], config: &Config) -> Vec<Host> {
+    let mut hosts = Vec::new();
 
-        hosts.retain(|h| !h.ignore);
+    Service::new()
+        .collect(&mut hosts, ether_paths, host_paths, config)
+        .await;
 
-        for host in &mut hosts {
-            host.build_id();
+    hosts
+}
+
+/// Log the differences between two lists of hosts, both sorted by id.
+fn log_changes(old: &[Host], new: &[Host]) {
+    let name = |host: &Host| host.names().next().unwrap_or_default().to_owned();
+
+    for host in new {
+        match old.binary_search_by_key(&host.id, |h| h.id) {
+            Err(..) => tracing::info!(id = ?host.id, "Added host {}", name(host)),
+            Ok(n) if old[n] != *host => {
+                tracing::info!(id = ?host.id, "Changed host {}", name(host))
+            }
+            Ok(..) => {}
         }
+    }
 
-        hosts.sort_by_key(|h| h.id);
+    for host in old {
+        if new.binary_search_by_key(&host.id, |h| h.id).is_err() {
+            tracing::info!(id = ?host.id, "Removed host {}", name(host));
+        }
+    }
+}
 
-        let existing = state.inner.hosts.read().await;
+/// Spawn the host monitoring task.
+pub async fn spawn(state: State) {
+    let mut hosts = Vec::new();
+    let mut service = Service::new();
 
-        'done: {
-            if existing.len() == hosts.len()
-                && existing.iter().zip(&hosts).all(|(a, b)| a.id == b.id)
-            {
-                hosts.clear();
-                break 'done;
+    loop {
+        let config = state.config().await;
+
+        service
+            .collect(
+                &mut hosts,
+                &state.inner.ether_paths,
+                &state.inner.host_paths,
+                &config,
+            )
+            .await;
+
+        state
+            .inner
+            .sources
+            .write()
+            .await
+            .clone_from(&service.sources);
+
+        state
+            .inner
+            .conflicts
+            .write()
+            .await
+            .clone_from(&service.conflicts);
+
+        let existing = state.hosts();
+
+        if *existing == *hosts {
+            hosts.clear();
+        } else {
+            if !existing.is_empty() {
+                log_changes(&existing, &hosts);
             }
 
-            tracing::info!("Updated hosts");
+            let hosts = Arc::from(mem::take(&mut hosts));
 
-            drop(existing);
-            let mut write = state.inner.hosts.write().await;
-            *write = hosts.drain(..).collect();
-        };
+            state.inner.hosts.send_modify(|current| {
+                current.generation += 1;
+                current.hosts = hosts;
+                tracing::info!(generation = current.generation, "Updated hosts");
+            });
+        }
 
-        time::sleep(Duration::from_secs(30)).await;
+        tokio::select! {
+            _ = time::sleep(Duration::from_secs(30)) => {}
+            _ = state.inner.reload.notified() => {}
+        }
     }
 }
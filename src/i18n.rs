@@ -0,0 +1,157 @@
+//! Translations of the user interface.
+//!
+//! Templates translate strings with `t("Wake")`, where the English string is
+//! the key. Translations into other locales are loaded from
+//! `static/locales/<locale>.toml`, and strings which are missing from a table
+//! are shown in English.
+
+use core::str;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, header};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::embed;
+use crate::hosts;
+
+/// The locale strings in templates are written in.
+pub(crate) const DEFAULT: &str = "en";
+
+tokio::task_local! {
+    /// The locale negotiated for the current request.
+    static LOCALE: &'static str;
+}
+
+/// Translation tables by locale.
+fn tables() -> &'static HashMap<String, HashMap<String, String>> {
+    static TABLES: OnceLock<HashMap<String, HashMap<String, String>>> = OnceLock::new();
+    TABLES.get_or_init(load)
+}
+
+fn load() -> HashMap<String, HashMap<String, String>> {
+    let mut tables = HashMap::new();
+
+    for name in embed::iter() {
+        let Some(locale) = name
+            .strip_prefix("locales/")
+            .and_then(|n| n.strip_suffix(".toml"))
+        else {
+            continue;
+        };
+
+        let Some(file) = embed::get(&name) else {
+            continue;
+        };
+
+        match parse(&file.data) {
+            Ok(table) => {
+                tables.insert(locale.to_owned(), table);
+            }
+            Err(error) => {
+                tracing::warn!("{name}: {error}");
+            }
+        }
+    }
+
+    tables
+}
+
+/// Parse a translation table.
+pub(crate) fn parse(data: &[u8]) -> Result<HashMap<String, String>, String> {
+    match str::from_utf8(data) {
+        Ok(text) => toml::from_str(text).map_err(|e| e.to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Find a supported locale by name.
+pub(crate) fn find(locale: &str) -> Option<&'static str> {
+    if locale == DEFAULT {
+        return Some(DEFAULT);
+    }
+
+    tables().get_key_value(locale).map(|(k, _)| k.as_str())
+}
+
+/// Test if the given locale is supported.
+pub(crate) fn is_supported(locale: &str) -> bool {
+    find(locale).is_some()
+}
+
+/// Get all supported locales, sorted by name.
+pub(crate) fn supported() -> Vec<&'static str> {
+    let mut locales = tables().keys().map(String::as_str).collect::<Vec<_>>();
+    locales.push(DEFAULT);
+    locales.sort();
+    locales
+}
+
+/// Get the locale of the current request.
+pub(crate) fn current() -> &'static str {
+    LOCALE.try_with(|locale| *locale).unwrap_or(DEFAULT)
+}
+
+/// Translate a string into the given locale.
+pub(crate) fn translate<'a>(locale: &str, key: &'a str) -> &'a str {
+    tables()
+        .get(locale)
+        .and_then(|table| table.get(key))
+        .map_or(key, String::as_str)
+}
+
+/// Negotiate the locale to use from the `Accept-Language` header, falling
+/// back to the configured default.
+fn negotiate(headers: &HeaderMap, default: Option<&str>) -> &'static str {
+    let mut best = None::<(f32, &'static str)>;
+
+    let accept = headers
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for entry in accept {
+        let mut parts = entry.split(';');
+
+        let Some(tag) = parts.next() else {
+            continue;
+        };
+
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let tag = tag.trim().to_ascii_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default();
+
+        let Some(locale) = find(&tag).or_else(|| find(primary)) else {
+            continue;
+        };
+
+        if q > 0.0 && best.is_none_or(|(best, _)| q > best) {
+            best = Some((q, locale));
+        }
+    }
+
+    if let Some((_, locale)) = best {
+        return locale;
+    }
+
+    default.and_then(find).unwrap_or(DEFAULT)
+}
+
+/// Middleware which negotiates the locale templates are rendered in.
+pub(crate) async fn middleware(
+    State(hosts): State<hosts::State>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let config = hosts.config().await;
+    let locale = negotiate(req.headers(), config.locale.as_deref());
+    LOCALE.scope(locale, next.run(req)).await
+}
@@ -0,0 +1,228 @@
+//! Reading the network interfaces of the machine wolo runs on.
+
+use core::ffi::CStr;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use macaddr::MacAddr6;
+use tokio::fs;
+
+use crate::config::Subnet;
+use crate::mac::Mac;
+
+/// Where network interfaces are listed on Linux.
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// A local network interface.
+#[derive(Debug, Clone)]
+pub struct Interface {
+    /// The name of the interface, like `eth0`.
+    pub name: String,
+    /// The index of the interface, if known.
+    pub index: Option<u32>,
+    /// The MAC address of the interface.
+    pub mac: Option<MacAddr6>,
+    /// The operational state of the link, like `up` or `down`.
+    pub state: String,
+    /// Whether the interface is wireless.
+    pub wireless: bool,
+    /// Whether the device of the interface is allowed to wake the system, if
+    /// known.
+    pub wakeup: Option<bool>,
+    /// Addresses assigned to the interface.
+    pub addresses: Vec<Address>,
+}
+
+impl Interface {
+    /// Test if the link of the interface is up.
+    pub fn is_up(&self) -> bool {
+        self.state == "up"
+    }
+}
+
+/// An address assigned to an interface.
+#[derive(Debug, Clone)]
+pub struct Address {
+    /// The address.
+    pub address: IpAddr,
+    /// The length of the network prefix.
+    pub prefix: u32,
+    /// The broadcast address of the network, if it has one.
+    pub broadcast: Option<Ipv4Addr>,
+}
+
+impl Address {
+    /// Test if the given address is in the network of this address.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        Subnet::new(self.address, self.prefix as u8).contains(address)
+    }
+}
+
+/// Read all local network interfaces, sorted by name.
+///
+/// This is empty if interfaces can't be listed, like when not running on
+/// Linux.
+pub async fn read() -> Vec<Interface> {
+    let mut interfaces = Vec::new();
+
+    let Ok(mut d) = fs::read_dir(SYS_CLASS_NET).await else {
+        return interfaces;
+    };
+
+    let mut addresses = addresses();
+
+    while let Ok(Some(e)) = d.next_entry().await {
+        let Some(name) = e.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+
+        let path = e.path();
+
+        let mac = read_value(&path.join("address"))
+            .await
+            .and_then(|value| value.parse::<Mac>().ok())
+            .map(|Mac(mac)| mac)
+            .filter(|mac| !mac.is_nil());
+
+        let index = read_value(&path.join("ifindex"))
+            .await
+            .and_then(|value| value.parse().ok());
+
+        let state = read_value(&path.join("operstate"))
+            .await
+            .unwrap_or_else(|| String::from("unknown"));
+
+        let wireless = fs::try_exists(path.join("wireless"))
+            .await
+            .unwrap_or_default();
+
+        let wakeup = read_value(&path.join("device/power/wakeup"))
+            .await
+            .map(|value| value == "enabled");
+
+        interfaces.push(Interface {
+            addresses: addresses.remove(&name).unwrap_or_default(),
+            name,
+            index,
+            mac,
+            state,
+            wireless,
+            wakeup,
+        });
+    }
+
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Test if an interface is selected by the given names, where no names
+/// selects all interfaces.
+pub fn is_selected(names: &[String], interface: &str) -> bool {
+    names.is_empty() || names.iter().any(|n| n == interface)
+}
+
+/// Get the broadcast addresses of the selected interfaces whose links are up.
+///
+/// This is empty if no interfaces are selected, in which case magic packets
+/// are sent to the limited broadcast address.
+pub async fn broadcasts(names: &[String]) -> Vec<Ipv4Addr> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    let mut broadcasts = Vec::new();
+
+    for i in read().await {
+        if !i.is_up() || !is_selected(names, &i.name) {
+            continue;
+        }
+
+        broadcasts.extend(i.addresses.iter().filter_map(|a| a.broadcast));
+    }
+
+    broadcasts
+}
+
+/// Read a single trimmed value from a sysfs file.
+async fn read_value(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).await.ok()?;
+    Some(value.trim().to_owned())
+}
+
+/// Get the addresses of all interfaces by interface name.
+fn addresses() -> HashMap<String, Vec<Address>> {
+    let mut out = HashMap::<String, Vec<Address>>::new();
+    let mut head = core::ptr::null_mut::<libc::ifaddrs>();
+
+    // SAFETY: On success `getifaddrs` populates `head` with a linked list which
+    // stays valid until it's passed to `freeifaddrs`.
+    unsafe {
+        if libc::getifaddrs(&mut head) != 0 {
+            return out;
+        }
+
+        let mut current = head;
+
+        while let Some(ifa) = current.as_ref() {
+            current = ifa.ifa_next;
+
+            let Some(address) = to_ip(ifa.ifa_addr) else {
+                continue;
+            };
+
+            let prefix = match to_ip(ifa.ifa_netmask) {
+                Some(IpAddr::V4(mask)) => mask.to_bits().count_ones(),
+                Some(IpAddr::V6(mask)) => mask.to_bits().count_ones(),
+                None => 0,
+            };
+
+            let broadcast = if ifa.ifa_flags & libc::IFF_BROADCAST as u32 != 0 {
+                match to_ip(ifa.ifa_ifu) {
+                    Some(IpAddr::V4(broadcast)) => Some(broadcast),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+
+            out.entry(name).or_default().push(Address {
+                address,
+                prefix,
+                broadcast,
+            });
+        }
+
+        libc::freeifaddrs(head);
+    }
+
+    out
+}
+
+/// Convert a socket address, like those from `getifaddrs`, into an IP address.
+///
+/// # Safety
+///
+/// The address must be null or point to a valid socket address.
+pub(crate) unsafe fn to_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    // SAFETY: The caller guarantees that the address is valid, and the family
+    // determines the type of the socket address.
+    unsafe {
+        match i32::from(addr.as_ref()?.sa_family) {
+            libc::AF_INET => {
+                let addr = &*addr.cast::<libc::sockaddr_in>();
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    addr.sin_addr.s_addr,
+                ))))
+            }
+            libc::AF_INET6 => {
+                let addr = &*addr.cast::<libc::sockaddr_in6>();
+                Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        }
+    }
+}
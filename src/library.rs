@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use tokio::fs;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use crate::zip::Archive;
+
+/// Extensions of files which are considered to be pages.
+const IMAGE_EXTENSIONS: &[&str] = &["avif", "gif", "jpeg", "jpg", "png", "webp"];
+
+/// Extensions of archives which can hold the pages of a volume.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["cbz", "zip"];
+
+/// Number of characters of context to include around a search match.
+const SNIPPET_CONTEXT: usize = 24;
+
+/// An index of the series and volumes in Mokuro libraries.
+///
+/// Series are scanned on first use and kept up to date by [`spawn`].
+#[derive(Clone)]
+pub struct Library {
+    cache: Arc<Mutex<HashMap<PathBuf, Cached>>>,
+}
+
+struct Cached {
+    modified: SystemTime,
+    series: Arc<Series>,
+}
+
+/// A series, which is a directory of volumes.
+pub struct Series {
+    /// The name of the series directory.
+    pub name: String,
+    /// The title of the series.
+    pub title: String,
+    /// Volumes in the series, sorted by name.
+    pub volumes: Vec<Volume>,
+}
+
+/// A single volume processed by Mokuro.
+pub struct Volume {
+    /// The name of the volume, which is the stem of its `.html` file or
+    /// archive.
+    pub name: String,
+    /// The title of the volume.
+    pub title: String,
+    /// Paths of the page images relative to the volume directory or archive,
+    /// in reading order.
+    pub pages: Vec<String>,
+    /// The OCR text of each page, which is empty unless the volume has a
+    /// `.mokuro` file.
+    pub text: Vec<String>,
+    /// Whether the volume has an `.html` reader generated by Mokuro.
+    pub html: bool,
+}
+
+impl Volume {
+    /// Path of the cover image relative to the volume directory or archive.
+    pub fn cover(&self) -> Option<&str> {
+        self.pages.first().map(String::as_str)
+    }
+}
+
+/// A page matching a search.
+pub struct Hit {
+    /// The series the page belongs to.
+    pub series: Arc<Series>,
+    /// Index of the volume in the series.
+    pub volume: usize,
+    /// The page number, starting at 1.
+    pub page: usize,
+    /// Text preceding the match.
+    pub before: String,
+    /// The matched text.
+    pub matched: String,
+    /// Text following the match.
+    pub after: String,
+}
+
+/// The parts of a `.mokuro` file we're interested in.
+#[derive(Deserialize)]
+struct MokuroFile {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    volume: Option<String>,
+    #[serde(default)]
+    pages: Vec<MokuroPage>,
+}
+
+#[derive(Deserialize)]
+struct MokuroPage {
+    img_path: String,
+    #[serde(default)]
+    blocks: Vec<MokuroBlock>,
+}
+
+#[derive(Deserialize)]
+struct MokuroBlock {
+    #[serde(default)]
+    lines: Vec<String>,
+}
+
+impl Library {
+    /// Construct a new empty library index.
+    pub fn new() -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get all series in the given library root, sorted by name.
+    pub async fn all(&self, root: &Path) -> Result<Vec<Arc<Series>>> {
+        let names = series_names(root).await?;
+        let mut series = Vec::with_capacity(names.len());
+
+        for name in names {
+            series.push(self.series(root, &name).await?);
+        }
+
+        Ok(series)
+    }
+
+    /// Get a single series in the given library root.
+    pub async fn series(&self, root: &Path, name: &str) -> Result<Arc<Series>> {
+        let path = root.join(name);
+
+        if let Some(cached) = self.cache.lock().await.get(&path) {
+            return Ok(cached.series.clone());
+        }
+
+        self.scan(path, name).await
+    }
+
+    /// Scan a series and store it in the cache.
+    async fn scan(&self, path: PathBuf, name: &str) -> Result<Arc<Series>> {
+        let modified = modified(&path).await?;
+        let series = Arc::new(scan(&path, name).await?);
+
+        self.cache.lock().await.insert(
+            path,
+            Cached {
+                modified,
+                series: series.clone(),
+            },
+        );
+
+        Ok(series)
+    }
+
+    /// Forget the series at the given path, so that it's scanned again the
+    /// next time it's used.
+    pub async fn forget(&self, path: &Path) {
+        self.cache.lock().await.remove(path);
+    }
+
+    /// Scan series in the given library roots which have been added or
+    /// modified, and forget the ones which have been removed.
+    async fn refresh(&self, roots: &[PathBuf]) {
+        let mut seen = HashSet::new();
+
+        for root in roots {
+            let names = match series_names(root).await {
+                Ok(names) => names,
+                Err(error) => {
+                    tracing::warn!("{}: {error}", root.display());
+                    continue;
+                }
+            };
+
+            for name in names {
+                let path = root.join(&name);
+
+                let Ok(modified) = modified(&path).await else {
+                    continue;
+                };
+
+                let existing = self
+                    .cache
+                    .lock()
+                    .await
+                    .get(&path)
+                    .map(|cached| cached.modified);
+
+                if existing != Some(modified) {
+                    match self.scan(path.clone(), &name).await {
+                        Ok(..) if existing.is_some() => {
+                            tracing::info!("Updated series {}", path.display())
+                        }
+                        Ok(..) => tracing::info!("Added series {}", path.display()),
+                        Err(error) => tracing::warn!("{}: {error}", path.display()),
+                    }
+                }
+
+                seen.insert(path);
+            }
+        }
+
+        self.cache.lock().await.retain(|path, _| {
+            let keep = seen.contains(path);
+
+            if !keep {
+                tracing::info!("Removed series {}", path.display());
+            }
+
+            keep
+        });
+    }
+
+    /// Search the OCR text of all volumes in the given library root.
+    ///
+    /// At most `limit` pages are returned.
+    pub async fn search(&self, root: &Path, query: &str, limit: usize) -> Result<Vec<Hit>> {
+        let mut hits = Vec::new();
+
+        if query.is_empty() {
+            return Ok(hits);
+        }
+
+        for series in self.all(root).await? {
+            for (volume, v) in series.volumes.iter().enumerate() {
+                for (page, text) in v.text.iter().enumerate() {
+                    if hits.len() == limit {
+                        return Ok(hits);
+                    }
+
+                    let Some(at) = text.find(query) else {
+                        continue;
+                    };
+
+                    let end = at + query.len();
+
+                    hits.push(Hit {
+                        series: series.clone(),
+                        volume,
+                        page: page + 1,
+                        before: tail(&text[..at], SNIPPET_CONTEXT).to_owned(),
+                        matched: text[at..end].to_owned(),
+                        after: head(&text[end..], SNIPPET_CONTEXT).to_owned(),
+                    });
+                }
+            }
+        }
+
+        Ok(hits)
+    }
+}
+
+/// Spawn the task which keeps the library index up to date with the given
+/// library roots.
+pub async fn spawn(library: Library, roots: Vec<PathBuf>) {
+    loop {
+        library.refresh(&roots).await;
+        time::sleep(Duration::from_secs(30)).await;
+    }
+}
+
+/// Get the names of all series in a library root, sorted by name.
+async fn series_names(root: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut d = fs::read_dir(root).await?;
+
+    while let Some(e) = d.next_entry().await? {
+        if !e.file_type().await?.is_dir() {
+            continue;
+        }
+
+        if let Some(name) = e.file_name().to_str() {
+            names.push(name.to_owned());
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Get the last time a series was modified.
+///
+/// This is the latest modification of the series directory or any of its
+/// entries, so that volumes being changed in place are picked up.
+async fn modified(path: &Path) -> Result<SystemTime> {
+    let mut modified = fs::metadata(path).await?.modified()?;
+    let mut d = fs::read_dir(path).await?;
+
+    while let Some(e) = d.next_entry().await? {
+        if let Ok(m) = e.metadata().await.and_then(|m| m.modified()) {
+            modified = modified.max(m);
+        }
+    }
+
+    Ok(modified)
+}
+
+/// Validate the contents of a `.mokuro` file.
+pub fn validate_mokuro(bytes: &[u8]) -> Result<()> {
+    let file = serde_json::from_slice::<MokuroFile>(bytes)?;
+
+    if file.pages.is_empty() {
+        bail!("no pages");
+    }
+
+    Ok(())
+}
+
+/// Scan a series directory for volumes.
+async fn scan(path: &Path, name: &str) -> Result<Series> {
+    let mut names = Vec::new();
+    let mut d = fs::read_dir(path).await?;
+
+    while let Some(e) = d.next_entry().await? {
+        let p = e.path();
+
+        let is_volume = p
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|ext| ext == "html" || ARCHIVE_EXTENSIONS.contains(&ext));
+
+        if !is_volume {
+            continue;
+        }
+
+        if let Some(stem) = p.file_stem().and_then(|s| s.to_str()) {
+            names.push(stem.to_owned());
+        }
+    }
+
+    names.sort();
+    names.dedup();
+
+    let mut title = None;
+    let mut volumes = Vec::with_capacity(names.len());
+
+    for name in names {
+        let (volume, series_title) = read_volume(path, name).await;
+        title = title.or(series_title);
+        volumes.push(volume);
+    }
+
+    Ok(Series {
+        name: name.to_owned(),
+        title: title.unwrap_or_else(|| name.to_owned()),
+        volumes,
+    })
+}
+
+/// Read a volume, preferring metadata from its `.mokuro` file and falling back
+/// to the images in its directory or archive.
+///
+/// Also returns the title of the series if it's known.
+async fn read_volume(series: &Path, name: String) -> (Volume, Option<String>) {
+    let html = fs::try_exists(series.join(format!("{name}.html")))
+        .await
+        .unwrap_or_default();
+
+    if let Ok(bytes) = fs::read(series.join(format!("{name}.mokuro"))).await {
+        match serde_json::from_slice::<MokuroFile>(&bytes) {
+            Ok(file) => {
+                let text = file
+                    .pages
+                    .iter()
+                    .map(|p| {
+                        p.blocks
+                            .iter()
+                            .flat_map(|b| b.lines.iter().map(String::as_str))
+                            .collect()
+                    })
+                    .collect();
+
+                let volume = Volume {
+                    title: file.volume.unwrap_or_else(|| name.clone()),
+                    name,
+                    pages: file.pages.into_iter().map(|p| p.img_path).collect(),
+                    text,
+                    html,
+                };
+
+                return (volume, file.title);
+            }
+            Err(error) => {
+                tracing::warn!("{}/{name}.mokuro: {error}", series.display());
+            }
+        }
+    }
+
+    let mut images = Vec::new();
+
+    if let Some(path) = find_archive(&series.join(&name)).await {
+        match Archive::open(&path).await {
+            Ok(archive) => {
+                for e in archive.entries() {
+                    if is_image(Path::new(&e.name)) {
+                        images.push(e.name.clone());
+                    }
+                }
+            }
+            Err(error) => {
+                tracing::warn!("{}: {error}", path.display());
+            }
+        }
+    } else if let Ok(mut d) = fs::read_dir(series.join(&name)).await {
+        while let Ok(Some(e)) = d.next_entry().await {
+            let p = e.path();
+
+            if let (true, Some(file_name)) = (is_image(&p), p.file_name().and_then(|s| s.to_str()))
+            {
+                images.push(file_name.to_owned());
+            }
+        }
+    }
+
+    images.sort();
+
+    let volume = Volume {
+        title: name.clone(),
+        name,
+        pages: images,
+        text: Vec::new(),
+        html,
+    };
+
+    (volume, None)
+}
+
+/// Find the archive holding the pages of the volume at the given path, if it
+/// has one.
+pub async fn find_archive(volume: &Path) -> Option<PathBuf> {
+    for ext in ARCHIVE_EXTENSIONS {
+        let mut path = volume.as_os_str().to_owned();
+        path.push(".");
+        path.push(ext);
+        let path = PathBuf::from(path);
+
+        if fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            return Some(path);
+        }
+    }
+
+    None
+}
+
+/// Test if the file at the given path is a page.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// The first `n` characters of a string.
+fn head(s: &str, n: usize) -> &str {
+    let at = s.char_indices().nth(n).map_or(s.len(), |(at, _)| at);
+    &s[..at]
+}
+
+/// The last `n` characters of a string.
+fn tail(s: &str, n: usize) -> &str {
+    let at = s
+        .char_indices()
+        .rev()
+        .take(n)
+        .last()
+        .map_or(s.len(), |(at, _)| at);
+    &s[at..]
+}
@@ -0,0 +1,95 @@
+use core::fmt;
+use core::str::FromStr;
+
+use macaddr::MacAddr6;
+
+/// Human readable description of the accepted MAC address formats.
+const FORMATS: &str = "aa:bb:cc:dd:ee:ff, aa-bb-cc-dd-ee-ff, aabb.ccdd.eeff or aabbccddeeff";
+
+/// A MAC address which can be parsed from any of the supported formats.
+///
+/// Parsing is case insensitive, and the separated forms also accept groups
+/// with leading zeros omitted like `0:1:2:3:4:5` as used in `/etc/ethers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mac(pub MacAddr6);
+
+impl FromStr for Mac {
+    type Err = ParseMacError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse(input.trim())
+            .map(|bytes| Mac(MacAddr6::from(bytes)))
+            .ok_or_else(|| ParseMacError {
+                input: input.to_owned(),
+            })
+    }
+}
+
+/// Error raised when a MAC address could not be parsed.
+#[derive(Debug)]
+pub struct ParseMacError {
+    input: String,
+}
+
+impl fmt::Display for ParseMacError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid MAC address `{}`, expected a format like {FORMATS}",
+            self.input
+        )
+    }
+}
+
+impl core::error::Error for ParseMacError {}
+
+fn parse(input: &str) -> Option<[u8; 6]> {
+    let mut bytes = [0u8; 6];
+
+    let separator = match (input.contains(':'), input.contains('-')) {
+        (true, false) => Some(':'),
+        (false, true) => Some('-'),
+        (false, false) => None,
+        (true, true) => return None,
+    };
+
+    if let Some(separator) = separator {
+        let mut groups = input.split(separator);
+
+        for b in &mut bytes {
+            *b = hex(groups.next()?, 1..=2)? as u8;
+        }
+
+        return groups.next().is_none().then_some(bytes);
+    }
+
+    if input.contains('.') {
+        let mut groups = input.split('.');
+
+        for pair in bytes.chunks_exact_mut(2) {
+            pair.copy_from_slice(&(hex(groups.next()?, 4..=4)? as u16).to_be_bytes());
+        }
+
+        return groups.next().is_none().then_some(bytes);
+    }
+
+    if input.len() != 12 {
+        return None;
+    }
+
+    for (b, n) in bytes.iter_mut().zip((0..12).step_by(2)) {
+        *b = hex(input.get(n..n + 2)?, 2..=2)? as u8;
+    }
+
+    Some(bytes)
+}
+
+/// Parse a group of hex digits whose length must be in the given range.
+fn hex(group: &str, len: core::ops::RangeInclusive<usize>) -> Option<u32> {
+    if !len.contains(&group.len()) || !group.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(group, 16).ok()
+}
@@ -14,8 +14,210 @@
 //! The `home.md` is used to populate the landing page, see [Landing
 //! Page](#landing-page) below for how to configure this.
 //!
+//! Running `wolo` without a command is the same as running `wolo serve`. The
+//! same configuration can also be used from the command line:
+//!
+//! * `wolo wake <host|mac>..` sends Wake-on-LAN packets to the given hosts or
+//!   MAC addresses using the configured wake settings, which can be overridden
+//!   with `--target` and `--port`.
+//! * `wolo ping <host>..` pings all addresses of the given hosts once and
+//!   prints the results as a table, or as JSON with `--json`.
+//! * `wolo hosts list` lists all known hosts.
+//! * `wolo bench --simulate --count 5000` drives the ping scheduler with
+//!   synthetic hosts and simulated responses for `--duration` (30s by default),
+//!   printing pings per second, scheduling lag and memory use every second.
+//! * `wolo assets list` lists the assets embedded in the binary with their size
+//!   and SHA-256 hash, and `wolo assets verify` checks that every required
+//!   asset is embedded, that templates parse and that translations load. It
+//!   exits with a non-zero status otherwise, so image builds can run it to fail
+//!   early.
+//! * `wolo check` validates configuration, see below.
+//! * `wolo status --url <url>` queries a running instance through its
+//!   `/api/v1/status` endpoint and prints a summary of host counts, pings per
+//!   second, resolver errors, uptime and version.
+//! * `wolo export-state <archive>` writes the history database and the loaded
+//!   configuration files to a zip archive, which `wolo import-state <archive>`
+//!   restores on another machine. Configuration files are only restored with
+//!   `--config-dir <dir>`, and existing files are only overwritten with
+//!   `--force`. The same archive can be downloaded from the admin page.
+//! * `wolo completions <shell>` and `wolo man` print shell completions and a
+//!   manual page generated from the command line definition.
+//!
 //! The `/network` page show an overview of the state of hosts on the network
-//! and the ability to wake them up if they have configured mac addresses.
+//! and the ability to wake them up if they have configured mac addresses. The
+//! same state is available as JSON from `/api/v1/hosts`, where round trip times
+//! are included in milliseconds as `rtt_ms` and hosts in their quiet hours are
+//! marked as `quiet`, so that alerts built on top of it can be suppressed.
+//! Hosts which don't respond while a host they `depends_on` is down have the
+//! status `unreachable` rather than `down`, with the id of that host as
+//! `dependency`. Hosts which ignore pings but are reachable according to the
+//! neighbor table of the machine wolo runs on are `up`, and are marked as
+//! responding to ARP only with `arp_only`.
+//!
+//! The merged hosts can be exported from `/api/v1/hosts/export` for other
+//! infrastructure to consume, where `format` is one of `hosts` for
+//! `/etc/hosts`, which is the default, `ethers` for `/etc/ethers`, or `dns-zone`
+//! for address records to include in a BIND zone. Addresses are the ones hosts
+//! were last pinged or seen at, and names which are addresses are left out.
+//!
+//! The same hosts are available as an Ansible dynamic inventory from
+//! `/api/v1/inventory`, with a group for each tag and variables like
+//! `ansible_host`, `wolo_macs` and `wolo_tags` for each host. It can be used
+//! as the output of a script which fetches it, like
+//! `curl -s http://localhost:3000/api/v1/inventory`.
+//!
+//! How hosts are sorted, which hosts have their details collapsed, how often
+//! the page refreshes, whether it uses a dark theme, a compact layout for small
+//! screens and which columns of ping results are shown are remembered per
+//! browser in a signed cookie. Cookies are signed with the `key` in
+//! `[sessions]`, or with a random key which changes every time wolo starts.
+//! Columns and density can also be picked for a single page, like
+//! `/network?columns=timing,loss,last-seen&density=compact`, where columns are
+//! out of `code`, `sequence`, `addresses`, `timing`, `checksum`, `loss` and
+//! `last-seen`.
+//!
+//! `/network?view=report`, which also works for segments, is a table of hosts
+//! with summary counts and when it was generated, without any buttons. It's
+//! meant to be printed or saved as PDF, like for audits of what's on the
+//! network.
+//!
+//! Host names which can't be resolved show whether the name doesn't exist or
+//! the DNS server failed or timed out. Names which don't exist are resolved
+//! again after a minute, backing off up to every 30 minutes while they keep
+//! failing.
+//!
+//! Hosts which send malformed replies can be diagnosed by running the service
+//! with `--icmp-dump <path>`, which writes every ICMP packet sent and received
+//! to a pcap file that can be opened with Wireshark.
+//!
+//! To develop or demonstrate the service on a machine without permission to
+//! send ICMP packets, run it with `--simulate`. Hosts then respond with
+//! plausible simulated results, where most are up and some are down or
+//! unreachable, and magic packets are logged instead of sent.
+//!
+//! When it starts, wolo checks that it can create ICMP sockets and broadcast
+//! magic packets, that every configured file can be read and that names can be
+//! resolved. The results are printed as a table with hints on how to fix what
+//! failed, and served as JSON from `/readyz`, which responds with
+//! `503 Service Unavailable` if any check failed. To refuse to start instead,
+//! run it with `--strict-start`. Otherwise, if no ICMP sockets can be created,
+//! wolo keeps serving the web interface, waking and discovering devices while
+//! pinging is disabled, and a banner on the network page explains how to
+//! enable it.
+//!
+//! Init systems which leave hardening to the service, like BSD rc scripts, can
+//! start wolo as root with `--user <name>` and optionally `--group <name>`. It
+//! then switches to them once its sockets have been created. `--pidfile <path>`
+//! writes the process id to a file which is removed on exit. It's written after
+//! switching user, so it has to be in a directory the user may write to, like
+//! `/var/run/wolo`. `--umask <mode>` sets the file mode creation mask, like
+//! `--umask 027`.
+//!
+//! On systemd, `wolo install-service` writes a hardened `wolo.service` and a
+//! `wolo.socket` which passes it the listener of the web interface, based on
+//! the options it is run with and the configuration. The service runs as a
+//! dynamic user with only the capabilities it needs, like `CAP_NET_RAW`, and
+//! may write to `/var/lib/wolo` along with the paths configured for history,
+//! reports and uploads. With `--user <name>`, a sysusers.d file creating the
+//! user is written as well. Use `--print` to see the units without writing
+//! them.
+//!
+//! An instance which is exposed to guests, like a public status page, can be
+//! run with `--read-only`. Every request which would change anything, like
+//! waking a host, is then rejected with `403 Forbidden`.
+//!
+//! Hosts which are down have hints next to their wake button, like when they
+//! were last seen and which interface their MAC address was last seen on
+//! according to the ARP table. If a MAC address belongs to a wireless interface
+//! of the machine wolo runs on, waking it is disabled since it can't work.
+//!
+//! Only hosts which aren't up can be woken. They are marked as sleeping (💤)
+//! if they were up within the last day or are still in the ARP table, and as
+//! absent (🔌) if they haven't been seen since. The same estimate is the
+//! `power` of hosts in `/api/v1/hosts`.
+//!
+//! The network page also shows the vitals of the machine wolo runs on, like
+//! its load, memory, temperature, how much space the history database takes
+//! and how many sockets and file descriptors are open, so a Raspberry Pi
+//! running wolo can be kept an eye on too. They are also available from
+//! `/api/v1/vitals`.
+//!
+//! Hosts which sleep when idle, like NAS boxes, can be woken on demand by
+//! setting `on_demand` ports in their `wake` settings. While such a host is
+//! down, wolo answers ARP and neighbor discovery for its addresses, and wakes
+//! it once something tries to connect to one of the ports. Every wake on
+//! demand is recorded in the audit log with the address which connected.
+//!
+//! Wake listeners are ports on the machine wolo runs on which wake a host when
+//! something connects to them, and which can forward the connection to the
+//! host once it accepts connections. Pointing `nas` at wolo and listening on
+//! port 22 makes `ssh nas` power on the NAS and connect to it.
+//!//!
+//! Devices which aren't configured can be woken by their MAC address through
+//! the form at the bottom of the network page, or by posting to `/api/v1/wake`:
+//!
+//! ```sh
+//! curl -X POST -H "Content-Type: application/json" \
+//!     -d '{"mac": "00:11:22:33:44:55", "target": "192.168.1.255"}' \
+//!     http://localhost:3000/api/v1/wake
+//! ```
+//!
+//! Configured hosts can be woken the same way with `{"host": "example.com"}`.
+//! Every wake is recorded in the audit log, which is shown on the admin page.
+//!
+//! Magic packets are sent from a queue, which resends them to configured hosts
+//! until they respond to a ping or run out of attempts. Progress is shown on the
+//! host in the network page, like "waking, attempt 2/5", as `waking` in
+//! `/api/v1/hosts` and as the list of wakes in progress from `GET /api/v1/wake`.
+//!
+//! How often waking a host succeeded, meaning that it responded before the
+//! attempts ran out, and how long it took on average are shown with its details
+//! in the network page. This helps telling which hosts need more `attempts` or
+//! another `target`. Outcomes are stored in the history database if one is
+//! configured.
+//!
+//! The interfaces of the machine wolo runs on are listed on
+//! `/network/interfaces` with their addresses and link state, and as JSON from
+//! `/api/v1/interfaces`.
+//!
+//! Hosts going up or down, wakes, configuration reloads, new devices, pings
+//! of this machine and hosts disappearing are shown as a timeline of events on
+//! `/network/timeline`, and as JSON from `/api/v1/events`. Both can be
+//! filtered by `host` and by `kind`, which is one of `up`, `down`, `wake`,
+//! `reload`, `discovery`, `ping` or `gone`. The most recent 1000 events are
+//! kept in memory.
+//!
+//! Hosts which disappear from the list of hosts, like when they are removed
+//! from a hosts file, are listed on the network page with their last known
+//! status for the time configured by `disappeared` in `[discovery]`.
+//!
+//! Hosts with service level objectives show how they are doing in the window
+//! in the network page, and as `slo` in `/api/v1/hosts`. Metrics for host
+//! status, round trip times, objectives and counters polled over SNMP are
+//! served in the Prometheus text format from `/metrics`, and can be pushed on
+//! an interval to a remote endpoint configured in `[metrics.push]`.
+//!
+//! A small badge with the status and round trip time of a host is served as
+//! an SVG image from `/badge/<name>.svg`, where the host is looked up by one of
+//! its names or its identifier. Badges can be embedded in wikis and READMEs,
+//! and may be cached for 30 seconds.
+//!
+//! The JSON API under `/api/v1` is described by an OpenAPI document served from
+//! `/api/v1/openapi.json`, which clients can be generated from. It can be
+//! browsed with the Swagger UI at `/api/v1/docs/`.
+//!
+//! Grafana can chart history through its JSON datasource plugin pointed at
+//! `/api/v1/grafana`. Targets are named `<host>/rtt` for the mean round trip
+//! time in milliseconds and `<host>/availability` for the share of answered
+//! pings between 0 and 1. Queries are allowed in read-only mode and for
+//! viewers, even though they are posted.
+//!
+//! Listing and waking hosts is also available over gRPC on the same address, as
+//! the `wolo.v1.Wolo` service defined in [`proto/wolo.proto`], along with
+//! `WatchHosts` which streams the state of every host and then each change to
+//! it. Wakes over gRPC are subject to read-only mode like all other changes.
+//!
+//! [`proto/wolo.proto`]: https://github.com/udoprog/wolo/blob/main/proto/wolo.proto
 //!
 //! <table>
 //! <tr>
@@ -40,7 +242,27 @@
 //!   addresses. Additional files of this format can be specified using
 //!   `--ethers <path>`.
 //! * Any number of optional configuration files can be specified using
-//!   `--config <path>`.
+//!   `--config <path>`. If a directory is specified, all `.toml` files in it
+//!   are loaded in lexical order.
+//!
+//! Configuration can be validated without starting the service using `wolo
+//! check`, which prints a report of all problems found and exits with a
+//! non-zero status if there are errors. This is suitable for use with
+//! `ExecStartPre=` in systemd units.
+//!
+//! Problems with the configuration are reported as either errors or warnings,
+//! such as unknown keys or settings which have no effect. Only errors prevent
+//! the service from starting unless `--strict` is specified, in which case
+//! warnings are treated as errors as well.
+//!
+//! The effective configuration after all files, command line options and host
+//! sources have been merged can be printed using `wolo dump-config`, optionally
+//! with `--format json`.
+//!
+//! Sending `SIGHUP` to a running service reloads its configuration. Added,
+//! removed and changed hosts are logged and applied without restarting, and
+//! hosts which did not change keep their ping and check history. Changes to
+//! `bind`, `home`, `showcase` and Mokuro libraries require a restart.
 //!
 //! The configuration files are in toml, and have the following format:
 //!
@@ -49,12 +271,231 @@
 //! # Can be IPv4 or IPv6.
 //! bind = "localhost:3000"
 //!
-//! # Simple variant of a list of hosts.
-//! hosts = ["example.com", "another.example.com"]
+//! # Reject every request which would change anything, like waking hosts, probes
+//! # or the actions of the admin page, with `403 Forbidden`. This can also be
+//! # enabled with `--read-only`, which takes precedence.
+//! read_only = false
+//!
+//! # Additional configuration files to load. Patterns are relative to the
+//! # directory of the current file, and matching files are loaded in lexical
+//! # order after this file so that settings in them take precedence.
+//! include = ["conf.d/*.toml"]
+//!
+//! # Interfaces used for discovery and Wake-on-LAN. Magic packets are sent to the
+//! # broadcast addresses of these interfaces unless a host has a `target`.
+//! # Defaults to all interfaces, sending magic packets to 255.255.255.255.
+//! interfaces = ["eth0"]
+//!
+//! # Simple variant of a list of hosts. Addresses are hosts without names, which
+//! # are pinged at and named by the address.
+//! hosts = ["example.com", "another.example.com", "192.168.1.20"]
+//!
+//! # DNS servers to query directly instead of using the system resolver, like
+//! # the router when a local stub resolver filters local names. The port
+//! # defaults to 53. The hosts file of the system isn't consulted when servers
+//! # are configured.
+//! [dns]
+//! servers = ["192.168.1.1:53"]
+//! # Domains tried in order for names without a dot, before the name itself.
+//! search = ["lan"]
+//! # Timeout of each query, which defaults to 2s.
+//! timeout = "2s"
+//!
+//! # Where the outcome of every ping and probe is stored, which is required to
+//! # evaluate service level objectives. History isn't recorded unless this is
+//! # set.
+//! [history]
+//! path = "/var/lib/wolo/history.db"
+//! # How long individual pings are kept before they are compacted into five
+//! # minute aggregates, which defaults to 7d.
+//! raw = "7d"
+//! # How long aggregates are kept, which defaults to 90d.
+//! aggregates = "180d"
+//!
+//! # Also write every ping to InfluxDB, or anything else which accepts its line
+//! # protocol, with the tags `host_id`, `host` and `address` and the fields
+//! # `answered` and `rtt` in seconds. This works without a history database.
+//! # Samples which fail to be written are retried. Only plain http:// URLs are
+//! # supported.
+//! [history.influx]
+//! url = "http://192.168.1.20:8086/api/v2/write?org=home&bucket=wolo"
+//! # Sent as `Authorization: Token <token>`.
+//! token = "…"
+//! # Defaults to `wolo_ping`.
+//! measurement = "wolo_ping"
+//!
+//! # Notify about devices which show up in the neighbor table of this machine
+//! # but have never been seen before, and which don't belong to a configured
+//! # host. Devices which have been seen are stored in the history database if one
+//! # is configured. Devices present when wolo starts without any stored devices
+//! # are considered known.
+//! [discovery]
+//! new_devices = true
+//! # List new devices on the network page until they are acknowledged. New
+//! # devices can also be marked as suspicious, which pins them to the top of the
+//! # network page and lists them in `/api/v1/devices/suspicious` until the mark
+//! # is removed. Marks are stored in the history database if one is configured.
+//! acknowledge = true
+//! # Passive mode, where echo requests other machines send to this machine are
+//! # recorded as `ping` events in the timeline, which helps spotting scanners.
+//! # Further pings from the same address within 10 minutes are only counted.
+//! # This needs `CAP_NET_RAW` and takes effect when wolo is started.
+//! echo_requests = true
+//! # How long hosts which disappear from the list of hosts are listed on the
+//! # network page with their last known status. Defaults to 1h, and 0s
+//! # disables it.
+//! disappeared = "1h"
+//!
+//! # Webhooks which notifications are posted to as JSON, like
+//! # `{"event": "new_device", "mac": "…", "address": "…", "device": "eth0"}`.
+//! # Only plain http:// URLs are supported.
+//! [notify]
+//! webhooks = ["http://192.168.1.10:8080/hooks/wolo"]
+//!
+//! # A summary of availability per host, notable outages, new devices and wakes,
+//! # which is generated once per interval. Availability and new devices come
+//! # from the history database, while outages and wakes come from the timeline
+//! # of events which is kept in memory. The report of the interval ending now
+//! # can also be viewed under `/network/report`, or `/network/report.md` as
+//! # markdown.
+//! [report]
+//! # Write the report to a file, as markdown if it ends with `.md` and otherwise
+//! # as HTML. The next report is generated one interval after the file was last
+//! # written.
+//! path = "/var/lib/wolo/report.html"
+//! # Post the report as markdown to the webhooks above, like
+//! # `{"event": "report", "markdown": "…"}`.
+//! notify = true
+//! # Defaults to a week.
+//! interval = "7d"
+//!
+//! # Push the metrics served from `/metrics` for when nothing scrapes them, like
+//! # to `http://prometheus:9090/api/v1/write` or the `/write` endpoint of
+//! # VictoriaMetrics or InfluxDB. Only plain http:// URLs are supported.
+//! [metrics.push]
+//! url = "http://192.168.1.20:8428/api/v1/write"
+//! # Either `remote-write` for the Prometheus remote-write protocol, which is
+//! # the default, or `influx` for the InfluxDB line protocol.
+//! format = "remote-write"
+//! # Defaults to 15s.
+//! interval = "15s"
+//!
+//! # A Rhai script with hooks which customize behavior, see https://rhai.rs.
+//! # The script is loaded again when it changes. It may define any of these
+//! # functions, each of which gets a map:
+//! #
+//! # * `on_host_state_change(change)` gets the `id`, `names`, `tags` and `macs`
+//! #   of a host which changed status `from` one `to` another, like "up" or
+//! #   "down".
+//! # * `before_wake(wake)` gets the `name` and `macs` of what is about to be
+//! #   woken, and the details of the host if there is one. Returning `false` or
+//! #   a string with the reason cancels the wake.
+//! # * `classify_device(device)` gets the `mac`, `address` and `device` of a new
+//! #   device. Returning "ignore" skips it, while returning "suspicious" marks it
+//! #   as suspicious.
+//! #
+//! # Scripts are sandboxed and can only perform a limited number of operations.
+//! # The current status of every host is available through `hosts()`.
+//! [script]
+//! path = "/etc/wolo/hooks.rhai"
+//!
+//! # Allow suspending, hibernating or powering off the machine wolo runs on from
+//! # the admin page, out of `suspend`, `hibernate` and `power-off`. This asks
+//! # logind over D-Bus. Unless wolo runs as root, this requires a polkit rule
+//! # which allows the user wolo runs as to perform actions like
+//! # `org.freedesktop.login1.suspend` without authentication.
+//! [power]
+//! actions = ["suspend"]
+//!
+//! # Background tasks like the ping loop, discovery and notifications are
+//! # restarted if they fail, waiting `backoff` before the first restart and
+//! # twice as long for every restart in a row up to `max_backoff`. Once a task
+//! # has been restarted `restarts` times in a row wolo exits. A task which ran
+//! # for ten minutes before failing starts counting from zero again. This takes
+//! # effect when wolo is started.
+//! [supervisor]
+//! restarts = 5
+//! backoff = "1s"
+//! max_backoff = "5m"
+//!
+//! # Preferences of the network page and sessions of signed in users are stored
+//! # in cookies signed with this secret, which must be at least 32 bytes long. A
+//! # random secret is used unless it is set, which forgets preferences and signs
+//! # everyone out when wolo restarts.
+//! [sessions]
+//! key = "a long random secret of at least 32 bytes"
+//!
+//! # Users who can sign in. Once any users are configured, every page requires
+//! # signing in. The hash is printed by `wolo hash-password`, which reads the
+//! # password from stdin. The role is `viewer`, `operator` or `admin`, and
+//! # defaults to `viewer`.
+//! [users.alice]
+//! password_hash = "$argon2id$v=19$m=19456,t=2,p=1$…"
+//! role = "operator"
+//!
+//! # Security headers sent with every response. The default policy only allows
+//! # wolo's own assets and the modules the network page loads from a CDN, and is
+//! # replaced entirely by setting `content_security_policy`. Volumes generated by
+//! # Mokuro are sent with a policy which also allows inline and external scripts.
+//! # Setting a header to an empty string leaves it out.
+//! # `X-Content-Type-Options: nosniff` is always sent.
+//! [headers]
+//! # Sites which may embed wolo in a frame, defaults to `'none'`.
+//! frame_ancestors = "https://dashboard.example.com"
+//! # Defaults to `same-origin`.
+//! referrer_policy = "no-referrer"
+//!
+//! # External executables which integrate wolo with other systems. Each time an
+//! # extension is run it gets a JSON object on stdin with a `hook` field. Used by
+//! # an exec check, it gets `{"hook": "check", "host": "…", "names": […],
+//! # "port": …}` and must print a verdict like `{"ok": false, "message": "…"}`
+//! # on stdout. Notified about an event in the timeline, it gets
+//! # `{"hook": "event", "kind": "down", "host": "…", "message": "…"}` and its
+//! # output is ignored.
+//! [extensions.disk-space]
+//! # The program to run followed by its arguments.
+//! command = ["/usr/local/bin/check-disk-space", "--min", "10%"]
+//! # Kinds of timeline events to be notified about, out of `up`, `down`,
+//! # `wake`, `reload`, `discovery`, `ping` and `gone`.
+//! events = []
+//! # Executables which run for longer are killed. Defaults to 10s, and exec
+//! # checks use it unless they have their own timeout.
+//! timeout = "10s"
+//! # How many instances may run at the same time, which defaults to 4.
+//! concurrency = 4
+//!
+//! # Named segments of the network, like VLANs, which are presented separately
+//! # under `/network/<name>`. Hosts belong to a segment if they have one of its
+//! # tags, or if any of their addresses are in one of its subnets.
+//! [segments.iot]
+//! # Defaults to the name of the segment.
+//! title = "Internet of things"
+//! subnets = ["192.168.20.0/24", "fd00:20::/64"]
+//! tags = ["iot"]
+//! # Requiring a password hides the hosts of the segment from `/network`.
+//! # This only restricts viewing, not the API or waking. The username defaults
+//! # to `guest`.
+//! username = "guest"
+//! password = "hunter2"
+//!
+//! # Ports which wake a host when something connects to them. They are bound
+//! # before privileges are dropped, so privileged ports can be used. Changing
+//! # them requires a restart.
+//! [wake_listeners."0.0.0.0:2222"]
+//! # The name of the host to wake, which isn't woken if it's already up.
+//! host = "nas"
+//! # Forward connections to this port of the host once it accepts connections
+//! # on it. Connections are closed after waking the host if this isn't set.
+//! forward = 22
+//! # How long to wait for the host to accept connections before giving up,
+//! # which defaults to 2m.
+//! timeout = "2m"
 //!
 //! # Detailed host configuration.
 //! [hosts."example.com"]
-//! # Collection of mac addresses associated with this host.
+//! # Collection of mac addresses associated with this host. Addresses can be
+//! # written as `00:11:22:33:44:55`, `00-11-22-33-44-55`, `0011.2233.4455` or
+//! # `001122334455` in either case.
 //! macs = ["00:11:22:33:44:55"]
 //! # Setting the preferred name will make it so that only this name is
 //! # displayed in the network view for this host.
@@ -64,8 +505,295 @@
 //! # Additional hosts to be ignored can be specified with the
 //! # `--ignore-host` option.
 //! ignore = false
+//! # Address which is pinged and checked instead of the addresses the names of
+//! # the host resolve to, like the address of its wired interface when its name
+//! # also resolves to its wireless one. The host is still shown by its name.
+//! ping_address = "192.168.1.10"
+//! # Tags associated with this host.
+//! tags = ["server"]
+//! # Names of hosts this host can only be reached through, like the switch it's
+//! # connected to. If any of them is down, this host is reported as unreachable
+//! # instead of down.
+//! depends_on = ["switch-upstairs"]
+//! # Settings from the named profile are applied to this host, underneath any
+//! # settings specified for the host itself.
+//! profile = "server"
+//! # The kind of device, which determines the icon shown in the network view.
+//! # One of `server`, `desktop`, `laptop`, `phone`, `tablet`, `printer`,
+//! # `router`, `storage`, `tv`, `camera` or `console`. If not set, the kind is
+//! # guessed from words in the host name like `nas` or `iphone`.
+//! kind = "server"
+//! # The number of addresses to show in the network view before the rest are
+//! # collapsed behind a link.
+//! display_addresses = 2
+//! # The address family to show first, either `ipv4` or `ipv6`. This can also
+//! # be toggled in the network view.
+//! display_prefer = "ipv4"
+//! # Link to the web interface of the host, shown as "Open web UI" on its row.
+//! # Unless set, a link is made when a `tcp` or `http` check finds port 443,
+//! # 80 or 8080 open, which can be turned off with `detect_url = false`.
+//! url = "https://example.com:5001/"
+//!
+//! # Ping settings for the host.
+//! [hosts."example.com".ping]
+//! # Whether the host should be pinged at all.
+//! enabled = true
+//! # Interval between pings. Durations are specified with a unit, like "500ms",
+//! # "2s", "5m" or "1h30m".
+//! interval = "1s"
+//! # Timeout after which a ping is considered lost.
+//! timeout = "10s"
+//! # Replies which take longer than this are shown in amber in the network view.
+//! slow = "200ms"
+//! # Which address families are pinged. One of `both`, `prefer-v4`,
+//! # `prefer-v6`, `v4-only` or `v6-only`, where the `prefer-*` variants only
+//! # fall back to the other family if the host has no addresses in the preferred
+//! # one. Set this in `[defaults.ping]` for networks with broken IPv6. Defaults
+//! # to `both`.
+//! family = "prefer-v4"
+//!
+//! # Wake-on-LAN settings for the host.
+//! [hosts."example.com".wake]
+//! # Whether the host can be woken. Disabling this hides the wake button, which
+//! # is useful for hosts which are always on like routers. This can also be
+//! # written as `wake = false` directly on the host.
+//! enabled = true
+//! # The address to send magic packets to. Defaults to the broadcast addresses
+//! # of the configured `interfaces`, or 255.255.255.255.
+//! target = "192.168.1.255"
+//! # The port to send magic packets to. Defaults to 9.
+//! port = 9
+//! # Optional SecureOn password appended to magic packets, written like a MAC
+//! # address.
+//! password = "01:23:45:67:89:ab"
+//! # The number of times magic packets are sent until the host responds to a
+//! # ping. Defaults to 3.
+//! attempts = 5
+//! # Time between attempts, which defaults to 1m.
+//! retry = "30s"
+//! # Wake the host on demand when something tries to connect to one of these
+//! # TCP ports while it is down. Until then, wolo answers ARP and neighbor
+//! # solicitations for the last known addresses of the host on the interface
+//! # they are on, so that connections to the host arrive at wolo. This stops
+//! # once the host is being woken. Requires CAP_NET_RAW and a restart if no
+//! # host was woken on demand when wolo started.
+//! on_demand = [445, 22]
+//!
+//! # Checks to perform against the host. Supported kinds are `tcp`, `http`,
+//! # `ntp` and `exec`.
+//! [[hosts."example.com".check]]
+//! kind = "tcp"
+//! port = 22
+//!
+//! [[hosts."example.com".check]]
+//! kind = "http"
+//! name = "Web UI"
+//! port = 8080
+//! path = "/health"
+//! interval = "1m"
+//! timeout = "5s"
+//!
+//! # NTP checks measure how far the clock of the host is from the clock of the
+//! # machine wolo runs on, and fail if it is off by more than `max_skew`, which
+//! # defaults to 1s. The port defaults to 123.
+//! [[hosts."example.com".check]]
+//! kind = "ntp"
+//! max_skew = "2s"
+//!
+//! # Exec checks run one of the `[extensions]` and use its verdict. The port is
+//! # optional and only passed on to the extension.
+//! [[hosts."example.com".check]]
+//! kind = "exec"
+//! name = "Disk space"
+//! extension = "disk-space"
+//!
+//! # Service level objectives for the host, evaluated every minute against the
+//! # ping history.
+//! [hosts."example.com".slo]
+//! # The window objectives are evaluated over, which defaults to 1h.
+//! window = "1h"
+//! # The share of pings which must be answered.
+//! availability = "99.9%"
+//! # The round trip time which answered pings must stay below at `percentile`.
+//! rtt = "5ms"
+//! # The percentile the round trip time objective applies to, defaults to 95.
+//! percentile = 95
+//!
+//! # Poll the host over SNMP v2c for its name, uptime and interface traffic,
+//! # which is shown in the network page and exported through `/metrics`. Polling
+//! # is only enabled if a community is set.
+//! [hosts."example.com".snmp]
+//! community = "public"
+//! # The port to poll, defaults to 161.
+//! port = 161
+//! # Time between polls, which defaults to 1m.
+//! interval = "1m"
+//!
+//! # Allow probing the throughput to the host from the network page, which sends
+//! # data as fast as possible to a TCP port which discards it, like one opened
+//! # with `nc -l 5201 > /dev/null`. The time it takes to connect to the port is
+//! # measured before and while data is sent to show latency under load. Results
+//! # are stored in the history database if one is configured.
+//! [hosts."example.com".probe]
+//! port = 5201
+//! # How long to send data for, which defaults to 5s and is at most 1m.
+//! duration = "5s"
+//!
+//! # Settings applied to all hosts. Supports `ping`, `wake`, `quiet_hours`,
+//! # `slo`, `snmp`, `probe`, `check`, `tags`, `depends_on`, `kind` and
+//! # `display_*` like host tables do.
+//! # Checks, tags and dependencies are added to those of each host, while other
+//! # settings are only used if they are not set for the host.
+//! [defaults.ping]
+//! interval = "2s"
+//!
+//! # Named profiles which hosts can refer to through `profile`. Profiles support
+//! # the same settings as `defaults`.
+//! [profiles.server]
+//! tags = ["always-on"]
+//!//!
+//! # Quiet hours in local time during which hosts are pinged less often, like
+//! # devices which sleep at night. They wrap around midnight if `end` is before
+//! # `start`.
+//! [profiles.iot.quiet_hours]
+//! start = "23:00"
+//! end = "07:00"
+//! # Interval between pings during quiet hours, which defaults to 1m.
+//! interval = "5m"
+//!
+//! [[profiles.server.check]]
+//! kind = "http"
+//! name = "Metrics"
+//! port = 9100
+//! path = "/metrics"
+//!
+//! # Settings for `--showcase`. Fake values are derived from real ones using
+//! # `seed`, so they stay the same across restarts.
+//! [showcase]
+//! seed = 42
+//! # Names to pick fake host names from.
+//! names = ["alpha", "beta", "gamma"]
+//! # Domain used for all but the first name of each host. Defaults to `lan`.
+//! domain = "example.org"
+//! # Hosts which are shown as-is.
+//! exclude = ["printer.lan"]
+//! ```
+//!
+//! <br>
+//!
+//! #### Languages
+//!
+//! The user interface is shown in the language preferred by the browser through
+//! its `Accept-Language` header, if a translation for it exists. Otherwise the
+//! configured `locale` is used, which defaults to English:
+//!
+//! ```toml
+//! locale = "sv"
+//! ```
+//!
+//! Translations are kept in `static/locales/<locale>.toml`, which map English
+//! strings to their translation. Strings which are missing are shown in English.
+//!
+//! <br>
+//!
+//! #### Admin Page
+//!
+//! The `/admin` page shows what wolo thinks is going on: loaded configuration
+//! files and their diagnostics, the status of each ethers and hosts file, which
+//! file each host name and MAC address was defined in, the state of the ping
+//! scheduler, the contents of the resolver cache and the audit log of recent
+//! actions. It also has buttons to reload configuration, pause monitoring,
+//! clear caches and export state.
+//!
+//! Conflicts between sources, like a MAC address which is given different names
+//! or an address which is claimed by several hosts, are listed on the page and
+//! logged as warnings.
+//!
+//! The page is protected using HTTP basic authentication and only exists if a
+//! password has been configured:
+//!
+//! ```toml
+//! [admin]
+//! # Defaults to `admin`.
+//! username = "admin"
+//! password = "hunter2"
+//! ```
+//!
+//! <br>
+//!
+//! #### Users
+//!
+//! Users with roles can be configured in `[users]`, which requires everyone to
+//! sign in through `/login` before using any page or the API:
+//!
+//! ```toml
+//! [users.alice]
+//! password_hash = "$argon2id$v=19$m=19456,t=2,p=1$…"
+//! role = "admin"
+//! ```
+//!
+//! Password hashes are generated with `echo hunter2 | wolo hash-password`.
+//! Viewers can look at every page, operators can also wake hosts and take the
+//! other actions of the network page, and admins can also use the admin page,
+//! where `[admin]` is no longer used. Sessions are kept in a cookie signed with
+//! the `key` in `[sessions]`. Clients of the API and the gRPC service can
+//! instead send the credentials of a user with HTTP basic authentication.
+//!
+//! Addresses which fail to sign in too many times are locked out for a while,
+//! and get `429 Too Many Requests` until the lockout ends. Failed attempts,
+//! users signing in and current lockouts are shown on the admin page:
+//!
+//! ```toml
+//! [login]
+//! # Failed attempts allowed from an address before it is locked out.
+//! max_failures = 5
+//! # How long an address is locked out, which is also how long failed attempts
+//! # are remembered.
+//! lockout = "15m"
+//! ```
+//!
+//! <br>
+//!
+//! #### Mokuro
+//!
+//! Directories of volumes processed by [Mokuro] can be served under `/mokuro`
+//! by specifying them with `--mokuro <path>` or in the configuration. Each
+//! library is listed in its own section, and the OCR text of all volumes can
+//! be searched. Volumes can be stored as directories or as `.cbz` or `.zip`
+//! archives without compression.
+//!
+//! Libraries are configured like this:
+//!
+//! ```toml
+//! [mokuro."/data/manga"]
+//! # Title of the library, defaults to the name of the directory. This is also
+//! # used to build the URLs of the library.
+//! title = "Manga"
+//! # Libraries are listed by this in ascending order, defaults to 0.
+//! order = 1
+//! # Whether the library is served, defaults to `true`.
+//! enabled = true
+//! # Whether volumes can be uploaded to this library through `/mokuro/upload`,
+//! # defaults to `false`. Only one library can receive uploads, and uploads
+//! # require a password to be set for the Mokuro pages as described below.
+//! upload = true
+//! ```
+//!
+//! The Mokuro pages can be protected using HTTP basic authentication, separately
+//! from the rest of wolo, or turned off entirely. These settings take effect
+//! without a restart:
+//!
+//! ```toml
+//! [mokuro]
+//! # Whether the Mokuro pages are served, defaults to `true`.
+//! enabled = true
+//! # Defaults to `mokuro`.
+//! username = "reader"
+//! password = "hunter2"
 //! ```
 //!
+//! [Mokuro]: https://github.com/kha-white/mokuro
+//!
 //! <br>
 //!
 //! #### Landing Page
@@ -91,8 +819,11 @@
 
 #![allow(clippy::drain_collect)]
 
+use core::fmt;
 use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use core::time::Duration;
 use std::env;
+use std::io;
 use std::net::ToSocketAddrs;
 use std::os::fd::FromRawFd;
 use std::path::PathBuf;
@@ -103,26 +834,76 @@ use anyhow::{Context, Result, anyhow};
 use axum::Router;
 use axum::extract::State;
 use axum::http::{StatusCode, Uri, header};
+use axum::middleware;
 use axum::response::{Html, IntoResponse, Response};
 use axum::routing::get;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 use tokio::net::TcpListener;
-use tokio::task;
+use tokio::sync::Mutex;
 
-use crate::config::Config;
+use crate::config::{Config, Diagnostic, Severity};
 use crate::utils::Templates;
 
+mod admin;
+mod api;
+mod audit;
+mod auth;
+mod badge;
+mod bench;
+mod checks;
+mod cli;
 mod config;
+mod daemon;
+mod disappeared;
+mod discovery;
+mod dns;
+mod dump;
+mod echo_requests;
 mod embed;
+mod error_page;
+mod events;
+mod extensions;
+mod grafana;
+mod grpc;
+mod history;
+mod history_sink;
 mod home;
 mod host_name_cache;
 mod hosts;
+mod i18n;
+mod interfaces;
+mod library;
+mod mac;
+mod metrics;
 mod mokuro;
+mod neighbors;
 mod network;
+mod notify;
 mod ping_loop;
+mod power;
+mod preferences;
+mod probe;
+mod read_only;
+mod reload;
+mod report;
+mod scripting;
+mod security_headers;
+mod self_check;
 mod showcase;
+mod slo;
+mod snmp;
+mod state;
+mod supervisor;
+mod systemd;
+mod upload;
 mod utils;
+mod validate;
+mod vitals;
+mod wake_listener;
+mod wake_on_demand;
 mod wake_on_lan;
+mod wake_queue;
+mod zip;
 
 const DEFAULT_BIND: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 3000));
 
@@ -131,6 +912,7 @@ const DEFAULT_BIND: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCA
 pub struct S {
     home: home::Home,
     templates: Templates,
+    showcase: showcase::Helper,
 }
 
 pub struct StaticFile(Uri);
@@ -144,108 +926,702 @@ impl IntoResponse for StaticFile {
                 let mime = mime_guess::from_path(path).first_or_octet_stream();
                 ([(header::CONTENT_TYPE, mime.as_ref())], content.data).into_response()
             }
-            None => (StatusCode::NOT_FOUND, "404 Not Found").into_response(),
+            None => Error::not_found().into_response(),
         }
     }
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Run the web service. This is the default if no command is specified.
+    Serve,
+    /// Send Wake-on-LAN magic packets to the given hosts or MAC addresses.
+    Wake {
+        /// Names of the hosts or MAC addresses to wake.
+        #[clap(required = true, value_name = "HOST|MAC")]
+        targets: Vec<String>,
+        /// Address to send magic packets to, overriding configuration.
+        #[clap(long)]
+        target: Option<Ipv4Addr>,
+        /// Port to send magic packets to, overriding configuration.
+        #[clap(long)]
+        port: Option<u16>,
+    },
+    /// Ping all addresses of the given hosts once and print the results.
+    ///
+    /// Exits with a non-zero status unless every address responded.
+    Ping {
+        /// Names of the hosts to ping.
+        #[clap(required = true, value_name = "HOST")]
+        names: Vec<String>,
+        /// Print results as JSON instead of a table.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Commands for inspecting known hosts.
+    Hosts {
+        #[command(subcommand)]
+        command: HostsCommand,
+    },
+    /// Commands for inspecting the assets embedded in the binary.
+    Assets {
+        #[command(subcommand)]
+        command: AssetsCommand,
+    },
+    /// Load and validate all configuration, hosts, ethers and home files,
+    /// print a report and exit with a non-zero status if there are errors.
+    Check,
+    /// Print the effective configuration after all files and host sources
+    /// have been loaded and merged.
+    DumpConfig {
+        /// The format to print the configuration in.
+        #[clap(long, value_enum, default_value = "toml")]
+        format: dump::Format,
+    },
+    /// Export the history database and configuration files into an
+    /// archive, which can be imported on another machine.
+    ExportState {
+        /// Path to write the archive to.
+        path: PathBuf,
+    },
+    /// Import an archive written by `export-state`.
+    ///
+    /// This should not be done while wolo is running.
+    ImportState {
+        /// Path to the archive to import.
+        path: PathBuf,
+        /// Directory to restore configuration files to. They are not restored
+        /// unless this is specified.
+        #[clap(long)]
+        config_dir: Option<PathBuf>,
+        /// Overwrite files which already exist.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Drive the ping scheduler with synthetic hosts and report throughput,
+    /// scheduling lag and memory use.
+    ///
+    /// This requires `--simulate`, since responses are simulated.
+    Bench {
+        /// Number of synthetic hosts. This isn't called `--hosts`, since that
+        /// option is used for hosts files.
+        #[clap(long, default_value_t = 1000)]
+        count: u32,
+        /// How long to run for, like `30s`.
+        #[clap(long, default_value = "30s", value_parser = config::parse_duration)]
+        duration: Duration,
+        /// Ping interval of each host, like `500ms`. Uses the default unless
+        /// specified.
+        #[clap(long, value_parser = config::parse_duration)]
+        interval: Option<Duration>,
+    },
+    /// Hash a password read from stdin, for use as the `password_hash` of a
+    /// user in `[users]`.
+    HashPassword,
+    /// Query a running instance and print a summary of its status.
+    Status {
+        /// Base URL of the running instance.
+        #[clap(long, default_value = "http://127.0.0.1:3000")]
+        url: String,
+    },
+    /// Write a hardened systemd service unit and a socket unit which passes
+    /// it the listener of the web interface, using the current options and
+    /// configuration.
+    ///
+    /// The service runs as a dynamic user with only the capabilities it
+    /// needs, unless `--user` is specified in which case a sysusers.d file
+    /// creating the user is written as well.
+    InstallService {
+        /// The name of the units.
+        #[clap(long, default_value = "wolo")]
+        name: String,
+        /// Directory to write the units to.
+        #[clap(long, default_value = "/etc/systemd/system")]
+        dir: PathBuf,
+        /// Directory to write the sysusers.d file to.
+        #[clap(long, default_value = "/etc/sysusers.d")]
+        sysusers_dir: PathBuf,
+        /// Print the units instead of writing them.
+        #[clap(long)]
+        print: bool,
+        /// Overwrite units which already exist.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Print shell completions for the given shell.
+    Completions {
+        /// The shell to generate completions for.
+        shell: clap_complete::Shell,
+    },
+    /// Print a manual page in roff format.
+    Man,
+}
+
+#[derive(Subcommand)]
+enum HostsCommand {
+    /// List all hosts known from configuration, ethers and hosts files.
+    List,
+}
+
+#[derive(Subcommand)]
+enum AssetsCommand {
+    /// List all embedded assets along with their size and SHA-256 hash.
+    List,
+    /// Check that every required asset is embedded, that templates parse and
+    /// that translations can be loaded, and exit with a non-zero status
+    /// otherwise.
+    Verify,
+}
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Path to load configuration files from.
-    #[clap(long, default_value = "/etc/wolo/config.toml")]
+    ///
+    /// If a directory is specified, all `.toml` files in it are loaded in
+    /// lexical order.
+    #[clap(long, global = true, default_value = "/etc/wolo/config.toml")]
     config: Vec<PathBuf>,
     /// Address and port to bind the server to. Defaults to `127.0.0.1:3000`.
-    #[clap(long)]
+    #[clap(long, global = true)]
     bind: Option<String>,
     /// Paths to load landing page configuration from.
-    #[clap(long, default_value = "/etc/wolo/home.md")]
+    #[clap(long, global = true, default_value = "/etc/wolo/home.md")]
     home: Vec<PathBuf>,
     /// Paths to load Mokuro files from.
-    #[clap(long)]
+    ///
+    /// Volumes can be stored as directories or as `.cbz` or `.zip` archives
+    /// next to their `.mokuro` file. Archives must be stored without
+    /// compression, like with `zip -0`.
+    #[clap(long, global = true)]
     mokuro: Vec<PathBuf>,
     /// Path to load an ethers file from. By default this is `/etc/ethers`.
     ///
     /// The files specified in here will be monitored for changes and reloaded
     /// if needed.
-    #[clap(long, default_value = "/etc/ethers")]
+    #[clap(long, global = true, default_value = "/etc/ethers")]
     ethers: Vec<PathBuf>,
     /// Path to load hosts files from. By default this is `/etc/hosts`.
     ///
     /// The files specified in here will be monitored for changes and reloaded
     /// if needed.
-    #[clap(long, default_value = "/etc/hosts")]
+    #[clap(long, global = true, default_value = "/etc/hosts")]
     hosts: Vec<PathBuf>,
     /// Specify hosts to ignore.
     ///
     /// This will ensure that the host is ignored even if it's part of
     /// configuration.
-    #[clap(long)]
+    #[clap(long, global = true)]
     ignore_host: Vec<String>,
+    /// Treat configuration warnings as errors.
+    #[clap(long, global = true)]
+    strict: bool,
     /// Replaces real hostnames, macs, and ips with fake ones for demonstration.
-    #[clap(long)]
+    ///
+    /// Landing page content and Mokuro listings are anonymized as well, which
+    /// means that Mokuro links cannot be followed.
+    #[clap(long, global = true)]
     showcase: bool,
+    /// Write all ICMP packets sent and received to the given path as a pcap
+    /// file, which can be opened with Wireshark.
+    ///
+    /// This is intended for diagnosing hosts which send malformed replies.
+    #[clap(long, global = true, value_name = "path")]
+    icmp_dump: Option<PathBuf>,
+    /// Simulate pings and Wake-on-LAN instead of sending any packets.
+    ///
+    /// Hosts respond with plausible results and wakes are only logged, which
+    /// allows the service to run without permission to send ICMP packets.
+    #[clap(long, global = true, conflicts_with = "icmp_dump")]
+    simulate: bool,
+    /// Reject every request which would change anything, like waking hosts or
+    /// the actions of the admin page.
+    ///
+    /// This takes precedence over `read_only` in configuration.
+    #[clap(long, global = true)]
+    read_only: bool,
+    /// Exit if any of the checks performed at startup fail, like creating
+    /// ICMP sockets or reading configured files.
+    #[clap(long, global = true)]
+    strict_start: bool,
+    /// Switch to this user once sockets have been created, which requires
+    /// starting as root.
+    ///
+    /// Unless `--group` is specified, the primary group of the user is used.
+    #[clap(long, global = true, value_name = "name")]
+    user: Option<String>,
+    /// Switch to this group once sockets have been created.
+    #[clap(long, global = true, value_name = "name")]
+    group: Option<String>,
+    /// Write the process id to this path, which is removed on exit. It's
+    /// written after switching to `--user` and `--group`.
+    #[clap(long, global = true, value_name = "path")]
+    pidfile: Option<PathBuf>,
+    /// Set the file mode creation mask in octal, like `027`.
+    #[clap(long, global = true, value_name = "mode", value_parser = daemon::parse_umask)]
+    umask: Option<u32>,
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
 
-    if let Err(err) = inner().await {
-        tracing::error!("Error: {err}");
+    match inner().await {
+        Ok(code) => code,
+        Err(err) => {
+            tracing::error!("Error: {err}");
+
+            for e in err.chain().skip(1) {
+                tracing::error!("Caused by: {e}");
+            }
+
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Load configuration from all configured paths, returning any diagnostics
+/// reported while loading it.
+///
+/// If `--strict` is specified, warnings are promoted to errors.
+fn load_config(opts: &Opts) -> Result<(Config, Vec<Diagnostic>)> {
+    let mut config = Config::default();
+    let mut diagnostics = Vec::new();
+
+    for path in &opts.config {
+        let d = config::Diagnostics::new();
+
+        config
+            .add_from_path(path, &d)
+            .with_context(|| path.display().to_string())?;
+
+        diagnostics.extend(d.into_diagnostics());
+    }
+
+    diagnostics.extend(config.diagnose());
 
-        for e in err.chain().skip(1) {
-            tracing::error!("Caused by: {e}");
+    if opts.strict {
+        for d in &mut diagnostics {
+            d.severity = Severity::Error;
         }
+    }
+
+    for host in &opts.ignore_host {
+        config.ignore_host(host);
+    }
 
-        return ExitCode::FAILURE;
+    for path in &opts.mokuro {
+        config.push_mokuro_path(path);
     }
 
-    ExitCode::SUCCESS
+    Ok((config, diagnostics))
 }
 
-async fn inner() -> Result<()> {
-    let templates = crate::utils::load_templates().context("templates")?;
+/// Log diagnostics, returning `true` if any of them are errors.
+fn log_diagnostics(diagnostics: &[Diagnostic]) -> bool {
+    let mut has_errors = false;
+
+    for d in diagnostics {
+        match d.severity {
+            Severity::Error => {
+                tracing::error!("{}", d.message);
+                has_errors = true;
+            }
+            Severity::Warning => {
+                tracing::warn!("{}", d.message);
+            }
+        }
+    }
 
+    has_errors
+}
+
+async fn inner() -> Result<ExitCode> {
     let opts = match Opts::try_parse() {
         Ok(opts) => opts,
         Err(error) => {
             print!("{error}");
-            return Ok(());
+            return Ok(ExitCode::SUCCESS);
         }
     };
 
-    let mut config = Config::default();
+    match &opts.command {
+        Some(Command::Serve) | None => serve(&opts).await,
+        Some(Command::Wake {
+            targets,
+            target,
+            port,
+        }) => {
+            let overrides = config::WakeConfig {
+                target: *target,
+                port: *port,
+                ..config::WakeConfig::default()
+            };
 
-    let mut has_errors = false;
+            let (config, hosts) = load(&opts).await?;
+            let broadcasts = interfaces::broadcasts(&config.interfaces).await;
+            cli::wake(&hosts, targets, &overrides, &broadcasts).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Ping { names, json }) => {
+            let (config, hosts) = load(&opts).await?;
 
-    for path in &opts.config {
-        let d = config::Diagnostics::new();
+            if !cli::ping(&hosts, &config.dns, names, *json).await? {
+                return Ok(ExitCode::FAILURE);
+            }
 
-        config
-            .add_from_path(path, &d)
-            .with_context(|| path.display().to_string())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Hosts {
+            command: HostsCommand::List,
+        }) => {
+            cli::list_hosts(&load_hosts(&opts).await?);
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Assets {
+            command: AssetsCommand::List,
+        }) => {
+            cli::list_assets();
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Assets {
+            command: AssetsCommand::Verify,
+        }) => {
+            let mut report = validate::Report::default();
+            cli::verify_assets(&mut report);
+            report.print();
+
+            if report.has_errors() {
+                return Ok(ExitCode::FAILURE);
+            }
+
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Bench {
+            count,
+            duration,
+            interval,
+        }) => {
+            if !opts.simulate {
+                return Err(anyhow!("Benchmarks only run with --simulate"));
+            }
+
+            let options = bench::Options {
+                hosts: *count,
+                duration: *duration,
+                interval: *interval,
+            };
+
+            bench::run(options).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::HashPassword) => {
+            cli::hash_password()?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Status { url }) => {
+            cli::status(url).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Check) => check(&opts).await,
+        Some(Command::DumpConfig { format }) => dump_config(&opts, *format).await,
+        Some(Command::ExportState { path }) => {
+            let (config, _) = load(&opts).await?;
+
+            let history = match &config.history.path {
+                Some(path) if path.exists() => history::History::open(path)?,
+                _ => history::History::disabled(),
+            };
 
-        for error in d.into_errors() {
-            tracing::error!("{}: {error}", path.display());
-            has_errors = true;
+            let archive = state::export(&config, &history).await?;
+            tokio::fs::write(path, archive)
+                .await
+                .with_context(|| format!("writing {}", path.display()))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::ImportState {
+            path,
+            config_dir,
+            force,
+        }) => {
+            let (config, _) = load(&opts).await?;
+
+            let import = state::Import {
+                history: config.history.path.as_deref(),
+                config_dir: config_dir.as_deref(),
+                force: *force,
+            };
+
+            state::import(path, import).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::InstallService {
+            name,
+            dir,
+            sysusers_dir,
+            print,
+            force,
+        }) => {
+            let (config, diagnostics) = load_config(&opts)?;
+
+            if log_diagnostics(&diagnostics) {
+                return Err(anyhow!("Configuration had errors"));
+            }
+
+            let bind = match opts.bind.as_deref().or(config.bind.as_deref()) {
+                Some(s) => to_socket_addr(s).context("parsing bind address")?,
+                None => DEFAULT_BIND,
+            };
+
+            let exe = env::current_exe().context("locating the wolo binary")?;
+            let args = service_args(&opts)?;
+
+            let options = systemd::Options {
+                name,
+                exe: &exe,
+                args: &args,
+                bind,
+                user: opts.user.as_deref(),
+                group: opts.group.as_deref(),
+                umask: opts.umask,
+                simulate: opts.simulate,
+                config: &config,
+            };
+
+            let units = systemd::generate(&options)?;
+
+            if *print {
+                println!("# {name}.service");
+                print!("{}", units.service);
+                println!();
+                println!("# {name}.socket");
+                print!("{}", units.socket);
+
+                if let Some(sysusers) = &units.sysusers {
+                    println!();
+                    println!("# {name}.conf");
+                    print!("{sysusers}");
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            for path in systemd::install(name, &units, dir, sysusers_dir, *force).await? {
+                println!("Wrote {}", path.display());
+            }
+
+            if units.sysusers.is_some() {
+                println!("Run `systemd-sysusers` to create the user.");
+            }
+
+            println!(
+                "Run `systemctl daemon-reload && systemctl enable --now {name}.socket {name}.service` to start wolo."
+            );
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Completions { shell }) => {
+            let mut command = Opts::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(*shell, &mut command, name, &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Man) => {
+            clap_mangen::Man::new(Opts::command()).render(&mut io::stdout())?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// The global options a service should be started with, with paths made
+/// absolute since services don't run in the current directory.
+///
+/// Options which systemd takes care of, like the user and the bind address,
+/// are left out.
+fn service_args(opts: &Opts) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+
+    let paths = [
+        ("--config", &opts.config),
+        ("--home", &opts.home),
+        ("--mokuro", &opts.mokuro),
+        ("--ethers", &opts.ethers),
+        ("--hosts", &opts.hosts),
+    ];
+
+    for (option, paths) in paths {
+        for path in paths {
+            let path = std::path::absolute(path)
+                .with_context(|| format!("{}: making path absolute", path.display()))?;
+            args.push(option.to_owned());
+            args.push(path.to_string_lossy().into_owned());
         }
     }
 
     for host in &opts.ignore_host {
-        config.ignore_host(host);
+        args.push(String::from("--ignore-host"));
+        args.push(host.clone());
     }
 
-    if has_errors {
+    if let Some(path) = &opts.icmp_dump {
+        let path = std::path::absolute(path)
+            .with_context(|| format!("{}: making path absolute", path.display()))?;
+        args.push(String::from("--icmp-dump"));
+        args.push(path.to_string_lossy().into_owned());
+    }
+
+    let flags = [
+        ("--strict", opts.strict),
+        ("--showcase", opts.showcase),
+        ("--simulate", opts.simulate),
+        ("--read-only", opts.read_only),
+        ("--strict-start", opts.strict_start),
+    ];
+
+    for (flag, enabled) in flags {
+        if enabled {
+            args.push(flag.to_owned());
+        }
+    }
+
+    Ok(args)
+}
+
+/// Load configuration and all hosts it refers to, failing if the
+/// configuration has errors.
+async fn load_hosts(opts: &Opts) -> Result<Vec<hosts::Host>> {
+    let (_, hosts) = load(opts).await?;
+    Ok(hosts)
+}
+
+/// Load configuration and the hosts it defines.
+async fn load(opts: &Opts) -> Result<(Config, Vec<hosts::Host>)> {
+    let (config, diagnostics) = load_config(opts)?;
+
+    if log_diagnostics(&diagnostics) {
         return Err(anyhow!("Configuration had errors"));
     }
 
-    fn to_socket_addr(bind: &str) -> Result<SocketAddr> {
-        if let Some(address) = bind.to_socket_addrs()?.next() {
-            return Ok(address);
+    let hosts = hosts::load(&opts.ethers, &opts.hosts, &config).await;
+    Ok((config, hosts))
+}
+
+fn to_socket_addr(bind: &str) -> Result<SocketAddr> {
+    if let Some(address) = bind.to_socket_addrs()?.next() {
+        return Ok(address);
+    }
+
+    Err(anyhow!("no addresses found for {bind}"))
+}
+
+/// Collect paths to load the landing page from.
+fn home_paths(opts: &Opts, config: &Config) -> Vec<PathBuf> {
+    let mut homes = Vec::new();
+
+    for path in &opts.home {
+        homes.push(path.clone());
+    }
+
+    for path in &config.home {
+        homes.push(path.clone());
+    }
+
+    homes
+}
+
+/// Reload configuration along with its diagnostics, where the configuration
+/// is `None` if it could not be loaded or had errors.
+fn reload_config(opts: &Opts) -> (Option<Config>, Vec<Diagnostic>) {
+    let (config, diagnostics) = match load_config(opts) {
+        Ok(output) => output,
+        Err(error) => {
+            tracing::error!("{error:#}");
+            let d = Diagnostic::new(Severity::Error, format_args!("{error:#}"));
+            return (None, vec![d]);
         }
+    };
 
-        Err(anyhow!("no addresses found for {bind}"))
+    if log_diagnostics(&diagnostics) {
+        return (None, diagnostics);
+    }
+
+    (Some(config), diagnostics)
+}
+
+/// Print the effective configuration.
+async fn dump_config(opts: &Opts, format: dump::Format) -> Result<ExitCode> {
+    let (config, diagnostics) = load_config(opts)?;
+    let has_errors = log_diagnostics(&diagnostics);
+
+    let bind = match opts.bind.as_deref().or(config.bind.as_deref()) {
+        Some(s) => s.to_owned(),
+        None => DEFAULT_BIND.to_string(),
+    };
+
+    let hosts = hosts::load(&opts.ethers, &opts.hosts, &config).await;
+
+    let sources = dump::Sources {
+        ethers: &opts.ethers,
+        hosts: &opts.hosts,
+        home: home_paths(opts, &config),
+    };
+
+    print!("{}", dump::render(format, bind, &config, sources, &hosts)?);
+
+    if has_errors {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Validate all configuration and print a report.
+async fn check(opts: &Opts) -> Result<ExitCode> {
+    let mut report = validate::Report::default();
+
+    let (config, diagnostics) = load_config(opts)?;
+
+    for d in diagnostics {
+        report.push(d);
+    }
+
+    if let Err(error) = crate::utils::load_templates() {
+        report.error(format_args!("templates: {error}"));
+    }
+
+    let paths = validate::Paths {
+        ethers: &opts.ethers,
+        hosts: &opts.hosts,
+        home: &opts.home,
+    };
+
+    validate::run(&mut report, &config, paths).await?;
+
+    if opts.strict {
+        report.promote_warnings();
+    }
+
+    report.print();
+
+    if report.has_errors() {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn serve(opts: &Opts) -> Result<ExitCode> {
+    if let Some(mode) = opts.umask {
+        daemon::set_umask(mode)?;
+    }
+
+    let templates = crate::utils::load_templates().context("templates")?;
+
+    let (config, diagnostics) = load_config(opts)?;
+
+    if log_diagnostics(&diagnostics) {
+        return Err(anyhow!("Configuration had errors"));
     }
 
     let bind = match opts.bind.as_deref().or(config.bind.as_deref()) {
@@ -253,13 +1629,69 @@ async fn inner() -> Result<()> {
         None => DEFAULT_BIND,
     };
 
-    for path in &opts.mokuro {
-        config.push_mokuro_path(path);
+    let paths = self_check::Paths {
+        ethers: &opts.ethers,
+        hosts: &opts.hosts,
+        home: &opts.home,
+    };
+
+    let self_check = self_check::run(&config, paths, opts.simulate).await;
+    self_check.print();
+
+    if opts.strict_start && self_check.has_failures() {
+        return Err(anyhow!("Startup checks failed"));
     }
 
+    let self_check = Arc::new(self_check);
+
+    let dump = match &opts.icmp_dump {
+        Some(path) => {
+            Some(lib::Dump::create(path).with_context(|| format!("creating {}", path.display()))?)
+        }
+        None => None,
+    };
+
+    // Everything which needs privileges is set up before they are dropped.
+    let transport = ping_loop::Transport::new(dump, opts.simulate);
+    let broadcast = wake_queue::socket(opts.simulate).await?;
+    let packet_socket = wake_on_demand::socket(opts.simulate, &config).await?;
+    let wake_listeners = wake_listener::bind(&config).await?;
+    let echo_sockets = echo_requests::sockets(opts.simulate, &config)?;
+
+    let listener = if let Some(listener) =
+        try_listener_from_env("LISTEN_FDS").context("setting up listen fd")?
+    {
+        tracing::info!("received socket through LISTEN_FDS");
+        listener
+    } else {
+        let listener = TcpListener::bind(&bind)
+            .await
+            .context("binding to address")?;
+
+        let addr = listener.local_addr()?;
+        tracing::info!("Listening on http://{addr}");
+        listener
+    };
+
+    if opts.user.is_some() || opts.group.is_some() {
+        daemon::drop_privileges(opts.user.as_deref(), opts.group.as_deref())?;
+    }
+
+    // The pidfile is written as the user we run as, so that it can also be
+    // removed on exit.
+    let _pidfile = match &opts.pidfile {
+        Some(path) => Some(daemon::Pidfile::create(path)?),
+        None => None,
+    };
+
+    let history = match &config.history.path {
+        Some(path) => history::History::open(path)?,
+        None => history::History::disabled(),
+    };
+
     let config = Arc::new(config);
 
-    let showcase = showcase::new(opts.showcase);
+    let showcase = showcase::new(opts.showcase, &config.showcase);
 
     let mut hosts = hosts::State::builder();
 
@@ -271,39 +1703,288 @@ async fn inner() -> Result<()> {
         hosts.add_hosts_path(path);
     }
 
-    let mut homes = Vec::new();
+    let home = home::new(home_paths(opts, &config));
+    let hosts = hosts.build(config.clone());
 
-    for path in &opts.home {
-        homes.push(path.clone());
-    }
+    // Background tasks are restarted if they fail, so everything they're
+    // constructed from is cloned for every time they're started.
+    let supervisor = config.supervisor.clone();
 
-    for path in &config.home {
-        homes.push(path.clone());
-    }
+    let hosts_handle = supervisor::supervise("hosts", &supervisor, {
+        let hosts = hosts.clone();
+        move || hosts::spawn(hosts.clone())
+    });
+
+    let ping_state = ping_loop::State::new(&transport);
+    let transport = Arc::new(Mutex::new(transport));
+
+    let pinger_handle = supervisor::supervise("pinger", &supervisor, {
+        let (ping_state, hosts, history) = (ping_state.clone(), hosts.clone(), history.clone());
+
+        move || {
+            ping_loop::new(
+                ping_state.clone(),
+                hosts.clone(),
+                history.clone(),
+                transport.clone(),
+            )
+        }
+    });
+
+    let history_handle = supervisor::supervise("history", &supervisor, {
+        let history = history.clone();
+        let (raw, aggregates) = (config.history.raw(), config.history.aggregates());
+        move || history::spawn(history.clone(), raw, aggregates)
+    });
+
+    let history_sink_handle = supervisor::supervise("history sink", &supervisor, {
+        let (history, hosts) = (history.clone(), hosts.clone());
+        move || history_sink::spawn(history.clone(), hosts.clone())
+    });
+
+    let slo = slo::State::new();
 
-    let home = home::new(homes);
-    let hosts = hosts.build();
-    let hosts_handle = tokio::spawn(hosts::spawn(hosts.clone(), config.clone()));
+    let slo_handle = supervisor::supervise("slo", &supervisor, {
+        let (slo, hosts, history) = (slo.clone(), hosts.clone(), history.clone());
+        move || slo::spawn(slo.clone(), hosts.clone(), history.clone())
+    });
 
-    let ping_state = ping_loop::State::new();
-    let pinger_handle = task::spawn(ping_loop::new(ping_state.clone(), hosts.clone()));
+    let checks = checks::State::new();
+    let extensions = extensions::Runner::new();
+
+    let checks_handle = supervisor::supervise("checks", &supervisor, {
+        let (checks, hosts, extensions) = (checks.clone(), hosts.clone(), extensions.clone());
+        move || checks::spawn(checks.clone(), hosts.clone(), extensions.clone())
+    });
+
+    let snmp = snmp::State::new();
+
+    let snmp_handle = supervisor::supervise("snmp", &supervisor, {
+        let (snmp, hosts) = (snmp.clone(), hosts.clone());
+        move || snmp::spawn(snmp.clone(), hosts.clone())
+    });
+
+    let probe = probe::State::new(history.clone()).await?;
+
+    let scripting = scripting::State::new(hosts.clone(), ping_state.clone());
+
+    let events = events::Log::new();
+
+    let events_handle = supervisor::supervise("events", &supervisor, {
+        let (events, ping_state, hosts, scripting) = (
+            events.clone(),
+            ping_state.clone(),
+            hosts.clone(),
+            scripting.clone(),
+        );
+
+        move || {
+            events::spawn(
+                events.clone(),
+                ping_state.clone(),
+                hosts.clone(),
+                scripting.clone(),
+            )
+        }
+    });
+
+    let notify_handle = supervisor::supervise("notify", &supervisor, {
+        let (events, hosts) = (events.clone(), hosts.clone());
+        move || notify::spawn(events.clone(), hosts.clone())
+    });
+
+    let disappeared = disappeared::State::new();
+
+    let disappeared_handle = supervisor::supervise("disappeared", &supervisor, {
+        let (disappeared, events, hosts) = (disappeared.clone(), events.clone(), hosts.clone());
+        move || disappeared::spawn(disappeared.clone(), events.clone(), hosts.clone())
+    });
+
+    let extensions_handle = supervisor::supervise("extensions", &supervisor, {
+        let (hosts, events) = (hosts.clone(), events.clone());
+        move || extensions::spawn(extensions.clone(), hosts.clone(), events.clone())
+    });
+
+    let echo_requests_handle = supervisor::supervise("echo requests", &supervisor, {
+        let (hosts, ping_state, events) = (hosts.clone(), ping_state.clone(), events.clone());
+
+        move || {
+            echo_requests::spawn(
+                echo_sockets.clone(),
+                hosts.clone(),
+                ping_state.clone(),
+                events.clone(),
+            )
+        }
+    });
+
+    let discovery = discovery::State::new(history.clone()).await?;
+
+    let discovery_handle = supervisor::supervise("discovery", &supervisor, {
+        let (discovery, hosts, history, events, scripting) = (
+            discovery.clone(),
+            hosts.clone(),
+            history.clone(),
+            events.clone(),
+            scripting.clone(),
+        );
+
+        move || {
+            discovery::spawn(
+                discovery.clone(),
+                hosts.clone(),
+                history.clone(),
+                events.clone(),
+                scripting.clone(),
+            )
+        }
+    });
+
+    let wake_queue = wake_queue::State::new(history.clone()).await?;
+
+    let wake_queue_handle = supervisor::supervise("wake queue", &supervisor, {
+        let (wake_queue, ping_state, events) =
+            (wake_queue.clone(), ping_state.clone(), events.clone());
+
+        move || {
+            wake_queue::spawn(
+                wake_queue.clone(),
+                ping_state.clone(),
+                events.clone(),
+                broadcast.clone(),
+            )
+        }
+    });
+
+    let report_handle = supervisor::supervise("report", &supervisor, {
+        let (hosts, history, events, templates, showcase) = (
+            hosts.clone(),
+            history.clone(),
+            events.clone(),
+            templates.clone(),
+            showcase.clone(),
+        );
+
+        move || {
+            report::spawn(
+                hosts.clone(),
+                history.clone(),
+                events.clone(),
+                templates.clone(),
+                showcase.clone(),
+            )
+        }
+    });
+
+    let metrics = metrics::Metrics::new(
+        ping_state.clone(),
+        hosts.clone(),
+        showcase.clone(),
+        slo.clone(),
+        snmp.clone(),
+    );
+
+    let metrics_handle = supervisor::supervise("metrics", &supervisor, {
+        let metrics = metrics.clone();
+        move || metrics::spawn(metrics.clone())
+    });
+
+    let reload = reload::State::new(diagnostics);
 
     let state = S {
         home: home.clone(),
         templates: templates.clone(),
+        showcase: showcase.clone(),
     };
 
+    let audit = audit::Log::new();
+
+    let waker = wake_on_demand::Waker::new(
+        hosts.clone(),
+        wake_queue.clone(),
+        scripting.clone(),
+        audit.clone(),
+    );
+
+    let wake_on_demand_handle = supervisor::supervise("wake on demand", &supervisor, {
+        let (waker, ping_state) = (waker.clone(), ping_state.clone());
+        move || wake_on_demand::spawn(waker.clone(), ping_state.clone(), packet_socket.clone())
+    });
+
+    let wake_listener_handle = supervisor::supervise("wake listener", &supervisor, {
+        let (hosts, ping_state) = (hosts.clone(), ping_state.clone());
+
+        move || {
+            wake_listener::spawn(
+                waker.clone(),
+                hosts.clone(),
+                ping_state.clone(),
+                wake_listeners.clone(),
+            )
+        }
+    });
+
+    let api = Arc::new(api::Service::new(
+        ping_state.clone(),
+        hosts.clone(),
+        showcase.clone(),
+        wake_queue.clone(),
+        slo.clone(),
+        events.clone(),
+        discovery.clone(),
+        audit.clone(),
+        scripting.clone(),
+    ));
+
+    let read_only = read_only::ReadOnly::new(opts.read_only, hosts.clone());
+    let auth = auth::Auth::new(hosts.clone(), audit.clone());
+
+    let grpc = grpc::router(api.clone(), read_only.clone(), auth.clone());
+    let api = api::router(api).merge(grafana::router(
+        hosts.clone(),
+        history.clone(),
+        showcase.clone(),
+    ));
+
+    let badge = badge::router(ping_state.clone(), hosts.clone(), showcase.clone());
+
+    let metrics = metrics::router(metrics);
+
+    let admin = admin::router(
+        "/admin",
+        templates.clone(),
+        hosts.clone(),
+        ping_state.clone(),
+        reload.clone(),
+        home.clone(),
+        audit.clone(),
+        history.clone(),
+        auth.clone(),
+    )
+    .await;
+
     let network = network::router(
         ping_state,
+        checks,
         "/network",
         templates.clone(),
         hosts.clone(),
-        showcase,
+        showcase.clone(),
         home,
+        wake_queue,
+        slo,
+        snmp,
+        probe,
+        events.clone(),
+        discovery,
+        disappeared,
+        audit,
+        history.clone(),
+        scripting,
     )
-    .await?;
+    .await;
 
-    let mokuro = mokuro::router(templates, config);
+    let mokuro = mokuro::router(templates.clone(), config, hosts.clone(), showcase.clone());
 
     // build our application with a route
     let app = Router::new()
@@ -311,39 +1992,128 @@ async fn inner() -> Result<()> {
         .with_state(state)
         .nest("/network", network)
         .nest("/mokuro", mokuro)
-        .fallback(get(static_handler));
-
-    let listener = if let Some(listener) =
-        try_listener_from_env("LISTEN_FDS").context("setting up listen fd")?
-    {
-        tracing::info!("received socket through LISTEN_FDS");
-        listener
-    } else {
-        let listener = TcpListener::bind(&bind)
-            .await
-            .context("binding to address")?;
-
-        let addr = listener.local_addr()?;
-        tracing::info!("Listening on http://{addr}");
-        listener
-    };
+        .nest("/api/v1", api)
+        .nest("/admin", admin)
+        .merge(metrics)
+        .merge(badge)
+        .merge(api::docs())
+        .route_layer(middleware::from_fn_with_state(
+            auth.clone(),
+            auth::middleware,
+        ))
+        .fallback(get(static_handler))
+        .layer(middleware::from_fn_with_state(
+            read_only,
+            read_only::middleware,
+        ))
+        // Signing in and out has to work in read-only mode.
+        .merge(auth::router(templates.clone(), auth))
+        // Probes don't sign in.
+        .merge(self_check::router(self_check))
+        .layer(middleware::from_fn_with_state(
+            templates,
+            error_page::middleware,
+        ))
+        // gRPC calls are all POST requests with errors of their own, so they
+        // bypass read-only mode and error pages.
+        .merge(grpc)
+        .layer(middleware::from_fn_with_state(
+            hosts.clone(),
+            i18n::middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            hosts.clone(),
+            security_headers::middleware,
+        ));
 
     tokio::select! {
         result = pinger_handle => {
-            result?.context("pinger")?;
+            result.context("pinger")?;
             tracing::info!("pinger task exited");
         }
         result = hosts_handle => {
             result.context("hosts")?;
             tracing::info!("hosts task exited");
         }
-        result = axum::serve(listener, app) => {
+        result = checks_handle => {
+            result.context("checks")?;
+            tracing::info!("checks task exited");
+        }
+        result = snmp_handle => {
+            result.context("snmp")?;
+            tracing::info!("snmp task exited");
+        }
+        result = events_handle => {
+            result.context("events")?;
+            tracing::info!("events task exited");
+        }
+        result = notify_handle => {
+            result.context("notify")?;
+            tracing::info!("notify task exited");
+        }
+        result = disappeared_handle => {
+            result.context("disappeared")?;
+            tracing::info!("disappeared task exited");
+        }
+        result = extensions_handle => {
+            result.context("extensions")?;
+            tracing::info!("extensions task exited");
+        }
+        result = discovery_handle => {
+            result.context("discovery")?;
+            tracing::info!("discovery task exited");
+        }
+        result = history_handle => {
+            result.context("history")?;
+            tracing::info!("history task exited");
+        }
+        result = history_sink_handle => {
+            result.context("history sink")?;
+            tracing::info!("history sink task exited");
+        }
+        result = slo_handle => {
+            result.context("slo")?;
+            tracing::info!("slo task exited");
+        }
+        result = report_handle => {
+            result.context("report")?;
+            tracing::info!("report task exited");
+        }
+        result = metrics_handle => {
+            result.context("metrics")?;
+            tracing::info!("metrics task exited");
+        }
+        result = wake_queue_handle => {
+            result.context("wake queue")?;
+            tracing::info!("wake queue task exited");
+        }
+        result = wake_on_demand_handle => {
+            result.context("wake on demand")?;
+            tracing::info!("wake on demand task exited");
+        }
+        result = wake_listener_handle => {
+            result.context("wake listener")?;
+            tracing::info!("wake listener task exited");
+        }
+        result = echo_requests_handle => {
+            result.context("echo requests")?;
+            tracing::info!("echo requests task exited");
+        }
+        result = reload::spawn(reload, hosts, events, || reload_config(opts)) => {
+            result.context("reload")?;
+            tracing::info!("reload task exited");
+        }
+        result = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()) => {
             result.context("server")?;
             tracing::warn!("server exited");
         }
+        result = daemon::terminated() => {
+            result.context("signals")?;
+            tracing::info!("Terminated");
+        }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
 #[cfg(not(unix))]
@@ -353,19 +2123,29 @@ fn try_listen_fds() -> Result<Option<TcpListener>> {
 
 #[cfg(unix)]
 fn try_listener_from_env(env: &'static str) -> Result<Option<TcpListener>> {
+    /// The first file descriptor passed through socket activation.
+    const SD_LISTEN_FDS_START: i32 = 3;
+
     let Ok(listen_fds) = env::var(env) else {
         return Ok(None);
     };
 
-    let listen_fd: i32 = listen_fds.parse().with_context(|| anyhow!("parse {env}"))?;
+    // The sockets were passed to another process which started us.
+    if let Ok(pid) = env::var("LISTEN_PID")
+        && pid.parse::<u32>().ok() != Some(std::process::id())
+    {
+        return Ok(None);
+    }
+
+    let listen_fds: i32 = listen_fds.parse().with_context(|| anyhow!("parse {env}"))?;
 
-    if listen_fd < 1 {
+    if listen_fds < 1 {
         return Ok(None);
     }
 
-    // NB: This is currently broken since what's passed in is a single connected
-    // peer, not a listening socket.
-    let listener = unsafe { std::net::TcpListener::from_raw_fd(listen_fd) };
+    // SAFETY: The socket unit passes a listening socket as the first file
+    // descriptor, which we take ownership of.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
     listener.set_nonblocking(true).context("set nonblocking")?;
     let listener = TcpListener::from_std(listener).context("converting to tcp listener")?;
     Ok(Some(listener))
@@ -382,10 +2162,24 @@ impl Error {
             kind: ErrorKind::NotFound,
         }
     }
+
+    fn bad_request(detail: impl fmt::Display) -> Self {
+        Self {
+            kind: ErrorKind::BadRequest(detail.to_string()),
+        }
+    }
+
+    fn forbidden(detail: impl fmt::Display) -> Self {
+        Self {
+            kind: ErrorKind::Forbidden(detail.to_string()),
+        }
+    }
 }
 
 enum ErrorKind {
     NotFound,
+    BadRequest(String),
+    Forbidden(String),
     Other(anyhow::Error),
 }
 
@@ -401,26 +2195,42 @@ where
 }
 
 // Tell axum how to convert `Error` into a response.
+//
+// The plain text body is only a fallback, the details attached to the response
+// are rendered by `error_page::middleware`.
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        match self.kind {
-            ErrorKind::NotFound => (StatusCode::NOT_FOUND, "404 Not Found").into_response(),
-            ErrorKind::Other(err) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Something went wrong: {err}"),
-            )
-                .into_response(),
-        }
+        let (status, detail) = match self.kind {
+            ErrorKind::NotFound => (StatusCode::NOT_FOUND, None),
+            ErrorKind::BadRequest(detail) => (StatusCode::BAD_REQUEST, Some(detail)),
+            ErrorKind::Forbidden(detail) => (StatusCode::FORBIDDEN, Some(detail)),
+            ErrorKind::Other(err) => {
+                tracing::error!("Request failed: {err:#}");
+                (StatusCode::INTERNAL_SERVER_ERROR, Some(format!("{err:#}")))
+            }
+        };
+
+        let body = status.canonical_reason().unwrap_or("Error");
+        let mut response = (status, body).into_response();
+
+        response
+            .extensions_mut()
+            .insert(error_page::ErrorDetails { detail });
+
+        response
     }
 }
 
 // basic handler that responds with a static string
 async fn root(
     State(S {
-        home, templates, ..
+        home,
+        templates,
+        showcase,
     }): State<S>,
 ) -> Result<Html<String>, Error> {
-    let home = home.build().await;
+    let mut home = home.build().await;
+    home.showcase(&mut showcase.lock().await);
     let o = templates.render("home.html", &home)?;
     Ok(Html(o))
 }
@@ -0,0 +1,490 @@
+//! Metrics in the Prometheus text exposition format, which can also be pushed
+//! to a remote endpoint through remote-write or the InfluxDB line protocol.
+
+use core::fmt::{self, Write};
+use core::time::Duration;
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use tokio::time::{self, Instant};
+
+use crate::config::PushFormat;
+use crate::hosts;
+use crate::notify;
+use crate::ping_loop::{self, HostStatus};
+use crate::showcase;
+use crate::slo;
+use crate::snmp;
+
+/// How often configuration is checked for whether metrics should be pushed.
+const CHECK: Duration = Duration::from_secs(1);
+/// How long to wait for the push endpoint to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct S {
+    ping_state: ping_loop::State,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+    slo: slo::State,
+    snmp: snmp::State,
+}
+
+/// The sources metrics are collected from.
+#[derive(Clone)]
+pub(super) struct Metrics {
+    inner: Arc<S>,
+}
+
+impl Metrics {
+    pub(super) fn new(
+        ping_state: ping_loop::State,
+        hosts: hosts::State,
+        showcase: showcase::Helper,
+        slo: slo::State,
+        snmp: snmp::State,
+    ) -> Self {
+        Self {
+            inner: Arc::new(S {
+                ping_state,
+                hosts,
+                showcase,
+                slo,
+                snmp,
+            }),
+        }
+    }
+}
+
+pub(super) fn router(metrics: Metrics) -> Router {
+    Router::new()
+        .route("/metrics", get(text))
+        .with_state(metrics.inner)
+}
+
+async fn text(State(state): State<Arc<S>>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    for metric in collect(&state).await {
+        metric.write(&mut out);
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+/// Push metrics on the interval configured in `[metrics.push]`.
+pub(super) async fn spawn(metrics: Metrics) {
+    let state = metrics.inner;
+    let mut last = None::<Instant>;
+    let mut tick = time::interval(CHECK);
+
+    loop {
+        tick.tick().await;
+
+        let config = state.hosts.config().await;
+        let push = &config.metrics.push;
+
+        let Some(url) = &push.url else {
+            last = None;
+            continue;
+        };
+
+        // The first push is made one interval after pushing is configured, so
+        // that hosts have been pinged by then.
+        let now = Instant::now();
+
+        if now.saturating_duration_since(*last.get_or_insert(now)) < push.interval() {
+            continue;
+        }
+
+        last = Some(now);
+
+        let metrics = collect(&state).await;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let result = match push.format() {
+            PushFormat::RemoteWrite => remote_write(&metrics, timestamp).map(|body| {
+                let headers = [
+                    ("Content-Type", "application/x-protobuf"),
+                    ("Content-Encoding", "snappy"),
+                    ("X-Prometheus-Remote-Write-Version", "0.1.0"),
+                ];
+
+                (headers.to_vec(), body)
+            }),
+            PushFormat::Influx => {
+                let headers = [("Content-Type", "text/plain; charset=utf-8")];
+                Ok((headers.to_vec(), influx(&metrics, timestamp).into_bytes()))
+            }
+        };
+
+        let result = match result {
+            Ok((headers, body)) => {
+                match time::timeout(TIMEOUT, notify::post(url, &headers, &body)).await {
+                    Ok(result) => result,
+                    Err(..) => Err(anyhow::anyhow!("timeout")),
+                }
+            }
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = result {
+            tracing::warn!("Pushing metrics to {url}: {error:#}");
+        }
+    }
+}
+
+/// Collect all metrics.
+async fn collect(state: &S) -> Vec<Metric> {
+    let hosts = state.hosts.hosts();
+    let slo_results = state.slo.results.lock().await;
+    let snmp_results = state.snmp.results.lock().await;
+    let pinged = state.ping_state.pinged.lock().await;
+    let statuses = ping_loop::statuses(&hosts, &pinged);
+    let stats = *state.ping_state.stats.lock().await;
+    let mut showcase = state.showcase.lock().await;
+
+    let mut up = Metric::gauge("wolo_host_up", "Whether the host responds to pings.");
+    let mut unreachable = Metric::gauge(
+        "wolo_host_unreachable",
+        "Whether the host is unreachable because a host it depends on is down.",
+    );
+    let mut rtt = Metric::gauge(
+        "wolo_host_rtt_seconds",
+        "Round trip time of the most recent reply from an address.",
+    );
+    let mut availability = Metric::gauge(
+        "wolo_slo_availability_ratio",
+        "Share of answered pings in the objective window.",
+    );
+    let mut availability_objective = Metric::gauge(
+        "wolo_slo_availability_objective_ratio",
+        "The availability objective.",
+    );
+    let mut rtt_percentile = Metric::gauge(
+        "wolo_slo_rtt_seconds",
+        "Round trip time at the objective percentile in the objective window.",
+    );
+    let mut rtt_objective = Metric::gauge(
+        "wolo_slo_rtt_objective_seconds",
+        "The round trip time objective.",
+    );
+    let mut breached = Metric::gauge(
+        "wolo_slo_breached",
+        "Whether a service level objective is breached.",
+    );
+    let mut uptime = Metric::gauge(
+        "wolo_snmp_uptime_seconds",
+        "How long the host has been up according to SNMP.",
+    );
+    let mut in_octets = Metric::counter(
+        "wolo_snmp_interface_in_octets_total",
+        "Octets received by an interface according to SNMP.",
+    );
+    let mut out_octets = Metric::counter(
+        "wolo_snmp_interface_out_octets_total",
+        "Octets sent by an interface according to SNMP.",
+    );
+
+    for host in hosts.iter() {
+        let Some(name) = host.names().next() else {
+            continue;
+        };
+
+        let name = showcase.host_name(host, name);
+        let labels = [("host", name.as_str())];
+
+        let status = statuses.get(&host.id).map(|s| s.status);
+        up.sample(&labels, f64::from(u8::from(status == Some(HostStatus::Up))));
+        unreachable.sample(
+            &labels,
+            f64::from(u8::from(status == Some(HostStatus::Unreachable))),
+        );
+
+        for r in pinged.get(&host.id).into_iter().flat_map(|p| &p.results) {
+            if !r.outcome.is_echo_reply() {
+                continue;
+            }
+
+            let address = showcase.ip(host, r.target).to_string();
+            rtt.sample(
+                &[("host", name.as_str()), ("address", address.as_str())],
+                r.rtt.as_secs_f64(),
+            );
+        }
+
+        if let Some(s) = snmp_results.get(&host.id) {
+            if let Some(value) = s.uptime {
+                uptime.sample(&labels, value.as_secs_f64());
+            }
+
+            for interface in s.interfaces.values() {
+                let labels = [
+                    ("host", name.as_str()),
+                    ("interface", interface.name.as_str()),
+                ];
+
+                in_octets.sample(&labels, interface.in_octets as f64);
+                out_octets.sample(&labels, interface.out_octets as f64);
+            }
+        }
+
+        let Some(r) = slo_results.get(&host.id) else {
+            continue;
+        };
+
+        if let Some(objective) = r.slo.availability {
+            availability_objective.sample(&labels, objective.ratio());
+
+            if let Some(value) = r.availability {
+                availability.sample(&labels, value);
+            }
+
+            breached.sample(
+                &[("host", name.as_str()), ("objective", "availability")],
+                f64::from(u8::from(r.availability_breached)),
+            );
+        }
+
+        if let Some(objective) = r.slo.rtt {
+            let percentile = r.slo.percentile().to_string();
+            let labels = [("host", name.as_str()), ("percentile", percentile.as_str())];
+
+            rtt_objective.sample(&labels, objective.as_secs_f64());
+
+            if let Some(value) = r.rtt {
+                rtt_percentile.sample(&labels, value.as_secs_f64());
+            }
+
+            breached.sample(
+                &[("host", name.as_str()), ("objective", "rtt")],
+                f64::from(u8::from(r.rtt_breached)),
+            );
+        }
+    }
+
+    let mut sent = Metric::counter("wolo_pings_sent_total", "Total number of pings sent.");
+    sent.sample(&[], stats.sent as f64);
+
+    vec![
+        up,
+        unreachable,
+        rtt,
+        availability,
+        availability_objective,
+        rtt_percentile,
+        rtt_objective,
+        breached,
+        uptime,
+        in_octets,
+        out_octets,
+        sent,
+    ]
+}
+
+/// A metric family.
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    samples: Vec<Sample>,
+}
+
+struct Sample {
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+impl Metric {
+    fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: "gauge",
+            samples: Vec::new(),
+        }
+    }
+
+    fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: "counter",
+            samples: Vec::new(),
+        }
+    }
+
+    /// Add a sample with the given labels.
+    fn sample(&mut self, labels: &[(&'static str, &str)], value: f64) {
+        let labels = labels.iter().map(|&(k, v)| (k, v.to_owned())).collect();
+        self.samples.push(Sample { labels, value });
+    }
+
+    /// Write the metric family in the text exposition format, unless it has
+    /// no samples.
+    fn write(self, out: &mut String) {
+        if self.samples.is_empty() {
+            return;
+        }
+
+        _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        _ = writeln!(out, "# TYPE {} {}", self.name, self.kind);
+
+        for sample in &self.samples {
+            out.push_str(self.name);
+
+            if !sample.labels.is_empty() {
+                out.push('{');
+
+                for (n, (key, value)) in sample.labels.iter().enumerate() {
+                    if n > 0 {
+                        out.push(',');
+                    }
+
+                    _ = write!(out, "{key}=\"{}\"", Escape(value));
+                }
+
+                out.push('}');
+            }
+
+            _ = writeln!(out, " {}", sample.value);
+        }
+    }
+}
+
+/// Escape a label value.
+struct Escape<'a>(&'a str);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Encode metrics as a snappy compressed remote-write request.
+fn remote_write(metrics: &[Metric], timestamp: Duration) -> Result<Vec<u8>> {
+    use prost::Message;
+
+    let timestamp = i64::try_from(timestamp.as_millis()).unwrap_or(i64::MAX);
+    let mut request = remote::WriteRequest::default();
+
+    for metric in metrics {
+        for sample in &metric.samples {
+            let mut labels = vec![remote::Label {
+                name: String::from("__name__"),
+                value: metric.name.to_owned(),
+            }];
+
+            labels.extend(sample.labels.iter().map(|(name, value)| remote::Label {
+                name: (*name).to_owned(),
+                value: value.clone(),
+            }));
+
+            // Receivers expect labels to be sorted by name.
+            labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+            request.timeseries.push(remote::TimeSeries {
+                labels,
+                samples: vec![remote::Sample {
+                    value: sample.value,
+                    timestamp,
+                }],
+            });
+        }
+    }
+
+    snap::raw::Encoder::new()
+        .compress_vec(&request.encode_to_vec())
+        .context("compressing")
+}
+
+/// Format metrics in the InfluxDB line protocol, with the value of each sample
+/// in the `value` field.
+fn influx(metrics: &[Metric], timestamp: Duration) -> String {
+    let mut out = String::new();
+
+    for metric in metrics {
+        for sample in &metric.samples {
+            _ = write!(out, "{}", InfluxEscape(metric.name));
+
+            // Tags can't have empty values.
+            for (key, value) in sample.labels.iter().filter(|(_, v)| !v.is_empty()) {
+                _ = write!(out, ",{}={}", InfluxEscape(key), InfluxEscape(value));
+            }
+
+            _ = writeln!(out, " value={} {}", sample.value, timestamp.as_nanos());
+        }
+    }
+
+    out
+}
+
+/// Escape a measurement, tag key or tag value in the InfluxDB line protocol.
+pub(crate) struct InfluxEscape<'a>(pub(crate) &'a str);
+
+impl fmt::Display for InfluxEscape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                ',' | '=' | ' ' | '\\' => {
+                    f.write_char('\\')?;
+                    f.write_char(c)?;
+                }
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Messages of the Prometheus remote-write protocol.
+mod remote {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct WriteRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub(super) timeseries: Vec<TimeSeries>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct TimeSeries {
+        #[prost(message, repeated, tag = "1")]
+        pub(super) labels: Vec<Label>,
+        #[prost(message, repeated, tag = "2")]
+        pub(super) samples: Vec<Sample>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct Label {
+        #[prost(string, tag = "1")]
+        pub(super) name: String,
+        #[prost(string, tag = "2")]
+        pub(super) value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub(super) struct Sample {
+        #[prost(double, tag = "1")]
+        pub(super) value: f64,
+        #[prost(int64, tag = "2")]
+        pub(super) timestamp: i64,
+    }
+}
@@ -1,145 +1,844 @@
+use core::ops::Range;
+
+use std::io::SeekFrom;
+use std::path::{Component, PathBuf};
 use std::sync::Arc;
 
+use anyhow::Context as _;
 use axum::Router;
-use axum::extract::{Path, State};
-use axum::http::header;
-use axum::response::{Html, IntoResponse, Response};
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Redirect, Response};
 use axum::routing::get;
-use serde::Serialize;
-use tokio::fs;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::Error;
-use crate::config::Config;
+use crate::admin;
+use crate::config::{Config, MokuroAccessConfig};
+use crate::hosts;
+use crate::library::{self, Library, Series};
+use crate::showcase;
+use crate::upload::{self, Received};
 use crate::utils::Templates;
+use crate::zip::Archive;
+
+/// Maximum size of an upload.
+const UPLOAD_LIMIT: usize = 1024 * 1024 * 1024;
 
 #[derive(Clone)]
 struct S {
     templates: Templates,
-    config: Arc<Config>,
+    sources: Arc<[Source]>,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+    library: Library,
+}
+
+/// A library which is being served.
+struct Source {
+    /// The identifier of the library used in URLs.
+    id: String,
+    title: String,
+    path: PathBuf,
+    /// Whether volumes can be uploaded to the library.
+    upload: bool,
 }
 
-pub(super) fn router(templates: Templates, config: Arc<Config>) -> Router {
+pub(super) fn router(
+    templates: Templates,
+    config: Arc<Config>,
+    hosts: hosts::State,
+    showcase: showcase::Helper,
+) -> Router {
+    let sources = sources(&config);
+    let library = Library::new();
+
+    let roots = sources.iter().map(|s| s.path.clone()).collect();
+    tokio::spawn(library::spawn(library.clone(), roots));
+
     Router::new()
         .route("/", get(list_all))
+        .route("/search", get(search))
+        .route(
+            "/upload",
+            get(upload_form)
+                .post(upload)
+                .layer(DefaultBodyLimit::max(UPLOAD_LIMIT)),
+        )
         .route("/{id}/{name}", get(list_one))
         .route("/{id}/{group}/{name}", get(load))
+        .route("/{id}/{group}/{name}/page/{n}", get(page))
         .route("/{id}/{group}/{name}/{*key}", get(static_file))
-        .with_state(S { templates, config })
+        .route_layer(middleware::from_fn_with_state(hosts.clone(), access))
+        .with_state(S {
+            templates,
+            sources: sources.into(),
+            hosts,
+            showcase,
+            library,
+        })
+}
+
+/// Apply the access settings in `[mokuro]`, which can be changed without a
+/// restart.
+async fn access(
+    State(hosts): State<hosts::State>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    let config = hosts.config().await;
+    let access = &config.mokuro_access;
+
+    if !access.enabled.unwrap_or(true) {
+        return Err(Error::not_found());
+    }
+
+    if let Some(password) = access.password.as_deref() {
+        let username = access
+            .username
+            .as_deref()
+            .unwrap_or(MokuroAccessConfig::DEFAULT_USERNAME);
+
+        if !admin::is_authorized(req.headers(), username, password) {
+            return Ok(admin::unauthorized("wolo mokuro"));
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Collect enabled libraries in the order they should be listed.
+fn sources(config: &Config) -> Vec<Source> {
+    let mut configs = config
+        .mokuro
+        .iter()
+        .filter(|m| m.enabled)
+        .collect::<Vec<_>>();
+    configs.sort_by_key(|m| m.order);
+
+    let mut sources = Vec::<Source>::with_capacity(configs.len());
+
+    for m in configs {
+        let title = m.title();
+        let base = slug(&title);
+        let mut id = base.clone();
+        let mut n = 1;
+
+        while sources.iter().any(|s| s.id == id) {
+            n += 1;
+            id = format!("{base}-{n}");
+        }
+
+        sources.push(Source {
+            id,
+            title,
+            path: m.path.clone(),
+            upload: m.upload,
+        });
+    }
+
+    sources
+}
+
+/// Turn a title into something which is nice to use in a URL.
+fn slug(title: &str) -> String {
+    let mut out = String::new();
+
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            out.extend(c.to_lowercase());
+        } else if !out.is_empty() && !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+
+    while out.ends_with('-') {
+        out.pop();
+    }
+
+    if out.is_empty() {
+        out.push_str("library");
+    }
+
+    out
+}
+
+/// Look up a library by its identifier.
+fn source<'a>(sources: &'a [Source], id: &str) -> Result<&'a Source, Error> {
+    let Some(source) = sources.iter().find(|s| s.id == id) else {
+        return Err(Error::not_found());
+    };
+
+    Ok(source)
+}
+
+/// Get the library uploads are stored in.
+///
+/// Uploads are only accepted if the Mokuro pages are protected by a password.
+async fn upload_source<'a>(sources: &'a [Source], hosts: &hosts::State) -> Option<&'a Source> {
+    hosts.config().await.mokuro_access.password.as_ref()?;
+    sources.iter().find(|s| s.upload)
 }
 
 #[derive(Serialize)]
-struct Link {
+struct VolumeContext {
     title: String,
     href: String,
+    /// Link to the first page in the page by page reader.
+    reader: String,
+    pages: usize,
+    cover: Option<String>,
 }
 
-async fn list_all(State(S { templates, config }): State<S>) -> Result<Html<String>, Error> {
-    #[derive(Serialize)]
-    struct Context {
-        links: Vec<Link>,
-    }
+#[derive(Serialize)]
+struct SeriesContext {
+    title: String,
+    href: String,
+    volumes: Vec<VolumeContext>,
+}
 
-    let mut links = Vec::new();
+#[derive(Serialize)]
+struct SourceContext {
+    title: String,
+    series: Vec<SeriesContext>,
+}
+
+#[derive(Serialize)]
+struct Context {
+    title: String,
+    sources: Vec<SourceContext>,
+    upload: bool,
+}
 
-    for (n, m) in config.mokuro.iter().enumerate() {
-        let mut d = fs::read_dir(&m.path).await?;
+/// Build the template context for a series.
+fn series_context(
+    showcase: &mut showcase::LockedHelper<'_>,
+    source: &Source,
+    series: &Series,
+) -> SeriesContext {
+    let id = showcase.path(&source.id);
+    let group = showcase.path(&series.name);
 
-        while let Some(d) = d.next_entry().await? {
-            let d = d.path();
+    let volumes = series
+        .volumes
+        .iter()
+        .map(|v| {
+            let base = format!("/mokuro/{id}/{group}/{}", showcase.path(&v.name));
+            let reader = format!("{base}/page/1");
 
-            let Some(file_name) = d.file_name().and_then(|s| s.to_str()) else {
-                continue;
+            // Covers would reveal the contents of the library.
+            let cover = match v.cover() {
+                Some(cover) if !showcase.is_enabled() => Some(format!("{base}/{cover}")),
+                _ => None,
             };
 
-            links.push(Link {
-                title: file_name.to_owned(),
-                href: format!("/mokuro/{n}/{file_name}"),
-            });
+            VolumeContext {
+                title: showcase.text(&v.title),
+                href: if v.html { base } else { reader.clone() },
+                reader,
+                pages: v.pages.len(),
+                cover,
+            }
+        })
+        .collect();
+
+    SeriesContext {
+        title: showcase.text(&series.title),
+        href: format!("/mokuro/{id}/{group}"),
+        volumes,
+    }
+}
+
+async fn list_all(
+    State(S {
+        templates,
+        sources,
+        hosts,
+        showcase,
+        library,
+    }): State<S>,
+) -> Result<Html<String>, Error> {
+    let mut showcase = showcase.lock().await;
+    let mut contexts = Vec::with_capacity(sources.len());
+
+    for source in sources.iter() {
+        let mut series = Vec::new();
+
+        for s in library.all(&source.path).await? {
+            series.push(series_context(&mut showcase, source, &s));
         }
+
+        contexts.push(SourceContext {
+            title: showcase.text(&source.title),
+            series,
+        });
     }
 
-    let context = Context { links };
+    let context = Context {
+        title: String::from("mokuro"),
+        sources: contexts,
+        upload: upload_source(&sources, &hosts).await.is_some(),
+    };
 
     let o = templates.render("mokuro.html", &context)?;
     Ok(Html(o))
 }
 
 async fn list_one(
-    State(S { templates, config }): State<S>,
-    Path((n, group)): Path<(usize, String)>,
+    State(S {
+        templates,
+        sources,
+        hosts,
+        showcase,
+        library,
+    }): State<S>,
+    Path((id, group)): Path<(String, String)>,
 ) -> Result<Html<String>, Error> {
-    #[derive(Serialize)]
-    struct Context {
-        links: Vec<Link>,
+    let source = source(&sources, &id)?;
+
+    sandboxed(&source.path, join(&source.path, [group.as_str()])?).await?;
+    let series = library.series(&source.path, &group).await?;
+
+    let mut showcase = showcase.lock().await;
+    let series = series_context(&mut showcase, source, &series);
+
+    let context = Context {
+        title: series.title.clone(),
+        sources: vec![SourceContext {
+            title: showcase.text(&source.title),
+            series: vec![series],
+        }],
+        upload: upload_source(&sources, &hosts).await.is_some(),
+    };
+
+    let o = templates.render("mokuro.html", &context)?;
+    Ok(Html(o))
+}
+
+#[derive(Serialize)]
+struct UploadContext {
+    title: String,
+    library: String,
+    series: Vec<String>,
+    error: Option<String>,
+}
+
+/// Render the upload page with an optional error.
+async fn upload_page(
+    state: &S,
+    source: &Source,
+    error: Option<String>,
+) -> Result<Html<String>, Error> {
+    let series = match state.library.all(&source.path).await {
+        Ok(series) => series.iter().map(|s| s.name.clone()).collect(),
+        Err(error) => {
+            tracing::warn!("{}: {error}", source.path.display());
+            Vec::new()
+        }
+    };
+
+    let context = UploadContext {
+        title: String::from("upload"),
+        library: source.title.clone(),
+        series,
+        error,
+    };
+
+    let o = state.templates.render("mokuro_upload.html", &context)?;
+    Ok(Html(o))
+}
+
+async fn upload_form(State(state): State<S>) -> Result<Html<String>, Error> {
+    let Some(source) = upload_source(&state.sources, &state.hosts).await else {
+        return Err(Error::not_found());
+    };
+
+    upload_page(&state, source, None).await
+}
+
+/// Receive an uploaded volume and store it in the upload library.
+async fn upload(
+    State(state): State<S>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, Error> {
+    let Some(source) = upload_source(&state.sources, &state.hosts).await else {
+        return Err(Error::not_found());
+    };
+
+    // Browsers attach basic credentials to cross-site form submissions.
+    if admin::is_cross_site(&headers) {
+        return Ok((StatusCode::FORBIDDEN, "Forbidden").into_response());
     }
 
-    let mut links = Vec::new();
+    let mut received = Vec::new();
+    let result = receive(source, multipart, &mut received).await;
 
-    'done: {
-        let Some(config) = config.mokuro.get(n) else {
-            break 'done;
-        };
+    for r in &received {
+        r.discard().await;
+    }
 
-        let mut d = fs::read_dir(config.path.join(&group)).await?;
+    match result {
+        Ok((series, volume)) => {
+            let path = source.path.join(&series);
+            tracing::info!("Uploaded volume {volume} to {}", path.display());
+            state.library.forget(&path).await;
+            Ok(Redirect::to(&format!("/mokuro/{}/{series}", source.id)).into_response())
+        }
+        Err(error) => {
+            let page = upload_page(&state, source, Some(format!("{error:#}"))).await?;
+            Ok((StatusCode::BAD_REQUEST, page).into_response())
+        }
+    }
+}
 
-        while let Some(d) = d.next_entry().await? {
-            let d = d.path();
+/// Receive the fields of an upload and store the volume.
+///
+/// Returns the names of the series and volume which were stored.
+async fn receive(
+    source: &Source,
+    mut multipart: Multipart,
+    received: &mut Vec<Received>,
+) -> anyhow::Result<(String, String)> {
+    let mut series = None;
 
-            if !matches!(d.extension().and_then(|s| s.to_str()), Some("html")) {
-                continue;
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name() {
+            Some("series") => {
+                series = Some(field.text().await?.trim().to_owned());
             }
+            Some("files") => {
+                let Some(file_name) = field.file_name().filter(|n| !n.is_empty()) else {
+                    continue;
+                };
 
-            let Some(file_name) = d.file_stem().and_then(|s| s.to_str()) else {
-                continue;
-            };
+                let r = Received::new(&source.path, file_name);
+                let mut file = File::create(&r.path).await?;
+                received.push(r);
+
+                while let Some(chunk) = field.chunk().await? {
+                    file.write_all(&chunk).await?;
+                }
+
+                file.flush().await?;
+            }
+            _ => {}
+        }
+    }
+
+    let series = series.unwrap_or_default();
+    upload::check_name("series", &series)?;
+
+    let path = source.path.join(&series);
+
+    // An existing series might be a link to outside of the library.
+    if fs::try_exists(&path).await? && sandboxed(&source.path, path.clone()).await.is_err() {
+        anyhow::bail!("invalid series `{series}`");
+    }
+
+    let volume = upload::store(&path, received)
+        .await
+        .context("storing volume")?;
+
+    Ok((series, volume))
+}
+
+/// Maximum number of pages returned by a search.
+const SEARCH_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: Option<String>,
+}
+
+async fn search(
+    State(S {
+        templates,
+        sources,
+        showcase,
+        library,
+        ..
+    }): State<S>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Html<String>, Error> {
+    #[derive(Serialize)]
+    struct Hit {
+        series: String,
+        volume: String,
+        href: String,
+        page: usize,
+        before: String,
+        matched: String,
+        after: String,
+    }
+
+    #[derive(Serialize)]
+    struct Context {
+        title: String,
+        query: String,
+        hits: Vec<Hit>,
+        truncated: bool,
+    }
 
-            links.push(Link {
-                title: file_name.to_owned(),
-                href: format!("/mokuro/{n}/{group}/{file_name}"),
+    let q = query.q.unwrap_or_default();
+    let q = q.trim();
+
+    let mut showcase = showcase.lock().await;
+    let mut hits = Vec::new();
+
+    for source in sources.iter() {
+        let limit = SEARCH_LIMIT + 1 - hits.len();
+
+        for hit in library.search(&source.path, q, limit).await? {
+            let volume = &hit.series.volumes[hit.volume];
+
+            hits.push(Hit {
+                series: showcase.text(&hit.series.title),
+                volume: showcase.text(&volume.title),
+                href: format!(
+                    "/mokuro/{}/{}/{}/page/{}",
+                    showcase.path(&source.id),
+                    showcase.path(&hit.series.name),
+                    showcase.path(&volume.name),
+                    hit.page
+                ),
+                page: hit.page,
+                before: showcase.text(&hit.before),
+                matched: showcase.text(&hit.matched),
+                after: showcase.text(&hit.after),
             });
         }
-    };
 
-    let context = Context { links };
+        if hits.len() > SEARCH_LIMIT {
+            break;
+        }
+    }
 
-    let o = templates.render("mokuro.html", &context)?;
+    let truncated = hits.len() > SEARCH_LIMIT;
+    hits.truncate(SEARCH_LIMIT);
+
+    let context = Context {
+        title: String::from("mokuro"),
+        query: q.to_owned(),
+        hits,
+        truncated,
+    };
+
+    let o = templates.render("mokuro_search.html", &context)?;
     Ok(Html(o))
 }
 
-async fn load(
-    State(S { config, .. }): State<S>,
-    Path((n, group, name)): Path<(usize, String, String)>,
-) -> Result<Html<Vec<u8>>, Error> {
-    let Some(config) = config.mokuro.get(n) else {
+/// Render a single page of a volume with links to the adjacent pages.
+async fn page(
+    State(S {
+        templates,
+        sources,
+        showcase,
+        library,
+        ..
+    }): State<S>,
+    Path((id, group, name, n)): Path<(String, String, String, usize)>,
+) -> Result<Html<String>, Error> {
+    #[derive(Serialize)]
+    struct Context {
+        title: String,
+        series: String,
+        series_href: String,
+        volume: String,
+        page: usize,
+        pages: usize,
+        image: Option<String>,
+        prev: Option<String>,
+        next: Option<String>,
+        preload: Vec<String>,
+    }
+
+    let source = source(&sources, &id)?;
+
+    sandboxed(&source.path, join(&source.path, [group.as_str()])?).await?;
+    let series = library.series(&source.path, &group).await?;
+
+    let Some(volume) = series.volumes.iter().find(|v| v.name == name) else {
         return Err(Error::not_found());
     };
 
-    let mut p = config.path.clone();
-    p.push(&group);
-    p.push(&name);
-    p.set_extension("html");
+    let Some(index) = n.checked_sub(1).filter(|&i| i < volume.pages.len()) else {
+        return Err(Error::not_found());
+    };
+
+    let mut showcase = showcase.lock().await;
+
+    let series_href = format!(
+        "/mokuro/{}/{}",
+        showcase.path(&source.id),
+        showcase.path(&series.name)
+    );
+
+    let base = format!("{series_href}/{}", showcase.path(&volume.name));
+    let page_href = |i: usize| format!("{base}/page/{}", i + 1);
+
+    // Pages would reveal the contents of the library.
+    let hidden = showcase.is_enabled();
+
+    let image = |i: usize| {
+        let page = volume.pages.get(i)?;
+        (!hidden).then(|| format!("{base}/{page}"))
+    };
+
+    let prev = index.checked_sub(1);
+    let next = Some(index + 1).filter(|&i| i < volume.pages.len());
+
+    let context = Context {
+        title: format!("{} - {n}", showcase.text(&volume.title)),
+        series: showcase.text(&series.title),
+        series_href: series_href.clone(),
+        volume: showcase.text(&volume.title),
+        page: n,
+        pages: volume.pages.len(),
+        image: image(index),
+        prev: prev.map(page_href),
+        next: next.map(page_href),
+        preload: prev.into_iter().chain(next).filter_map(image).collect(),
+    };
+
+    let o = templates.render("mokuro_page.html", &context)?;
+    Ok(Html(o))
+}
+
+/// Serve the HTML of a volume as generated by Mokuro.
+///
+/// It uses inline scripts, so it's sent with a policy of its own instead of
+/// the default one.
+async fn load(
+    State(S { sources, hosts, .. }): State<S>,
+    Path((id, group, name)): Path<(String, String, String)>,
+) -> Result<Response, Error> {
+    let source = source(&sources, &id)?;
+
+    let p = join(&source.path, [group.as_str(), &format!("{name}.html")])?;
+    let p = sandboxed(&source.path, p).await?;
 
     let bytes = fs::read(&p).await?;
-    Ok(Html(bytes))
+    let policy = hosts
+        .config()
+        .await
+        .headers
+        .mokuro_content_security_policy();
+
+    let mut response = Html(bytes).into_response();
+
+    if !policy.is_empty()
+        && let Ok(value) = HeaderValue::from_str(&policy)
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_SECURITY_POLICY, value);
+    }
+
+    Ok(response)
 }
 
+/// Serve a file belonging to a volume.
+///
+/// Volumes stored in a `.cbz` or `.zip` archive have their files served out of
+/// the archive. Single byte ranges are supported in both cases.
 async fn static_file(
-    State(S { config, .. }): State<S>,
-    Path((n, group, name, rest)): Path<(usize, String, String, String)>,
+    State(S { sources, .. }): State<S>,
+    Path((id, group, name, rest)): Path<(String, String, String, String)>,
+    headers: HeaderMap,
 ) -> Result<Response, Error> {
-    let Some(config) = config.mokuro.get(n) else {
+    let source = source(&sources, &id)?;
+
+    let volume = join(&source.path, [group.as_str(), &name])?;
+    let mime = mime_guess::from_path(&rest).first_or_octet_stream();
+
+    if let Some(path) = library::find_archive(&volume).await {
+        let path = sandboxed(&source.path, path).await?;
+        let mut archive = Archive::open(&path).await?;
+
+        let Some(entry) = archive.find(&rest).cloned() else {
+            return Err(Error::not_found());
+        };
+
+        let Ok(range) = requested_range(&headers, entry.size) else {
+            return Ok(unsatisfiable(entry.size));
+        };
+
+        let bytes = archive
+            .read(&entry, range.clone().unwrap_or(0..entry.size))
+            .await?;
+
+        return Ok(ranged(mime.as_ref(), entry.size, range, bytes));
+    }
+
+    let p = sandboxed(&source.path, join(&volume, rest.split('/'))?).await?;
+
+    let mut file = File::open(&p).await?;
+    let len = file.metadata().await?.len();
+
+    let Ok(range) = requested_range(&headers, len) else {
+        return Ok(unsatisfiable(len));
+    };
+
+    let bytes = match &range {
+        Some(range) => {
+            let mut bytes = vec![0; (range.end - range.start) as usize];
+            file.seek(SeekFrom::Start(range.start)).await?;
+            file.read_exact(&mut bytes).await?;
+            bytes
+        }
+        None => {
+            let mut bytes = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut bytes).await?;
+            bytes
+        }
+    };
+
+    Ok(ranged(mime.as_ref(), len, range, bytes))
+}
+
+/// Join path segments from a request onto a path in a library.
+///
+/// Every segment must be a plain file name, so segments like `..` or ones
+/// which are absolute paths can't be used to escape the root.
+fn join<'a>(
+    root: &std::path::Path,
+    segments: impl IntoIterator<Item = &'a str>,
+) -> Result<PathBuf, Error> {
+    let mut path = root.to_owned();
+
+    for segment in segments {
+        let mut components = std::path::Path::new(segment).components();
+
+        let (Some(Component::Normal(c)), None) = (components.next(), components.next()) else {
+            return Err(Error::not_found());
+        };
+
+        if c != segment {
+            return Err(Error::not_found());
+        }
+
+        path.push(c);
+    }
+
+    Ok(path)
+}
+
+/// Resolve a path and ensure that it is still inside of the root of the
+/// library, which might not be the case if it's a symbolic link.
+async fn sandboxed(root: &std::path::Path, path: PathBuf) -> Result<PathBuf, Error> {
+    let (Ok(root), Ok(path)) = (fs::canonicalize(root).await, fs::canonicalize(&path).await) else {
         return Err(Error::not_found());
     };
 
-    let mut p = config.path.clone();
-    p.push(&group);
-    p.push(&name);
+    if !path.starts_with(&root) {
+        tracing::warn!("{}: outside of library {}", path.display(), root.display());
+        return Err(Error::not_found());
+    }
+
+    Ok(path)
+}
+
+/// Parse the `Range` header of a request for a file of the given length.
+///
+/// Only a single range is supported, if multiple are requested the whole file
+/// is served. Errors if the range can't be satisfied.
+fn requested_range(headers: &HeaderMap, len: u64) -> Result<Option<Range<u64>>, ()> {
+    let Some(value) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return Ok(None);
+    };
+
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
 
-    for segment in rest.split('/') {
-        p.push(segment);
+    if spec.contains(',') {
+        return Ok(None);
     }
 
-    let mime = mime_guess::from_path(&p).first_or_octet_stream();
-    let bytes = fs::read(&p).await?;
-    Ok(([(header::CONTENT_TYPE, mime.as_ref())], bytes).into_response())
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return Ok(None),
+        ("", suffix) => {
+            let Ok(suffix) = suffix.parse::<u64>() else {
+                return Ok(None);
+            };
+
+            len.saturating_sub(suffix)..len
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return Ok(None);
+            };
+
+            let end = match end {
+                "" => len,
+                end => {
+                    let Ok(end) = end.parse::<u64>() else {
+                        return Ok(None);
+                    };
+
+                    if end < start {
+                        return Ok(None);
+                    }
+
+                    end.saturating_add(1).min(len)
+                }
+            };
+
+            if start >= len {
+                return Err(());
+            }
+
+            start..end
+        }
+    };
+
+    if range.is_empty() {
+        return Err(());
+    }
+
+    Ok(Some(range))
+}
+
+/// Build the response for a file, which is partial if a range was requested.
+fn ranged(mime: &str, len: u64, range: Option<Range<u64>>, bytes: Vec<u8>) -> Response {
+    let Some(range) = range else {
+        return (
+            [
+                (header::CONTENT_TYPE, mime.to_owned()),
+                (header::ACCEPT_RANGES, String::from("bytes")),
+            ],
+            bytes,
+        )
+            .into_response();
+    };
+
+    let content_range = format!("bytes {}-{}/{len}", range.start, range.end - 1);
+
+    (
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::CONTENT_TYPE, mime.to_owned()),
+            (header::ACCEPT_RANGES, String::from("bytes")),
+            (header::CONTENT_RANGE, content_range),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+fn unsatisfiable(len: u64) -> Response {
+    (
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        [(header::CONTENT_RANGE, format!("bytes */{len}"))],
+    )
+        .into_response()
 }
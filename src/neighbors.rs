@@ -0,0 +1,225 @@
+//! Reading the neighbor table of the machine wolo runs on.
+
+use core::mem;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use std::collections::HashSet;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+use macaddr::MacAddr6;
+use tokio::{fs, task};
+
+use crate::mac::Mac;
+
+/// Path to the IPv4 neighbor table on Linux.
+const ARP_TABLE: &str = "/proc/net/arp";
+
+/// Flag set on entries which have been resolved.
+const ATF_COM: u32 = 0x2;
+
+/// Size of a netlink message header.
+const NLMSG_HEADER: usize = 16;
+
+/// Size of the neighbor message following the netlink header.
+const NDMSG: usize = 12;
+
+/// An entry in the neighbor table.
+#[derive(Debug, Clone)]
+pub struct Neighbor {
+    /// The address of the neighbor.
+    pub address: Ipv4Addr,
+    /// The MAC address the address resolved to.
+    pub mac: MacAddr6,
+    /// The local interface the neighbor was seen on.
+    pub device: String,
+}
+
+/// Read the resolved entries in the neighbor table.
+///
+/// This is empty if the table isn't available, like when not running on
+/// Linux.
+pub async fn read() -> Vec<Neighbor> {
+    let Ok(table) = fs::read_to_string(ARP_TABLE).await else {
+        return Vec::new();
+    };
+
+    table.lines().skip(1).filter_map(parse).collect()
+}
+
+/// Parse a line of the neighbor table, like:
+///
+/// ```text
+/// 192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0
+/// ```
+fn parse(line: &str) -> Option<Neighbor> {
+    let mut columns = line.split_whitespace();
+
+    let address = columns.next()?.parse().ok()?;
+    let _hw_type = columns.next()?;
+    let flags = columns.next()?.strip_prefix("0x")?;
+    let Mac(mac) = columns.next()?.parse().ok()?;
+    let _mask = columns.next()?;
+    let device = columns.next()?;
+
+    if u32::from_str_radix(flags, 16).ok()? & ATF_COM == 0 || mac.is_nil() {
+        return None;
+    }
+
+    Some(Neighbor {
+        address,
+        mac,
+        device: device.to_owned(),
+    })
+}
+
+/// Read the addresses of neighbors which are reachable, which means that they
+/// have recently responded to ARP or neighbor discovery.
+///
+/// This is empty if the neighbor table can't be read, like when not running
+/// on Linux.
+pub async fn reachable() -> HashSet<IpAddr> {
+    let result = task::spawn_blocking(dump_reachable).await;
+
+    match result {
+        Ok(Ok(addresses)) => addresses,
+        Ok(Err(error)) => {
+            tracing::debug!("Reading neighbor table: {error}");
+            HashSet::new()
+        }
+        Err(error) => {
+            tracing::debug!("Reading neighbor table: {error}");
+            HashSet::new()
+        }
+    }
+}
+
+/// Dump the neighbor table over netlink, which unlike `/proc/net/arp` has the
+/// state of each entry and includes IPv6 neighbors.
+fn dump_reachable() -> io::Result<HashSet<IpAddr>> {
+    // SAFETY: Creating a socket has no preconditions, and on success we take
+    // ownership of the returned descriptor.
+    let socket = unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        );
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        OwnedFd::from_raw_fd(fd)
+    };
+
+    let mut request = [0u8; NLMSG_HEADER + NDMSG];
+    request[0..4].copy_from_slice(&((NLMSG_HEADER + NDMSG) as u32).to_ne_bytes());
+    request[4..6].copy_from_slice(&libc::RTM_GETNEIGH.to_ne_bytes());
+    request[6..8].copy_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+    request[8..12].copy_from_slice(&1u32.to_ne_bytes());
+    // The remaining fields are zero, which includes the address family of
+    // the neighbor message so that neighbors of all families are dumped.
+
+    // SAFETY: An all-zero address is valid, and refers to the kernel once the
+    // family is set.
+    let mut kernel = unsafe { mem::zeroed::<libc::sockaddr_nl>() };
+    kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+
+    // SAFETY: The buffer and address are valid for the duration of the call.
+    let sent = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            request.as_ptr().cast(),
+            request.len(),
+            0,
+            (&raw const kernel).cast(),
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut out = HashSet::new();
+    let mut buf = vec![0u8; 32 * 1024];
+
+    loop {
+        // SAFETY: The buffer is valid for writes of its length.
+        let n = unsafe { libc::recv(socket.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+
+        let Ok(n) = usize::try_from(n) else {
+            return Err(io::Error::last_os_error());
+        };
+
+        let mut rest = &buf[..n];
+
+        while rest.len() >= NLMSG_HEADER {
+            let len = u32::from_ne_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = u16::from_ne_bytes([rest[4], rest[5]]);
+
+            if len < NLMSG_HEADER || len > rest.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "truncated netlink message",
+                ));
+            }
+
+            match i32::from(kind) {
+                libc::NLMSG_DONE => return Ok(out),
+                libc::NLMSG_ERROR => {
+                    let code = rest
+                        .get(NLMSG_HEADER..NLMSG_HEADER + 4)
+                        .map_or(0, |b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+                    return Err(io::Error::from_raw_os_error(-code));
+                }
+                _ if kind == libc::RTM_NEWNEIGH => {
+                    if let Some(address) = parse_reachable(&rest[NLMSG_HEADER..len]) {
+                        out.insert(address);
+                    }
+                }
+                _ => {}
+            }
+
+            rest = rest.get(len.next_multiple_of(4)..).unwrap_or_default();
+        }
+    }
+}
+
+/// Parse a neighbor message, returning its address if it's reachable.
+fn parse_reachable(message: &[u8]) -> Option<IpAddr> {
+    let header = message.get(..NDMSG)?;
+    let family = i32::from(header[0]);
+    let state = u16::from_ne_bytes([header[8], header[9]]);
+
+    if state & libc::NUD_REACHABLE == 0 {
+        return None;
+    }
+
+    let mut attributes = &message[NDMSG..];
+
+    while attributes.len() >= 4 {
+        let len = usize::from(u16::from_ne_bytes([attributes[0], attributes[1]]));
+        let kind = u16::from_ne_bytes([attributes[2], attributes[3]]);
+        let data = attributes.get(4..len)?;
+
+        if kind == libc::NDA_DST {
+            return match (family, data.len()) {
+                (libc::AF_INET, 4) => Some(IpAddr::V4(Ipv4Addr::new(
+                    data[0], data[1], data[2], data[3],
+                ))),
+                (libc::AF_INET6, 16) => {
+                    Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)))
+                }
+                _ => None,
+            };
+        }
+
+        attributes = attributes
+            .get(len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+
+    None
+}
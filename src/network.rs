@@ -1,65 +1,125 @@
-use core::fmt;
-use core::net::IpAddr;
-use core::time::Duration;
+use core::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use axum::Router;
-use axum::extract::{OriginalUri, Query, State};
+use axum::extract::{ConnectInfo, OriginalUri, Path, Query, State};
 use axum::http::uri::Builder;
-use axum::response::{Html, Redirect};
+use axum::http::{HeaderMap, header};
+use axum::response::{Html, IntoResponse, Redirect, Response};
 use axum::routing::{get, post};
 use axum_extra::extract::Form;
+use macaddr::MacAddr6;
 use serde::{Deserialize, Serialize};
 use tokio::time::Instant;
 use uuid::Uuid;
 
+use crate::admin;
+use crate::audit;
+use crate::checks;
+use crate::config::{AddressFamily, Config, HostKind, SegmentConfig, SloConfig};
+use crate::disappeared;
+use crate::discovery;
 use crate::embed::Base64;
+use crate::events;
+use crate::history::History;
 use crate::hosts;
-use crate::ping_loop;
+use crate::interfaces::{self, Interface};
+use crate::mac::Mac;
+use crate::neighbors::{self, Neighbor};
+use crate::ping_loop::{self, HostStatus, PowerState};
+use crate::preferences::{self, Density, Preferences, Sort, Theme};
+use crate::probe;
+use crate::report;
+use crate::scripting;
 use crate::showcase;
-use crate::utils::Templates;
-use crate::wake_on_lan::{BroadcastSocket, MagicPacket};
+use crate::slo;
+use crate::snmp;
+use crate::utils::{self, Templates, millis};
+use crate::vitals;
+use crate::wake_on_lan;
+use crate::wake_queue;
 use crate::{Error, home};
 
 struct S {
     prefix: &'static str,
     ping_state: ping_loop::State,
+    checks: checks::State,
     templates: Templates,
     hosts: hosts::State,
     showcase: showcase::Helper,
     home: home::HomePage,
-    socket: BroadcastSocket,
+    wake_queue: wake_queue::State,
+    slo: slo::State,
+    snmp: snmp::State,
+    probe: probe::State,
+    events: events::Log,
+    discovery: discovery::State,
+    disappeared: disappeared::State,
+    audit: audit::Log,
+    history: History,
+    scripting: scripting::State,
+    keys: preferences::Keys,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn router(
     ping_state: ping_loop::State,
+    checks: checks::State,
     prefix: &'static str,
     templates: Templates,
     hosts: hosts::State,
     showcase: showcase::Helper,
     home: home::Home,
-) -> Result<Router> {
+    wake_queue: wake_queue::State,
+    slo: slo::State,
+    snmp: snmp::State,
+    probe: probe::State,
+    events: events::Log,
+    discovery: discovery::State,
+    disappeared: disappeared::State,
+    audit: audit::Log,
+    history: History,
+    scripting: scripting::State,
+) -> Router {
     let home = home.build().await;
-    let socket = BroadcastSocket::bind()
-        .await
-        .context("binding broadcast socket")?;
 
-    let router = Router::new()
+    Router::new()
         .route("/", get(entry))
         .route("/wake", post(wake))
+        .route("/wake-mac", post(wake_mac))
+        .route("/probe", post(start_probe))
+        .route("/acknowledge", post(acknowledge))
+        .route("/mark-suspicious", post(mark_suspicious))
+        .route("/unmark-suspicious", post(unmark_suspicious))
+        .route("/interfaces", get(list_interfaces))
+        .route("/timeline", get(timeline))
+        .route("/report", get(report))
+        .route("/report.md", get(report_markdown))
+        .route("/preferences", get(update_preferences))
+        .route("/{segment}", get(segment))
         .with_state(Arc::new(S {
             ping_state,
+            checks,
             prefix,
             templates,
             hosts,
             showcase,
             home,
-            socket,
-        }));
-
-    Ok(router)
+            wake_queue,
+            slo,
+            snmp,
+            probe,
+            events,
+            discovery,
+            disappeared,
+            audit,
+            history,
+            scripting,
+            keys: preferences::Keys::new(),
+        }))
 }
 
 #[derive(Deserialize)]
@@ -68,23 +128,103 @@ struct Network {
     woke: Option<Uuid>,
     #[serde(default)]
     error: Option<String>,
+    /// MAC address which a magic packet was sent to.
+    #[serde(default)]
+    woke_mac: Option<String>,
+    /// Host for which all addresses should be displayed.
+    #[serde(default)]
+    expand: Option<Uuid>,
+    /// Address family to display first, overriding configuration.
+    #[serde(default)]
+    prefer: Option<AddressFamily>,
+    /// Comma-separated columns to show, overriding preferences.
+    #[serde(default)]
+    columns: Option<String>,
+    /// Density of the page, overriding preferences.
+    #[serde(default)]
+    density: Option<Density>,
+    /// Alternative view of the page.
+    #[serde(default)]
+    view: Option<View>,
+}
+
+/// Alternative views of the network page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum View {
+    /// A table of hosts suitable for printing, like for audits of what's on
+    /// the network.
+    Report,
 }
 
-// basic handler that responds with a static string
 async fn entry(
     State(state): State<Arc<S>>,
+    headers: HeaderMap,
     Query(query): Query<Network>,
+) -> Result<Html<String>, Error> {
+    let key = state.keys.key(&*state.hosts.config().await);
+    let preferences = preferences::read(&headers, key);
+    render(&state, query, None, &preferences).await
+}
+
+/// The network page restricted to the hosts of a segment.
+async fn segment(
+    State(state): State<Arc<S>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<Network>,
+) -> Result<Response, Error> {
+    let config = state.hosts.config().await;
+
+    let Some(segment) = config.segments.get(&name) else {
+        return Err(Error::not_found());
+    };
+
+    if let Some(password) = &segment.password {
+        let username = segment
+            .username
+            .as_deref()
+            .unwrap_or(SegmentConfig::DEFAULT_USERNAME);
+
+        if !admin::is_authorized(&headers, username, password) {
+            return Ok(admin::unauthorized(&format!("wolo {name}")));
+        }
+    }
+
+    let preferences = preferences::read(&headers, state.keys.key(&config));
+    let page = render(&state, query, Some(&name), &preferences).await?;
+    Ok(page.into_response())
+}
+
+/// Render the network page, either with all hosts or the hosts of a segment.
+///
+/// Hosts which belong to a segment with a password are only shown on the page
+/// of that segment.
+async fn render(
+    state: &S,
+    query: Network,
+    segment: Option<&str>,
+    preferences: &Preferences,
 ) -> Result<Html<String>, Error> {
     let S {
         prefix,
         ref templates,
         ref hosts,
         ref ping_state,
+        ref checks,
         ref showcase,
         ref home,
         ..
     } = *state;
 
+    #[derive(Serialize)]
+    struct Segment {
+        name: String,
+        title: String,
+        /// Whether a password is required to view the segment.
+        protected: bool,
+    }
+
     #[derive(Serialize)]
     struct PingError {
         error: String,
@@ -92,7 +232,11 @@ async fn entry(
         address: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         host: Option<String>,
-        age: String,
+        /// The kind of error if the host name couldn't be resolved.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resolve: Option<String>,
+        /// Time since the ping was attempted in milliseconds.
+        age_ms: f64,
     }
 
     #[derive(Serialize)]
@@ -105,25 +249,205 @@ async fn entry(
         target: IpAddr,
         source: IpAddr,
         dest: IpAddr,
-        rtt: String,
-        age: String,
+        /// Round trip time in milliseconds.
+        rtt_ms: f64,
+        /// Time since the ping in milliseconds.
+        age_ms: f64,
         checksum: u16,
         expected_checksum: u16,
     }
 
+    #[derive(Serialize)]
+    struct Check {
+        class: &'static str,
+        kind: String,
+        name: Option<String>,
+        port: Option<u16>,
+        message: String,
+        /// Time the check took in milliseconds.
+        duration_ms: f64,
+        /// Time since the check in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Slo {
+        breached: bool,
+        /// Share of answered pings as a percentage.
+        availability: Option<String>,
+        availability_objective: Option<String>,
+        availability_breached: bool,
+        /// Round trip time at the percentile in milliseconds.
+        rtt_ms: Option<f64>,
+        rtt_objective_ms: Option<f64>,
+        rtt_breached: bool,
+        percentile: u8,
+        /// The window objectives are evaluated over in milliseconds.
+        window_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Snmp {
+        name: Option<String>,
+        /// How long the host has been up in milliseconds.
+        uptime_ms: Option<f64>,
+        /// The most recent bandwidth in bytes per second.
+        bandwidth: Option<f64>,
+        /// Points of a line showing recent bandwidth.
+        sparkline: String,
+        error: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Probe {
+        /// Whether probing is configured for the host.
+        enabled: bool,
+        running: bool,
+        /// Throughput of the most recent probe in bytes per second.
+        throughput: Option<f64>,
+        /// Round trip times before and during the probe in milliseconds.
+        idle_rtt_ms: Option<f64>,
+        loaded_rtt_ms: Option<f64>,
+        /// Time since the most recent probe finished, or since the running
+        /// probe started in milliseconds.
+        age_ms: Option<f64>,
+        error: Option<String>,
+    }
+
     #[derive(Serialize)]
     struct Pending {
         errors: Vec<PingError>,
         results: Vec<PingResult>,
+        /// Number of results which are collapsed.
+        hidden: usize,
     }
 
     #[derive(Serialize)]
     struct Host {
         id: Uuid,
+        status: HostStatus,
+        /// Whether the details of the host are collapsed.
+        collapsed: bool,
         just_woke: bool,
+        /// Icon for the kind of device.
+        icon: &'static str,
+        /// Estimate of whether the host is sleeping or gone if it isn't up.
+        power: PowerState,
+        /// Whether the host can be woken, which isn't offered while it's up.
+        wake: bool,
+        /// Whether the host can't be woken because of how it's connected.
+        unwakeable: bool,
+        /// Hints shown next to the wake button.
+        hints: Vec<WakeHint>,
+        /// Whether quiet hours are in effect for the host.
+        quiet: bool,
+        /// Progress of waking the host, if it's being woken.
+        waking: Option<Waking>,
+        /// Outcomes of past wakes of the host, if it has been woken.
+        wakes: Option<Wakes>,
+        /// The name of the dependency which is down, if the host is
+        /// unreachable because of it.
+        dependency: Option<String>,
+        /// Whether the host only responds to ARP and not to pings.
+        arp_only: bool,
+        /// Share of recent pings which went unanswered.
+        loss: Option<String>,
+        /// Time since the host last replied in milliseconds.
+        last_seen_ms: Option<f64>,
         names: Vec<String>,
-        mac: Vec<String>,
+        mac: Vec<MacAddr6>,
         pending: Option<Pending>,
+        checks: Vec<Check>,
+        /// Service level objectives of the host, once evaluated.
+        slo: Option<Slo>,
+        /// State polled over SNMP, once polled.
+        snmp: Option<Snmp>,
+        /// Throughput probing of the host.
+        probe: Probe,
+        /// Link which displays all addresses of the host.
+        expand: String,
+        /// Link to the web interface of the host.
+        url: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Device {
+        mac: MacAddr6,
+        address: IpAddr,
+        device: String,
+        /// Time since the device was first seen in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Disappeared {
+        name: String,
+        /// The status of the host when it disappeared.
+        status: HostStatus,
+        class: Option<&'static str>,
+        /// Time since the host disappeared in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Suspicious {
+        mac: MacAddr6,
+        /// Where the device is in the neighbor table, if it's present.
+        address: Option<IpAddr>,
+        device: Option<String>,
+        /// Time since the device was marked in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Column {
+        name: String,
+        shown: bool,
+    }
+
+    /// Vitals of this machine formatted for display.
+    #[derive(Serialize)]
+    struct Vitals {
+        load: Option<String>,
+        memory: Option<String>,
+        history: Option<String>,
+        temperature: Option<String>,
+        file_descriptors: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct ReportHost {
+        names: Vec<String>,
+        status: HostStatus,
+        mac: Vec<MacAddr6>,
+        /// Addresses the host was most recently pinged at.
+        addresses: Vec<IpAddr>,
+        loss: Option<String>,
+        last_seen_ms: Option<f64>,
+    }
+
+    /// Number of hosts by status.
+    #[derive(Default, Serialize)]
+    struct Summary {
+        hosts: usize,
+        up: usize,
+        down: usize,
+        unreachable: usize,
+        unknown: usize,
+    }
+
+    #[derive(Serialize)]
+    struct Report {
+        title: String,
+        /// When the report was generated in local time.
+        generated: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        segment: Option<Segment>,
+        summary: Summary,
+        hosts: Vec<ReportHost>,
+        devices: Vec<Device>,
+        suspicious: Vec<Suspicious>,
+        disappeared: Vec<Disappeared>,
     }
 
     #[derive(Serialize)]
@@ -132,45 +456,266 @@ async fn entry(
         title: String,
         prefix: &'static str,
         hosts: Vec<Host>,
+        /// New devices which haven't been acknowledged.
+        devices: Vec<Device>,
+        /// Devices which have been marked as suspicious.
+        suspicious: Vec<Suspicious>,
+        /// Hosts which recently disappeared.
+        disappeared: Vec<Disappeared>,
+        /// The segment being viewed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        segment: Option<Segment>,
+        /// Segments which can be viewed separately.
+        segments: Vec<Segment>,
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<&'static str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        woke_mac: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        prefer: Option<String>,
+        sort: Sort,
+        theme: Theme,
+        density: Density,
+        /// Columns of ping results which can be shown or hidden.
+        columns: Vec<Column>,
+        /// Names of the columns which are shown.
+        shown: Vec<String>,
+        /// How often the page is refreshed in milliseconds, or 0 if it isn't.
+        refresh_ms: u32,
+        /// The refresh intervals which can be picked in seconds.
+        refresh_intervals: &'static [u32],
+        vitals: Vitals,
+        /// Why nothing is pinged, if ICMP sockets couldn't be created.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pinging_unavailable: Option<String>,
     }
 
+    let config = hosts.config().await;
+    let wakes = state.wake_queue.wakes().await;
+    let wake_stats = state.wake_queue.stats().await;
+
+    let shown = match query.columns.as_deref() {
+        Some(columns) => preferences::Column::parse_list(columns).map_err(Error::bad_request)?,
+        None => preferences.columns.clone(),
+    };
+
+    let neighbors = neighbors::read()
+        .await
+        .into_iter()
+        .filter(|n| interfaces::is_selected(&config.interfaces, &n.device))
+        .collect::<Vec<_>>();
+
+    let interfaces = interfaces::read().await;
+    let v = vitals::read(config.history.path.as_deref()).await;
+
     let mut showcase = showcase.lock().await;
 
-    let hosts = hosts.hosts().await;
+    let hosts = hosts.hosts();
     let pinged = ping_state.pinged.lock().await;
+    let statuses = ping_loop::statuses(&hosts, &pinged);
+    let check_results = checks.results.lock().await;
+    let slo_results = state.slo.results.lock().await;
+    let snmp_results = state.snmp.results.lock().await;
+    let probe_results = state.probe.results.lock().await;
 
     let mut context = Context {
         hash: crate::embed::hash(),
-        title: home.title.clone().into_owned(),
+        title: showcase.text(&home.title),
         prefix,
         hosts: Vec::new(),
+        devices: Vec::new(),
+        suspicious: Vec::new(),
+        disappeared: Vec::new(),
+        segment: None,
+        segments: Vec::new(),
         error: match query.error.as_deref() {
             Some("unknown-host") => Some("Unknown host specified"),
+            Some("wake-disabled") => Some("Waking is disabled for this host"),
+            Some("wake-cancelled") => Some("Waking was cancelled by a script"),
+            Some("invalid-mac") => Some("Invalid MAC address"),
+            Some("invalid-target") => Some("Invalid broadcast address"),
+            Some("probe-unavailable") => Some("Probing is unavailable for this host"),
+            Some("unknown-device") => Some("Unknown device specified"),
             _ => None,
         },
+        woke_mac: query.woke_mac,
+        prefer: query.prefer.map(|p| p.to_string()),
+        sort: preferences.sort,
+        theme: preferences.theme,
+        density: query.density.unwrap_or(preferences.density),
+        columns: preferences::Column::ALL
+            .iter()
+            .map(|c| Column {
+                name: c.to_string(),
+                shown: shown.contains(c),
+            })
+            .collect(),
+        shown: shown.iter().map(|c| c.to_string()).collect(),
+        refresh_ms: preferences.refresh.saturating_mul(1000),
+        refresh_intervals: &preferences::REFRESH_INTERVALS,
+        vitals: Vitals {
+            load: v.load.map(|[a, b, c]| format!("{a:.2} {b:.2} {c:.2}")),
+            memory: v.memory_total.map(|total| {
+                let used = total.saturating_sub(v.memory_available.unwrap_or(total));
+                format!("{} / {}", utils::size(used), utils::size(total))
+            }),
+            history: v.history.map(|h| match h.available {
+                Some(available) => {
+                    format!("{} ({} free)", utils::size(h.size), utils::size(available))
+                }
+                None => utils::size(h.size).to_string(),
+            }),
+            temperature: v.temperature.map(|t| format!("{t:.1} °C")),
+            file_descriptors: v.file_descriptors.map(|n| {
+                let mut s = n.to_string();
+
+                if let Some(limit) = v.file_descriptor_limit {
+                    s.push_str(&format!(" / {limit}"));
+                }
+
+                if let Some(sockets) = v.sockets {
+                    s.push_str(&format!(" ({sockets} sockets)"));
+                }
+
+                s
+            }),
+        },
+        pinging_unavailable: ping_state.unavailable.as_deref().map(str::to_owned),
+    };
+
+    // Preserve the preferred address family when expanding hosts.
+    let prefer_query = match query.prefer {
+        Some(prefer) => format!("&prefer={prefer}"),
+        None => String::new(),
     };
 
     let now = Instant::now();
+    let wall = SystemTime::now();
+    let time = utils::time_of_day();
+
+    let current = segment.and_then(|name| Some((name, config.segments.get(name)?)));
+
+    for (name, s) in &config.segments {
+        let segment = Segment {
+            name: name.clone(),
+            title: showcase.text(s.title.as_deref().unwrap_or(name)),
+            protected: s.password.is_some(),
+        };
+
+        if current.is_some_and(|(current, _)| current == name) {
+            context.segment = Some(segment);
+        } else {
+            context.segments.push(segment);
+        }
+    }
+
+    // Devices aren't tagged, so they are only included in segments by subnet.
+    let device_visible = |address: Ipv4Addr| match current {
+        Some((_, s)) => s.subnets.iter().any(|n| n.contains(IpAddr::V4(address))),
+        None => true,
+    };
+
+    for d in state.discovery.unacknowledged.lock().await.iter() {
+        if !device_visible(d.address) {
+            continue;
+        }
+
+        context.devices.push(Device {
+            mac: showcase.local_mac(d.mac),
+            address: showcase.local_ip(IpAddr::V4(d.address)),
+            device: d.device.clone(),
+            age_ms: wall.duration_since(d.first_seen).map_or(0.0, millis),
+        });
+    }
+
+    for (&mac, &marked) in state.discovery.suspicious.lock().await.iter() {
+        let neighbor = neighbors.iter().find(|n| n.mac == mac);
+
+        if current.is_some() && !neighbor.is_some_and(|n| device_visible(n.address)) {
+            continue;
+        }
+
+        context.suspicious.push(Suspicious {
+            mac: showcase.local_mac(mac),
+            address: neighbor.map(|n| showcase.local_ip(IpAddr::V4(n.address))),
+            device: neighbor.map(|n| n.device.clone()),
+            age_ms: wall.duration_since(marked).map_or(0.0, millis),
+        });
+    }
+
+    // What hosts which disappeared belonged to isn't known, so they are only
+    // listed when all hosts are.
+    if current.is_none() {
+        for h in state.disappeared.hosts.lock().await.iter().rev() {
+            context.disappeared.push(Disappeared {
+                name: showcase.text(&h.name),
+                status: h.status,
+                class: match h.status {
+                    HostStatus::Up => Some("success"),
+                    HostStatus::Down | HostStatus::Unreachable => Some("error"),
+                    HostStatus::Unknown => None,
+                },
+                age_ms: wall.duration_since(h.disappeared).map_or(0.0, millis),
+            });
+        }
+    }
 
     for host in hosts.iter() {
-        let pending = match pinged.get(&host.id) {
+        let visible = match current {
+            Some((_, s)) => in_segment(s, host, pinged.get(&host.id)),
+            None => !config
+                .segments
+                .values()
+                .any(|s| s.password.is_some() && in_segment(s, host, pinged.get(&host.id))),
+        };
+
+        if !visible {
+            continue;
+        }
+
+        let dependency = statuses
+            .get(&host.id)
+            .and_then(|s| s.dependency)
+            .and_then(|id| hosts.iter().find(|h| h.id == id))
+            .and_then(|h| Some(showcase.host_name(h, h.names().next()?)));
+
+        // Pings of unreachable hosts are hidden, since they only fail
+        // because of the dependency.
+        let pending = match pinged.get(&host.id).filter(|_| dependency.is_none()) {
             Some(pending) => {
                 let mut errors = Vec::with_capacity(pending.errors.len());
 
                 for e in &pending.errors {
                     errors.push(PingError {
                         error: e.error.clone(),
-                        address: e.kind.as_address().map(|a| showcase.ip(a).to_string()),
-                        host: e.kind.as_host().map(|n| showcase.host_name(host.id, n)),
-                        age: duration(now.duration_since(e.sampled)).to_string(),
+                        address: e
+                            .kind
+                            .as_address()
+                            .map(|a| showcase.ip(host, a).to_string()),
+                        host: e.kind.as_host().map(|n| showcase.host_name(host, n)),
+                        resolve: e.kind.as_resolve().map(|k| k.to_string()),
+                        age_ms: millis(now.duration_since(e.sampled)),
                     });
                 }
 
-                let mut results = Vec::with_capacity(pending.results.len());
+                let mut ordered = pending.results.iter().collect::<Vec<_>>();
+
+                if let Some(prefer) = query.prefer.or(host.display.prefer) {
+                    ordered.sort_by_key(|r| !prefer.matches(r.target));
+                }
+
+                let hidden = match host.display.addresses {
+                    Some(limit) if query.expand != Some(host.id) => {
+                        let hidden = ordered.len().saturating_sub(limit);
+                        ordered.truncate(limit);
+                        hidden
+                    }
+                    _ => 0,
+                };
+
+                let mut results = Vec::with_capacity(ordered.len());
 
-                for r in &pending.results {
+                for r in ordered {
                     let code = match r.outcome {
                         lib::Outcome::V4(lib::icmp::v4::Type::UNREACHABLE) => {
                             let code = lib::icmp::v4::UnreachableCode::new(r.code);
@@ -190,7 +735,9 @@ async fn entry(
                     };
 
                     results.push(PingResult {
-                        class: if r.outcome.is_echo_reply() {
+                        class: if r.is_slow(host.ping.slow) {
+                            "slow"
+                        } else if r.outcome.is_echo_reply() {
                             "success"
                         } else {
                             "error"
@@ -199,102 +746,642 @@ async fn entry(
                         outcome: r.outcome.to_string(),
                         sequence: r.sequence,
                         code,
-                        target: showcase.ip(r.target),
-                        source: showcase.ip(r.source),
-                        dest: showcase.ip(r.dest),
-                        rtt: duration(r.rtt).to_string(),
-                        age: duration(now.duration_since(r.sampled)).to_string(),
+                        target: showcase.ip(host, r.target),
+                        source: showcase.ip(host, r.source),
+                        dest: showcase.ip(host, r.dest),
+                        rtt_ms: millis(r.rtt),
+                        age_ms: millis(now.duration_since(r.sampled)),
                         checksum: r.checksum,
                         expected_checksum: r.expected_checksum,
                     });
                 }
 
-                Some(Pending { errors, results })
+                Some(Pending {
+                    errors,
+                    results,
+                    hidden,
+                })
             }
             None => None,
         };
 
+        let mut checks = Vec::new();
+
+        for r in check_results
+            .get(&host.id)
+            .into_iter()
+            .flat_map(|r| r.values())
+        {
+            let (class, message) = match &r.outcome {
+                Ok(message) => ("success", message.clone()),
+                Err(error) => ("error", error.clone()),
+            };
+
+            checks.push(Check {
+                class,
+                kind: r.check.kind.to_string(),
+                name: r.check.name.clone(),
+                port: (r.check.port != 0).then_some(r.check.port),
+                message,
+                duration_ms: millis(r.duration),
+                age_ms: millis(now.duration_since(r.sampled)),
+            });
+        }
+
+        let mut probe = Probe {
+            enabled: host.probe.port.is_some(),
+            running: false,
+            throughput: None,
+            idle_rtt_ms: None,
+            loaded_rtt_ms: None,
+            age_ms: None,
+            error: None,
+        };
+
+        match probe_results.get(&host.id) {
+            Some(probe::Status::Running { started }) => {
+                probe.running = true;
+                probe.age_ms = Some(millis(now.duration_since(*started)));
+            }
+            Some(probe::Status::Done(p)) => {
+                probe.throughput = Some(p.throughput());
+                probe.idle_rtt_ms = p.idle_rtt.map(millis);
+                probe.loaded_rtt_ms = p.loaded_rtt.map(millis);
+                probe.age_ms = wall.duration_since(p.sampled).ok().map(millis);
+            }
+            Some(probe::Status::Failed { error, sampled }) => {
+                probe.error = Some(error.clone());
+                probe.age_ms = wall.duration_since(*sampled).ok().map(millis);
+            }
+            None => {}
+        }
+
         let just_woke = query.woke.map(|id| id == host.id).unwrap_or_default();
 
+        let (hints, unwakeable) =
+            wake_hints(host, pinged.get(&host.id), &neighbors, &interfaces, now);
+
+        let stale = neighbors.iter().any(|n| host.macs.contains(&n.mac));
+        let power = PowerState::estimate(pinged.get(&host.id), stale, now);
+
         context.hosts.push(Host {
             id: host.id,
+            status: statuses
+                .get(&host.id)
+                .map_or(HostStatus::Unknown, |s| s.status),
+            collapsed: preferences.collapsed.contains(&host.id),
             just_woke,
-            names: host
-                .names()
-                .map(|n| showcase.host_name(host.id, n))
-                .collect(),
-            mac: host
-                .macs
+            icon: host.kind().map_or("💻", HostKind::icon),
+            power,
+            wake: host.wake.enabled != Some(false) && power != PowerState::Up,
+            unwakeable,
+            hints,
+            quiet: host.quiet_hours.contains(time),
+            waking: wakes
                 .iter()
-                .map(|m| showcase.mac(*m).to_string())
-                .collect(),
+                .find(|w| w.host == Some(host.id))
+                .map(|w| Waking {
+                    attempt: w.attempt,
+                    attempts: w.attempts,
+                }),
+            wakes: wake_stats.get(&host.id).map(|s| Wakes {
+                class: match s.woke {
+                    0 => "error",
+                    woke if woke == s.wakes => "success",
+                    _ => "slow",
+                },
+                wakes: s.wakes,
+                woke: s.woke,
+                rate: format!("{:.0}%", s.woke as f64 / s.wakes as f64 * 100.0),
+                time_to_wake_ms: s.average_time_to_wake().map(millis),
+            }),
+            dependency,
+            arp_only: pinged.get(&host.id).is_some_and(|p| p.is_arp_only()),
+            loss: pinged
+                .get(&host.id)
+                .and_then(|p| p.loss())
+                .map(|loss| format!("{:.0}%", loss * 100.0)),
+            last_seen_ms: pinged
+                .get(&host.id)
+                .and_then(|p| p.last_seen)
+                .map(|s| millis(now.saturating_duration_since(s))),
+            slo: slo_results.get(&host.id).map(|r| Slo {
+                breached: r.is_breached(),
+                availability: r.availability.map(|a| format!("{:.2}%", a * 100.0)),
+                availability_objective: r.slo.availability.map(|a| a.to_string()),
+                availability_breached: r.availability_breached,
+                rtt_ms: r.rtt.map(millis),
+                rtt_objective_ms: r.slo.rtt.map(millis),
+                rtt_breached: r.rtt_breached,
+                percentile: r.slo.percentile(),
+                window_ms: millis(r.slo.window.unwrap_or(SloConfig::DEFAULT_WINDOW)),
+            }),
+            snmp: snmp_results.get(&host.id).map(|s| Snmp {
+                name: s.name.as_deref().map(|n| showcase.host_name(host, n)),
+                uptime_ms: s.uptime.map(millis),
+                bandwidth: s.bandwidth.back().copied(),
+                sparkline: sparkline(s.bandwidth.iter().copied()),
+                error: s.error.clone(),
+            }),
+            names: host.names().map(|n| showcase.host_name(host, n)).collect(),
+            mac: host.macs.iter().map(|m| showcase.mac(host, *m)).collect(),
             pending,
+            checks,
+            probe,
+            expand: format!("?expand={}{prefer_query}#host-{}", host.id, host.id),
+            url: checks::web_ui(host, check_results.get(&host.id)).map(|u| showcase.path(&u)),
         });
     }
 
+    match preferences.sort {
+        Sort::Configured => {}
+        Sort::Name => context.hosts.sort_by(|a, b| a.names.cmp(&b.names)),
+        Sort::Status => context.hosts.sort_by_key(|h| match h.status {
+            HostStatus::Down => 0,
+            HostStatus::Unreachable => 1,
+            HostStatus::Unknown => 2,
+            HostStatus::Up => 3,
+        }),
+    }
+
+    if query.view == Some(View::Report) {
+        let mut summary = Summary {
+            hosts: context.hosts.len(),
+            ..Summary::default()
+        };
+
+        let mut hosts = Vec::with_capacity(context.hosts.len());
+
+        for h in context.hosts {
+            match h.status {
+                HostStatus::Up => summary.up += 1,
+                HostStatus::Down => summary.down += 1,
+                HostStatus::Unreachable => summary.unreachable += 1,
+                HostStatus::Unknown => summary.unknown += 1,
+            }
+
+            let mut addresses = h
+                .pending
+                .iter()
+                .flat_map(|p| p.results.iter().map(|r| r.target))
+                .collect::<Vec<_>>();
+
+            addresses.sort();
+            addresses.dedup();
+
+            hosts.push(ReportHost {
+                names: h.names,
+                status: h.status,
+                mac: h.mac,
+                addresses,
+                loss: h.loss,
+                last_seen_ms: h.last_seen_ms,
+            });
+        }
+
+        let report = Report {
+            title: context.title,
+            generated: utils::timestamp(),
+            segment: context.segment,
+            summary,
+            hosts,
+            devices: context.devices,
+            suspicious: context.suspicious,
+            disappeared: context.disappeared,
+        };
+
+        let o = templates.render("network_report.html", report)?;
+        return Ok(Html(o));
+    }
+
     let o = templates.render("network.html", context)?;
     Ok(Html(o))
 }
 
-fn duration(d: Duration) -> impl fmt::Display {
-    struct D(Duration);
+/// Test if a host belongs to a segment, either through its tags or any of
+/// its addresses.
+fn in_segment(
+    segment: &SegmentConfig,
+    host: &hosts::Host,
+    pinged: Option<&ping_loop::Pinged>,
+) -> bool {
+    let names = host.names().filter_map(|n| n.parse::<IpAddr>().ok());
+    let targets = pinged
+        .into_iter()
+        .flat_map(|p| p.results.iter().map(|r| r.target));
+    segment.contains(&host.tags, names.chain(targets))
+}
 
-    impl fmt::Display for D {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            let mut secs = self.0.as_secs();
+/// The page a form was submitted from, which is either the network page or
+/// the page of a segment.
+fn page(prefix: &str, config: &Config, segment: Option<&str>) -> String {
+    match segment {
+        Some(segment) if config.segments.contains_key(segment) => format!("{prefix}/{segment}"),
+        _ => prefix.to_owned(),
+    }
+}
 
-            if secs >= 86400 {
-                write!(f, "{}d", secs / 86400)?;
-                secs %= 86400;
-            }
+#[derive(Deserialize)]
+struct UpdatePreferences {
+    #[serde(default)]
+    sort: Option<Sort>,
+    /// How often the page is refreshed in seconds.
+    #[serde(default)]
+    refresh: Option<u32>,
+    #[serde(default)]
+    theme: Option<Theme>,
+    #[serde(default)]
+    density: Option<Density>,
+    /// Column of ping results which is shown or hidden.
+    #[serde(default)]
+    column: Option<String>,
+    /// Host whose details are collapsed or expanded.
+    #[serde(default)]
+    toggle: Option<Uuid>,
+    /// Segment the preferences were changed from.
+    #[serde(default)]
+    segment: Option<String>,
+}
 
-            if secs >= 3600 {
-                write!(f, "{}H", secs / 3600)?;
-                secs %= 3600;
-            }
+/// Change the preferences stored in the cookie of the browser, and return to
+/// the page they were changed from.
+///
+/// This doesn't change anything on the server, so it's a `GET` request which
+/// also works in read-only mode.
+async fn update_preferences(
+    State(state): State<Arc<S>>,
+    headers: HeaderMap,
+    Query(update): Query<UpdatePreferences>,
+) -> Result<Response, Error> {
+    let config = state.hosts.config().await;
+    let key = state.keys.key(&config);
+    let mut preferences = preferences::read(&headers, key.clone());
 
-            if secs >= 60 {
-                write!(f, "{}m", secs / 60)?;
-                secs %= 60;
-            }
+    if let Some(sort) = update.sort {
+        preferences.sort = sort;
+    }
 
-            if secs > 0 {
-                return write!(f, "{}s", secs);
-            }
+    if let Some(refresh) = update.refresh {
+        if !preferences::REFRESH_INTERVALS.contains(&refresh) {
+            return Err(Error::bad_request(format_args!(
+                "{refresh}: unsupported refresh interval"
+            )));
+        }
 
-            let nanos = self.0.subsec_nanos();
+        preferences.refresh = refresh;
+    }
 
-            if nanos >= 1_000_000 {
-                let millis = nanos / 1_000_000;
-                return write!(f, "{millis}ms");
-            }
+    if let Some(theme) = update.theme {
+        preferences.theme = theme;
+    }
+
+    if let Some(density) = update.density {
+        preferences.density = density;
+    }
+
+    if let Some(column) = &update.column {
+        let column = column
+            .parse::<preferences::Column>()
+            .map_err(Error::bad_request)?;
 
-            let micros = nanos / 1_000;
-            write!(f, "{micros}µs")
+        if !preferences.columns.remove(&column) {
+            preferences.columns.insert(column);
         }
     }
 
-    D(d)
+    let mut page = page(state.prefix, &config, update.segment.as_deref());
+
+    if let Some(id) = update.toggle {
+        if !preferences.collapsed.remove(&id) {
+            preferences.collapsed.insert(id);
+        }
+
+        // Forget hosts which no longer exist.
+        let hosts = state.hosts.hosts();
+        preferences
+            .collapsed
+            .retain(|id| hosts.iter().any(|h| h.id == *id));
+
+        page = format!("{page}#host-{id}");
+    }
+
+    let jar = preferences::write(&preferences, key);
+    Ok((jar, Redirect::to(&page)).into_response())
+}
+
+async fn list_interfaces(State(state): State<Arc<S>>) -> Result<Html<String>, Error> {
+    #[derive(Serialize)]
+    struct Address {
+        address: IpAddr,
+        prefix: u32,
+        broadcast: Option<IpAddr>,
+    }
+
+    #[derive(Serialize)]
+    struct Interface {
+        name: String,
+        mac: Option<MacAddr6>,
+        state: String,
+        up: bool,
+        wireless: bool,
+        wakeup: Option<bool>,
+        /// Whether the interface is used for discovery and Wake-on-LAN.
+        selected: bool,
+        addresses: Vec<Address>,
+    }
+
+    #[derive(Serialize)]
+    struct Context {
+        hash: Base64,
+        title: String,
+        prefix: &'static str,
+        /// Whether all interfaces are selected.
+        all: bool,
+        interfaces: Vec<Interface>,
+    }
+
+    let config = state.hosts.config().await;
+    let mut showcase = state.showcase.lock().await;
+
+    let interfaces = interfaces::read()
+        .await
+        .into_iter()
+        .map(|i| Interface {
+            selected: interfaces::is_selected(&config.interfaces, &i.name),
+            up: i.is_up(),
+            mac: i.mac.map(|m| showcase.local_mac(m)),
+            addresses: i
+                .addresses
+                .iter()
+                .map(|a| Address {
+                    address: showcase.local_ip(a.address),
+                    prefix: a.prefix,
+                    broadcast: a.broadcast.map(|b| showcase.local_ip(IpAddr::V4(b))),
+                })
+                .collect(),
+            name: i.name,
+            state: i.state,
+            wireless: i.wireless,
+            wakeup: i.wakeup,
+        })
+        .collect();
+
+    let context = Context {
+        hash: crate::embed::hash(),
+        title: showcase.text(&state.home.title),
+        prefix: state.prefix,
+        all: config.interfaces.is_empty(),
+        interfaces,
+    };
+
+    let o = state.templates.render("interfaces.html", context)?;
+    Ok(Html(o))
+}
+
+#[derive(Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    host: Option<Uuid>,
+    #[serde(default)]
+    kind: Option<events::Kind>,
+}
+
+async fn timeline(
+    State(state): State<Arc<S>>,
+    Query(query): Query<Timeline>,
+) -> Result<Html<String>, Error> {
+    #[derive(Serialize)]
+    struct Event {
+        kind: events::Kind,
+        host: Option<Uuid>,
+        name: Option<String>,
+        message: String,
+        /// Time since the event in milliseconds.
+        age_ms: f64,
+    }
+
+    #[derive(Serialize)]
+    struct Context {
+        hash: Base64,
+        title: String,
+        prefix: &'static str,
+        kinds: [events::Kind; 7],
+        kind: Option<events::Kind>,
+        host: Option<Uuid>,
+        /// The name of the host events are filtered by.
+        host_name: Option<String>,
+        /// Query string which preserves the host filter.
+        host_query: String,
+        events: Vec<Event>,
+    }
+
+    let hosts = state.hosts.hosts();
+    let mut showcase = state.showcase.lock().await;
+    let now = SystemTime::now();
+
+    let mut name_of = |id: Uuid| {
+        let host = hosts.iter().find(|h| h.id == id)?;
+        Some(showcase.host_name(host, host.names().next()?))
+    };
+
+    let host_name = query.host.and_then(&mut name_of);
+    let mut events = Vec::new();
+
+    for e in state.events.events().await {
+        if query.host.is_some_and(|id| e.host != Some(id))
+            || query.kind.is_some_and(|kind| e.kind != kind)
+        {
+            continue;
+        }
+
+        let name = e.host.and_then(&mut name_of);
+
+        events.push(Event {
+            kind: e.kind,
+            host: e.host,
+            name,
+            message: e.message,
+            age_ms: now.duration_since(e.sampled).map_or(0.0, millis),
+        });
+    }
+
+    // Messages of events which don't concern a host can mention anything,
+    // like names of hosts added to configuration.
+    for e in &mut events {
+        if e.host.is_none() {
+            e.message = showcase.text(&e.message);
+        }
+    }
+
+    let context = Context {
+        hash: crate::embed::hash(),
+        title: showcase.text(&state.home.title),
+        prefix: state.prefix,
+        kinds: events::Kind::ALL,
+        kind: query.kind,
+        host: query.host,
+        host_name,
+        host_query: match query.host {
+            Some(host) => format!("host={host}&"),
+            None => String::new(),
+        },
+        events,
+    };
+
+    let o = state.templates.render("timeline.html", context)?;
+    Ok(Html(o))
+}
+
+/// A hint about whether waking a host is plausible.
+/// The summary report of the configured period, ending now.
+async fn report(State(state): State<Arc<S>>) -> Result<Html<String>, Error> {
+    let period = state.hosts.config().await.report.interval();
+    let report = report::build(
+        &state.hosts,
+        &state.history,
+        &state.events,
+        &state.showcase,
+        period,
+    )
+    .await?;
+
+    Ok(Html(report.html(&state.templates)?))
+}
+
+/// The summary report as markdown.
+async fn report_markdown(State(state): State<Arc<S>>) -> Result<Response, Error> {
+    let period = state.hosts.config().await.report.interval();
+    let report = report::build(
+        &state.hosts,
+        &state.history,
+        &state.events,
+        &state.showcase,
+        period,
+    )
+    .await?;
+
+    let headers = [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")];
+    Ok((headers, report.markdown()).into_response())
+}
+
+#[derive(Serialize)]
+struct WakeHint {
+    /// The untranslated message, where `{device}` and `{age}` are replaced.
+    message: &'static str,
+    device: String,
+    /// Time since the host was seen in milliseconds.
+    age_ms: f64,
+}
+
+/// Collect hints about whether waking a host is plausible, and whether it's
+/// known to not be wakeable at all.
+fn wake_hints(
+    host: &hosts::Host,
+    pinged: Option<&ping_loop::Pinged>,
+    neighbors: &[Neighbor],
+    interfaces: &[Interface],
+    now: Instant,
+) -> (Vec<WakeHint>, bool) {
+    let mut hints = Vec::new();
+    let mut unwakeable = false;
+
+    if host.macs.is_empty() {
+        return (hints, unwakeable);
+    }
+
+    let hint = |message, device: &str, age_ms| WakeHint {
+        message,
+        device: device.to_owned(),
+        age_ms,
+    };
+
+    // The host is the machine wolo runs on, so we know how it's connected.
+    for i in interfaces {
+        if !i.mac.is_some_and(|mac| host.macs.contains(&mac)) {
+            continue;
+        }
+
+        if i.wireless {
+            hints.push(hint(
+                "{device} is wireless and can't be woken",
+                &i.name,
+                0.0,
+            ));
+            unwakeable = true;
+        } else if i.wakeup == Some(false) {
+            hints.push(hint("Wake-on-LAN is disabled for {device}", &i.name, 0.0));
+        }
+    }
+
+    if pinged.is_some_and(|p| p.status() == HostStatus::Up) {
+        return (hints, unwakeable);
+    }
+
+    let device = neighbors
+        .iter()
+        .find(|n| host.macs.contains(&n.mac))
+        .map(|n| n.device.as_str());
+
+    let last_seen = pinged
+        .and_then(|p| p.last_seen)
+        .map(|seen| millis(now.duration_since(seen)));
+
+    hints.push(match (last_seen, device) {
+        (Some(age_ms), Some(device)) => hint("Last seen {age} ago on {device}", device, age_ms),
+        (Some(age_ms), None) => hint("Last seen {age} ago", "", age_ms),
+        (None, Some(device)) => hint("Seen on {device}", device, 0.0),
+        (None, None) => hint("Not seen since wolo started", "", 0.0),
+    });
+
+    (hints, unwakeable)
+}
+
+/// Progress of waking a host.
+#[derive(Serialize)]
+struct Waking {
+    attempt: u32,
+    attempts: u32,
+}
+
+/// Outcomes of past wakes of a host.
+#[derive(Serialize)]
+struct Wakes {
+    class: &'static str,
+    wakes: u64,
+    /// The number of wakes after which the host responded.
+    woke: u64,
+    /// The share of wakes after which the host responded.
+    rate: String,
+    /// Average time until the host responded in milliseconds.
+    time_to_wake_ms: Option<f64>,
 }
 
 #[derive(Deserialize)]
 struct Wake {
     host: Uuid,
+    /// The segment the host was woken from.
+    #[serde(default)]
+    segment: Option<String>,
 }
 
 async fn wake(
     State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     OriginalUri(uri): OriginalUri,
     Form(wake): Form<Wake>,
 ) -> Result<Redirect, Error> {
     let S {
         prefix,
         ref hosts,
-        ref socket,
+        ref wake_queue,
+        ref audit,
+        ref scripting,
         ..
     } = *state;
 
-    let hosts = hosts.hosts().await;
+    let config = hosts.config().await;
+    let page = page(prefix, &config, wake.segment.as_deref());
+    let hosts = hosts.hosts();
 
     let Some(host) = hosts.iter().find(|h| h.id == wake.host) else {
         let redirect = format!("{uri}?error=unknown-host");
@@ -302,15 +1389,256 @@ async fn wake(
         return Ok(redirect);
     };
 
-    let builder = Builder::from(uri).path_and_query(format!("{prefix}?woke={}", host.id));
+    if host.wake.enabled == Some(false) {
+        let redirect = format!("{page}?error=wake-disabled#host-{}", host.id);
+        let redirect = Redirect::to(&redirect);
+        return Ok(redirect);
+    }
+
+    let builder = Builder::from(uri).path_and_query(format!("{page}?woke={}", host.id));
     let uri = builder.build()?;
 
-    for mac in &host.macs {
-        let packet = MagicPacket::new(*mac);
-        socket.send(&packet).await?;
+    let broadcasts = interfaces::broadcasts(&config.interfaces).await;
+    let targets = wake_on_lan::targets(&host.wake, &broadcasts);
+
+    let name = format!("host `{}`", host.names().next().unwrap_or_default());
+    let description = wake_on_lan::describe(&targets);
+
+    let macs = host.macs.iter().copied().collect();
+    let wake = wake_queue::Wake::new(Some(host.id), name, macs, host.wake.clone(), targets);
+
+    if let Err(reason) = scripting.before_wake(&wake).await {
+        tracing::warn!("Not waking {}: {reason}", wake.name);
+        let redirect = format!("{page}?error=wake-cancelled#host-{}", host.id);
+        let redirect = Redirect::to(&redirect);
+        return Ok(redirect);
     }
 
+    audit
+        .record(
+            peer.ip(),
+            format!("Woke {} through {description}", wake.name),
+        )
+        .await;
+
+    wake_queue.push(wake).await;
+
     let redirect = format!("{uri}#host-{}", host.id);
     let redirect = Redirect::to(&redirect);
     Ok(redirect)
 }
+
+#[derive(Deserialize)]
+struct WakeMac {
+    mac: String,
+    /// Optional address to send the magic packet to.
+    #[serde(default)]
+    target: String,
+}
+
+/// Wake a MAC address which doesn't need to belong to a configured host.
+async fn wake_mac(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(wake): Form<WakeMac>,
+) -> Result<Redirect, Error> {
+    let S {
+        prefix,
+        ref hosts,
+        ref wake_queue,
+        ref audit,
+        ref scripting,
+        ..
+    } = *state;
+
+    let Ok(Mac(mac)) = wake.mac.parse::<Mac>() else {
+        return Ok(Redirect::to(&format!("{prefix}?error=invalid-mac")));
+    };
+
+    let mut config = wake_on_lan::config_for(&hosts.hosts(), mac);
+
+    if !wake.target.trim().is_empty() {
+        let Ok(target) = wake.target.trim().parse() else {
+            return Ok(Redirect::to(&format!("{prefix}?error=invalid-target")));
+        };
+
+        config.target = Some(target);
+    }
+
+    let broadcasts = interfaces::broadcasts(&hosts.config().await.interfaces).await;
+    let targets = wake_on_lan::targets(&config, &broadcasts);
+
+    let description = wake_on_lan::describe(&targets);
+
+    let wake = wake_queue::Wake::new(None, mac.to_string(), vec![mac], config, targets);
+
+    if let Err(reason) = scripting.before_wake(&wake).await {
+        tracing::warn!("Not waking {mac}: {reason}");
+        return Ok(Redirect::to(&format!("{prefix}?error=wake-cancelled")));
+    }
+
+    audit
+        .record(peer.ip(), format!("Woke {mac} through {description}"))
+        .await;
+
+    wake_queue.push(wake).await;
+
+    Ok(Redirect::to(&format!("{prefix}?woke_mac={mac}")))
+}
+
+#[derive(Deserialize)]
+struct Acknowledge {
+    mac: String,
+}
+
+/// Acknowledge a new device, removing it from the network page.
+async fn acknowledge(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(form): Form<Acknowledge>,
+) -> Result<Redirect, Error> {
+    let S {
+        prefix,
+        ref discovery,
+        ref audit,
+        ..
+    } = *state;
+
+    let Ok(Mac(mac)) = form.mac.parse::<Mac>() else {
+        return Ok(Redirect::to(&format!("{prefix}?error=unknown-device")));
+    };
+
+    if !discovery.acknowledge(mac).await {
+        return Ok(Redirect::to(&format!("{prefix}?error=unknown-device")));
+    }
+
+    audit
+        .record(peer.ip(), format!("Acknowledged new device {mac}"))
+        .await;
+
+    Ok(Redirect::to(prefix))
+}
+
+/// Mark a device as suspicious, pinning it to the top of the network page.
+async fn mark_suspicious(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(form): Form<Acknowledge>,
+) -> Result<Redirect, Error> {
+    let S {
+        prefix,
+        ref discovery,
+        ref audit,
+        ..
+    } = *state;
+
+    let Ok(Mac(mac)) = form.mac.parse::<Mac>() else {
+        return Ok(Redirect::to(&format!("{prefix}?error=unknown-device")));
+    };
+
+    discovery.mark_suspicious(mac).await?;
+
+    audit
+        .record(peer.ip(), format!("Marked device {mac} as suspicious"))
+        .await;
+
+    Ok(Redirect::to(prefix))
+}
+
+async fn unmark_suspicious(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(form): Form<Acknowledge>,
+) -> Result<Redirect, Error> {
+    let S {
+        prefix,
+        ref discovery,
+        ref audit,
+        ..
+    } = *state;
+
+    let Ok(Mac(mac)) = form.mac.parse::<Mac>() else {
+        return Ok(Redirect::to(&format!("{prefix}?error=unknown-device")));
+    };
+
+    if !discovery.unmark_suspicious(mac).await? {
+        return Ok(Redirect::to(&format!("{prefix}?error=unknown-device")));
+    }
+
+    audit
+        .record(
+            peer.ip(),
+            format!("Removed suspicious mark of device {mac}"),
+        )
+        .await;
+
+    Ok(Redirect::to(prefix))
+}
+
+#[derive(Deserialize)]
+struct StartProbe {
+    host: Uuid,
+    /// The segment the probe was started from.
+    #[serde(default)]
+    segment: Option<String>,
+}
+
+/// Start probing the throughput to a host.
+async fn start_probe(
+    State(state): State<Arc<S>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Form(form): Form<StartProbe>,
+) -> Result<Redirect, Error> {
+    let S {
+        prefix,
+        ref hosts,
+        ref probe,
+        ref audit,
+        ..
+    } = *state;
+
+    let config = hosts.config().await;
+    let page = page(prefix, &config, form.segment.as_deref());
+    let hosts = hosts.hosts();
+
+    let Some(host) = hosts.iter().find(|h| h.id == form.host) else {
+        return Ok(Redirect::to(&format!("{page}?error=unknown-host")));
+    };
+
+    if host.probe.port.is_none() {
+        let redirect = format!("{page}?error=probe-unavailable#host-{}", host.id);
+        return Ok(Redirect::to(&redirect));
+    }
+
+    if probe.start(host).await {
+        let name = host.names().next().unwrap_or_default();
+        audit
+            .record(peer.ip(), format!("Probed host `{name}`"))
+            .await;
+    }
+
+    Ok(Redirect::to(&format!("{page}#host-{}", host.id)))
+}
+
+/// Build the points of a line for the given samples, scaled to fit a box
+/// which is 100 wide and 20 high.
+fn sparkline(samples: impl ExactSizeIterator<Item = f64> + Clone) -> String {
+    use core::fmt::Write;
+
+    let mut out = String::new();
+    let n = samples.len();
+
+    if n < 2 {
+        return out;
+    }
+
+    let max = samples.clone().fold(0.0f64, f64::max).max(1.0);
+
+    for (i, value) in samples.enumerate() {
+        let x = i as f64 * 100.0 / (n - 1) as f64;
+        let y = 20.0 - value / max * 20.0;
+        _ = write!(out, "{x:.1},{y:.1} ");
+    }
+
+    out
+}
@@ -0,0 +1,139 @@
+//! Notifications, which are posted as JSON to the configured webhooks.
+
+use core::fmt::Write;
+use core::net::Ipv4Addr;
+use core::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+
+use crate::config::{NotifyConfig, Webhook};
+use crate::events::{self, Event};
+use crate::hosts;
+
+/// How long to wait for a webhook to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A notification, which is serialized with an `event` field naming its kind.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// A device which has never been seen before appeared in the neighbor
+    /// table.
+    NewDevice {
+        mac: String,
+        address: Ipv4Addr,
+        /// The local interface the device was seen on.
+        device: String,
+    },
+    /// A periodic summary report.
+    Report { markdown: String },
+}
+
+/// Send a notification to all configured webhooks in the background.
+pub fn send(config: &NotifyConfig, notification: &Notification) {
+    if config.webhooks.is_empty() {
+        return;
+    }
+
+    let body = match serde_json::to_string(notification) {
+        Ok(body) => body,
+        Err(error) => {
+            tracing::error!("Serializing notification: {error}");
+            return;
+        }
+    };
+
+    for webhook in &config.webhooks {
+        let webhook = webhook.clone();
+        let body = body.clone();
+
+        tokio::spawn(async move {
+            let headers = [("Content-Type", "application/json")];
+            let post = post(&webhook, &headers, body.as_bytes());
+
+            let result = match time::timeout(TIMEOUT, post).await {
+                Ok(result) => result,
+                Err(..) => Err(anyhow::anyhow!("timeout")),
+            };
+
+            if let Err(error) = result {
+                tracing::warn!("Posting notification to {webhook}: {error:#}");
+            }
+        });
+    }
+}
+
+/// Spawn the task which sends notifications about published events.
+pub async fn spawn(events: events::Log, hosts: hosts::State) {
+    let mut receiver = events.subscribe();
+
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(n)) => {
+                tracing::warn!("Notifications missed {n} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let notification = match event {
+            Event::DeviceDiscovered {
+                mac,
+                address,
+                device,
+            } => Notification::NewDevice {
+                mac: mac.to_string(),
+                address,
+                device,
+            },
+            _ => continue,
+        };
+
+        send(&hosts.config().await.notify, &notification);
+    }
+}
+
+/// Post a body with the given headers, failing unless the response has a
+/// successful status.
+pub(crate) async fn post(webhook: &Webhook, headers: &[(&str, &str)], body: &[u8]) -> Result<()> {
+    let mut stream = TcpStream::connect((webhook.host.as_str(), webhook.port))
+        .await
+        .context("connecting")?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\nUser-Agent: wolo\r\n",
+        webhook.path,
+        webhook.host,
+        body.len()
+    );
+
+    for (name, value) in headers {
+        _ = write!(request, "{name}: {value}\r\n");
+    }
+
+    request.push_str("\r\n");
+
+    stream.write_all(request.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+
+    let Some(status) = line.split_ascii_whitespace().nth(1) else {
+        bail!("malformed response");
+    };
+
+    let status = status.parse::<u16>().context("malformed status code")?;
+
+    if !(200..300).contains(&status) {
+        bail!("status {status}");
+    }
+
+    Ok(())
+}
@@ -1,23 +1,80 @@
 use core::fmt;
-use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::mem;
+use core::net::IpAddr;
 use core::pin::pin;
 use core::time::Duration;
 
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use anyhow::{Context, Error};
-use lib::{Buffer, Outcome, Pinger, Response};
-use tokio::sync::Mutex;
+use anyhow::{Context, Error, bail};
+use lib::{AnyPinger, Buffer, Dump, Family, Outcome, Pinger, Response, Simulator};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedMutexGuard};
 use tokio::task::JoinSet;
 use tokio::time::{self, Instant};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::host_name_cache::{CacheNameResult, HostNameCache};
+use crate::config::{PingConfig, PingFamily, QuietHoursConfig, TimeOfDay};
+use crate::history::{History, Sample};
+use crate::host_name_cache::{CacheNameResult, HostNameCache, ResolveErrorKind};
 use crate::hosts;
+use crate::neighbors;
+use crate::utils;
 
 const TIMEOUT: Duration = Duration::from_secs(10);
 const NEXT: Duration = Duration::from_secs(1);
+/// How long a host which stopped responding is considered to be sleeping
+/// rather than absent.
+const SLEEPING: Duration = Duration::from_secs(24 * 60 * 60);
+/// The number of recent pings of a host packet loss is calculated over.
+const RECENT: usize = 100;
+/// How often statistics are updated.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Effective ping settings for a host.
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    /// Interval between pings.
+    interval: Duration,
+    /// Timeout after which a ping is considered lost.
+    timeout: Duration,
+    /// Which address families are pinged.
+    family: PingFamily,
+}
+
+impl Settings {
+    const DEFAULT: Self = Self {
+        interval: NEXT,
+        timeout: TIMEOUT,
+        family: PingFamily::Both,
+    };
+
+    fn new(config: &PingConfig, quiet_hours: &QuietHoursConfig, time: TimeOfDay) -> Self {
+        let mut interval = config
+            .interval
+            .unwrap_or(NEXT)
+            .max(Duration::from_millis(100));
+
+        // Pings are stretched out during quiet hours, but never sent more
+        // often than configured.
+        if quiet_hours.contains(time) {
+            interval = interval.max(
+                quiet_hours
+                    .interval
+                    .unwrap_or(QuietHoursConfig::DEFAULT_INTERVAL),
+            );
+        }
+
+        Self {
+            interval,
+            timeout: config.timeout.unwrap_or(TIMEOUT),
+            family: config.family.unwrap_or_default(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -56,6 +113,21 @@ impl fmt::Display for PingKind {
 pub struct Pinged {
     pub errors: Vec<PingError>,
     pub results: Vec<PingResult>,
+    /// When the host last replied to a ping, or was seen in the neighbor
+    /// table.
+    pub last_seen: Option<Instant>,
+    /// Addresses of the host which are reachable according to the neighbor
+    /// table, meaning that they respond to ARP or neighbor discovery.
+    pub neighbors: Vec<IpAddr>,
+    /// Whether each of the most recent pings of the host was answered.
+    recent: VecDeque<bool>,
+}
+
+impl PingResult {
+    /// Test if this is a reply which took longer than the given threshold.
+    pub fn is_slow(&self, threshold: Option<Duration>) -> bool {
+        self.outcome.is_echo_reply() && threshold.is_some_and(|t| self.rtt > t)
+    }
 }
 
 impl Pinged {
@@ -63,6 +135,12 @@ impl Pinged {
         self.errors
             .retain(|e| e.kind.as_address() != Some(result.target));
 
+        self.sample(result.outcome.is_echo_reply());
+
+        if result.outcome.is_echo_reply() {
+            self.last_seen = Some(result.sampled);
+        }
+
         if let Some(r) = self.results.iter_mut().find(|r| r.target == result.target) {
             *r = result;
             return;
@@ -72,10 +150,53 @@ impl Pinged {
         self.results.sort_by_key(|r| r.target);
     }
 
+    /// Get the status of the host based on its most recent pings.
+    ///
+    /// Hosts which don't respond to pings are still up if they respond to
+    /// ARP, since ICMP is commonly filtered.
+    pub fn status(&self) -> HostStatus {
+        if self.results.iter().any(|r| r.outcome.is_echo_reply()) || !self.neighbors.is_empty() {
+            return HostStatus::Up;
+        }
+
+        let address_errors = self.errors.iter().any(|e| e.kind.as_address().is_some());
+
+        if !self.results.is_empty() || address_errors {
+            return HostStatus::Down;
+        }
+
+        HostStatus::Unknown
+    }
+
+    /// The share of the most recent pings which went unanswered, between 0
+    /// and 1, unless the host hasn't been pinged.
+    pub fn loss(&self) -> Option<f64> {
+        if self.recent.is_empty() {
+            return None;
+        }
+
+        let lost = self.recent.iter().filter(|answered| !**answered).count();
+        Some(lost as f64 / self.recent.len() as f64)
+    }
+
+    fn sample(&mut self, answered: bool) {
+        if self.recent.len() == RECENT {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back(answered);
+    }
+
+    /// Test if the host is only up because it responds to ARP.
+    pub fn is_arp_only(&self) -> bool {
+        !self.neighbors.is_empty() && !self.results.iter().any(|r| r.outcome.is_echo_reply())
+    }
+
     /// Add a ping error, replacing any existing error of the same kind.
     pub fn error(&mut self, error: PingError) {
         if let PingErrorKind::Address(addr) = error.kind {
             self.results.retain(|r| r.target != addr);
+            self.sample(false);
         }
 
         if let Some(e) = self.errors.iter_mut().find(|e| e.kind == error.kind) {
@@ -89,18 +210,196 @@ impl Pinged {
     }
 }
 
+/// The status of a host derived from its most recent pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HostStatus {
+    /// At least one address responded.
+    Up,
+    /// Addresses were pinged, but none responded.
+    Down,
+    /// The host has not been pinged or its addresses could not be resolved.
+    Unknown,
+    /// The host isn't up, but a host it depends on is down or unreachable.
+    Unreachable,
+}
+
+impl HostStatus {
+    /// Test if the host is known to not be reachable.
+    fn is_down(self) -> bool {
+        matches!(self, HostStatus::Down | HostStatus::Unreachable)
+    }
+}
+
+/// An estimate of whether a host which isn't up is sleeping or gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerState {
+    /// The host is up.
+    Up,
+    /// The host was recently up, or is still in the neighbor table of this
+    /// machine without answering, like when it's suspended.
+    Sleeping,
+    /// The host hasn't been seen since wolo started, or not for a long time.
+    Absent,
+}
+
+impl PowerState {
+    /// Estimate the power state of a host from its pings, where `stale` is
+    /// whether one of its MAC addresses is still in the neighbor table.
+    pub fn estimate(pinged: Option<&Pinged>, stale: bool, now: Instant) -> Self {
+        if pinged.is_some_and(|p| p.status() == HostStatus::Up) {
+            return PowerState::Up;
+        }
+
+        let recent = pinged
+            .and_then(|p| p.last_seen)
+            .is_some_and(|seen| now.saturating_duration_since(seen) < SLEEPING);
+
+        if recent || stale {
+            PowerState::Sleeping
+        } else {
+            PowerState::Absent
+        }
+    }
+}
+
+/// The status of a host taking its dependencies into account.
+#[derive(Debug, Clone)]
+pub struct Status {
+    /// The status of the host.
+    pub status: HostStatus,
+    /// The dependency which is down, if the host is unreachable.
+    pub dependency: Option<Uuid>,
+}
+
+/// Get the status of all hosts, where hosts which aren't up are unreachable
+/// if a host they depend on is down or unreachable.
+pub fn statuses(hosts: &[hosts::Host], pinged: &HashMap<Uuid, Pinged>) -> HashMap<Uuid, Status> {
+    let mut by_name = HashMap::new();
+
+    for (index, host) in hosts.iter().enumerate() {
+        for name in &host.names {
+            by_name.insert(name.as_str(), index);
+        }
+    }
+
+    let base = hosts
+        .iter()
+        .map(|h| {
+            pinged
+                .get(&h.id)
+                .map_or(HostStatus::Unknown, Pinged::status)
+        })
+        .collect::<Vec<_>>();
+
+    let mut resolved = vec![None; hosts.len()];
+
+    for index in 0..hosts.len() {
+        resolve(
+            index,
+            hosts,
+            &by_name,
+            &base,
+            &mut resolved,
+            &mut HashSet::new(),
+        );
+    }
+
+    hosts
+        .iter()
+        .zip(resolved)
+        .filter_map(|(h, s)| Some((h.id, s?)))
+        .collect()
+}
+
+fn resolve(
+    index: usize,
+    hosts: &[hosts::Host],
+    by_name: &HashMap<&str, usize>,
+    base: &[HostStatus],
+    resolved: &mut [Option<Status>],
+    visiting: &mut HashSet<usize>,
+) -> HostStatus {
+    if let Some(s) = &resolved[index] {
+        return s.status;
+    }
+
+    // Hosts in a dependency cycle can't make each other unreachable.
+    if !visiting.insert(index) {
+        return HostStatus::Unknown;
+    }
+
+    let mut status = Status {
+        status: base[index],
+        dependency: None,
+    };
+
+    if status.status != HostStatus::Up {
+        for name in &hosts[index].depends_on {
+            let Some(&dep) = by_name.get(name.as_str()) else {
+                continue;
+            };
+
+            if dep != index && resolve(dep, hosts, by_name, base, resolved, visiting).is_down() {
+                status.status = HostStatus::Unreachable;
+                status.dependency = Some(hosts[dep].id);
+                break;
+            }
+        }
+    }
+
+    visiting.remove(&index);
+    let out = status.status;
+    resolved[index] = Some(status);
+    out
+}
+
+/// Statistics about the pings being sent.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Stats {
+    /// Total number of pings sent.
+    pub sent: u64,
+    /// Pings sent per second, averaged over the last second.
+    pub per_second: f64,
+    /// Number of addresses scheduled to be pinged.
+    pub scheduled: usize,
+    /// Number of pings waiting for a response.
+    pub in_flight: usize,
+    /// How late scheduled pings and timeouts were handled on average over
+    /// the last second.
+    pub lag: Duration,
+    /// How late the latest scheduled ping or timeout was handled over the last
+    /// second.
+    pub max_lag: Duration,
+}
+
 #[derive(Clone)]
 pub struct State {
     /// Hosts that have been pinged.
     pub pinged: Arc<Mutex<HashMap<Uuid, Pinged>>>,
+    /// Statistics about sent pings.
+    pub stats: Arc<Mutex<Stats>>,
+    /// Cache of resolved host names.
+    pub cache: HostNameCache,
+    /// Why nothing is pinged, if ICMP sockets couldn't be created.
+    pub unavailable: Option<Arc<str>>,
 }
 
 impl State {
-    /// Construct a new empty state.
+    /// Construct a new empty state for pinging over the given transport.
     #[inline]
-    pub fn new() -> Self {
+    pub fn new(transport: &Transport) -> Self {
+        let unavailable = match transport {
+            Transport::Unavailable(reason) => Some(reason.clone()),
+            _ => None,
+        };
+
         Self {
             pinged: Arc::new(Mutex::new(HashMap::new())),
+            stats: Arc::new(Mutex::new(Stats::default())),
+            cache: HostNameCache::new(),
+            unavailable,
         }
     }
 }
@@ -110,7 +409,7 @@ impl State {
 #[non_exhaustive]
 pub enum PingErrorKind {
     Address(IpAddr),
-    Host(String),
+    Host(String, ResolveErrorKind),
 }
 
 impl PingErrorKind {
@@ -118,7 +417,7 @@ impl PingErrorKind {
     pub fn as_address(&self) -> Option<IpAddr> {
         match self {
             PingErrorKind::Address(addr) => Some(*addr),
-            PingErrorKind::Host(_) => None,
+            PingErrorKind::Host(..) => None,
         }
     }
 
@@ -126,7 +425,16 @@ impl PingErrorKind {
     pub fn as_host(&self) -> Option<&str> {
         match self {
             PingErrorKind::Address(_) => None,
-            PingErrorKind::Host(name) => Some(name),
+            PingErrorKind::Host(name, _) => Some(name),
+        }
+    }
+
+    /// Get the kind of resolver error, if this is an error resolving a host
+    /// name.
+    pub fn as_resolve(&self) -> Option<ResolveErrorKind> {
+        match self {
+            PingErrorKind::Address(_) => None,
+            PingErrorKind::Host(_, kind) => Some(*kind),
         }
     }
 }
@@ -138,9 +446,61 @@ pub struct PingError {
     pub sampled: Instant,
 }
 
+/// How pings are sent and received.
+pub(super) enum Transport {
+    /// ICMP sockets, of the families which are available.
+    Sockets {
+        v4: Option<Pinger>,
+        v6: Option<Pinger>,
+    },
+    /// Simulated responses, used with `--simulate`.
+    Simulated(Simulator),
+    /// No ICMP sockets could be created, so nothing is pinged, with the
+    /// reason why.
+    Unavailable(Arc<str>),
+}
+
+impl Transport {
+    /// Set up the transport, which creates ICMP sockets unless simulating.
+    ///
+    /// This is done before the ping loop is spawned so that sockets can be
+    /// created before privileges are dropped. If only one address family is
+    /// available, addresses of the other fail to be pinged, and if neither is
+    /// the service keeps running without pinging anything.
+    pub(super) fn new(dump: Option<Dump>, simulate: bool) -> Self {
+        if simulate {
+            return Transport::Simulated(Simulator::new());
+        }
+
+        let any = match Pinger::any() {
+            Ok(any) => any,
+            Err(error) => {
+                tracing::error!("ICMP sockets are unavailable, so nothing will be pinged: {error}");
+                return Transport::Unavailable(error.to_string().into());
+            }
+        };
+
+        for (family, error) in &any.errors {
+            tracing::warn!("{family} is unavailable, so it won't be pinged: {error}");
+        }
+
+        let families = any.families().map(|f| f.to_string()).collect::<Vec<_>>();
+        tracing::info!("Pinging over {}", families.join(" and "));
+
+        let AnyPinger { mut v4, mut v6, .. } = any;
+
+        if let Some(dump) = dump {
+            for pinger in v4.iter_mut().chain(v6.iter_mut()) {
+                pinger.set_dump(dump.clone());
+            }
+        }
+
+        Transport::Sockets { v4, v6 }
+    }
+}
+
 struct PingerService {
-    v4: Pinger,
-    v6: Pinger,
+    transport: OwnedMutexGuard<Transport>,
     b1: Buffer,
     b2: Buffer,
     id: u64,
@@ -148,56 +508,80 @@ struct PingerService {
 
 impl PingerService {
     async fn ping(&mut self, address: IpAddr) -> Result<Option<u64>, Error> {
-        match address {
+        let unicast = match address {
             IpAddr::V4(ip) => {
-                pub fn is_unicast(addr: &Ipv4Addr) -> bool {
-                    !addr.is_multicast()
-                        && !addr.is_loopback()
-                        && !addr.is_link_local()
-                        && !addr.is_broadcast()
-                        && !addr.is_documentation()
-                        && !addr.is_unspecified()
-                }
-
-                if !is_unicast(&ip) {
-                    return Ok(None);
-                }
-
-                let id = self.id;
-                let bytes = id.to_be_bytes();
-                self.v4.ping(&mut self.b1, IpAddr::V4(ip), &bytes).await?;
-                self.id = self.id.wrapping_add(1);
-                Ok(Some(id))
+                !ip.is_multicast()
+                    && !ip.is_loopback()
+                    && !ip.is_link_local()
+                    && !ip.is_broadcast()
+                    && !ip.is_documentation()
+                    && !ip.is_unspecified()
             }
             IpAddr::V6(ip) => {
-                pub fn is_unicast(addr: &Ipv6Addr) -> bool {
-                    !addr.is_multicast()
-                        && !addr.is_loopback()
-                        && !addr.is_unicast_link_local()
-                        && !addr.is_unspecified()
-                }
+                !ip.is_multicast()
+                    && !ip.is_loopback()
+                    && !ip.is_unicast_link_local()
+                    && !ip.is_unspecified()
+            }
+        };
 
-                if !is_unicast(&ip) {
-                    return Ok(None);
-                }
+        if !unicast {
+            return Ok(None);
+        }
+
+        let id = self.id;
+        let bytes = id.to_be_bytes();
 
-                let id = self.id;
-                let bytes = id.to_be_bytes();
-                self.v6.ping(&mut self.b2, IpAddr::V6(ip), &bytes).await?;
-                self.id = self.id.wrapping_add(1);
-                Ok(Some(id))
+        match &mut *self.transport {
+            Transport::Sockets { v4, .. } if address.is_ipv4() => {
+                let Some(v4) = v4 else {
+                    bail!("{} is unavailable", Family::V4);
+                };
+
+                v4.ping(&mut self.b1, address, &bytes).await?;
+            }
+            Transport::Sockets { v6, .. } => {
+                let Some(v6) = v6 else {
+                    bail!("{} is unavailable", Family::V6);
+                };
+
+                v6.ping(&mut self.b2, address, &bytes).await?;
+            }
+            Transport::Simulated(simulator) => {
+                simulator.ping(address, &bytes);
+            }
+            Transport::Unavailable(reason) => {
+                bail!("ICMP is unavailable: {reason}");
             }
         }
+
+        self.id = self.id.wrapping_add(1);
+        Ok(Some(id))
     }
 
     async fn wait_for_result(&mut self) -> Result<(Response, PingKind, u64), Error> {
-        let (response, kind, b) = tokio::select! {
-            r = self.v4.recv(&mut self.b1) => {
-                (r?, PingKind::V4, &self.b1)
+        let (response, kind, b) = match &mut *self.transport {
+            Transport::Sockets { v4, v6 } => {
+                tokio::select! {
+                    r = recv(v4.as_ref(), &mut self.b1) => {
+                        (r?, PingKind::V4, &self.b1)
+                    }
+                    r = recv(v6.as_ref(), &mut self.b2) => {
+                        (r?, PingKind::V6, &self.b2)
+                    }
+                }
             }
-            r = self.v6.recv(&mut self.b2) => {
-                (r?, PingKind::V6, &self.b2)
+            Transport::Simulated(simulator) => {
+                let r = simulator.recv(&mut self.b1).await;
+
+                let kind = match r.dest {
+                    IpAddr::V4(..) => PingKind::V4,
+                    IpAddr::V6(..) => PingKind::V6,
+                };
+
+                (r, kind, &self.b1)
             }
+            Transport::Unavailable(..) => std::future::pending().await,
         };
 
         let bytes = *b.read::<[u8; 8]>().context("reading response payload")?;
@@ -206,17 +590,33 @@ impl PingerService {
     }
 }
 
-pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error> {
+/// Receive from a pinger, or never if its address family is unavailable.
+async fn recv(pinger: Option<&Pinger>, buf: &mut Buffer) -> Result<Response, lib::Error> {
+    match pinger {
+        Some(pinger) => pinger.recv(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Run the ping loop, which locks the transport for as long as it runs so
+/// that it can be handed to the loop again if it's restarted.
+pub(super) async fn new(
+    state: State,
+    hosts: hosts::State,
+    history: History,
+    transport: Arc<Mutex<Transport>>,
+) -> Result<(), Error> {
     let mut service = PingerService {
-        v4: Pinger::v4()?,
-        v6: Pinger::v6()?,
+        transport: transport.lock_owned().await,
         b1: Buffer::new(),
         b2: Buffer::new(),
         id: 0u64,
     };
 
     // A host cache.
-    let mut cache = HostNameCache::new();
+    let mut cache = state.cache.clone();
+    // Changes to the list of hosts.
+    let mut subscription = hosts.subscribe();
     // Update host list every 10 seconds.
     let mut host_update = time::interval(Duration::from_secs(10));
     // Working set of host ids.
@@ -229,6 +629,15 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
     let mut domains = BTreeMap::<Uuid, Arc<CacheNameResult>>::new();
     // Pending pings.
     let mut deferred = HashMap::<u64, Defer>::new();
+    // Ping settings for each host.
+    let mut settings = HashMap::<Uuid, Settings>::new();
+    // Number of pings sent, and when statistics were last updated.
+    let mut sent = 0u64;
+    let mut stats_updated = (Instant::now(), 0u64);
+    let mut stats_update = time::interval(STATS_INTERVAL);
+    // Scheduling lag since statistics were last updated, as the total, the
+    // maximum and the number of tasks handled.
+    let mut lag = (Duration::ZERO, Duration::ZERO, 0u32);
 
     let mut tasks = Tasks::default();
     // Wakeup for next task.
@@ -240,17 +649,80 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
         }
 
         tokio::select! {
+            _ = subscription.changed() => {
+                // Pick up changed hosts right away instead of waiting for the
+                // next scheduled update.
+                host_update.reset_immediately();
+            }
             _ = host_update.tick() => {
+                cache.set_dns(&hosts.config().await.dns).await;
                 cache.evict_old().await;
 
+                let now = Instant::now();
+
+                {
+                    let reachable = neighbors::reachable().await;
+                    let mut pinged = state.pinged.lock().await;
+
+                    for (id, resolved) in &domains {
+                        let Some(p) = pinged.get_mut(id) else {
+                            continue;
+                        };
+
+                        p.neighbors.clear();
+                        p.neighbors.extend(resolved.addresses.iter().filter(|a| reachable.contains(a)));
+
+                        if !p.neighbors.is_empty() {
+                            p.last_seen = Some(now);
+                        }
+                    }
+                }
+
                 new.clear();
 
-                for host in hosts.hosts().await.iter() {
+                let previous = mem::take(&mut settings);
+                let current = subscription.borrow_and_update().hosts.clone();
+                let time = utils::time_of_day();
+
+                for host in current.iter() {
+                    if host.ping.enabled == Some(false) {
+                        continue;
+                    }
+
                     new.insert(host.id);
 
-                    let lookup = cache.get(host).await;
+                    let s = Settings::new(&host.ping, &host.quiet_hours, time);
+
+                    // Pings scheduled during quiet hours are brought forward
+                    // once they end.
+                    if let Some(p) = previous.get(&host.id) && s.interval < p.interval {
+                        tasks.bring_forward(host.id, now + s.interval);
+                    }
+
+                    // Forget the addresses of the host, so that they are
+                    // scheduled again once resolved with the new families.
+                    if previous.get(&host.id).is_some_and(|p| p.family != s.family) {
+                        domains.remove(&host.id);
+                    }
+
+                    settings.insert(host.id, s);
+
                     let id = host.id;
 
+                    // A configured address is pinged instead of the
+                    // addresses the names of the host resolve to.
+                    if let Some(address) = host.ping_address {
+                        let result = Arc::new(CacheNameResult {
+                            errors: Vec::new(),
+                            addresses: vec![address],
+                        });
+
+                        domain.spawn(async move { (id, Ok(result)) });
+                        continue;
+                    }
+
+                    let lookup = cache.get(host).await;
+
                     domain.spawn(async move {
                         let result = lookup.get().await;
                         (id, result)
@@ -269,6 +741,24 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                     old.extend(new.iter().copied());
                 }
             }
+            _ = stats_update.tick() => {
+                let now = Instant::now();
+                let (last, last_sent) = stats_updated;
+                let elapsed = now.saturating_duration_since(last).as_secs_f64();
+
+                if elapsed > 0.0 {
+                    let (total, max, count) = mem::take(&mut lag);
+
+                    let mut stats = state.stats.lock().await;
+                    stats.sent = sent;
+                    stats.per_second = (sent - last_sent) as f64 / elapsed;
+                    stats.scheduled = tasks.len();
+                    stats.in_flight = deferred.len();
+                    stats.lag = total.checked_div(count).unwrap_or_default();
+                    stats.max_lag = max;
+                    stats_updated = (now, sent);
+                }
+            }
             result = domain.join_next(), if !domain.is_empty() => {
                 let Some(result) = result else {
                     continue;
@@ -297,12 +787,14 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                 for error in new.errors.iter() {
                     p.error(PingError {
                         error: error.error.to_string(),
-                        kind: PingErrorKind::Host(error.name.clone()),
+                        kind: PingErrorKind::Host(error.name.clone(), error.kind),
                         sampled: now,
                     });
                 }
 
-                for &addr in new.addresses.iter() {
+                let family = settings.get(&id).map_or(PingFamily::Both, |s| s.family);
+
+                for addr in family.select(&new.addresses) {
                     tracing::trace!(?id, ?addr, "scheduling ping");
                     tasks.insert(Key { id, addr, deadline: now }, What::Ping);
                 }
@@ -324,6 +816,15 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                 tasks.with_mut(k.id, k.addr, async |t| {
                     let now = Instant::now();
 
+                    let rtt = now.saturating_duration_since(k.started);
+
+                    history.record(Sample {
+                        host: k.id,
+                        address: k.addr,
+                        sampled: SystemTime::now(),
+                        rtt: r.outcome.is_echo_reply().then_some(rtt),
+                    });
+
                     let mut pinged = state.pinged.lock().await;
                     let p = pinged.entry(k.id).or_default();
 
@@ -332,7 +833,7 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                         outcome: r.outcome,
                         code: r.code,
                         sequence: r.sequence,
-                        rtt: now.saturating_duration_since(k.started),
+                        rtt,
                         sampled: now,
                         target: k.addr,
                         source: r.source,
@@ -341,7 +842,8 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                         expected_checksum: r.expected_checksum,
                     });
 
-                    t.key.deadline = (k.started + NEXT).max(now);
+                    let settings = settings.get(&k.id).copied().unwrap_or(Settings::DEFAULT);
+                    t.key.deadline = (k.started + settings.interval).max(now);
                     t.what = What::Ping;
                 }).await;
             }
@@ -349,20 +851,37 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                 let now = Instant::now();
 
                 let remove = tasks.next_task(async |t| {
+                    let late = now.saturating_duration_since(t.key.deadline);
+                    lag = (lag.0 + late, lag.1.max(late), lag.2 + 1);
+
+                    let settings = settings.get(&t.key.id).copied().unwrap_or(Settings::DEFAULT);
+
                     match t.what {
                         What::Ping => {
+                            if hosts.is_paused() || state.unavailable.is_some() {
+                                t.key.deadline = now + settings.interval;
+                                return None;
+                            }
+
                             tracing::trace!(?t, "pinging");
 
                             let ping_id = match service.ping(t.key.addr).await {
                                 Ok(ping_id) => ping_id,
                                 Err(error) => {
+                                    history.record(Sample {
+                                        host: t.key.id,
+                                        address: t.key.addr,
+                                        sampled: SystemTime::now(),
+                                        rtt: None,
+                                    });
+
                                     state.pinged.lock().await.entry(t.key.id).or_default().error(PingError {
                                         error: error.to_string(),
                                         kind: PingErrorKind::Address(t.key.addr),
                                         sampled: now,
                                     });
 
-                                    t.key.deadline = now + NEXT;
+                                    t.key.deadline = now + settings.interval;
                                     t.what = What::Ping;
                                     return None;
                                 }
@@ -372,13 +891,22 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                                 return Some(t.key);
                             };
 
+                            sent += 1;
+
                             deferred.insert(ping_id, Defer { id: t.key.id, addr: t.key.addr, started: now });
 
-                            t.key.deadline = now + TIMEOUT;
+                            t.key.deadline = now + settings.timeout;
                             t.what = What::Timeout;
                             None
                         }
                         What::Timeout => {
+                            history.record(Sample {
+                                host: t.key.id,
+                                address: t.key.addr,
+                                sampled: SystemTime::now(),
+                                rtt: None,
+                            });
+
                             let mut p = state.pinged.lock().await;
                             let p = p.entry(t.key.id).or_default();
 
@@ -388,7 +916,7 @@ pub(super) async fn new(state: State, hosts: hosts::State) -> Result<(), Error>
                                 sampled: now,
                             });
 
-                            t.key.deadline = now + NEXT;
+                            t.key.deadline = now + settings.interval;
                             t.what = What::Ping;
                             None
                         }
@@ -441,6 +969,10 @@ impl Tasks {
         self.timeouts.is_empty()
     }
 
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
     /// Get the next deadline.
     fn next_deadline(&mut self) -> Option<Instant> {
         if !self.modified {
@@ -464,6 +996,21 @@ impl Tasks {
         });
     }
 
+    /// Bring pings scheduled for the given host forward to the given deadline
+    /// if they are scheduled later than it.
+    fn bring_forward(&mut self, id: Uuid, deadline: Instant) {
+        for t in self.tasks.values_mut() {
+            if t.key.id != id || !matches!(t.what, What::Ping) || t.key.deadline <= deadline {
+                continue;
+            }
+
+            self.modified = true;
+            self.timeouts.remove(&t.key);
+            t.key.deadline = deadline;
+            self.timeouts.insert(t.key);
+        }
+    }
+
     fn insert(&mut self, key: Key, what: What) {
         self.modified = true;
         self.tasks.insert((key.id, key.addr), Task { key, what });
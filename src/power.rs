@@ -0,0 +1,37 @@
+//! Changing the power state of the machine wolo runs on through logind.
+//!
+//! Unless wolo runs as root, logind consults polkit which has to allow the
+//! user wolo runs as to perform the action without asking, since there's no
+//! one to ask.
+
+use anyhow::{Context, Result};
+use zbus::Connection;
+
+use crate::config::PowerAction;
+
+const DESTINATION: &str = "org.freedesktop.login1";
+const PATH: &str = "/org/freedesktop/login1";
+const INTERFACE: &str = "org.freedesktop.login1.Manager";
+
+/// Ask logind to perform the given action, which returns once it has been
+/// accepted.
+pub async fn perform(action: PowerAction) -> Result<()> {
+    let method = match action {
+        PowerAction::Suspend => "Suspend",
+        PowerAction::Hibernate => "Hibernate",
+        PowerAction::PowerOff => "PowerOff",
+    };
+
+    let connection = Connection::system()
+        .await
+        .context("connecting to the system bus")?;
+
+    // The argument is whether polkit may ask for authentication
+    // interactively, which it can't since there's no one to ask.
+    connection
+        .call_method(Some(DESTINATION), PATH, Some(INTERFACE), method, &(false,))
+        .await
+        .with_context(|| format!("calling {INTERFACE}.{method}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! Per-browser preferences of the network page, like how hosts are sorted,
+//! which are stored in a signed cookie so that they stick without requiring
+//! accounts.
+
+use core::fmt;
+use core::str::FromStr;
+
+use std::collections::BTreeSet;
+
+use axum::http::HeaderMap;
+use axum_extra::extract::SignedCookieJar;
+use axum_extra::extract::cookie::{Cookie, Key, SameSite};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// The name of the cookie preferences are stored in.
+const COOKIE: &str = "wolo-preferences";
+
+/// The intervals the page may be refreshed at in seconds, where 0 turns
+/// refreshing off.
+pub(crate) const REFRESH_INTERVALS: [u32; 4] = [0, 1, 5, 30];
+
+/// How hosts are ordered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Sort {
+    /// The order hosts are configured in.
+    #[default]
+    Configured,
+    /// By their first name.
+    Name,
+    /// Hosts which are down first.
+    Status,
+}
+
+/// The color scheme of pages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// How densely hosts are laid out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Density {
+    #[default]
+    Normal,
+    /// Less spacing and records side by side, for small screens.
+    Compact,
+}
+
+/// Columns of ping results on the network page which can be shown or
+/// hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Column {
+    /// The ICMP code of a response.
+    Code,
+    /// The ICMP sequence number.
+    Sequence,
+    /// Source and destination addresses of responses which differ from the
+    /// address pinged.
+    Addresses,
+    /// Round trip time.
+    Timing,
+    /// The ICMP checksum.
+    Checksum,
+    /// Share of recent pings of the host which went unanswered.
+    Loss,
+    /// When the host last replied.
+    LastSeen,
+}
+
+impl Column {
+    /// All columns, in the order they are presented.
+    pub(crate) const ALL: [Column; 7] = [
+        Column::Code,
+        Column::Sequence,
+        Column::Addresses,
+        Column::Timing,
+        Column::Checksum,
+        Column::Loss,
+        Column::LastSeen,
+    ];
+
+    /// Columns which are shown unless configured otherwise.
+    const DEFAULT: [Column; 5] = [
+        Column::Code,
+        Column::Sequence,
+        Column::Addresses,
+        Column::Timing,
+        Column::Checksum,
+    ];
+
+    /// Parse a comma-separated list of columns, like `timing,loss`.
+    pub(crate) fn parse_list(s: &str) -> Result<BTreeSet<Column>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Column::Code => write!(f, "code"),
+            Column::Sequence => write!(f, "sequence"),
+            Column::Addresses => write!(f, "addresses"),
+            Column::Timing => write!(f, "timing"),
+            Column::Checksum => write!(f, "checksum"),
+            Column::Loss => write!(f, "loss"),
+            Column::LastSeen => write!(f, "last-seen"),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "code" => Ok(Column::Code),
+            "sequence" => Ok(Column::Sequence),
+            "addresses" => Ok(Column::Addresses),
+            "timing" => Ok(Column::Timing),
+            "checksum" => Ok(Column::Checksum),
+            "loss" => Ok(Column::Loss),
+            "last-seen" => Ok(Column::LastSeen),
+            other => Err(format!(
+                "unknown column `{other}`, expected code, sequence, addresses, timing, checksum, loss or last-seen"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Preferences {
+    pub(crate) sort: Sort,
+    /// Hosts whose details are collapsed.
+    pub(crate) collapsed: BTreeSet<Uuid>,
+    /// How often the page is refreshed in seconds.
+    pub(crate) refresh: u32,
+    pub(crate) theme: Theme,
+    pub(crate) density: Density,
+    /// Columns of ping results which are shown.
+    pub(crate) columns: BTreeSet<Column>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            sort: Sort::default(),
+            collapsed: BTreeSet::new(),
+            refresh: 1,
+            theme: Theme::default(),
+            density: Density::default(),
+            columns: BTreeSet::from(Column::DEFAULT),
+        }
+    }
+}
+
+/// The keys cookies are signed with.
+#[derive(Clone)]
+pub(crate) struct Keys {
+    /// Key used unless one is configured.
+    fallback: Key,
+}
+
+impl Keys {
+    pub(crate) fn new() -> Self {
+        Self {
+            fallback: Key::generate(),
+        }
+    }
+
+    /// The key to sign cookies with under the given configuration.
+    pub(crate) fn key(&self, config: &Config) -> Key {
+        match &config.sessions.key {
+            Some(key) => Key::derive_from(key.as_bytes()),
+            None => self.fallback.clone(),
+        }
+    }
+}
+
+/// Read preferences from the signed cookie of a request, falling back to
+/// defaults if it's missing or has been tampered with.
+pub(crate) fn read(headers: &HeaderMap, key: Key) -> Preferences {
+    let jar = SignedCookieJar::from_headers(headers, key);
+
+    let Some(cookie) = jar.get(COOKIE) else {
+        return Preferences::default();
+    };
+
+    serde_json::from_str(cookie.value()).unwrap_or_default()
+}
+
+/// Store preferences in a signed cookie.
+pub(crate) fn write(preferences: &Preferences, key: Key) -> SignedCookieJar {
+    let value = serde_json::to_string(preferences).unwrap_or_default();
+
+    let cookie = Cookie::build((COOKIE, value))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .permanent()
+        .build();
+
+    SignedCookieJar::new(key).add(cookie)
+}
@@ -0,0 +1,197 @@
+//! On-demand probes of the throughput and latency under load to a host.
+//!
+//! A probe sends data as fast as possible to a TCP port on the host, like the
+//! discard service or a `nc -l` sink, while repeatedly measuring how long it
+//! takes to connect to the same port.
+
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{Result, bail};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::history::{History, Probe};
+use crate::hosts::Host;
+
+/// The size of each write while sending data.
+const CHUNK: usize = 64 * 1024;
+/// How long to wait to connect to the host.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How often latency is measured while sending data.
+const LOADED_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of connections used to measure latency before sending data.
+const IDLE_SAMPLES: usize = 3;
+
+/// The status of the most recent probe of a host.
+#[derive(Debug, Clone)]
+pub enum Status {
+    /// A probe is in progress.
+    Running { started: Instant },
+    /// The probe completed.
+    Done(Probe),
+    /// The probe failed.
+    Failed { error: String, sampled: SystemTime },
+}
+
+/// State shared between probes and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// The most recent probe of each host.
+    pub results: Arc<Mutex<HashMap<Uuid, Status>>>,
+    history: History,
+}
+
+impl State {
+    /// Construct a new state, populated with the most recent probes stored
+    /// in history.
+    pub async fn new(history: History) -> Result<Self> {
+        let mut results = HashMap::new();
+
+        for probe in history.latest_probes().await? {
+            results.insert(probe.host, Status::Done(probe));
+        }
+
+        Ok(Self {
+            results: Arc::new(Mutex::new(results)),
+            history,
+        })
+    }
+
+    /// Start probing the given host.
+    ///
+    /// Returns `false` if probing isn't configured for the host or a probe
+    /// is already in progress.
+    pub async fn start(&self, host: &Host) -> bool {
+        let Some(port) = host.probe.port else {
+            return false;
+        };
+
+        let Some(name) = host.names().next() else {
+            return false;
+        };
+
+        {
+            let mut results = self.results.lock().await;
+
+            if let Some(Status::Running { .. }) = results.get(&host.id) {
+                return false;
+            }
+
+            let started = Instant::now();
+            results.insert(host.id, Status::Running { started });
+        }
+
+        let state = self.clone();
+        let id = host.id;
+        let name = name.to_owned();
+        let duration = host.probe.duration();
+
+        tokio::spawn(async move {
+            let status = match run(id, &name, port, duration).await {
+                Ok(probe) => {
+                    if let Err(error) = state.history.record_probe(probe).await {
+                        tracing::error!("Recording probe of {name}: {error:#}");
+                    }
+
+                    Status::Done(probe)
+                }
+                Err(error) => Status::Failed {
+                    error: format!("{error:#}"),
+                    sampled: SystemTime::now(),
+                },
+            };
+
+            state.results.lock().await.insert(id, status);
+        });
+
+        true
+    }
+}
+
+async fn run(host: Uuid, name: &str, port: u16, duration: Duration) -> Result<Probe> {
+    let mut idle = Vec::with_capacity(IDLE_SAMPLES);
+
+    for _ in 0..IDLE_SAMPLES {
+        idle.extend(connect_time(name, port).await);
+    }
+
+    let Some(mut stream) = connect(name, port).await? else {
+        bail!("timeout");
+    };
+
+    stream.set_nodelay(true)?;
+
+    let chunk = vec![0; CHUNK];
+    let mut bytes = 0u64;
+    let mut loaded = Vec::new();
+
+    let start = Instant::now();
+    let deadline = time::sleep(duration);
+    tokio::pin!(deadline);
+
+    // Latency is measured while data is being sent, where each measurement
+    // is allowed to finish before the next starts.
+    let loaded_time = || async move {
+        time::sleep(LOADED_INTERVAL).await;
+        connect_time(name, port).await
+    };
+
+    let mut measure = Box::pin(loaded_time());
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => {
+                break;
+            }
+            rtt = &mut measure => {
+                loaded.extend(rtt);
+                measure = Box::pin(loaded_time());
+            }
+            result = stream.write(&chunk) => {
+                bytes += result? as u64;
+            }
+        }
+    }
+
+    let elapsed = start.elapsed();
+
+    if bytes == 0 {
+        bail!("no data could be sent");
+    }
+
+    Ok(Probe {
+        host,
+        sampled: SystemTime::now(),
+        duration: elapsed,
+        bytes,
+        idle_rtt: median(idle),
+        loaded_rtt: median(loaded),
+    })
+}
+
+async fn connect(name: &str, port: u16) -> Result<Option<TcpStream>> {
+    match time::timeout(TIMEOUT, TcpStream::connect((name, port))).await {
+        Ok(stream) => Ok(Some(stream?)),
+        Err(..) => Ok(None),
+    }
+}
+
+/// Measure how long it takes to connect, if a connection could be
+/// established.
+async fn connect_time(name: &str, port: u16) -> Option<Duration> {
+    let start = Instant::now();
+    let _stream = connect(name, port).await.ok()??;
+    Some(start.elapsed())
+}
+
+fn median(mut samples: Vec<Duration>) -> Option<Duration> {
+    samples.sort();
+    samples.get(samples.len() / 2).copied()
+}
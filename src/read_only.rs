@@ -0,0 +1,65 @@
+//! Read-only mode, where every request which would change state is rejected,
+//! like waking hosts or the actions of the admin page.
+
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::Error;
+use crate::hosts;
+
+/// State of the read-only middleware.
+#[derive(Clone)]
+pub(crate) struct ReadOnly {
+    /// Whether read-only mode was enabled with `--read-only`, which takes
+    /// precedence over configuration.
+    forced: bool,
+    hosts: hosts::State,
+}
+
+impl ReadOnly {
+    pub(crate) fn new(forced: bool, hosts: hosts::State) -> Self {
+        Self { forced, hosts }
+    }
+
+    /// Whether read-only mode is in effect.
+    pub(crate) async fn is_enabled(&self) -> bool {
+        self.forced || self.hosts.config().await.read_only()
+    }
+}
+
+/// Reject requests which aren't safe with `403 Forbidden` in read-only mode.
+///
+/// All actions in wolo are submitted with methods other than `GET` or `HEAD`,
+/// so this covers every route without them having to know about it.
+pub(crate) async fn middleware(
+    State(state): State<ReadOnly>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Error> {
+    if !is_change(req.method(), req.uri().path()) {
+        return Ok(next.run(req).await);
+    }
+
+    if state.is_enabled().await {
+        return Err(Error::forbidden(
+            "This instance is read-only, so nothing can be changed through it",
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Whether a request may change state, which is every request with a method
+/// other than `GET`, `HEAD` or `OPTIONS`.
+///
+/// The exception is the Grafana datasource, which only reads but submits its
+/// queries with `POST`.
+pub(crate) fn is_change(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+
+    !(*method == Method::POST && path.starts_with("/api/v1/grafana/"))
+}
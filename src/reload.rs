@@ -0,0 +1,320 @@
+use core::fmt;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::{Mutex, Notify};
+
+use crate::config::{Config, Diagnostic, HostConfig};
+use crate::events::{self, Event};
+use crate::hosts;
+
+/// State shared between the reload task and the web server.
+#[derive(Clone)]
+pub struct State {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Notified when a reload has been requested.
+    requested: Notify,
+    /// Diagnostics produced the last time configuration was loaded.
+    diagnostics: Mutex<Vec<Diagnostic>>,
+}
+
+impl State {
+    /// Construct a new reload state with the diagnostics produced when
+    /// configuration was first loaded.
+    pub fn new(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                requested: Notify::new(),
+                diagnostics: Mutex::new(diagnostics),
+            }),
+        }
+    }
+
+    /// Request that configuration is reloaded.
+    pub fn request(&self) {
+        self.inner.requested.notify_one();
+    }
+
+    /// Get the diagnostics produced the last time configuration was loaded.
+    pub async fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.inner.diagnostics.lock().await.clone()
+    }
+}
+
+/// A single difference between two configurations.
+pub enum Change {
+    /// A host was added.
+    Added(String),
+    /// A host was removed.
+    Removed(String),
+    /// A host or option was changed and has been applied.
+    Changed(String),
+    /// An option was changed, but requires a restart to take effect.
+    RequiresRestart(&'static str),
+}
+
+impl fmt::Display for Change {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(name) => write!(f, "added host {name}"),
+            Change::Removed(name) => write!(f, "removed host {name}"),
+            Change::Changed(name) => write!(f, "changed {name}"),
+            Change::RequiresRestart(option) => {
+                write!(
+                    f,
+                    "changed {option}, which requires a restart to take effect"
+                )
+            }
+        }
+    }
+}
+
+/// Compute the differences between two configurations.
+pub fn diff(old: &Config, new: &Config) -> Vec<Change> {
+    fn by_name(config: &Config) -> BTreeMap<String, &HostConfig> {
+        let mut hosts = BTreeMap::new();
+
+        for host in &config.hosts {
+            hosts.insert(host.label(), host);
+        }
+
+        hosts
+    }
+
+    let mut changes = Vec::new();
+
+    if old.bind != new.bind {
+        changes.push(Change::RequiresRestart("bind"));
+    }
+
+    if old.read_only != new.read_only {
+        changes.push(Change::Changed(String::from("read_only")));
+    }
+
+    if old.home != new.home {
+        changes.push(Change::RequiresRestart("home"));
+    }
+
+    if old.mokuro != new.mokuro {
+        changes.push(Change::RequiresRestart("mokuro"));
+    }
+
+    if old.showcase != new.showcase {
+        changes.push(Change::RequiresRestart("showcase"));
+    }
+
+    if old.history != new.history {
+        changes.push(Change::RequiresRestart("history"));
+    }
+
+    if old.locale != new.locale {
+        changes.push(Change::Changed(String::from("locale")));
+    }
+
+    if old.dns != new.dns {
+        changes.push(Change::Changed(String::from("dns")));
+    }
+
+    if old.discovery != new.discovery {
+        changes.push(Change::Changed(String::from("discovery")));
+    }
+
+    if old.notify != new.notify {
+        changes.push(Change::Changed(String::from("notify")));
+    }
+
+    if old.extensions != new.extensions {
+        changes.push(Change::Changed(String::from("extensions")));
+    }
+
+    if old.report != new.report {
+        changes.push(Change::Changed(String::from("report")));
+    }
+
+    if old.metrics != new.metrics {
+        changes.push(Change::Changed(String::from("metrics")));
+    }
+
+    if old.script != new.script {
+        changes.push(Change::Changed(String::from("script")));
+    }
+
+    if old.power != new.power {
+        changes.push(Change::Changed(String::from("power")));
+    }
+
+    if old.supervisor != new.supervisor {
+        changes.push(Change::RequiresRestart("supervisor"));
+    }
+
+    if old.sessions != new.sessions {
+        changes.push(Change::Changed(String::from("sessions")));
+    }
+
+    if old.users != new.users {
+        changes.push(Change::Changed(String::from("users")));
+    }
+
+    if old.login != new.login {
+        changes.push(Change::Changed(String::from("login")));
+    }
+
+    if old.headers != new.headers {
+        changes.push(Change::Changed(String::from("headers")));
+    }
+
+    if old.segments != new.segments {
+        changes.push(Change::Changed(String::from("segments")));
+    }
+
+    if old.interfaces != new.interfaces {
+        changes.push(Change::Changed(String::from("interfaces")));
+    }
+
+    if old.admin != new.admin {
+        changes.push(Change::Changed(String::from("admin")));
+    }
+
+    if old.mokuro_access != new.mokuro_access {
+        changes.push(Change::Changed(String::from("mokuro access")));
+    }
+
+    if old.wake_listeners != new.wake_listeners {
+        changes.push(Change::RequiresRestart("wake_listeners"));
+    }
+
+    if old.defaults != new.defaults {
+        changes.push(Change::Changed(String::from("defaults")));
+    }
+
+    for (name, profile) in &new.profiles {
+        if old.profiles.get(name) != Some(profile) {
+            changes.push(Change::Changed(format!("profile {name}")));
+        }
+    }
+
+    for name in old.profiles.keys() {
+        if !new.profiles.contains_key(name) {
+            changes.push(Change::Changed(format!("profile {name}")));
+        }
+    }
+
+    let old = by_name(old);
+    let new = by_name(new);
+
+    for (name, host) in &new {
+        match old.get(name) {
+            None => changes.push(Change::Added(name.clone())),
+            Some(existing) if *existing != *host => {
+                changes.push(Change::Changed(format!("host {name}")))
+            }
+            Some(..) => {}
+        }
+    }
+
+    for name in old.keys() {
+        if !new.contains_key(name) {
+            changes.push(Change::Removed(name.clone()));
+        }
+    }
+
+    changes
+}
+
+/// Reload configuration using `load` whenever `SIGHUP` is received or a
+/// reload is requested, applying the differences to the host monitoring state.
+///
+/// If `load` doesn't return a configuration it had errors and the existing
+/// configuration is kept.
+pub async fn spawn(
+    state: State,
+    hosts: hosts::State,
+    events: events::Log,
+    mut load: impl FnMut() -> (Option<Config>, Vec<Diagnostic>),
+) -> Result<()> {
+    let mut hangup = Hangup::new()?;
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {}
+            _ = state.inner.requested.notified() => {}
+        }
+
+        tracing::info!("Reloading configuration");
+
+        let (config, diagnostics) = load();
+        *state.inner.diagnostics.lock().await = diagnostics;
+
+        let Some(config) = config else {
+            tracing::error!("Configuration had errors, keeping existing configuration");
+            events.publish(Event::ConfigRejected).await;
+            continue;
+        };
+
+        let changes = diff(&*hosts.config().await, &config);
+
+        if changes.is_empty() {
+            tracing::info!("Configuration unchanged");
+            events
+                .publish(Event::ConfigReloaded {
+                    changes: Vec::new(),
+                })
+                .await;
+            continue;
+        }
+
+        for change in &changes {
+            tracing::info!("Configuration {change}");
+        }
+
+        let changes = changes.iter().map(|c| c.to_string()).collect();
+
+        events.publish(Event::ConfigReloaded { changes }).await;
+
+        hosts.set_config(Arc::new(config)).await;
+    }
+}
+
+/// Receiver of `SIGHUP` signals.
+#[cfg(unix)]
+struct Hangup {
+    signal: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Hangup {
+    fn new() -> Result<Self> {
+        use anyhow::Context;
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let signal = signal(SignalKind::hangup()).context("installing SIGHUP handler")?;
+        Ok(Self { signal })
+    }
+
+    async fn recv(&mut self) {
+        if self.signal.recv().await.is_none() {
+            core::future::pending::<()>().await;
+        }
+    }
+}
+
+/// There are no `SIGHUP` signals outside of unix, so this never receives.
+#[cfg(not(unix))]
+struct Hangup;
+
+#[cfg(not(unix))]
+impl Hangup {
+    fn new() -> Result<Self> {
+        Ok(Self)
+    }
+
+    async fn recv(&mut self) {
+        core::future::pending().await
+    }
+}
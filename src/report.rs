@@ -0,0 +1,348 @@
+//! Periodic summary reports of availability, outages, new devices and wakes,
+//! which are written to a file or posted to webhooks.
+//!
+//! Availability and new devices come from history, while outages and wakes
+//! come from the timeline of events. Reports therefore only cover what is in
+//! the timeline, which is kept in memory.
+
+use core::fmt::Write;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::fs;
+use tokio::time;
+use uuid::Uuid;
+
+use crate::config::{NotifyConfig, ReportConfig};
+use crate::events::{self, Kind};
+use crate::history::History;
+use crate::hosts;
+use crate::notify::{self, Notification};
+use crate::showcase;
+use crate::utils::{self, Templates, millis};
+use crate::wake_queue;
+
+/// How often to check if a report is due.
+const CHECK: Duration = Duration::from_secs(60);
+
+/// Outages shorter than this aren't included in reports.
+const NOTABLE: Duration = Duration::from_secs(300);
+
+/// Percentile used for round trip times in history, which isn't part of the
+/// report but has to be specified.
+const PERCENTILE: u8 = 95;
+
+/// A summary of a period of time.
+#[derive(Serialize)]
+pub struct Report {
+    /// The length of the period in milliseconds.
+    period_ms: f64,
+    hosts: Vec<HostSummary>,
+    /// Outages which lasted long enough to be notable, in the order they
+    /// started.
+    outages: Vec<Outage>,
+    /// Devices seen for the first time during the period.
+    devices: Vec<Device>,
+    /// Wakes of all hosts and MAC addresses.
+    wakes: usize,
+}
+
+#[derive(Serialize)]
+struct HostSummary {
+    name: String,
+    /// Share of answered pings as a percentage, if any pings are in history.
+    availability: Option<String>,
+    wakes: usize,
+}
+
+#[derive(Serialize)]
+struct Outage {
+    name: String,
+    /// Time since the outage started in milliseconds.
+    age_ms: f64,
+    /// How long the outage lasted in milliseconds, unless it's ongoing.
+    duration_ms: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Device {
+    mac: String,
+    /// Time since the device was first seen in milliseconds.
+    age_ms: f64,
+}
+
+/// Build a report of the given period, ending now.
+pub async fn build(
+    hosts: &hosts::State,
+    history: &History,
+    events: &events::Log,
+    showcase: &showcase::Helper,
+    period: Duration,
+) -> Result<Report> {
+    let now = SystemTime::now();
+    let since = now.checked_sub(period).unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let hosts = hosts.hosts();
+    let mut showcase = showcase.lock().await;
+
+    let mut names = HashMap::new();
+
+    for host in hosts.iter() {
+        let name = host.names().next().unwrap_or_default();
+        names.insert(host.id, showcase.host_name(host, name));
+    }
+
+    let mut wakes = 0;
+    let mut host_wakes = HashMap::<Uuid, usize>::new();
+    let mut down = HashMap::<Uuid, SystemTime>::new();
+    let mut outages = Vec::new();
+
+    let mut timeline = events.events().await;
+    timeline.reverse();
+
+    for e in timeline.iter().filter(|e| e.sampled >= since) {
+        match e.kind {
+            Kind::Wake if e.message.starts_with(wake_queue::SENT) => {
+                wakes += 1;
+
+                if let Some(host) = e.host {
+                    *host_wakes.entry(host).or_default() += 1;
+                }
+            }
+            Kind::Down => {
+                if let Some(host) = e.host {
+                    down.entry(host).or_insert(e.sampled);
+                }
+            }
+            Kind::Up => {
+                let Some(started) = e.host.and_then(|h| down.remove(&h)) else {
+                    continue;
+                };
+
+                let duration = e.sampled.duration_since(started).unwrap_or_default();
+
+                if duration >= NOTABLE {
+                    outages.push((started, e.host, Some(duration)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (host, started) in down {
+        outages.push((started, Some(host), None));
+    }
+
+    outages.sort_by_key(|&(started, ..)| started);
+
+    let outages = outages
+        .into_iter()
+        .filter_map(|(started, host, duration)| {
+            Some(Outage {
+                name: names.get(&host?)?.clone(),
+                age_ms: now.duration_since(started).map_or(0.0, millis),
+                duration_ms: duration.map(millis),
+            })
+        })
+        .collect();
+
+    let mut summaries = Vec::with_capacity(hosts.len());
+
+    for host in hosts.iter() {
+        let stats = history.stats(host.id, since, PERCENTILE).await?;
+
+        let availability = (stats.samples > 0).then(|| {
+            let ratio = stats.answered as f64 / stats.samples as f64;
+            format!("{:.2}%", ratio * 100.0)
+        });
+
+        summaries.push(HostSummary {
+            name: names.get(&host.id).cloned().unwrap_or_default(),
+            availability,
+            wakes: host_wakes.get(&host.id).copied().unwrap_or_default(),
+        });
+    }
+
+    let mut devices = Vec::new();
+
+    for (mac, first_seen) in history.devices_since(since).await? {
+        devices.push(Device {
+            mac: showcase.local_mac(mac).to_string(),
+            age_ms: now.duration_since(first_seen).map_or(0.0, millis),
+        });
+    }
+
+    Ok(Report {
+        period_ms: millis(period),
+        hosts: summaries,
+        outages,
+        devices,
+        wakes,
+    })
+}
+
+impl Report {
+    /// Render the report as HTML.
+    pub fn html(&self, templates: &Templates) -> Result<String> {
+        templates.render("report.html", self)
+    }
+
+    /// Render the report as markdown.
+    pub fn markdown(&self) -> String {
+        let mut out = String::new();
+
+        let period = utils::duration(Duration::from_millis(self.period_ms as u64));
+
+        _ = writeln!(out, "# Summary of the last {period}");
+        _ = writeln!(out);
+        _ = writeln!(out, "## Hosts");
+        _ = writeln!(out);
+        _ = writeln!(out, "| Host | Availability | Wakes |");
+        _ = writeln!(out, "|------|--------------|-------|");
+
+        for h in &self.hosts {
+            let availability = h.availability.as_deref().unwrap_or("-");
+            _ = writeln!(out, "| {} | {availability} | {} |", h.name, h.wakes);
+        }
+
+        _ = writeln!(out);
+        _ = writeln!(out, "## Outages");
+        _ = writeln!(out);
+
+        if self.outages.is_empty() {
+            _ = writeln!(out, "No notable outages.");
+        }
+
+        for o in &self.outages {
+            let age = utils::duration(Duration::from_millis(o.age_ms as u64));
+
+            match o.duration_ms {
+                Some(ms) => {
+                    let duration = utils::duration(Duration::from_millis(ms as u64));
+                    _ = writeln!(out, "- {} was down for {duration}, {age} ago", o.name);
+                }
+                None => {
+                    _ = writeln!(out, "- {} has been down for {age}", o.name);
+                }
+            }
+        }
+
+        _ = writeln!(out);
+        _ = writeln!(out, "## New devices");
+        _ = writeln!(out);
+
+        if self.devices.is_empty() {
+            _ = writeln!(out, "No new devices.");
+        }
+
+        for d in &self.devices {
+            let age = utils::duration(Duration::from_millis(d.age_ms as u64));
+            _ = writeln!(out, "- {} first seen {age} ago", d.mac);
+        }
+
+        _ = writeln!(out);
+        _ = writeln!(out, "{} wakes in total.", self.wakes);
+        out
+    }
+}
+
+/// Spawn the task which generates reports as configured.
+///
+/// The first report is generated one interval after the report file was last
+/// written, or one interval after starting if there is no report file.
+pub async fn spawn(
+    hosts: hosts::State,
+    history: History,
+    events: events::Log,
+    templates: Templates,
+    showcase: showcase::Helper,
+) {
+    let mut last = None::<SystemTime>;
+    let mut tick = time::interval(CHECK);
+
+    loop {
+        tick.tick().await;
+
+        let config = hosts.config().await;
+
+        if !config.report.is_enabled() {
+            last = None;
+            continue;
+        }
+
+        let now = SystemTime::now();
+
+        let previous = match last {
+            Some(last) => last,
+            None => {
+                let modified = match &config.report.path {
+                    Some(path) => modified(path).await,
+                    None => None,
+                };
+
+                *last.insert(modified.unwrap_or(now))
+            }
+        };
+
+        let interval = config.report.interval();
+
+        if now < previous + interval {
+            continue;
+        }
+
+        last = Some(now);
+
+        let report = match build(&hosts, &history, &events, &showcase, interval).await {
+            Ok(report) => report,
+            Err(error) => {
+                tracing::error!("Building report: {error:#}");
+                continue;
+            }
+        };
+
+        if let Err(error) = deliver(&config.report, &config.notify, &report, &templates).await {
+            tracing::error!("Delivering report: {error:#}");
+        }
+    }
+}
+
+async fn deliver(
+    config: &ReportConfig,
+    notify: &NotifyConfig,
+    report: &Report,
+    templates: &Templates,
+) -> Result<()> {
+    if config.notify() {
+        let notification = Notification::Report {
+            markdown: report.markdown(),
+        };
+
+        notify::send(notify, &notification);
+    }
+
+    if let Some(path) = &config.path {
+        let contents = if path.extension().is_some_and(|e| e == "md") {
+            report.markdown()
+        } else {
+            report.html(templates)?
+        };
+
+        fs::write(path, contents)
+            .await
+            .with_context(|| path.display().to_string())?;
+
+        tracing::info!("Wrote report to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// When a file was last modified, if it exists.
+async fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).await.ok()?.modified().ok()
+}
@@ -0,0 +1,324 @@
+//! Hooks written in [Rhai], which customize behavior without recompiling.
+//!
+//! The script is loaded from the configured path and reloaded when it
+//! changes. It can define any of these functions, which are called with a map
+//! describing what happened:
+//!
+//! * `on_host_state_change(change)` when a host goes up or down.
+//! * `before_wake(wake)` before magic packets are sent, which can cancel the
+//!   wake by returning `false` or a string with the reason.
+//! * `classify_device(device)` when a new device is discovered, which can
+//!   return `"ignore"` or `"suspicious"`.
+//!
+//! Scripts are sandboxed. They can't load modules or access files, and the
+//! number of operations they may perform is limited. The `hosts()` function
+//! gives read-only access to the configured hosts and their status.
+//!
+//! [Rhai]: https://rhai.rs
+
+use core::net::Ipv4Addr;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use macaddr::MacAddr6;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{AST, Array, Dynamic, Engine, Map, Scope};
+use tokio::task;
+
+use crate::hosts::{self, Host};
+use crate::ping_loop::{self, HostStatus};
+use crate::wake_queue::Wake;
+
+/// The largest number of operations a single hook may perform.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// How a script classified a new device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classification {
+    /// The device is handled like any other new device.
+    Default,
+    /// The device isn't reported.
+    Ignore,
+    /// The device is marked as suspicious.
+    Suspicious,
+}
+
+/// A compiled script and when the file it was compiled from was modified.
+struct Script {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// The compiled script, unless it failed to compile.
+    ast: Option<Arc<AST>>,
+}
+
+struct Inner {
+    engine: Engine,
+    /// Hosts made available through `hosts()` while a hook is running.
+    snapshot: Arc<Mutex<Array>>,
+    script: Option<Script>,
+}
+
+/// State shared by everything which calls hooks.
+#[derive(Clone)]
+pub struct State {
+    inner: Arc<Mutex<Inner>>,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+}
+
+impl State {
+    /// Construct a new state with a sandboxed engine.
+    pub fn new(hosts: hosts::State, ping_state: ping_loop::State) -> Self {
+        let snapshot = Arc::new(Mutex::new(Array::new()));
+
+        let mut engine = Engine::new();
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_string_size(64 * 1024);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+        engine.on_print(|text| tracing::info!("script: {text}"));
+        engine.on_debug(|text, _, _| tracing::debug!("script: {text}"));
+
+        let hosts_snapshot = snapshot.clone();
+        engine.register_fn("hosts", move || hosts_snapshot.lock().unwrap().clone());
+
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                engine,
+                snapshot,
+                script: None,
+            })),
+            hosts,
+            ping_state,
+        }
+    }
+
+    /// Call `on_host_state_change` for a host which went from one status to
+    /// another.
+    pub async fn on_host_state_change(&self, host: &Host, from: HostStatus, to: HostStatus) {
+        let mut change = host_map(host);
+        change.insert("from".into(), status(from).into());
+        change.insert("to".into(), status(to).into());
+
+        self.call("on_host_state_change", change).await;
+    }
+
+    /// Call `before_wake` for a wake which is about to be queued, returning
+    /// the reason if the script cancelled it.
+    pub async fn before_wake(&self, wake: &Wake) -> Result<(), String> {
+        let hosts = self.hosts.hosts();
+
+        let mut map = match wake.host.and_then(|id| hosts.iter().find(|h| h.id == id)) {
+            Some(host) => host_map(host),
+            None => Map::new(),
+        };
+
+        map.insert("name".into(), wake.name.clone().into());
+        map.insert("macs".into(), macs(&wake.macs).into());
+
+        let Some(result) = self.call("before_wake", map).await else {
+            return Ok(());
+        };
+
+        if let Ok(false) = result.as_bool() {
+            return Err(String::from("cancelled by script"));
+        }
+
+        if result.is_string() {
+            return Err(result.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Call `classify_device` for a device which was seen for the first time.
+    pub async fn classify_device(
+        &self,
+        mac: MacAddr6,
+        address: Ipv4Addr,
+        device: &str,
+    ) -> Classification {
+        let mut map = Map::new();
+        map.insert("mac".into(), mac.to_string().into());
+        map.insert("address".into(), address.to_string().into());
+        map.insert("device".into(), device.into());
+
+        let Some(result) = self.call("classify_device", map).await else {
+            return Classification::Default;
+        };
+
+        if !result.is_string() {
+            return Classification::Default;
+        }
+
+        match result.to_string().as_str() {
+            "ignore" => Classification::Ignore,
+            "suspicious" => Classification::Suspicious,
+            other => {
+                tracing::warn!("classify_device: unknown classification `{other}`");
+                Classification::Default
+            }
+        }
+    }
+
+    /// Call a hook with a single argument, returning what it returned if
+    /// it's defined and didn't fail.
+    async fn call(&self, name: &str, argument: Map) -> Option<Dynamic> {
+        let path = self.hosts.config().await.script.path.clone()?;
+        let snapshot = self.snapshot().await;
+
+        // Loading and running the script blocks, so it's kept off the threads
+        // which drive async tasks.
+        let inner = self.inner.clone();
+        let hook = name.to_owned();
+
+        let result = task::spawn_blocking(move || {
+            inner.lock().unwrap().call(&path, &hook, snapshot, argument)
+        })
+        .await;
+
+        match result {
+            Ok(value) => value,
+            Err(error) => {
+                tracing::error!("{name}: {error}");
+                None
+            }
+        }
+    }
+
+    /// Take a snapshot of hosts and their status.
+    async fn snapshot(&self) -> Array {
+        let hosts = self.hosts.hosts();
+
+        let statuses = {
+            let pinged = self.ping_state.pinged.lock().await;
+            ping_loop::statuses(&hosts, &pinged)
+        };
+
+        let mut out = Array::with_capacity(hosts.len());
+
+        for host in hosts.iter() {
+            let mut map = host_map(host);
+
+            let s = statuses
+                .get(&host.id)
+                .map_or(HostStatus::Unknown, |s| s.status);
+
+            map.insert("status".into(), status(s).into());
+            out.push(map.into());
+        }
+
+        out
+    }
+}
+
+impl Inner {
+    /// Call a hook in the script at the given path, with `snapshot` as the
+    /// hosts available through `hosts()`.
+    fn call(&mut self, path: &Path, name: &str, snapshot: Array, argument: Map) -> Option<Dynamic> {
+        let ast = self.load(path)?;
+
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == 1)
+        {
+            return None;
+        }
+
+        *self.snapshot.lock().unwrap() = snapshot;
+
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &ast, name, (argument,));
+
+        self.snapshot.lock().unwrap().clear();
+
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                tracing::warn!("{}: {name}: {error}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Load the script at the given path, compiling it again if it has been
+    /// modified.
+    fn load(&mut self, path: &Path) -> Option<Arc<AST>> {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+
+        if let Some(script) = &self.script
+            && script.path == path
+            && script.modified == modified
+        {
+            return script.ast.clone();
+        }
+
+        let ast = match fs::read_to_string(path) {
+            Ok(source) => match self.engine.compile(&source) {
+                Ok(ast) => {
+                    tracing::info!("{}: loaded script", path.display());
+                    Some(Arc::new(ast))
+                }
+                Err(error) => {
+                    tracing::error!("{}: {error}", path.display());
+                    None
+                }
+            },
+            Err(error) => {
+                tracing::error!("{}: {error}", path.display());
+                None
+            }
+        };
+
+        self.script = Some(Script {
+            path: path.to_owned(),
+            modified,
+            ast: ast.clone(),
+        });
+
+        ast
+    }
+}
+
+/// Describe a host to a script.
+fn host_map(host: &Host) -> Map {
+    let names = host
+        .names()
+        .map(|n| Dynamic::from(n.to_owned()))
+        .collect::<Array>();
+
+    let tags = host
+        .tags
+        .iter()
+        .map(|t| Dynamic::from(t.clone()))
+        .collect::<Array>();
+
+    let mut map = Map::new();
+    map.insert("id".into(), host.id.to_string().into());
+    map.insert("names".into(), names.into());
+    map.insert("tags".into(), tags.into());
+    map.insert("macs".into(), macs(&host.macs).into());
+    map
+}
+
+fn macs<'a>(macs: impl IntoIterator<Item = &'a MacAddr6>) -> Array {
+    macs.into_iter()
+        .map(|m| Dynamic::from(m.to_string()))
+        .collect()
+}
+
+fn status(status: HostStatus) -> &'static str {
+    match status {
+        HostStatus::Up => "up",
+        HostStatus::Down => "down",
+        HostStatus::Unknown => "unknown",
+        HostStatus::Unreachable => "unreachable",
+    }
+}
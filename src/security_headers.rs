@@ -0,0 +1,48 @@
+//! Security headers which are added to every response, like a
+//! `Content-Security-Policy` which only allows the assets wolo embeds.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::hosts;
+
+/// Add security headers to responses which don't already set them.
+///
+/// Headers configured as an empty string in `[headers]` are left out.
+pub(crate) async fn middleware(
+    State(hosts): State<hosts::State>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let config = hosts.config().await;
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+
+    insert(
+        headers,
+        header::CONTENT_SECURITY_POLICY,
+        &config.headers.content_security_policy(),
+    );
+    insert(
+        headers,
+        header::REFERRER_POLICY,
+        config.headers.referrer_policy(),
+    );
+    insert(headers, header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    response
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if value.is_empty() || headers.contains_key(&name) {
+        return;
+    }
+
+    let Ok(value) = HeaderValue::from_str(value) else {
+        tracing::warn!("{name}: invalid header value");
+        return;
+    };
+
+    headers.insert(name, value);
+}
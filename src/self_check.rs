@@ -0,0 +1,268 @@
+//! Checks of what wolo needs from the system it runs on, which are performed
+//! when it starts.
+//!
+//! The results are printed as a table with hints on how to fix what failed,
+//! and served from `/readyz` so that orchestrators can tell whether wolo is
+//! able to do its job.
+
+use core::fmt;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use tokio::task;
+
+use crate::cli;
+use crate::config::Config;
+use crate::host_name_cache::{self, ResolveErrorKind};
+use crate::wake_on_lan::BroadcastSocket;
+
+/// How a check turned out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Status {
+    Ok,
+    /// The check wasn't performed, like when it isn't needed.
+    Skipped,
+    /// Something is off, but wolo works regardless.
+    Warning,
+    Failed,
+}
+
+impl fmt::Display for Status {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Skipped => write!(f, "skipped"),
+            Status::Warning => write!(f, "warning"),
+            Status::Failed => write!(f, "FAILED"),
+        }
+    }
+}
+
+/// The result of a single check.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+    /// How to fix the problem if the check didn't pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'static str>,
+}
+
+/// The results of all checks.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn push(&mut self, name: impl fmt::Display, status: Status, detail: impl fmt::Display) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            status,
+            detail: detail.to_string(),
+            hint: None,
+        });
+    }
+
+    fn push_hint(
+        &mut self,
+        name: impl fmt::Display,
+        status: Status,
+        detail: impl fmt::Display,
+        hint: &'static str,
+    ) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            status,
+            detail: detail.to_string(),
+            hint: Some(hint),
+        });
+    }
+
+    /// Test if any check failed.
+    pub(crate) fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == Status::Failed)
+    }
+
+    /// Print the report as a table followed by hints for checks which didn't
+    /// pass.
+    pub(crate) fn print(&self) {
+        let rows = self
+            .checks
+            .iter()
+            .map(|c| [c.name.clone(), c.status.to_string(), c.detail.clone()])
+            .collect::<Vec<_>>();
+
+        cli::print_table(["CHECK", "STATUS", "DETAIL"], &rows);
+
+        for c in &self.checks {
+            if let (Status::Warning | Status::Failed, Some(hint)) = (c.status, c.hint) {
+                println!("hint: {}: {hint}", c.name);
+            }
+        }
+    }
+}
+
+/// Paths which are checked in addition to those in configuration.
+pub(crate) struct Paths<'a> {
+    pub(crate) ethers: &'a [PathBuf],
+    pub(crate) hosts: &'a [PathBuf],
+    pub(crate) home: &'a [PathBuf],
+}
+
+/// Perform all checks.
+///
+/// Sockets aren't needed when simulating, so they are skipped.
+pub(crate) async fn run(config: &Config, paths: Paths<'_>, simulate: bool) -> Report {
+    let mut report = Report::default();
+
+    check_sockets(&mut report, simulate).await;
+
+    let files = paths
+        .ethers
+        .iter()
+        .chain(paths.hosts)
+        .chain(paths.home)
+        .chain(&config.home)
+        .chain(&config.script.path)
+        .chain(&config.history.path);
+
+    for path in files {
+        check_file(&mut report, path);
+    }
+
+    for m in &config.mokuro {
+        match fs::read_dir(&m.path) {
+            Ok(..) => report.push(m.path.display(), Status::Ok, "readable"),
+            Err(error) => report.push_hint(
+                m.path.display(),
+                Status::Failed,
+                error,
+                "check that the directory exists and that wolo may read it",
+            ),
+        }
+    }
+
+    check_dns(&mut report, config).await;
+    report
+}
+
+async fn check_sockets(report: &mut Report, simulate: bool) {
+    const ICMP_HINT: &str = "run wolo as root, grant it CAP_NET_RAW, or allow unprivileged \
+        ICMP sockets through the net.ipv4.ping_group_range sysctl";
+
+    if simulate {
+        report.push("ICMPv4 socket", Status::Skipped, "simulating");
+        report.push("ICMPv6 socket", Status::Skipped, "simulating");
+        report.push("Wake-on-LAN socket", Status::Skipped, "simulating");
+        return;
+    }
+
+    // Pings are sent over whichever families are available, so a missing one
+    // only warrants a warning as long as the other one is there.
+    match lib::Pinger::any() {
+        Ok(any) => {
+            for family in any.families() {
+                report.push(format!("{family} socket"), Status::Ok, "created");
+            }
+
+            for (family, error) in any.errors {
+                report.push_hint(
+                    format!("{family} socket"),
+                    Status::Warning,
+                    error,
+                    ICMP_HINT,
+                );
+            }
+        }
+        Err(error) => report.push_hint("ICMP sockets", Status::Failed, error, ICMP_HINT),
+    }
+
+    match BroadcastSocket::bind().await {
+        Ok(..) => report.push("Wake-on-LAN socket", Status::Ok, "bound with broadcast"),
+        Err(error) => report.push_hint(
+            "Wake-on-LAN socket",
+            Status::Failed,
+            error,
+            "check that wolo may bind UDP sockets and send broadcasts, \
+            which sandboxes and firewalls can prevent",
+        ),
+    }
+}
+
+/// Check that a file is readable if it exists.
+fn check_file(report: &mut Report, path: &Path) {
+    match fs::File::open(path) {
+        Ok(..) => report.push(path.display(), Status::Ok, "readable"),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            report.push(path.display(), Status::Skipped, "does not exist");
+        }
+        Err(error) => report.push_hint(
+            path.display(),
+            Status::Failed,
+            error,
+            "check the permissions of the file and the directories leading to it",
+        ),
+    }
+}
+
+/// Check that names can be resolved by resolving the first configured host.
+///
+/// A name which doesn't exist is still an answer, so only failing to get one
+/// counts.
+async fn check_dns(report: &mut Report, config: &Config) {
+    let Some(name) = config.hosts.iter().flat_map(|h| &h.names).next().cloned() else {
+        report.push("DNS", Status::Skipped, "no hosts configured");
+        return;
+    };
+
+    let dns = config.dns.clone();
+
+    let result = task::spawn_blocking(move || {
+        let result = host_name_cache::resolve(&dns, &name);
+        (name, result)
+    })
+    .await;
+
+    match result {
+        Ok((name, Ok(..) | Err((ResolveErrorKind::NotFound, _)))) => {
+            report.push("DNS", Status::Ok, format_args!("resolved `{name}`"));
+        }
+        Ok((name, Err((_, error)))) => report.push_hint(
+            "DNS",
+            Status::Warning,
+            format_args!("`{name}`: {error}"),
+            "check that the DNS servers in [dns] or /etc/resolv.conf are reachable",
+        ),
+        Err(error) => report.push("DNS", Status::Warning, error),
+    }
+}
+
+/// Route which serves the report, with `503 Service Unavailable` if any check
+/// failed.
+pub(crate) fn router(report: Arc<Report>) -> Router {
+    Router::new()
+        .route("/readyz", get(readyz))
+        .with_state(report)
+}
+
+async fn readyz(State(report): State<Arc<Report>>) -> (StatusCode, Json<Arc<Report>>) {
+    let status = if report.has_failures() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    (status, Json(report))
+}
@@ -1,15 +1,17 @@
-use core::net::IpAddr;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
-use std::net::Ipv4Addr;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 use macaddr::MacAddr6;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use tokio::sync::{Mutex, MutexGuard};
+use twox_hash::xxhash3_128;
 use uuid::Uuid;
 
+use crate::config::ShowcaseConfig;
+use crate::hosts::Host;
+
 #[derive(Clone)]
 enum Inner {
     Enabled(Arc<Mutex<State>>),
@@ -44,40 +46,176 @@ pub struct LockedHelper<'a> {
 }
 
 impl LockedHelper<'_> {
-    /// Get a host name..
-    pub fn host_name(&mut self, host_id: Uuid, name: &str) -> String {
+    /// Test if showcase mode is enabled.
+    pub fn is_enabled(&self) -> bool {
+        matches!(self.inner, LockKind::Enabled(..))
+    }
+
+    /// Get a name of the given host.
+    pub fn host_name(&mut self, host: &Host, name: &str) -> String {
+        match &mut self.inner {
+            LockKind::Enabled(state) if !state.is_excluded(host) => state.host_name(host.id, name),
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Get a MAC address of the given host.
+    pub fn mac(&mut self, host: &Host, mac: MacAddr6) -> MacAddr6 {
+        match &mut self.inner {
+            LockKind::Enabled(state) if !state.is_excluded(host) => state.mac(mac),
+            _ => mac,
+        }
+    }
+
+    /// Get an IP address of the given host.
+    pub fn ip(&mut self, host: &Host, ip: IpAddr) -> IpAddr {
         match &mut self.inner {
-            LockKind::Enabled(state) => state.host_name(host_id, name),
-            LockKind::Disabled => name.to_owned(),
+            LockKind::Enabled(state) if !state.is_excluded(host) => state.ip(ip),
+            _ => ip,
         }
     }
 
-    /// Get a MAC address.
-    pub fn mac(&mut self, mac: MacAddr6) -> MacAddr6 {
+    /// Get a MAC address of the machine wolo runs on.
+    pub fn local_mac(&mut self, mac: MacAddr6) -> MacAddr6 {
         match &mut self.inner {
             LockKind::Enabled(state) => state.mac(mac),
             LockKind::Disabled => mac,
         }
     }
 
-    /// Get an IP address.
-    pub fn ip(&mut self, ip: IpAddr) -> IpAddr {
+    /// Get an IP address of the machine wolo runs on.
+    pub fn local_ip(&mut self, ip: IpAddr) -> IpAddr {
         match &mut self.inner {
             LockKind::Enabled(state) => state.ip(ip),
             LockKind::Disabled => ip,
         }
     }
+
+    /// Get a piece of free-form text, like a title or a paragraph.
+    pub fn text(&mut self, text: &str) -> String {
+        match &mut self.inner {
+            LockKind::Enabled(state) => state.text(text),
+            LockKind::Disabled => text.to_owned(),
+        }
+    }
+
+    /// Get a path, or a URL in which case the host is replaced as well.
+    pub fn path(&mut self, path: &str) -> String {
+        match &mut self.inner {
+            LockKind::Enabled(state) => state.path(path),
+            LockKind::Disabled => path.to_owned(),
+        }
+    }
 }
 
-#[derive(Default)]
+/// Words used to replace text.
+const WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "labore",
+    "magna",
+    "aliqua",
+];
+
+// Kinds of values which are hashed, so that equal bytes of different kinds
+// produce different showcase values.
+const HOST: u8 = 0x01;
+const MAC: u8 = 0x02;
+const IP: u8 = 0x03;
+const TEXT: u8 = 0x04;
+const SEGMENT: u8 = 0x05;
+
+/// Host names used to replace real ones unless configured otherwise.
+const HOST_NAMES: &[&str] = &[
+    "desktop",
+    "raspberrypi",
+    "router",
+    "laptop",
+    "jumphost",
+    "nas",
+    "printer",
+    "media",
+    "workstation",
+    "homeassistant",
+];
+
+/// Documentation subnets which fake IPv4 addresses are allocated from.
+const V4_SUBNETS: [[u8; 3]; 3] = [[192, 0, 2], [198, 51, 100], [203, 0, 113]];
+
+/// Showcase values are derived by hashing the real value together with a
+/// seed, so that they are the same across restarts.
 struct State {
-    host_to_index: HashMap<Uuid, usize>,
+    seed: u64,
+    /// Names to pick host names from.
+    names: Vec<String>,
+    /// Domain of all but the first name of each host.
+    domain: String,
+    /// Names of hosts which are not anonymized.
+    exclude: BTreeSet<String>,
     host_names: HashMap<(Uuid, String), String>,
+    /// Base names which have been assigned to hosts.
+    bases: HashMap<String, Uuid>,
     mac: HashMap<MacAddr6, MacAddr6>,
     ips: HashMap<IpAddr, IpAddr>,
+    /// Real /24 networks and the documentation subnet they are mapped to.
+    v4_subnets: HashMap<[u8; 3], [u8; 3]>,
+    texts: HashMap<String, String>,
+    segments: HashMap<String, String>,
 }
 
 impl State {
+    fn new(config: &ShowcaseConfig) -> Self {
+        let names = if config.names.is_empty() {
+            HOST_NAMES.iter().map(|n| (*n).to_owned()).collect()
+        } else {
+            config.names.clone()
+        };
+
+        Self {
+            seed: config.seed.unwrap_or_default(),
+            names,
+            domain: config.domain.clone().unwrap_or_else(|| String::from("lan")),
+            exclude: config.exclude.clone(),
+            host_names: HashMap::new(),
+            bases: HashMap::new(),
+            mac: HashMap::new(),
+            ips: HashMap::new(),
+            v4_subnets: HashMap::new(),
+            texts: HashMap::new(),
+            segments: HashMap::new(),
+        }
+    }
+
+    /// Test if the given host is excluded from being anonymized.
+    fn is_excluded(&self, host: &Host) -> bool {
+        host.names.iter().any(|n| self.exclude.contains(n))
+    }
+
+    /// Hash the given bytes together with the seed.
+    fn hash(&self, kind: u8, bytes: &[u8]) -> u64 {
+        let mut hasher = xxhash3_128::Hasher::default();
+        hasher.write(&self.seed.to_be_bytes());
+        hasher.write(&[kind]);
+        hasher.write(bytes);
+        hasher.finish_128() as u64
+    }
+
+    /// Construct a random number generator seeded from the given bytes.
+    fn rng(&self, kind: u8, bytes: &[u8]) -> SmallRng {
+        SmallRng::seed_from_u64(self.hash(kind, bytes))
+    }
+
     fn host_name(&mut self, host_id: Uuid, name: &str) -> String {
         let key = (host_id, name.to_owned());
 
@@ -85,16 +223,7 @@ impl State {
             return existing.clone();
         }
 
-        let index = self.host_index(host_id);
-
-        let base = match index {
-            0 => "desktop".to_owned(),
-            1 => "raspberrypi".to_owned(),
-            2 => "router".to_owned(),
-            3 => "laptop".to_owned(),
-            4 => "jumphost".to_owned(),
-            _ => format!("host{index}"),
-        };
+        let base = self.host_base(host_id);
 
         let showcase_name = match self
             .host_names
@@ -103,28 +232,46 @@ impl State {
             .count()
         {
             0 => base.to_string(),
-            _ => format!("{base}.lan"),
+            _ => format!("{base}.{}", self.domain),
         };
 
         self.host_names.insert(key, showcase_name.clone());
         showcase_name
     }
 
+    /// Get the base name of a host.
+    ///
+    /// Names are picked from the configured names based on the host id, and
+    /// only if two hosts pick the same name is a number appended to it.
+    fn host_base(&mut self, host_id: Uuid) -> String {
+        if let Some((base, _)) = self.bases.iter().find(|(_, id)| **id == host_id) {
+            return base.clone();
+        }
+
+        let index = self.hash(HOST, host_id.as_bytes()) as usize % self.names.len();
+        let name = self.names[index].clone();
+        let mut base = name.clone();
+        let mut n = 1;
+
+        while self.bases.contains_key(&base) {
+            n += 1;
+            base = format!("{name}{n}");
+        }
+
+        self.bases.insert(base.clone(), host_id);
+        base
+    }
+
     fn mac(&mut self, mac: MacAddr6) -> MacAddr6 {
         if let Some(existing) = self.mac.get(&mac) {
             return *existing;
         }
 
-        let mut rng = SmallRng::seed_from_u64(self.mac.len() as u64);
-
-        let out = MacAddr6::new(
-            rng.random(),
-            rng.random(),
-            rng.random(),
-            rng.random(),
-            rng.random(),
-            rng.random(),
-        );
+        let mut rng = self.rng(MAC, mac.as_bytes());
+        let mut bytes = rng.random::<[u8; 6]>();
+        // Make the address a locally administered unicast address.
+        bytes[0] = (bytes[0] | 0b10) & !0b1;
+        let out = MacAddr6::from(bytes);
 
         self.mac.insert(mac, out);
         out
@@ -135,49 +282,142 @@ impl State {
             return *existing;
         }
 
-        let mut rng = SmallRng::seed_from_u64(self.ips.len() as u64);
-
         let out = match ip {
-            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::new(
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-            )),
-            IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::new(
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-                rng.random(),
-            )),
+            IpAddr::V4(ip) => {
+                let [a, b, c, d] = ip.octets();
+                let [a, b, c] = self.v4_subnet([a, b, c]);
+                IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+            }
+            IpAddr::V6(ip) => {
+                let octets = ip.octets();
+                // The network part determines the subnet and the interface
+                // part is randomized separately, since it might be derived
+                // from a MAC address.
+                let subnet = self.hash(IP, &octets[..8]) as u32;
+                let interface = self.hash(IP, &octets) & 0xffff_ffff_ffff;
+
+                IpAddr::V6(Ipv6Addr::new(
+                    0x2001,
+                    0xdb8,
+                    (subnet >> 16) as u16,
+                    subnet as u16,
+                    0,
+                    (interface >> 32) as u16,
+                    (interface >> 16) as u16,
+                    interface as u16,
+                ))
+            }
         };
 
         self.ips.insert(ip, out);
         out
     }
 
-    fn host_index(&mut self, host_id: Uuid) -> usize {
-        let next = self.host_to_index.len();
+    /// Map a real /24 network to a documentation subnet, preferring one which
+    /// hasn't been used yet so that distinct networks stay distinct.
+    fn v4_subnet(&mut self, network: [u8; 3]) -> [u8; 3] {
+        if let Some(subnet) = self.v4_subnets.get(&network) {
+            return *subnet;
+        }
+
+        let start = self.hash(IP, &network) as usize;
+
+        let subnet = (0..V4_SUBNETS.len())
+            .map(|n| V4_SUBNETS[(start + n) % V4_SUBNETS.len()])
+            .find(|s| !self.v4_subnets.values().any(|v| v == s))
+            .unwrap_or(V4_SUBNETS[start % V4_SUBNETS.len()]);
+
+        self.v4_subnets.insert(network, subnet);
+        subnet
+    }
+
+    fn text(&mut self, text: &str) -> String {
+        if let Some(existing) = self.texts.get(text) {
+            return existing.clone();
+        }
+
+        let mut rng = self.rng(TEXT, text.as_bytes());
+        let mut out = String::with_capacity(text.len());
+        let mut in_word = false;
+
+        // Replace every word while keeping whitespace, punctuation and
+        // capitalization so that the text still looks plausible.
+        for c in text.chars() {
+            if !c.is_alphanumeric() {
+                in_word = false;
+                out.push(c);
+                continue;
+            }
+
+            if in_word {
+                continue;
+            }
+
+            in_word = true;
+
+            let word = WORDS[rng.random_range(..WORDS.len())];
+            let mut chars = word.chars();
+
+            if c.is_uppercase()
+                && let Some(first) = chars.next()
+            {
+                out.extend(first.to_uppercase());
+            }
+
+            out.push_str(chars.as_str());
+        }
+
+        self.texts.insert(text.to_owned(), out.clone());
+        out
+    }
+
+    fn path(&mut self, path: &str) -> String {
+        let (prefix, path) = match path.split_once("://") {
+            Some((scheme, rest)) => {
+                let rest = rest.find('/').map(|n| &rest[n..]).unwrap_or_default();
+                (format!("{scheme}://example.com"), rest)
+            }
+            None => (String::new(), path),
+        };
+
+        let mut out = prefix;
+
+        for (n, segment) in path.split('/').enumerate() {
+            if n > 0 {
+                out.push('/');
+            }
 
-        match self.host_to_index.entry(host_id) {
-            Entry::Occupied(occupied_entry) => *occupied_entry.get(),
-            Entry::Vacant(vacant_entry) => {
-                vacant_entry.insert(next);
-                next
+            if !segment.is_empty() {
+                out.push_str(&self.segment(segment));
             }
         }
+
+        out
+    }
+
+    fn segment(&mut self, segment: &str) -> String {
+        if let Some(existing) = self.segments.get(segment) {
+            return existing.clone();
+        }
+
+        let id = self.hash(SEGMENT, segment.as_bytes()) as u16;
+
+        // Keep the extension so that the kind of file is still apparent.
+        let out = match segment.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => format!("item{id:04x}.{ext}"),
+            _ => format!("item{id:04x}"),
+        };
+
+        self.segments.insert(segment.to_owned(), out.clone());
+        out
     }
 }
 
 /// Construct a new showcase helper.
-pub fn new(showcase: bool) -> Helper {
+pub fn new(showcase: bool, config: &ShowcaseConfig) -> Helper {
     Helper {
         inner: if showcase {
-            Inner::Enabled(Arc::new(Mutex::new(State::default())))
+            Inner::Enabled(Arc::new(Mutex::new(State::new(config))))
         } else {
             Inner::Disabled
         },
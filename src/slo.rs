@@ -0,0 +1,117 @@
+//! Evaluation of service level objectives against the history store.
+
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::sync::Mutex;
+use tokio::time;
+use uuid::Uuid;
+
+use crate::config::SloConfig;
+use crate::history::History;
+use crate::hosts;
+
+/// How often objectives are evaluated.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// The outcome of evaluating the objectives of a host.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SloResult {
+    /// The objectives which were evaluated.
+    pub slo: SloConfig,
+    /// The number of pings in the window.
+    pub samples: u64,
+    /// The share of pings which were answered, between 0 and 1.
+    pub availability: Option<f64>,
+    /// The round trip time at the configured percentile.
+    pub rtt: Option<Duration>,
+    /// Whether the availability objective is breached.
+    pub availability_breached: bool,
+    /// Whether the round trip time objective is breached.
+    pub rtt_breached: bool,
+}
+
+impl SloResult {
+    /// Test if any objective is breached.
+    pub fn is_breached(&self) -> bool {
+        self.availability_breached || self.rtt_breached
+    }
+}
+
+/// State shared between the evaluation task and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// Results of the most recent evaluation by host.
+    pub results: Arc<Mutex<HashMap<Uuid, SloResult>>>,
+}
+
+impl State {
+    /// Construct a new empty state.
+    pub fn new() -> Self {
+        Self {
+            results: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// Spawn the evaluation task.
+pub async fn spawn(state: State, hosts: hosts::State, history: History) {
+    let mut tick = time::interval(INTERVAL);
+
+    loop {
+        tick.tick().await;
+
+        if !history.is_enabled() {
+            continue;
+        }
+
+        let hosts = hosts.hosts();
+        let mut results = HashMap::new();
+
+        for host in hosts.iter() {
+            if !host.slo.is_configured() {
+                continue;
+            }
+
+            let window = host.slo.window.unwrap_or(SloConfig::DEFAULT_WINDOW);
+            let since = SystemTime::now() - window;
+
+            let stats = match history.stats(host.id, since, host.slo.percentile()).await {
+                Ok(stats) => stats,
+                Err(error) => {
+                    tracing::warn!(id = ?host.id, "Evaluating objectives: {error:#}");
+                    continue;
+                }
+            };
+
+            let availability =
+                (stats.samples > 0).then(|| stats.answered as f64 / stats.samples as f64);
+
+            results.insert(
+                host.id,
+                SloResult {
+                    slo: host.slo.clone(),
+                    samples: stats.samples,
+                    availability,
+                    rtt: stats.rtt,
+                    availability_breached: host
+                        .slo
+                        .availability
+                        .zip(availability)
+                        .is_some_and(|(objective, a)| a < objective.ratio()),
+                    rtt_breached: host
+                        .slo
+                        .rtt
+                        .zip(stats.rtt)
+                        .is_some_and(|(objective, rtt)| rtt > objective),
+                },
+            );
+        }
+
+        *state.results.lock().await = results;
+    }
+}
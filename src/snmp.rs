@@ -0,0 +1,628 @@
+//! Polling of hosts over SNMP v2c.
+//!
+//! The name and uptime of hosts are polled, along with the traffic counters of
+//! their interfaces which are used to calculate bandwidth.
+
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::time::Duration;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use tokio::net::{self, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::config::SnmpConfig;
+use crate::hosts;
+
+/// How long to wait for each response.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// The number of bandwidth samples kept for each host.
+const SAMPLES: usize = 30;
+/// The number of rows requested at a time when walking a table.
+const MAX_REPETITIONS: i64 = 20;
+/// The largest number of requests performed when walking a table.
+const MAX_REQUESTS: usize = 100;
+
+const SYS_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 5, 0];
+const SYS_UP_TIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+const IF_NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
+const IF_HC_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 6];
+const IF_HC_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 10];
+const IF_IN_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 10];
+const IF_OUT_OCTETS: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 16];
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIME_TICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+const PDU_GET: u8 = 0xa0;
+const PDU_RESPONSE: u8 = 0xa2;
+const PDU_GET_BULK: u8 = 0xa5;
+
+/// Version 2c is encoded as 1.
+const VERSION_2C: i64 = 1;
+
+/// The traffic counters of an interface.
+#[derive(Debug, Clone)]
+pub struct Interface {
+    /// The name of the interface.
+    pub name: String,
+    /// Octets received.
+    pub in_octets: u64,
+    /// Octets sent.
+    pub out_octets: u64,
+}
+
+/// The most recent state polled from a host.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SnmpStatus {
+    /// The configuration the host was polled with.
+    pub config: SnmpConfig,
+    /// The name the host reports for itself.
+    pub name: Option<String>,
+    /// How long the host has been up.
+    pub uptime: Option<Duration>,
+    /// Interfaces by their index.
+    pub interfaces: BTreeMap<u32, Interface>,
+    /// The error of the most recent poll, if it failed.
+    pub error: Option<String>,
+    /// When the host was last polled.
+    pub sampled: Instant,
+    /// Bandwidth over all interfaces in bytes per second, oldest first.
+    pub bandwidth: VecDeque<f64>,
+    /// Total octets over all interfaces when they were last polled.
+    total: Option<(Instant, u64)>,
+}
+
+/// State shared between the polling task and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// The state polled from each host.
+    pub results: Arc<Mutex<HashMap<Uuid, SnmpStatus>>>,
+}
+
+impl State {
+    /// Construct a new empty state.
+    pub fn new() -> Self {
+        Self {
+            results: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// Spawn the polling task.
+pub async fn spawn(state: State, hosts: hosts::State) {
+    // When each host was last polled.
+    let mut last = HashMap::<Uuid, Instant>::new();
+    // Polls in flight.
+    let mut tasks = JoinSet::new();
+    let mut tick = time::interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {
+                let now = Instant::now();
+                let paused = hosts.is_paused();
+                let hosts = hosts.hosts();
+
+                last.retain(|id, _| hosts.iter().any(|h| h.id == *id && h.snmp.is_configured()));
+
+                // Discard the state of hosts whose configuration has changed.
+                state.results.lock().await.retain(|id, status| {
+                    let keep = hosts.iter().any(|h| h.id == *id && h.snmp == status.config);
+
+                    if !keep {
+                        last.remove(id);
+                    }
+
+                    keep
+                });
+
+                if paused {
+                    continue;
+                }
+
+                for host in hosts.iter() {
+                    let Some(community) = &host.snmp.community else {
+                        continue;
+                    };
+
+                    let Some(name) = host.names().next() else {
+                        continue;
+                    };
+
+                    if let Some(last) = last.get(&host.id)
+                        && now.saturating_duration_since(*last) < host.snmp.interval()
+                    {
+                        continue;
+                    }
+
+                    last.insert(host.id, now);
+
+                    let id = host.id;
+                    let name = name.to_owned();
+                    let community = community.clone();
+                    let config = host.snmp.clone();
+
+                    tasks.spawn(async move {
+                        let result = poll(&name, config.port(), &community).await;
+                        (id, config, result)
+                    });
+                }
+            }
+            result = tasks.join_next(), if !tasks.is_empty() => {
+                let Some(Ok((id, config, result))) = result else {
+                    continue;
+                };
+
+                let now = Instant::now();
+                let mut results = state.results.lock().await;
+
+                let status = results.entry(id).or_insert_with(|| SnmpStatus {
+                    config,
+                    name: None,
+                    uptime: None,
+                    interfaces: BTreeMap::new(),
+                    error: None,
+                    sampled: now,
+                    bandwidth: VecDeque::new(),
+                    total: None,
+                });
+
+                status.sampled = now;
+
+                let poll = match result {
+                    Ok(poll) => poll,
+                    Err(error) => {
+                        status.error = Some(format!("{error:#}"));
+                        continue;
+                    }
+                };
+
+                let total = poll
+                    .interfaces
+                    .values()
+                    .map(|i| i.in_octets.wrapping_add(i.out_octets))
+                    .fold(0u64, u64::wrapping_add);
+
+                // Counters which have been reset, like when the host
+                // restarted, don't give a sample.
+                if let Some((then, last)) = status.total
+                    && total >= last
+                {
+                    let elapsed = now.saturating_duration_since(then).as_secs_f64();
+
+                    if elapsed > 0.0 {
+                        if status.bandwidth.len() == SAMPLES {
+                            status.bandwidth.pop_front();
+                        }
+
+                        status.bandwidth.push_back((total - last) as f64 / elapsed);
+                    }
+                }
+
+                status.total = Some((now, total));
+                status.name = poll.name;
+                status.uptime = poll.uptime;
+                status.interfaces = poll.interfaces;
+                status.error = None;
+            }
+        }
+    }
+}
+
+/// The outcome of polling a host.
+struct Poll {
+    name: Option<String>,
+    uptime: Option<Duration>,
+    interfaces: BTreeMap<u32, Interface>,
+}
+
+/// Poll a single host.
+async fn poll(name: &str, port: u16, community: &str) -> Result<Poll> {
+    let Some(address) = net::lookup_host((name, port)).await?.next() else {
+        bail!("{name}: no addresses");
+    };
+
+    let bind = match address {
+        SocketAddr::V4(..) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(..) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+
+    let socket = UdpSocket::bind(bind).await?;
+    socket
+        .connect(address)
+        .await
+        .with_context(|| format!("connecting to {address}"))?;
+
+    let client = Client { socket, community };
+
+    let mut poll = Poll {
+        name: None,
+        uptime: None,
+        interfaces: BTreeMap::new(),
+    };
+
+    for (oid, value) in client.get(&[SYS_NAME, SYS_UP_TIME]).await? {
+        match (oid.as_slice(), value) {
+            (SYS_NAME, Value::String(name)) => {
+                poll.name = Some(String::from_utf8_lossy(&name).into_owned());
+            }
+            // Time ticks are in hundredths of a second.
+            (SYS_UP_TIME, Value::TimeTicks(ticks)) => {
+                poll.uptime = Some(Duration::from_millis(ticks.saturating_mul(10)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut in_octets = client.walk(IF_HC_IN_OCTETS).await?;
+    let mut out_octets = client.walk(IF_HC_OUT_OCTETS).await?;
+
+    // Fall back to the 32-bit counters for hosts which don't have the
+    // high-capacity ones.
+    if in_octets.is_empty() {
+        in_octets = client.walk(IF_IN_OCTETS).await?;
+        out_octets = client.walk(IF_OUT_OCTETS).await?;
+    }
+
+    let names = client.walk(IF_NAME).await?;
+
+    for (index, value) in in_octets {
+        let Value::Unsigned(value) = value else {
+            continue;
+        };
+
+        let name = match names.get(&index) {
+            Some(Value::String(name)) => String::from_utf8_lossy(name).into_owned(),
+            _ => index.to_string(),
+        };
+
+        let out_octets = match out_octets.get(&index) {
+            Some(Value::Unsigned(value)) => *value,
+            _ => 0,
+        };
+
+        poll.interfaces.insert(
+            index,
+            Interface {
+                name,
+                in_octets: value,
+                out_octets,
+            },
+        );
+    }
+
+    Ok(poll)
+}
+
+/// A decoded value.
+#[derive(Debug)]
+enum Value {
+    /// Counters and gauges.
+    Unsigned(u64),
+    /// Time ticks, in hundredths of a second.
+    TimeTicks(u64),
+    String(Vec<u8>),
+    /// Null, a marker that there is no such object or a type which isn't
+    /// used.
+    Missing,
+    EndOfMibView,
+}
+
+/// Objects and their values in a response.
+type Bindings = Vec<(Vec<u32>, Value)>;
+
+struct Client<'a> {
+    socket: UdpSocket,
+    community: &'a str,
+}
+
+impl Client<'_> {
+    /// Get the given objects.
+    async fn get(&self, oids: &[&[u32]]) -> Result<Bindings> {
+        self.request(PDU_GET, 0, 0, oids).await
+    }
+
+    /// Walk a table column, returning values by the index of their row.
+    async fn walk(&self, base: &[u32]) -> Result<BTreeMap<u32, Value>> {
+        let mut out = BTreeMap::new();
+        let mut next = base.to_vec();
+
+        for _ in 0..MAX_REQUESTS {
+            let response = self
+                .request(PDU_GET_BULK, 0, MAX_REPETITIONS, &[&next])
+                .await?;
+
+            if response.is_empty() {
+                break;
+            }
+
+            for (oid, value) in response {
+                // Stop at the end of the column, and guard against agents
+                // which don't make progress.
+                if !oid.starts_with(base) || oid <= next || matches!(value, Value::EndOfMibView) {
+                    return Ok(out);
+                }
+
+                if let &[index] = &oid[base.len()..] {
+                    out.insert(index, value);
+                }
+
+                next = oid;
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Perform a single request, where `a` and `b` are the error status and
+    /// index fields, or non-repeaters and max-repetitions for bulk requests.
+    async fn request(&self, pdu: u8, a: i64, b: i64, oids: &[&[u32]]) -> Result<Bindings> {
+        let id = i64::from(rand::random::<u16>());
+
+        let mut bindings = Vec::new();
+
+        for oid in oids {
+            let mut binding = Vec::new();
+            encode_oid(&mut binding, oid);
+            encode(&mut binding, TAG_NULL, &[]);
+            encode(&mut bindings, TAG_SEQUENCE, &binding);
+        }
+
+        let mut body = Vec::new();
+        encode_integer(&mut body, id);
+        encode_integer(&mut body, a);
+        encode_integer(&mut body, b);
+        encode(&mut body, TAG_SEQUENCE, &bindings);
+
+        let mut message = Vec::new();
+        encode_integer(&mut message, VERSION_2C);
+        encode(&mut message, TAG_OCTET_STRING, self.community.as_bytes());
+        encode(&mut message, pdu, &body);
+
+        let mut request = Vec::new();
+        encode(&mut request, TAG_SEQUENCE, &message);
+
+        self.socket.send(&request).await?;
+
+        let mut buf = vec![0; 65536];
+
+        let result = time::timeout(TIMEOUT, async {
+            loop {
+                let n = self.socket.recv(&mut buf).await?;
+
+                // Stray datagrams, like late answers to earlier requests or
+                // ones which can't be decoded, are ignored.
+                if let Some(response) = decode_response(&buf[..n], id) {
+                    return response;
+                }
+            }
+        })
+        .await;
+
+        let Ok(result) = result else {
+            bail!("timeout");
+        };
+
+        result
+    }
+}
+
+/// Decode a response with the given request id, or `None` if the datagram
+/// isn't one.
+fn decode_response(bytes: &[u8], id: i64) -> Option<Result<Bindings>> {
+    let mut pdu = decode_pdu(bytes).ok()?;
+
+    if pdu.integer().ok()? != id {
+        return None;
+    }
+
+    Some(decode_bindings(pdu))
+}
+
+/// Decode the response PDU of a message.
+fn decode_pdu(bytes: &[u8]) -> Result<Reader<'_>> {
+    let mut r = Reader(bytes);
+    let mut message = r.expect(TAG_SEQUENCE)?;
+    let _version = message.integer()?;
+    let _community = message.expect(TAG_OCTET_STRING)?;
+    message.expect(PDU_RESPONSE)
+}
+
+/// Decode the status and bindings of a response PDU, following its request
+/// id.
+fn decode_bindings(mut pdu: Reader<'_>) -> Result<Bindings> {
+    let status = pdu.integer()?;
+    let _index = pdu.integer()?;
+
+    match status {
+        0 => {}
+        2 => bail!("no such name"),
+        5 => bail!("general error"),
+        status => bail!("error status {status}"),
+    }
+
+    let mut bindings = pdu.expect(TAG_SEQUENCE)?;
+    let mut out = Vec::new();
+
+    while !bindings.0.is_empty() {
+        let mut binding = bindings.expect(TAG_SEQUENCE)?;
+        let oid = decode_oid(binding.expect(TAG_OID)?.0)?;
+        out.push((oid, binding.value()?));
+    }
+
+    Ok(out)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Read the next tag and its contents.
+    fn next(&mut self) -> Result<(u8, Reader<'a>)> {
+        let [tag, len, rest @ ..] = self.0 else {
+            bail!("truncated response");
+        };
+
+        let (len, rest) = if len & 0x80 == 0 {
+            (usize::from(*len), rest)
+        } else {
+            let n = usize::from(len & 0x7f);
+
+            if n > 4 || rest.len() < n {
+                bail!("invalid length");
+            }
+
+            let len = rest[..n]
+                .iter()
+                .fold(0usize, |len, &b| (len << 8) | usize::from(b));
+
+            (len, &rest[n..])
+        };
+
+        if rest.len() < len {
+            bail!("truncated response");
+        }
+
+        let (content, rest) = rest.split_at(len);
+        self.0 = rest;
+        Ok((*tag, Reader(content)))
+    }
+
+    /// Read the contents of the next value, which must have the given tag.
+    fn expect(&mut self, expected: u8) -> Result<Reader<'a>> {
+        let (tag, content) = self.next()?;
+
+        if tag != expected {
+            bail!("expected tag 0x{expected:02x}, found 0x{tag:02x}");
+        }
+
+        Ok(content)
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        let content = self.expect(TAG_INTEGER)?;
+        Ok(decode_integer(content.0))
+    }
+
+    /// Decode the contents as an unsigned integer.
+    fn unsigned(&self) -> u64 {
+        self.0
+            .iter()
+            .fold(0u64, |value, &b| (value << 8) | u64::from(b))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let (tag, content) = self.next()?;
+
+        Ok(match tag {
+            TAG_OCTET_STRING => Value::String(content.0.to_vec()),
+            TAG_COUNTER32 | TAG_GAUGE32 | TAG_COUNTER64 => Value::Unsigned(content.unsigned()),
+            TAG_TIME_TICKS => Value::TimeTicks(content.unsigned()),
+            TAG_END_OF_MIB_VIEW => Value::EndOfMibView,
+            _ => Value::Missing,
+        })
+    }
+}
+
+fn encode(out: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    out.push(tag);
+
+    match content.len() {
+        len @ 0..0x80 => out.push(len as u8),
+        len => {
+            let bytes = (len as u32).to_be_bytes();
+            let skip = bytes.iter().take_while(|&&b| b == 0).count();
+            out.push(0x80 | (4 - skip) as u8);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+    }
+
+    out.extend_from_slice(content);
+}
+
+fn encode_integer(out: &mut Vec<u8>, value: i64) {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+
+    // Use the shortest two's complement encoding.
+    while start < 7 {
+        let (b, next) = (bytes[start], bytes[start + 1]);
+
+        if (b == 0 && next & 0x80 == 0) || (b == 0xff && next & 0x80 != 0) {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+
+    encode(out, TAG_INTEGER, &bytes[start..]);
+}
+
+fn decode_integer(bytes: &[u8]) -> i64 {
+    let init = if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+        -1
+    } else {
+        0
+    };
+
+    bytes
+        .iter()
+        .fold(init, |value: i64, &b| (value << 8) | i64::from(b))
+}
+
+fn encode_oid(out: &mut Vec<u8>, oid: &[u32]) {
+    let mut content = Vec::new();
+
+    if let [a, b, rest @ ..] = oid {
+        content.push((a * 40 + b) as u8);
+
+        for &n in rest {
+            let mut bytes = Vec::new();
+            let mut n = n;
+            bytes.push((n & 0x7f) as u8);
+
+            while n >= 0x80 {
+                n >>= 7;
+                bytes.push(0x80 | (n & 0x7f) as u8);
+            }
+
+            content.extend(bytes.iter().rev());
+        }
+    }
+
+    encode(out, TAG_OID, &content);
+}
+
+fn decode_oid(bytes: &[u8]) -> Result<Vec<u32>> {
+    let Some((first, rest)) = bytes.split_first() else {
+        bail!("empty object identifier");
+    };
+
+    let mut out = vec![u32::from(first / 40), u32::from(first % 40)];
+    let mut n = 0u32;
+
+    for &b in rest {
+        n = n.checked_mul(128).context("object identifier overflow")? | u32::from(b & 0x7f);
+
+        if b & 0x80 == 0 {
+            out.push(n);
+            n = 0;
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,166 @@
+//! Exporting and importing the state kept by wolo, so that an install can be
+//! moved to another machine.
+//!
+//! Archives are zip files which contain a manifest, the history database if
+//! one is configured and the configuration files which were loaded.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::config::Config;
+use crate::history::History;
+use crate::zip::{Archive, Writer};
+
+const MANIFEST: &str = "manifest.json";
+const HISTORY: &str = "history.db";
+const CONFIG: &str = "config/";
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// The version of wolo which wrote the archive.
+    version: String,
+    /// When the archive was written, in seconds since the unix epoch.
+    created: u64,
+    /// Configuration files in the archive along with where they were loaded
+    /// from.
+    #[serde(default)]
+    config: Vec<ConfigFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConfigFile {
+    name: String,
+    path: PathBuf,
+}
+
+/// Build an archive of the current state.
+pub async fn export(config: &Config, history: &History) -> Result<Vec<u8>> {
+    let mut writer = Writer::new();
+    let mut files = Vec::new();
+
+    for (n, path) in config.files.iter().enumerate() {
+        let data = fs::read(path)
+            .await
+            .with_context(|| format!("reading {}", path.display()))?;
+
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+
+        // The index keeps files in the order they were loaded in when the
+        // directory they are restored to is loaded.
+        let name = format!("{n:02}-{file_name}");
+        writer.add(&format!("{CONFIG}{name}"), &data)?;
+
+        files.push(ConfigFile {
+            name,
+            path: path.clone(),
+        });
+    }
+
+    if let Some(data) = history.snapshot().await? {
+        writer.add(HISTORY, &data)?;
+    }
+
+    let manifest = Manifest {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        config: files,
+    };
+
+    writer.add(MANIFEST, &serde_json::to_vec_pretty(&manifest)?)?;
+    writer.finish()
+}
+
+/// Where the contents of an archive are restored to.
+pub struct Import<'a> {
+    /// Path to restore the history database to.
+    pub history: Option<&'a Path>,
+    /// Directory to restore configuration files to.
+    pub config_dir: Option<&'a Path>,
+    /// Whether existing files are overwritten.
+    pub force: bool,
+}
+
+/// Restore the contents of an archive written by [`export`].
+pub async fn import(path: &Path, import: Import<'_>) -> Result<()> {
+    let mut archive = Archive::open(path)
+        .await
+        .with_context(|| format!("opening {}", path.display()))?;
+
+    let Some(entry) = archive.find(MANIFEST).cloned() else {
+        bail!("{}: not a wolo state archive", path.display());
+    };
+
+    let manifest = archive.read(&entry, 0..entry.size).await?;
+    let manifest: Manifest = serde_json::from_slice(&manifest).context("reading manifest")?;
+
+    println!(
+        "Archive written by wolo {} at {}",
+        manifest.version, manifest.created
+    );
+
+    if let Some(entry) = archive.find(HISTORY).cloned() {
+        let Some(to) = import.history else {
+            bail!(
+                "archive contains history, but no `[history] path` is configured to restore it to"
+            );
+        };
+
+        let data = archive.read(&entry, 0..entry.size).await?;
+        write(to, &data, import.force).await?;
+        println!("Restored history to {}", to.display());
+    }
+
+    for file in &manifest.config {
+        let Some(dir) = import.config_dir else {
+            println!(
+                "Skipped {}, use --config-dir to restore configuration",
+                file.path.display()
+            );
+            continue;
+        };
+
+        let Some(entry) = archive.find(&format!("{CONFIG}{}", file.name)).cloned() else {
+            bail!("{}: missing from archive", file.name);
+        };
+
+        // Names in the archive are untrusted, so only their last component is
+        // used.
+        let Some(name) = Path::new(&file.name).file_name() else {
+            bail!("{}: invalid name in archive", file.name);
+        };
+
+        let to = dir.join(name);
+        let data = archive.read(&entry, 0..entry.size).await?;
+        write(&to, &data, import.force).await?;
+        println!("Restored {} to {}", file.path.display(), to.display());
+    }
+
+    Ok(())
+}
+
+async fn write(path: &Path, data: &[u8], force: bool) -> Result<()> {
+    if !force && fs::try_exists(path).await? {
+        bail!(
+            "{}: already exists, use --force to overwrite",
+            path.display()
+        );
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    fs::write(path, data)
+        .await
+        .with_context(|| format!("writing {}", path.display()))
+}
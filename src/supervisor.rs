@@ -0,0 +1,87 @@
+//! Supervision of background tasks, which are restarted with exponential
+//! backoff if they fail or panic instead of taking the whole service down.
+
+use core::time::Duration;
+
+use anyhow::{Error, Result, anyhow};
+use tokio::task;
+use tokio::time::{self, Instant};
+
+use crate::config::{SupervisorConfig, format_duration};
+
+/// A task which has run for this long before failing is considered to have
+/// recovered, so its next restart isn't counted as in a row.
+const STABLE: Duration = Duration::from_secs(10 * 60);
+
+/// What a background task returns when it exits.
+pub trait Exit {
+    /// Convert into a result, where an error means that the task failed.
+    fn into_result(self) -> Result<()>;
+}
+
+impl Exit for () {
+    #[inline]
+    fn into_result(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Exit for Result<()> {
+    #[inline]
+    fn into_result(self) -> Result<()> {
+        self
+    }
+}
+
+/// Run the task constructed by `task`, and construct and run it again if it
+/// fails or panics.
+///
+/// This returns once the task exits without failing, or with the error of
+/// the task once it has been restarted more times in a row than configured.
+pub async fn supervise<F, T>(
+    name: &'static str,
+    config: &SupervisorConfig,
+    mut task: F,
+) -> Result<()>
+where
+    F: FnMut() -> T,
+    T: Future<Output: Exit + Send + 'static> + Send + 'static,
+{
+    let mut restarts = 0u32;
+
+    loop {
+        let started = Instant::now();
+
+        let error = match task::spawn(task()).await {
+            Ok(exit) => match exit.into_result() {
+                Ok(()) => return Ok(()),
+                Err(error) => error,
+            },
+            Err(error) if error.is_panic() => anyhow!("{name} task panicked"),
+            Err(error) => Error::new(error),
+        };
+
+        if started.elapsed() >= STABLE {
+            restarts = 0;
+        }
+
+        if restarts >= config.restarts() {
+            return Err(error.context(format!("{name} failed after {restarts} restarts in a row")));
+        }
+
+        let backoff = config
+            .backoff()
+            .saturating_mul(1u32.checked_shl(restarts).unwrap_or(u32::MAX))
+            .min(config.max_backoff());
+
+        restarts += 1;
+
+        tracing::error!(
+            "{name} task failed, restarting in {} ({restarts} of {} in a row): {error:#}",
+            format_duration(backoff),
+            config.restarts(),
+        );
+
+        time::sleep(backoff).await;
+    }
+}
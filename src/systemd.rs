@@ -0,0 +1,274 @@
+//! Generating systemd units which run wolo with the permissions it needs and
+//! little else.
+
+use core::fmt::Write;
+use core::net::SocketAddr;
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tokio::fs;
+
+use crate::config::Config;
+
+/// Directory the dynamic user may write to, which is where state like the
+/// history database belongs.
+const STATE_DIRECTORY: &str = "/var/lib/wolo";
+
+/// Ports below this require `CAP_NET_BIND_SERVICE` to bind.
+const PRIVILEGED_PORTS: u16 = 1024;
+
+/// What units are generated from.
+pub struct Options<'a> {
+    /// The name of the units, like `wolo` for `wolo.service`.
+    pub name: &'a str,
+    /// Path to the wolo binary.
+    pub exe: &'a Path,
+    /// Arguments to start the service with.
+    pub args: &'a [String],
+    /// Address the socket unit listens on.
+    pub bind: SocketAddr,
+    /// A static user to run as instead of a dynamic one.
+    pub user: Option<&'a str>,
+    pub group: Option<&'a str>,
+    /// The file mode creation mask of the service.
+    pub umask: Option<u32>,
+    /// Whether pings and wakes are simulated, which needs no capabilities.
+    pub simulate: bool,
+    pub config: &'a Config,
+}
+
+/// Generated units.
+pub struct Units {
+    pub service: String,
+    pub socket: String,
+    /// A sysusers.d file creating the static user, if one is used.
+    pub sysusers: Option<String>,
+}
+
+/// Generate a service unit, the socket unit which passes it the listener of
+/// the web interface, and a sysusers.d file if a static user is used.
+pub fn generate(o: &Options<'_>) -> Result<Units> {
+    let Options { name, config, .. } = o;
+
+    let mut capabilities = Vec::new();
+    let mut families = vec!["AF_INET", "AF_INET6", "AF_UNIX", "AF_NETLINK"];
+
+    if !o.simulate {
+        capabilities.push("CAP_NET_RAW");
+
+        if config.wakes_on_demand() {
+            families.push("AF_PACKET");
+        }
+    }
+
+    if config
+        .wake_listeners
+        .keys()
+        .any(|bind| bind.port() < PRIVILEGED_PORTS)
+    {
+        capabilities.push("CAP_NET_BIND_SERVICE");
+    }
+
+    let mut writable = BTreeSet::new();
+
+    let files = [&config.history.path, &config.report.path];
+
+    for path in files.into_iter().flatten() {
+        if let Some(parent) = path.parent() {
+            writable.insert(parent.to_owned());
+        }
+    }
+
+    for mokuro in &config.mokuro {
+        if mokuro.enabled && mokuro.upload {
+            writable.insert(mokuro.path.clone());
+        }
+    }
+
+    writable.retain(|path| !path.starts_with(STATE_DIRECTORY));
+
+    let mut exec = quote(&o.exe.to_string_lossy());
+
+    for arg in o.args {
+        exec.push(' ');
+        exec.push_str(&quote(arg));
+    }
+
+    let mut s = String::new();
+
+    writeln!(s, "# Generated by `wolo install-service`.")?;
+    writeln!(s, "[Unit]")?;
+    writeln!(s, "Description=Wake-on-LAN and network monitoring")?;
+    writeln!(s, "Documentation=https://github.com/udoprog/wolo")?;
+    writeln!(s, "Requires={name}.socket")?;
+    writeln!(s, "After={name}.socket network-online.target")?;
+    writeln!(s, "Wants=network-online.target")?;
+    writeln!(s)?;
+    writeln!(s, "[Service]")?;
+    writeln!(s, "Type=simple")?;
+    writeln!(s, "ExecStartPre={exec} check")?;
+    writeln!(s, "ExecStart={exec} serve")?;
+    writeln!(s, "ExecReload=/bin/kill -HUP $MAINPID")?;
+    writeln!(s, "Restart=on-failure")?;
+
+    match o.user {
+        Some(user) => {
+            writeln!(s, "User={user}")?;
+
+            if let Some(group) = o.group {
+                writeln!(s, "Group={group}")?;
+            }
+        }
+        None => {
+            writeln!(s, "DynamicUser=yes")?;
+        }
+    }
+
+    writeln!(s, "StateDirectory=wolo")?;
+
+    for path in &writable {
+        writeln!(s, "ReadWritePaths={}", quote(&path.to_string_lossy()))?;
+    }
+
+    let capabilities = capabilities.join(" ");
+    writeln!(s, "AmbientCapabilities={capabilities}")?;
+    writeln!(s, "CapabilityBoundingSet={capabilities}")?;
+    writeln!(s, "RestrictAddressFamilies={}", families.join(" "))?;
+    writeln!(s, "NoNewPrivileges=yes")?;
+    writeln!(s, "ProtectSystem=strict")?;
+    writeln!(s, "ProtectHome=yes")?;
+    writeln!(s, "PrivateTmp=yes")?;
+    writeln!(s, "PrivateDevices=yes")?;
+    writeln!(s, "ProtectClock=yes")?;
+    writeln!(s, "ProtectHostname=yes")?;
+    writeln!(s, "ProtectKernelTunables=yes")?;
+    writeln!(s, "ProtectKernelModules=yes")?;
+    writeln!(s, "ProtectKernelLogs=yes")?;
+    writeln!(s, "ProtectControlGroups=yes")?;
+    writeln!(s, "RestrictNamespaces=yes")?;
+    writeln!(s, "RestrictRealtime=yes")?;
+    writeln!(s, "RestrictSUIDSGID=yes")?;
+    writeln!(s, "LockPersonality=yes")?;
+    writeln!(s, "MemoryDenyWriteExecute=yes")?;
+    writeln!(s, "SystemCallArchitectures=native")?;
+    writeln!(s, "SystemCallFilter=@system-service")?;
+
+    if let Some(umask) = o.umask {
+        writeln!(s, "UMask={umask:04o}")?;
+    }
+
+    writeln!(s)?;
+    writeln!(s, "[Install]")?;
+    writeln!(s, "WantedBy=multi-user.target")?;
+    writeln!(s, "Also={name}.socket")?;
+
+    let service = s;
+
+    let mut s = String::new();
+
+    writeln!(s, "# Generated by `wolo install-service`.")?;
+    writeln!(s, "[Unit]")?;
+    writeln!(s, "Description=Web interface of wolo")?;
+    writeln!(s)?;
+    writeln!(s, "[Socket]")?;
+    writeln!(s, "ListenStream={}", o.bind)?;
+    writeln!(s)?;
+    writeln!(s, "[Install]")?;
+    writeln!(s, "WantedBy=sockets.target")?;
+
+    let socket = s;
+
+    let sysusers = match o.user {
+        Some(user) => {
+            let mut s = String::new();
+            writeln!(s, "# Generated by `wolo install-service`.")?;
+            writeln!(s, "u {user} - \"wolo\" {STATE_DIRECTORY}")?;
+
+            if let Some(group) = o.group {
+                writeln!(s, "g {group} -")?;
+                writeln!(s, "m {user} {group}")?;
+            }
+
+            Some(s)
+        }
+        None => None,
+    };
+
+    Ok(Units {
+        service,
+        socket,
+        sysusers,
+    })
+}
+
+/// Write units to the given directories.
+pub async fn install(
+    name: &str,
+    units: &Units,
+    dir: &Path,
+    sysusers_dir: &Path,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![
+        (dir.join(format!("{name}.service")), &units.service),
+        (dir.join(format!("{name}.socket")), &units.socket),
+    ];
+
+    if let Some(sysusers) = &units.sysusers {
+        files.push((sysusers_dir.join(format!("{name}.conf")), sysusers));
+    }
+
+    for (path, _) in &files {
+        if !force && fs::try_exists(path).await? {
+            bail!(
+                "{}: already exists, use --force to overwrite",
+                path.display()
+            );
+        }
+    }
+
+    let mut written = Vec::new();
+
+    for (path, contents) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(&path, contents)
+            .await
+            .with_context(|| format!("writing {}", path.display()))?;
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Quote an argument of a command line in a unit, which also escapes the
+/// specifiers and variables systemd would otherwise expand.
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%").replace('$', "$$");
+
+    if !arg.is_empty()
+        && !arg
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return arg;
+    }
+
+    let mut out = String::from("\"");
+
+    for c in arg.chars() {
+        if matches!(c, '"' | '\\') {
+            out.push('\\');
+        }
+
+        out.push(c);
+    }
+
+    out.push('"');
+    out
+}
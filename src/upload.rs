@@ -0,0 +1,165 @@
+//! Storing volumes which are uploaded to a Mokuro library.
+
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tokio::fs;
+
+use crate::library;
+use crate::zip::Archive;
+
+/// A file which has been received and written to a temporary location.
+pub struct Received {
+    /// The name of the file as it was uploaded.
+    pub file_name: String,
+    /// The temporary location of the file.
+    pub path: PathBuf,
+}
+
+impl Received {
+    /// Construct a new received file, which is stored temporarily in the given
+    /// library root so that it can be moved into place cheaply.
+    pub fn new(root: &Path, file_name: &str) -> Self {
+        // Some browsers include the path of the file on the client.
+        let file_name = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+
+        Self {
+            file_name: file_name.to_owned(),
+            path: root.join(format!(".upload-{:016x}", rand::random::<u64>())),
+        }
+    }
+
+    /// Remove the temporary file if it still exists.
+    pub async fn discard(&self) {
+        _ = fs::remove_file(&self.path).await;
+    }
+}
+
+/// Check that a name can be used for a series or volume in a library.
+pub fn check_name(what: &str, name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+
+    let (Some(Component::Normal(c)), None) = (components.next(), components.next()) else {
+        bail!("invalid {what} name `{name}`");
+    };
+
+    if c != name || name.starts_with('.') {
+        bail!("invalid {what} name `{name}`");
+    }
+
+    Ok(())
+}
+
+/// Validate the received files as a single volume and move them into the
+/// given series directory.
+///
+/// Returns the name of the stored volume.
+pub async fn store(series: &Path, files: &[Received]) -> Result<String> {
+    let mut archive = None;
+    let mut mokuro = None;
+
+    for f in files {
+        let ext = Path::new(&f.file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let slot = match ext.as_deref() {
+            Some(ext) if library::ARCHIVE_EXTENSIONS.contains(&ext) => &mut archive,
+            Some("mokuro") => &mut mokuro,
+            _ => bail!("{}: expected a .cbz, .zip or .mokuro file", f.file_name),
+        };
+
+        if slot.replace(f).is_some() {
+            bail!("{}: only one volume can be uploaded at a time", f.file_name);
+        }
+    }
+
+    let Some(archive) = archive else {
+        bail!("a .cbz or .zip archive is required");
+    };
+
+    let file_name = Path::new(&archive.file_name);
+
+    let (Some(name), Some(ext)) = (
+        file_name.file_stem().and_then(|s| s.to_str()),
+        file_name.extension().and_then(|s| s.to_str()),
+    ) else {
+        bail!("{}: invalid file name", archive.file_name);
+    };
+
+    check_name("volume", name)?;
+
+    let mut opened = Archive::open(&archive.path)
+        .await
+        .with_context(|| archive.file_name.clone())?;
+
+    let mut pages = 0;
+
+    for e in opened.entries() {
+        if !library::is_image(Path::new(&e.name)) {
+            continue;
+        }
+
+        if !e.is_stored() {
+            bail!(
+                "{}: {} is compressed, the archive has to be stored without compression",
+                archive.file_name,
+                e.name
+            );
+        }
+
+        pages += 1;
+    }
+
+    if pages == 0 {
+        bail!("{}: archive contains no images", archive.file_name);
+    }
+
+    // Processed volumes carry their `.mokuro` file in the archive.
+    let bytes = match mokuro {
+        Some(mokuro) => Some(fs::read(&mokuro.path).await?),
+        None => {
+            let entry = opened
+                .entries()
+                .iter()
+                .find(|e| e.name.ends_with(".mokuro"))
+                .cloned();
+
+            match entry {
+                Some(e) => Some(opened.read(&e, 0..e.size).await?),
+                None => None,
+            }
+        }
+    };
+
+    if let Some(bytes) = &bytes {
+        library::validate_mokuro(bytes).context("invalid .mokuro file")?;
+    }
+
+    let mut existing = vec![
+        series.join(name),
+        series.join(format!("{name}.html")),
+        series.join(format!("{name}.mokuro")),
+    ];
+
+    for ext in library::ARCHIVE_EXTENSIONS {
+        existing.push(series.join(format!("{name}.{ext}")));
+    }
+
+    for path in existing {
+        if fs::try_exists(&path).await? {
+            bail!("volume `{name}` already exists");
+        }
+    }
+
+    fs::create_dir_all(series).await?;
+
+    if let Some(bytes) = bytes {
+        fs::write(series.join(format!("{name}.mokuro")), bytes).await?;
+    }
+
+    let ext = ext.to_ascii_lowercase();
+    fs::rename(&archive.path, series.join(format!("{name}.{ext}"))).await?;
+    Ok(name.to_owned())
+}
@@ -1,13 +1,21 @@
+use core::fmt;
 use core::str;
+use core::time::Duration;
 
 use std::sync::Arc;
 
 use anyhow::Error;
-use minijinja::Environment;
+use macaddr::MacAddr6;
+use minijinja::value::{Kwargs, Value};
+use minijinja::{Environment, ErrorKind, State, context};
 use relative_path::RelativePath;
 use serde::Serialize;
 
+use crate::auth;
+use crate::config::TimeOfDay;
 use crate::embed;
+use crate::i18n;
+use crate::mac::Mac;
 
 /// Handler for templates.
 #[derive(Clone)]
@@ -16,19 +24,39 @@ pub(crate) struct Templates {
 }
 
 impl Templates {
-    /// Render a template by name.
+    /// Render a template by name in the locale of the current request, along
+    /// with the user who is signed in.
     pub(crate) fn render(&self, name: &str, context: impl Serialize) -> Result<String, Error> {
         let template = self.env.get_template(name)?;
-        let rendered = template.render(context)?;
+
+        let rendered = template.render(context! {
+            locale => i18n::current(),
+            user => auth::current(),
+            ..Value::from_serialize(context)
+        })?;
+
         Ok(rendered)
     }
 }
 
-pub(crate) fn load_templates() -> Result<Templates, Error> {
+/// An environment with the syntax options templates are written for.
+fn environment() -> Environment<'static> {
     let mut env = Environment::new();
     env.set_keep_trailing_newline(false);
     env.set_trim_blocks(true);
     env.set_lstrip_blocks(true);
+    env
+}
+
+/// Parse a single template, which is used to report errors per template.
+pub(crate) fn parse_template(name: &str, source: &str) -> Result<(), Error> {
+    let mut env = environment();
+    env.add_template_owned(name.to_owned(), source.to_owned())?;
+    Ok(())
+}
+
+pub(crate) fn load_templates() -> Result<Templates, Error> {
+    let mut env = environment();
 
     for name in embed::iter() {
         let path = RelativePath::new(name.as_ref());
@@ -49,5 +77,224 @@ pub(crate) fn load_templates() -> Result<Templates, Error> {
     }
 
     env.add_filter("hex", |value: u16| Ok(format!("0x{:x}", value)));
+    env.add_filter("duration", |ms: f64| {
+        Ok(duration(from_millis(ms)).to_string())
+    });
+    env.add_filter("rel_time", |state: &State, ms: f64| {
+        let time = duration(from_millis(ms)).to_string();
+        let text = i18n::translate(locale(state), "{time} ago");
+        Ok(text.replace("{time}", &time))
+    });
+    env.add_filter("bit_rate", |bytes: f64| Ok(bit_rate(bytes).to_string()));
+    env.add_function("t", translate);
+    env.add_filter("mac", |value: Value| Ok(to_mac(&value)?.to_string()));
+    env.add_filter("short_mac", |value: Value| Ok(short_mac(to_mac(&value)?)));
     Ok(Templates { env: Arc::new(env) })
 }
+
+/// Get the locale a template is being rendered in.
+fn locale(state: &State) -> &'static str {
+    let locale = state.lookup("locale");
+    let locale = locale.as_ref().and_then(Value::as_str);
+    i18n::find(locale.unwrap_or(i18n::DEFAULT)).unwrap_or(i18n::DEFAULT)
+}
+
+/// Translate a string, replacing placeholders like `{n}` with keyword
+/// arguments like in `t("{n} pages", n=3)`.
+fn translate(state: &State, key: &str, kwargs: Kwargs) -> Result<String, minijinja::Error> {
+    let mut text = i18n::translate(locale(state), key).to_owned();
+
+    for name in kwargs.args() {
+        let value = kwargs.get::<Value>(name)?;
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+
+    Ok(text)
+}
+
+/// Get a duration in milliseconds, which is how durations are passed to
+/// templates.
+pub(crate) fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn from_millis(ms: f64) -> Duration {
+    Duration::try_from_secs_f64(ms / 1000.0).unwrap_or_default()
+}
+
+/// Convert a template value into a MAC address, which is either a string or
+/// the bytes of a serialized address.
+fn to_mac(value: &Value) -> Result<MacAddr6, minijinja::Error> {
+    if let Some(s) = value.as_str()
+        && let Ok(Mac(mac)) = s.parse::<Mac>()
+    {
+        return Ok(mac);
+    }
+
+    let mut bytes = [0u8; 6];
+
+    if let Ok(iter) = value.try_iter()
+        && value.len() == Some(bytes.len())
+    {
+        for (b, v) in bytes.iter_mut().zip(iter) {
+            *b = u8::try_from(v).map_err(|_| invalid_mac(value))?;
+        }
+
+        return Ok(MacAddr6::from(bytes));
+    }
+
+    Err(invalid_mac(value))
+}
+
+fn invalid_mac(value: &Value) -> minijinja::Error {
+    minijinja::Error::new(
+        ErrorKind::InvalidOperation,
+        format!("invalid MAC address `{value}`"),
+    )
+}
+
+/// Format a MAC address in its shortest form, like `0011.2233.4455`.
+fn short_mac(mac: MacAddr6) -> String {
+    let [a, b, c, d, e, f] = mac.into_array();
+    format!("{a:02x}{b:02x}.{c:02x}{d:02x}.{e:02x}{f:02x}")
+}
+
+/// Format a duration compactly, like `1H30m` or `12ms`.
+pub(crate) fn duration(d: Duration) -> impl fmt::Display {
+    struct D(Duration);
+
+    impl fmt::Display for D {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut secs = self.0.as_secs();
+
+            if secs >= 86400 {
+                write!(f, "{}d", secs / 86400)?;
+                secs %= 86400;
+            }
+
+            if secs >= 3600 {
+                write!(f, "{}H", secs / 3600)?;
+                secs %= 3600;
+            }
+
+            if secs >= 60 {
+                write!(f, "{}m", secs / 60)?;
+                secs %= 60;
+            }
+
+            if secs > 0 {
+                return write!(f, "{}s", secs);
+            }
+
+            // Fractions of a second are only shown for durations under a
+            // second.
+            if self.0.as_secs() > 0 {
+                return Ok(());
+            }
+
+            let nanos = self.0.subsec_nanos();
+
+            if nanos >= 1_000_000 {
+                let millis = nanos / 1_000_000;
+                return write!(f, "{millis}ms");
+            }
+
+            let micros = nanos / 1_000;
+            write!(f, "{micros}µs")
+        }
+    }
+
+    D(d)
+}
+
+/// Format a rate given in bytes per second as bits per second, like
+/// `1.5 Mbit/s`.
+pub(crate) fn bit_rate(bytes: f64) -> impl fmt::Display {
+    struct R(f64);
+
+    impl fmt::Display for R {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut bits = self.0 * 8.0;
+
+            for unit in ["bit/s", "kbit/s", "Mbit/s"] {
+                if bits < 1000.0 {
+                    return write!(f, "{bits:.1} {unit}");
+                }
+
+                bits /= 1000.0;
+            }
+
+            write!(f, "{bits:.1} Gbit/s")
+        }
+    }
+
+    R(bytes)
+}
+
+/// Format a size in bytes using binary units.
+pub(crate) fn size(bytes: u64) -> impl fmt::Display {
+    struct S(u64);
+
+    impl fmt::Display for S {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.0 < 1024 {
+                return write!(f, "{} B", self.0);
+            }
+
+            let mut size = self.0 as f64 / 1024.0;
+
+            for unit in ["KiB", "MiB", "GiB"] {
+                if size < 1024.0 {
+                    return write!(f, "{size:.1} {unit}");
+                }
+
+                size /= 1024.0;
+            }
+
+            write!(f, "{size:.1} TiB")
+        }
+    }
+
+    S(bytes)
+}
+
+/// Get the current local time of day.
+pub(crate) fn time_of_day() -> TimeOfDay {
+    let Some(tm) = local_time() else {
+        return TimeOfDay::MIDNIGHT;
+    };
+
+    let hours = u16::try_from(tm.tm_hour).unwrap_or_default();
+    let minutes = u16::try_from(tm.tm_min).unwrap_or_default();
+    TimeOfDay::new(hours, minutes).unwrap_or(TimeOfDay::MIDNIGHT)
+}
+
+/// Format the current local date and time, like `2024-05-01 12:00`.
+pub(crate) fn timestamp() -> Option<String> {
+    let tm = local_time()?;
+
+    Some(format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    ))
+}
+
+/// Get the current local time.
+fn local_time() -> Option<libc::tm> {
+    // SAFETY: `localtime_r` only writes to the provided `tm`, and a null
+    // pointer to `time` means the current time is used.
+    unsafe {
+        let now = libc::time(core::ptr::null_mut());
+        let mut tm = core::mem::zeroed::<libc::tm>();
+
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return None;
+        }
+
+        Some(tm)
+    }
+}
@@ -0,0 +1,217 @@
+use core::fmt;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use macaddr::MacAddr6;
+use tokio::task;
+
+use crate::config::{Config, Diagnostic, Severity};
+use crate::host_name_cache;
+use crate::hosts;
+
+/// A report of findings produced while validating configuration.
+#[derive(Default)]
+pub struct Report {
+    findings: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Record a diagnostic.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.findings.push(diagnostic);
+    }
+
+    /// Record an error.
+    pub fn error(&mut self, message: impl fmt::Display) {
+        self.push(Diagnostic::new(Severity::Error, message));
+    }
+
+    /// Record a warning.
+    pub fn warning(&mut self, message: impl fmt::Display) {
+        self.push(Diagnostic::new(Severity::Warning, message));
+    }
+
+    /// Promote all warnings to errors.
+    pub fn promote_warnings(&mut self) {
+        for d in &mut self.findings {
+            d.severity = Severity::Error;
+        }
+    }
+
+    /// Count the number of findings with the given severity.
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    }
+
+    /// Test if the report contains errors.
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    /// Print the report to stdout.
+    pub fn print(&self) {
+        for d in &self.findings {
+            println!("{d}");
+        }
+
+        println!(
+            "{} error(s), {} warning(s)",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        );
+    }
+}
+
+/// Paths to validate.
+pub struct Paths<'a> {
+    pub ethers: &'a [PathBuf],
+    pub hosts: &'a [PathBuf],
+    pub home: &'a [PathBuf],
+}
+
+/// Perform cross-checks over the loaded configuration and all its sources.
+pub async fn run(report: &mut Report, config: &Config, paths: Paths<'_>) -> Result<()> {
+    for path in paths.ethers.iter().chain(paths.hosts).chain(paths.home) {
+        check_file(report, path);
+    }
+
+    for path in config.home.iter() {
+        check_file(report, path);
+    }
+
+    for m in &config.mokuro {
+        if let Err(error) = fs::read_dir(&m.path) {
+            report.error(format_args!(
+                "{}: mokuro directory is not readable: {error}",
+                m.path.display()
+            ));
+        }
+    }
+
+    check_duplicate_macs(report, config, paths.ethers).await;
+
+    let hosts = hosts::load(paths.ethers, paths.hosts, config).await;
+
+    let mut names = BTreeSet::new();
+
+    for host in &hosts {
+        names.extend(host.names.iter().cloned());
+    }
+
+    let dns = config.dns.clone();
+
+    let unresolved = task::spawn_blocking(move || {
+        let mut unresolved = Vec::new();
+
+        for name in names {
+            if let Err((_, error)) = host_name_cache::resolve(&dns, &name) {
+                unresolved.push((name, error));
+            }
+        }
+
+        unresolved
+    })
+    .await?;
+
+    for (name, error) in unresolved {
+        report.warning(format_args!("{name}: name could not be resolved: {error}"));
+    }
+
+    for host in &hosts {
+        let name = host.names().next().unwrap_or_default();
+
+        for dependency in &host.depends_on {
+            if !hosts.iter().any(|h| h.names.contains(dependency)) {
+                report.warning(format_args!(
+                    "{name}: depends on unknown host `{dependency}`"
+                ));
+            }
+        }
+
+        if host.slo.is_configured() && config.history.path.is_none() {
+            report.warning(format_args!(
+                "{name}: objectives are only evaluated if a history path is configured"
+            ));
+        }
+    }
+
+    let macs = hosts.iter().filter(|h| !h.macs.is_empty()).count();
+    println!("{} host(s), {macs} with MAC addresses", hosts.len());
+    Ok(())
+}
+
+/// Check that a file that exists is readable.
+fn check_file(report: &mut Report, path: &Path) {
+    match fs::File::open(path) {
+        Ok(..) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            report.warning(format_args!("{}: file does not exist", path.display()));
+        }
+        Err(error) => {
+            report.error(format_args!(
+                "{}: file is not readable: {error}",
+                path.display()
+            ));
+        }
+    }
+}
+
+/// Check that ethers files are well-formed and that the same MAC address is
+/// not assigned to unrelated hosts.
+async fn check_duplicate_macs(report: &mut Report, config: &Config, ethers: &[PathBuf]) {
+    let mut by_mac = BTreeMap::<MacAddr6, Vec<(BTreeSet<String>, String)>>::new();
+
+    for path in ethers {
+        let ethers = hosts::read_ethers(path).await;
+
+        for line in ethers.invalid {
+            report.warning(line);
+        }
+
+        for (mac, name) in ethers.entries {
+            by_mac
+                .entry(mac)
+                .or_default()
+                .push((BTreeSet::from([name]), path.display().to_string()));
+        }
+    }
+
+    for host in &config.hosts {
+        for mac in &host.macs {
+            by_mac
+                .entry(*mac)
+                .or_default()
+                .push((host.names.clone(), String::from("config")));
+        }
+    }
+
+    for (mac, entries) in by_mac {
+        let conflict = entries
+            .iter()
+            .enumerate()
+            .any(|(n, (a, _))| entries[n + 1..].iter().any(|(b, _)| a.is_disjoint(b)));
+
+        if !conflict {
+            continue;
+        }
+
+        let sources = entries
+            .iter()
+            .map(|(names, source)| {
+                let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+                format!("{} ({source})", names.join("/"))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        report.error(format_args!(
+            "MAC address {mac} is assigned to multiple hosts: {sources}"
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! Vitals of the machine wolo runs on, so that it can be kept an eye on like
+//! the hosts it monitors.
+//!
+//! Everything is read from `/proc` and `/sys`, so vitals are missing when not
+//! running on Linux.
+
+use std::ffi::CString;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use tokio::task;
+
+/// Where thermal zones are listed on Linux.
+const SYS_CLASS_THERMAL: &str = "/sys/class/thermal";
+
+/// Vitals of the machine wolo runs on.
+#[derive(Debug, Default, Clone)]
+pub struct Vitals {
+    /// Load averages over 1, 5 and 15 minutes.
+    pub load: Option<[f64; 3]>,
+    /// Total memory in bytes.
+    pub memory_total: Option<u64>,
+    /// Memory available for starting new applications in bytes.
+    pub memory_available: Option<u64>,
+    /// Disk usage of the history store, if one is configured.
+    pub history: Option<Storage>,
+    /// Temperature of the warmest thermal zone in degrees Celsius.
+    pub temperature: Option<f64>,
+    /// Number of file descriptors open by wolo.
+    pub file_descriptors: Option<usize>,
+    /// The limit of open file descriptors of wolo.
+    pub file_descriptor_limit: Option<u64>,
+    /// Number of the open file descriptors which are sockets.
+    pub sockets: Option<usize>,
+}
+
+/// Disk usage of a file.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    /// Size of the file and its journals in bytes.
+    pub size: u64,
+    /// Space available on the file system it's stored on in bytes.
+    pub available: Option<u64>,
+    /// Size of the file system it's stored on in bytes.
+    pub total: Option<u64>,
+}
+
+/// Read the vitals of this machine, where `history` is the path to the
+/// history store.
+pub async fn read(history: Option<&Path>) -> Vitals {
+    let history = history.map(Path::to_owned);
+    let result = task::spawn_blocking(move || read_blocking(history.as_deref())).await;
+
+    match result {
+        Ok(vitals) => vitals,
+        Err(error) => {
+            tracing::debug!("Reading vitals: {error}");
+            Vitals::default()
+        }
+    }
+}
+
+fn read_blocking(history: Option<&Path>) -> Vitals {
+    let (memory_total, memory_available) = memory();
+    let (file_descriptors, sockets) = descriptors();
+
+    Vitals {
+        load: load(),
+        memory_total,
+        memory_available,
+        history: history.map(storage),
+        temperature: temperature(),
+        file_descriptors,
+        file_descriptor_limit: file_descriptor_limit(),
+        sockets,
+    }
+}
+
+/// Parse load averages from `/proc/loadavg`, like:
+///
+/// ```text
+/// 0.52 0.58 0.59 2/1234 5678
+/// ```
+fn load() -> Option<[f64; 3]> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let mut columns = loadavg.split_whitespace();
+
+    Some([
+        columns.next()?.parse().ok()?,
+        columns.next()?.parse().ok()?,
+        columns.next()?.parse().ok()?,
+    ])
+}
+
+/// Read total and available memory from `/proc/meminfo`, where values are in
+/// kibibytes like `MemTotal:  3884096 kB`.
+fn memory() -> (Option<u64>, Option<u64>) {
+    let Ok(meminfo) = fs::read_to_string("/proc/meminfo") else {
+        return (None, None);
+    };
+
+    let mut total = None;
+    let mut available = None;
+
+    for line in meminfo.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        let value = value.trim().trim_end_matches(" kB").parse::<u64>().ok();
+
+        match key {
+            "MemTotal" => total = value.map(|kb| kb * 1024),
+            "MemAvailable" => available = value.map(|kb| kb * 1024),
+            _ => {}
+        }
+    }
+
+    (total, available)
+}
+
+/// Disk usage of a SQLite database, including its write-ahead log.
+fn storage(path: &Path) -> Storage {
+    let mut size = 0;
+
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+
+        if let Ok(m) = fs::metadata(&path) {
+            size += m.len();
+        }
+    }
+
+    let (available, total) = match path.parent().and_then(file_system) {
+        Some((available, total)) => (Some(available), Some(total)),
+        None => (None, None),
+    };
+
+    Storage {
+        size,
+        available,
+        total,
+    }
+}
+
+/// Get the available and total space of the file system a directory is on.
+fn file_system(dir: &Path) -> Option<(u64, u64)> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: The path is nul-terminated and `statvfs` initializes `stat` if
+    // it succeeds.
+    let stat = unsafe {
+        if libc::statvfs(dir.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        stat.assume_init()
+    };
+
+    let block = stat.f_frsize as u64;
+    Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+/// The temperature of the warmest thermal zone, which is in millidegrees
+/// Celsius in `/sys/class/thermal/thermal_zone*/temp`.
+fn temperature() -> Option<f64> {
+    let mut warmest = None::<f64>;
+
+    for e in fs::read_dir(SYS_CLASS_THERMAL).ok()?.flatten() {
+        if !e.file_name().as_bytes().starts_with(b"thermal_zone") {
+            continue;
+        }
+
+        let Ok(temp) = fs::read_to_string(e.path().join("temp")) else {
+            continue;
+        };
+
+        let Ok(temp) = temp.trim().parse::<i64>() else {
+            continue;
+        };
+
+        let temp = temp as f64 / 1000.0;
+        warmest = Some(warmest.map_or(temp, |w| w.max(temp)));
+    }
+
+    warmest
+}
+
+/// Count the file descriptors of this process, and how many of them are
+/// sockets.
+fn descriptors() -> (Option<usize>, Option<usize>) {
+    let Ok(dir) = fs::read_dir("/proc/self/fd") else {
+        return (None, None);
+    };
+
+    let mut count = 0usize;
+    let mut sockets = 0;
+
+    for e in dir.flatten() {
+        count += 1;
+
+        if fs::read_link(e.path()).is_ok_and(|l| l.as_os_str().as_bytes().starts_with(b"socket:")) {
+            sockets += 1;
+        }
+    }
+
+    // The directory being read is itself a descriptor.
+    (Some(count.saturating_sub(1)), Some(sockets))
+}
+
+/// The soft limit of open file descriptors.
+fn file_descriptor_limit() -> Option<u64> {
+    let mut limit = MaybeUninit::<libc::rlimit>::uninit();
+
+    // SAFETY: `getrlimit` initializes `limit` if it succeeds.
+    let limit = unsafe {
+        if libc::getrlimit(libc::RLIMIT_NOFILE, limit.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        limit.assume_init()
+    };
+
+    (limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur)
+}
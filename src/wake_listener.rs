@@ -0,0 +1,194 @@
+//! Ports which wake a host when something connects to them, and forward the
+//! connection to the host once it's up, so that something like `ssh nas`
+//! powers on a NAS which is asleep.
+
+use core::net::{IpAddr, SocketAddr};
+use core::time::Duration;
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::config::{Config, WakeListenerConfig, format_duration};
+use crate::hosts;
+use crate::ping_loop::{self, HostStatus};
+use crate::wake_on_demand::Waker;
+
+/// Time between attempts to connect to a host which is being woken.
+const RETRY: Duration = Duration::from_secs(1);
+
+/// A bound listener.
+pub struct Listener {
+    bind: SocketAddr,
+    config: Arc<WakeListenerConfig>,
+    listener: TcpListener,
+}
+
+/// Bind the configured listeners, which is done before privileges are
+/// dropped so that privileged ports can be used.
+pub async fn bind(config: &Config) -> Result<Vec<Arc<Listener>>> {
+    let mut listeners = Vec::new();
+
+    for (&bind, config) in &config.wake_listeners {
+        let listener = TcpListener::bind(bind)
+            .await
+            .with_context(|| format!("binding wake listener {bind}"))?;
+
+        tracing::info!(%bind, "Waking host `{}` on connections", config.host);
+
+        listeners.push(Arc::new(Listener {
+            bind,
+            config: Arc::new(config.clone()),
+            listener,
+        }));
+    }
+
+    Ok(listeners)
+}
+
+struct S {
+    waker: Waker,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+}
+
+/// Spawn the task which accepts connections on the given listeners.
+pub async fn spawn(
+    waker: Waker,
+    hosts: hosts::State,
+    ping_state: ping_loop::State,
+    listeners: Vec<Arc<Listener>>,
+) -> Result<()> {
+    if listeners.is_empty() {
+        return std::future::pending().await;
+    }
+
+    let state = Arc::new(S {
+        waker,
+        hosts,
+        ping_state,
+    });
+
+    let mut tasks = JoinSet::new();
+
+    for listener in listeners {
+        tasks.spawn(accept(state.clone(), listener));
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        result?;
+    }
+
+    Ok(())
+}
+
+async fn accept(state: Arc<S>, listener: Arc<Listener>) {
+    loop {
+        let (stream, peer) = match listener.listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                tracing::warn!(bind = %listener.bind, "Accepting connection: {error}");
+                time::sleep(RETRY).await;
+                continue;
+            }
+        };
+
+        tokio::spawn(handle(
+            state.clone(),
+            listener.bind,
+            listener.config.clone(),
+            stream,
+            peer,
+        ));
+    }
+}
+
+/// Wake the host of a listener unless it's up, and forward the connection to
+/// it if configured.
+async fn handle(
+    state: Arc<S>,
+    bind: SocketAddr,
+    config: Arc<WakeListenerConfig>,
+    mut stream: TcpStream,
+    peer: SocketAddr,
+) {
+    let hosts = state.hosts.hosts();
+
+    let Some(host) = hosts.iter().find(|h| {
+        h.names.contains(&config.host) || h.preferred_name.as_ref() == Some(&config.host)
+    }) else {
+        tracing::warn!(%bind, "No host named `{}` to wake", config.host);
+        return;
+    };
+
+    let status = {
+        let pinged = state.ping_state.pinged.lock().await;
+        let statuses = ping_loop::statuses(&hosts, &pinged);
+        statuses.get(&host.id).map(|s| s.status)
+    };
+
+    if status != Some(HostStatus::Up) {
+        state.waker.wake(host.id, peer.ip(), bind.port()).await;
+    }
+
+    let Some(port) = config.forward else {
+        return;
+    };
+
+    let deadline = Instant::now() + config.timeout();
+
+    let Some(mut upstream) = connect(&state.ping_state, host.id, port, deadline).await else {
+        tracing::warn!(
+            %peer,
+            "Host `{}` didn't accept connections to port {port} within {}",
+            config.host,
+            format_duration(config.timeout())
+        );
+        return;
+    };
+
+    tracing::debug!(%peer, "Forwarding connection to host `{}`", config.host);
+
+    if let Err(error) = io::copy_bidirectional(&mut stream, &mut upstream).await {
+        tracing::debug!(%peer, "Forwarding connection: {error}");
+    }
+}
+
+/// Connect to a port of a host once it replies to pings, trying each address
+/// which it replies on until the deadline.
+async fn connect(
+    ping_state: &ping_loop::State,
+    host: Uuid,
+    port: u16,
+    deadline: Instant,
+) -> Option<TcpStream> {
+    while Instant::now() < deadline {
+        let addresses = {
+            let pinged = ping_state.pinged.lock().await;
+
+            pinged
+                .get(&host)
+                .into_iter()
+                .flat_map(|p| &p.results)
+                .filter(|r| r.outcome.is_echo_reply())
+                .map(|r| r.target)
+                .collect::<Vec<IpAddr>>()
+        };
+
+        for address in addresses {
+            if let Ok(Ok(stream)) = time::timeout(RETRY, TcpStream::connect((address, port))).await
+            {
+                return Some(stream);
+            }
+        }
+
+        time::sleep_until(deadline.min(Instant::now() + RETRY)).await;
+    }
+
+    None
+}
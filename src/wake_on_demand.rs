@@ -0,0 +1,446 @@
+//! Waking hosts on demand, like NAS boxes which sleep when idle.
+//!
+//! While a host with `on_demand` ports is down, wolo answers ARP and neighbor
+//! solicitations for its addresses with the MAC address of its own interface,
+//! so that connections meant for the host arrive here instead. Once something
+//! tries to connect to one of the ports the host is woken, and wolo stops
+//! answering for it so that the host can take its addresses back.
+
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use lib::PacketSocket;
+use macaddr::MacAddr6;
+use tokio::time;
+use uuid::Uuid;
+
+use crate::audit;
+use crate::config::Config;
+use crate::hosts;
+use crate::interfaces;
+use crate::ping_loop::{self, HostStatus};
+use crate::scripting;
+use crate::wake_on_lan;
+use crate::wake_queue::{self, Wake};
+
+/// How often the addresses which are answered for are updated.
+const REFRESH: Duration = Duration::from_secs(2);
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+const PROTOCOL_TCP: u8 = 6;
+const PROTOCOL_ICMPV6: u8 = 58;
+
+const NEIGHBOR_SOLICITATION: u8 = 135;
+const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+
+/// The smallest Ethernet frame without its checksum, which shorter frames
+/// are padded to.
+const MIN_FRAME: usize = 60;
+
+/// An address which is answered for on behalf of a sleeping host.
+struct Proxied {
+    host: Uuid,
+    /// The MAC addresses of the host itself.
+    host_macs: Vec<MacAddr6>,
+    /// Ports which wake the host.
+    ports: Vec<u16>,
+    /// The interface the host is reachable through.
+    ifindex: u32,
+    /// The MAC address of that interface, which is given out for the host.
+    mac: MacAddr6,
+}
+
+/// What to do about a received frame.
+enum Action {
+    /// Send a frame in response.
+    Reply(Vec<u8>),
+    /// Wake a host because of a connection from `source` to `port`.
+    Wake {
+        host: Uuid,
+        source: IpAddr,
+        port: u16,
+    },
+}
+
+/// Open the socket frames are answered through, which is only done if any
+/// host is woken on demand when starting and `simulate` isn't set.
+pub async fn socket(simulate: bool, config: &Config) -> Result<Option<Arc<PacketSocket>>> {
+    if !config.wakes_on_demand() {
+        return Ok(None);
+    }
+
+    if simulate {
+        tracing::info!("Not waking hosts on demand while simulating");
+        return Ok(None);
+    }
+
+    let socket = PacketSocket::open().context("opening packet socket")?;
+    Ok(Some(Arc::new(socket)))
+}
+
+/// Wakes hosts because something tried to connect to them, which is shared
+/// with [`crate::wake_listener`].
+#[derive(Clone)]
+pub struct Waker {
+    hosts: hosts::State,
+    wake_queue: wake_queue::State,
+    scripting: scripting::State,
+    audit: audit::Log,
+}
+
+impl Waker {
+    pub fn new(
+        hosts: hosts::State,
+        wake_queue: wake_queue::State,
+        scripting: scripting::State,
+        audit: audit::Log,
+    ) -> Self {
+        Self {
+            hosts,
+            wake_queue,
+            scripting,
+            audit,
+        }
+    }
+
+    /// Get the addresses to answer for, which are the last known addresses of
+    /// hosts which are down, and which aren't already being woken.
+    async fn proxied(&self, ping_state: &ping_loop::State) -> HashMap<IpAddr, Proxied> {
+        let config = self.hosts.config().await;
+        let hosts = self.hosts.hosts();
+        let wakes = self.wake_queue.wakes().await;
+
+        let interfaces = interfaces::read()
+            .await
+            .into_iter()
+            .filter(|i| i.is_up() && interfaces::is_selected(&config.interfaces, &i.name))
+            .filter_map(|i| Some((i.index?, i.mac?, i.addresses)))
+            .collect::<Vec<_>>();
+
+        let pinged = ping_state.pinged.lock().await;
+        let statuses = ping_loop::statuses(&hosts, &pinged);
+        let mut out = HashMap::new();
+
+        for host in hosts.iter() {
+            if host.wake.on_demand.is_empty()
+                || host.wake.enabled == Some(false)
+                || host.macs.is_empty()
+                || host.ignore
+            {
+                continue;
+            }
+
+            if statuses.get(&host.id).map(|s| s.status) != Some(HostStatus::Down) {
+                continue;
+            }
+
+            if wakes.iter().any(|w| w.host == Some(host.id)) {
+                continue;
+            }
+
+            let Some(p) = pinged.get(&host.id) else {
+                continue;
+            };
+
+            for r in &p.results {
+                let Some((ifindex, mac, _)) = interfaces
+                    .iter()
+                    .find(|(_, _, addresses)| addresses.iter().any(|a| a.contains(r.target)))
+                else {
+                    continue;
+                };
+
+                out.insert(
+                    r.target,
+                    Proxied {
+                        host: host.id,
+                        host_macs: host.macs.iter().copied().collect(),
+                        ports: host.wake.on_demand.clone(),
+                        ifindex: *ifindex,
+                        mac: *mac,
+                    },
+                );
+            }
+        }
+
+        out
+    }
+
+    /// Wake a host because `source` tried to connect to `port`, unless it's
+    /// already being woken.
+    pub async fn wake(&self, id: Uuid, source: IpAddr, port: u16) {
+        if self
+            .wake_queue
+            .wakes()
+            .await
+            .iter()
+            .any(|w| w.host == Some(id))
+        {
+            return;
+        }
+
+        let config = self.hosts.config().await;
+        let hosts = self.hosts.hosts();
+
+        let Some(host) = hosts.iter().find(|h| h.id == id) else {
+            return;
+        };
+
+        let broadcasts = interfaces::broadcasts(&config.interfaces).await;
+        let targets = wake_on_lan::targets(&host.wake, &broadcasts);
+
+        let name = format!("host `{}`", host.names().next().unwrap_or_default());
+        let description = wake_on_lan::describe(&targets);
+
+        let macs = host.macs.iter().copied().collect();
+        let wake = Wake::new(Some(host.id), name, macs, host.wake.clone(), targets);
+
+        if let Err(reason) = self.scripting.before_wake(&wake).await {
+            tracing::warn!("Not waking {}: {reason}", wake.name);
+            return;
+        }
+
+        self.audit
+            .record(
+                source,
+                format!(
+                    "Woke {} on demand through {description} after a connection to port {port}",
+                    wake.name
+                ),
+            )
+            .await;
+
+        tracing::info!(%source, port, "Waking {} on demand", wake.name);
+        self.wake_queue.push(wake).await;
+    }
+}
+
+/// Spawn the task which answers for sleeping hosts and wakes them.
+pub async fn spawn(
+    waker: Waker,
+    ping_state: ping_loop::State,
+    socket: Option<Arc<PacketSocket>>,
+) -> Result<()> {
+    // Nothing is woken on demand unless it was configured when starting.
+    let Some(socket) = socket else {
+        return std::future::pending().await;
+    };
+
+    let mut refresh = time::interval(REFRESH);
+    let mut proxied = HashMap::new();
+    // Only headers are inspected, so larger frames are truncated.
+    let mut buf = vec![0; 256];
+
+    loop {
+        let received = tokio::select! {
+            _ = refresh.tick() => {
+                let next = waker.proxied(&ping_state).await;
+                log_changes(&waker, &proxied, &next);
+                proxied = next;
+                continue;
+            }
+            received = socket.recv(&mut buf) => received,
+        };
+
+        let received = match received {
+            Ok(received) => received,
+            Err(error) => {
+                tracing::warn!("Receiving frame: {error}");
+                continue;
+            }
+        };
+
+        if received.outgoing {
+            continue;
+        }
+
+        let frame = &buf[..received.len];
+
+        match handle(frame, received.ifindex, &proxied) {
+            Some(Action::Reply(reply)) => {
+                if let Err(error) = socket.send(received.ifindex, &reply).await {
+                    tracing::warn!("Sending frame: {error}");
+                }
+            }
+            Some(Action::Wake { host, source, port }) => {
+                proxied.retain(|_, p| p.host != host);
+                waker.wake(host, source, port).await;
+            }
+            None => {}
+        }
+    }
+}
+
+/// Log addresses which are answered for, or no longer answered for.
+fn log_changes(waker: &Waker, before: &HashMap<IpAddr, Proxied>, after: &HashMap<IpAddr, Proxied>) {
+    let hosts = waker.hosts.hosts();
+
+    let name = |id: Uuid| {
+        hosts
+            .iter()
+            .find(|h| h.id == id)
+            .and_then(|h| h.names().next())
+            .unwrap_or_default()
+    };
+
+    for (address, p) in after {
+        if !before.contains_key(address) {
+            tracing::info!(%address, "Answering on behalf of sleeping host `{}`", name(p.host));
+        }
+    }
+
+    for (address, p) in before {
+        if !after.contains_key(address) {
+            tracing::info!(%address, "No longer answering on behalf of host `{}`", name(p.host));
+        }
+    }
+}
+
+/// Decide what to do about a frame received on the given interface.
+fn handle(frame: &[u8], ifindex: u32, proxied: &HashMap<IpAddr, Proxied>) -> Option<Action> {
+    let ethertype = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+    let payload = &frame[14..];
+
+    let lookup = |address: IpAddr| proxied.get(&address).filter(|p| p.ifindex == ifindex);
+
+    match ethertype {
+        ETHERTYPE_ARP => arp(frame, payload, lookup),
+        ETHERTYPE_IPV4 => {
+            let ip = payload.get(..20)?;
+
+            // Only the first fragment has a TCP header.
+            if ip[0] >> 4 != 4 || ip[9] != PROTOCOL_TCP || (ip[6] & 0x1f) | ip[7] != 0 {
+                return None;
+            }
+
+            let source = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+            let dest = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+            let tcp = payload.get(usize::from(ip[0] & 0x0f) * 4..)?;
+            syn(tcp, source.into(), lookup(dest.into())?)
+        }
+        ETHERTYPE_IPV6 => {
+            let ip = payload.get(..40)?;
+
+            if ip[0] >> 4 != 6 {
+                return None;
+            }
+
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(&ip[8..24]).ok()?);
+            let dest = Ipv6Addr::from(<[u8; 16]>::try_from(&ip[24..40]).ok()?);
+            let body = &payload[40..];
+
+            match ip[6] {
+                PROTOCOL_ICMPV6 => neighbor_solicitation(frame, source, body, lookup),
+                PROTOCOL_TCP => syn(body, source.into(), lookup(dest.into())?),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Answer an ARP request for an address which is answered for.
+fn arp<'a>(
+    frame: &[u8],
+    arp: &[u8],
+    lookup: impl Fn(IpAddr) -> Option<&'a Proxied>,
+) -> Option<Action> {
+    let arp = arp.get(..28)?;
+
+    // Requests for IPv4 addresses over Ethernet.
+    if arp[..8] != [0, 1, 8, 0, 6, 4, 0, 1] {
+        return None;
+    }
+
+    let sender_mac = &arp[8..14];
+    let sender = Ipv4Addr::new(arp[14], arp[15], arp[16], arp[17]);
+    let target = Ipv4Addr::new(arp[24], arp[25], arp[26], arp[27]);
+    let p = lookup(target.into())?;
+
+    // Probes for whether an address is in use, like the one a host makes when
+    // it wakes up, must not be answered.
+    if sender.is_unspecified() || p.host_macs.iter().any(|m| m.as_bytes() == sender_mac) {
+        return None;
+    }
+
+    let mut reply = Vec::with_capacity(MIN_FRAME);
+    reply.extend_from_slice(&frame[6..12]);
+    reply.extend_from_slice(p.mac.as_bytes());
+    reply.extend_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+    reply.extend_from_slice(&[0, 1, 8, 0, 6, 4, 0, 2]);
+    reply.extend_from_slice(p.mac.as_bytes());
+    reply.extend_from_slice(&target.octets());
+    reply.extend_from_slice(sender_mac);
+    reply.extend_from_slice(&sender.octets());
+    reply.resize(MIN_FRAME, 0);
+    Some(Action::Reply(reply))
+}
+
+/// Answer a neighbor solicitation for an address which is answered for.
+fn neighbor_solicitation<'a>(
+    frame: &[u8],
+    source: Ipv6Addr,
+    icmp: &[u8],
+    lookup: impl Fn(IpAddr) -> Option<&'a Proxied>,
+) -> Option<Action> {
+    let icmp = icmp.get(..24)?;
+
+    if icmp[0] != NEIGHBOR_SOLICITATION {
+        return None;
+    }
+
+    let target = Ipv6Addr::from(<[u8; 16]>::try_from(&icmp[8..24]).ok()?);
+    let p = lookup(target.into())?;
+
+    // Solicitations from an unspecified address detect duplicate addresses,
+    // and must not be answered.
+    if source.is_unspecified() || p.host_macs.iter().any(|m| m.as_bytes() == &frame[6..12]) {
+        return None;
+    }
+
+    // A solicited advertisement which overrides cached entries, with the
+    // target link-layer address option.
+    let mut advertisement = vec![NEIGHBOR_ADVERTISEMENT, 0, 0, 0, 0x60, 0, 0, 0];
+    advertisement.extend_from_slice(&target.octets());
+    advertisement.extend_from_slice(&[2, 1]);
+    advertisement.extend_from_slice(p.mac.as_bytes());
+
+    let checksum = lib::icmp::v6::checksum(&target, &source, &advertisement);
+    advertisement[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut reply = Vec::with_capacity(14 + 40 + advertisement.len());
+    reply.extend_from_slice(&frame[6..12]);
+    reply.extend_from_slice(p.mac.as_bytes());
+    reply.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+    reply.extend_from_slice(&[0x60, 0, 0, 0]);
+    reply.extend_from_slice(&(advertisement.len() as u16).to_be_bytes());
+    reply.extend_from_slice(&[PROTOCOL_ICMPV6, 255]);
+    reply.extend_from_slice(&target.octets());
+    reply.extend_from_slice(&source.octets());
+    reply.extend_from_slice(&advertisement);
+    Some(Action::Reply(reply))
+}
+
+/// Wake a host if a TCP segment is an attempt to connect to one of its ports.
+fn syn(tcp: &[u8], source: IpAddr, p: &Proxied) -> Option<Action> {
+    let tcp = tcp.get(..14)?;
+    let port = u16::from_be_bytes([tcp[2], tcp[3]]);
+
+    // SYN without ACK.
+    if tcp[13] & 0x12 != 0x02 || !p.ports.contains(&port) {
+        return None;
+    }
+
+    Some(Action::Wake {
+        host: p.host,
+        source,
+        port,
+    })
+}
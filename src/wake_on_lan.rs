@@ -1,16 +1,83 @@
-use core::mem::size_of;
-
 use core::net::SocketAddrV4;
+use core::time::Duration;
 use std::io;
 use std::net::Ipv4Addr;
 
 use macaddr::MacAddr6;
 use tokio::net::UdpSocket;
 
+use crate::config::WakeConfig;
+use crate::hosts::Host;
+
 const FROM: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
-const TO: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::BROADCAST, 9);
+const TARGET: Ipv4Addr = Ipv4Addr::BROADCAST;
+const PORT: u16 = 9;
+const ATTEMPTS: u32 = 3;
+const RETRY: Duration = Duration::from_secs(60);
 const MAGIC_BYTES_HEADER: [u8; 6] = [0xFF; 6];
 
+/// Get the address magic packets should be sent to for the given
+/// configuration.
+pub fn target(config: &WakeConfig) -> SocketAddrV4 {
+    SocketAddrV4::new(config.target.unwrap_or(TARGET), config.port.unwrap_or(PORT))
+}
+
+/// Get the number of times magic packets are sent until the host responds.
+pub fn attempts(config: &WakeConfig) -> u32 {
+    config.attempts.unwrap_or(ATTEMPTS).max(1)
+}
+
+/// Get the time between attempts to wake a host.
+pub fn retry(config: &WakeConfig) -> Duration {
+    config.retry.unwrap_or(RETRY)
+}
+
+/// Get the addresses magic packets should be sent to for the given
+/// configuration.
+///
+/// Unless a target has been configured, packets are sent to the broadcast
+/// addresses of the selected interfaces in `broadcasts`, or to the limited
+/// broadcast address if there are none.
+pub fn targets(config: &WakeConfig, broadcasts: &[Ipv4Addr]) -> Vec<SocketAddrV4> {
+    if config.target.is_some() || broadcasts.is_empty() {
+        return vec![target(config)];
+    }
+
+    let port = config.port.unwrap_or(PORT);
+
+    broadcasts
+        .iter()
+        .map(|&b| SocketAddrV4::new(b, port))
+        .collect()
+}
+
+/// Describe a collection of targets, like `192.168.1.255:9, 10.0.0.255:9`.
+pub fn describe(targets: &[SocketAddrV4]) -> String {
+    let targets = targets.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    targets.join(", ")
+}
+
+/// Get the wake settings to use for a literal MAC address, which are those
+/// of a known host with the same MAC address if there is one.
+pub fn config_for(hosts: &[Host], address: MacAddr6) -> WakeConfig {
+    hosts
+        .iter()
+        .find(|h| h.macs.contains(&address))
+        .map(|h| h.wake.clone())
+        .unwrap_or_default()
+}
+
+/// Construct the magic packet to send for the given address and
+/// configuration.
+pub fn packet(address: MacAddr6, config: &WakeConfig) -> MagicPacket {
+    let packet = MagicPacket::new(address);
+
+    match config.password {
+        Some(password) => packet.with_password(password),
+        None => packet,
+    }
+}
+
 /// Configure a broadcast socket used for sending Wake-on-LAN magic packets.
 pub struct BroadcastSocket {
     socket: UdpSocket,
@@ -25,43 +92,44 @@ impl BroadcastSocket {
         Ok(Self { socket })
     }
 
-    /// Sends the given magic packet via this socket to the broadcast address.
-    pub async fn send(&self, packet: &MagicPacket) -> io::Result<()> {
-        self.socket.send_to(packet.as_bytes(), TO).await?;
+    /// Sends the given magic packet via this socket to the given address.
+    pub async fn send(&self, packet: &MagicPacket, to: SocketAddrV4) -> io::Result<()> {
+        self.socket.send_to(packet.as_bytes(), to).await?;
         Ok(())
     }
 }
 
-#[repr(C)]
+/// Length of a magic packet without a SecureOn password.
+const LEN: usize = 6 + 16 * 6;
+
+/// A Wake-on-LAN magic packet, consisting of 6 bytes of `0xFF` followed by 16
+/// repetitions of the target MAC address and an optional SecureOn password.
 pub struct MagicPacket {
-    // 6 bytes of 0xFF.
-    header: [u8; 6],
-    // 16 repetitions of the target MAC address.
-    dest: [[u8; 6]; 16],
+    bytes: [u8; LEN + 6],
+    len: usize,
 }
 
-const _: () = const {
-    assert!(size_of::<MagicPacket>() == 102);
-};
-
 impl MagicPacket {
     /// Creates a new `MagicPacket` intended for `mac_address` (but doesn't send it yet).
     pub fn new(address: MacAddr6) -> Self {
-        let mut dest = [[0u8; 6]; 16];
+        let mut bytes = [0u8; LEN + 6];
+        bytes[..6].copy_from_slice(&MAGIC_BYTES_HEADER);
 
-        for d in dest.iter_mut() {
-            *d = address.into_array();
+        for chunk in bytes[6..LEN].chunks_exact_mut(6) {
+            chunk.copy_from_slice(address.as_bytes());
         }
 
-        Self {
-            header: MAGIC_BYTES_HEADER,
-            dest,
-        }
+        Self { bytes, len: LEN }
+    }
+
+    /// Append a SecureOn password to the packet.
+    pub fn with_password(mut self, password: [u8; 6]) -> Self {
+        self.bytes[LEN..].copy_from_slice(&password);
+        self.len = LEN + 6;
+        self
     }
 
     fn as_bytes(&self) -> &[u8] {
-        // SAFETY: `MagicPacket` is `repr(C)` and consists entirely of `u8`
-        // arrays.
-        unsafe { &*(self as *const Self as *const [u8; size_of::<Self>()]) }
+        &self.bytes[..self.len]
     }
 }
@@ -0,0 +1,293 @@
+//! Queue of hosts being woken, which resends magic packets until the host
+//! responds to a ping or it runs out of attempts.
+//!
+//! The outcome of each wake of a configured host is stored in history, so
+//! that how reliably each host wakes up can be shown.
+
+use core::net::SocketAddrV4;
+use core::time::Duration;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use macaddr::MacAddr6;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{self, Instant};
+use uuid::Uuid;
+
+use crate::config::WakeConfig;
+use crate::events::{self, Event};
+use crate::history::{self, History, WakeStats};
+use crate::ping_loop::{self, HostStatus};
+use crate::wake_on_lan::{self, BroadcastSocket};
+
+/// How long to wait for new wakes when the queue is empty.
+const IDLE: Duration = Duration::from_secs(3600);
+
+/// The message of the event recorded when magic packets are first sent for a
+/// wake.
+pub const SENT: &str = "Sent magic packets";
+
+/// A wake in the queue.
+#[derive(Debug, Clone)]
+pub struct Wake {
+    /// The host being woken, if the wake is for a configured host.
+    pub host: Option<Uuid>,
+    /// Description of what is being woken, like "host `example`".
+    pub name: String,
+    /// MAC addresses to send magic packets for.
+    pub macs: Vec<MacAddr6>,
+    /// Wake settings, which determine the packets sent.
+    pub config: WakeConfig,
+    /// Where magic packets are sent.
+    pub targets: Vec<SocketAddrV4>,
+    /// The number of attempts which have been made.
+    pub attempt: u32,
+    /// The number of attempts to make before giving up.
+    pub attempts: u32,
+    /// When the wake was queued.
+    pub queued: Instant,
+    /// When the next attempt is made.
+    pub next: Instant,
+}
+
+impl Wake {
+    /// Construct a new wake which is attempted right away.
+    ///
+    /// Wakes which aren't for a configured host are only attempted once,
+    /// since there's no host to ping.
+    pub fn new(
+        host: Option<Uuid>,
+        name: String,
+        macs: Vec<MacAddr6>,
+        config: WakeConfig,
+        targets: Vec<SocketAddrV4>,
+    ) -> Self {
+        let now = Instant::now();
+
+        let attempts = match host {
+            Some(..) => wake_on_lan::attempts(&config),
+            None => 1,
+        };
+
+        Self {
+            host,
+            name,
+            macs,
+            config,
+            targets,
+            attempt: 0,
+            attempts,
+            queued: now,
+            next: now,
+        }
+    }
+}
+
+/// State shared between the wake queue task and the web server.
+#[derive(Clone)]
+pub struct State {
+    /// Wakes which are in progress.
+    wakes: Arc<Mutex<Vec<Wake>>>,
+    /// Notified when a wake is queued.
+    notify: Arc<Notify>,
+    /// Statistics over the outcomes of wakes by host.
+    stats: Arc<Mutex<HashMap<Uuid, WakeStats>>>,
+    history: History,
+}
+
+impl State {
+    /// Construct a new empty queue, with statistics over wakes loaded from
+    /// history.
+    pub async fn new(history: History) -> Result<Self> {
+        let stats = history.wake_stats().await?;
+
+        Ok(Self {
+            wakes: Arc::new(Mutex::new(Vec::new())),
+            notify: Arc::new(Notify::new()),
+            stats: Arc::new(Mutex::new(stats.into_iter().collect())),
+            history,
+        })
+    }
+
+    /// Queue a wake, replacing any wake in progress for the same host.
+    pub async fn push(&self, wake: Wake) {
+        let mut wakes = self.wakes.lock().await;
+
+        if wake.host.is_some() {
+            wakes.retain(|w| w.host != wake.host);
+        }
+
+        wakes.push(wake);
+        self.notify.notify_one();
+    }
+
+    /// Get the wakes which are in progress, in the order they were queued.
+    pub async fn wakes(&self) -> Vec<Wake> {
+        self.wakes.lock().await.clone()
+    }
+
+    /// Get statistics over the outcomes of wakes by host.
+    pub async fn stats(&self) -> HashMap<Uuid, WakeStats> {
+        self.stats.lock().await.clone()
+    }
+
+    /// Record the outcome of a wake.
+    async fn record(&self, wake: history::Wake) {
+        self.stats
+            .lock()
+            .await
+            .entry(wake.host)
+            .or_default()
+            .add(&wake);
+
+        if let Err(error) = self.history.record_wake(wake).await {
+            tracing::error!("Recording wake of {}: {error:#}", wake.host);
+        }
+    }
+}
+
+/// Bind the socket magic packets are sent through, unless `simulate` is set in
+/// which case magic packets are logged instead.
+pub async fn socket(simulate: bool) -> Result<Option<Arc<BroadcastSocket>>> {
+    if simulate {
+        return Ok(None);
+    }
+
+    let socket = BroadcastSocket::bind()
+        .await
+        .context("binding broadcast socket")?;
+
+    Ok(Some(Arc::new(socket)))
+}
+
+/// Spawn the wake queue task.
+pub async fn spawn(
+    state: State,
+    ping_state: ping_loop::State,
+    events: events::Log,
+    socket: Option<Arc<BroadcastSocket>>,
+) -> Result<()> {
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        let mut done = Vec::new();
+        let mut outcomes = Vec::new();
+
+        let next = {
+            let mut wakes = state.wakes.lock().await;
+            let pinged = ping_state.pinged.lock().await;
+
+            wakes.retain(|w| {
+                let up = w.host.and_then(|id| pinged.get(&id)).is_some_and(|p| {
+                    p.status() == HostStatus::Up && p.last_seen.is_some_and(|s| s > w.queued)
+                });
+
+                if up {
+                    tracing::info!(attempts = w.attempt, "Woke up {}", w.name);
+                    done.push(Event::WakeSucceeded {
+                        host: w.host,
+                        attempt: w.attempt,
+                    });
+                    outcomes.extend(outcome(w, now, true));
+                    return false;
+                }
+
+                if w.attempt == w.attempts && now >= w.next {
+                    tracing::warn!(attempts = w.attempt, "Gave up waking {}", w.name);
+                    done.push(Event::WakeGaveUp {
+                        host: w.host,
+                        attempt: w.attempt,
+                    });
+                    outcomes.extend(outcome(w, now, false));
+                    return false;
+                }
+
+                true
+            });
+
+            for w in wakes.iter_mut() {
+                if w.attempt < w.attempts && now >= w.next {
+                    w.attempt += 1;
+                    w.next = now + wake_on_lan::retry(&w.config);
+                    due.push(w.clone());
+                }
+            }
+
+            // Wakes which aren't for a configured host can't be followed up
+            // on, so they are done once sent.
+            wakes.retain(|w| w.host.is_some() || w.attempt < w.attempts);
+
+            wakes.iter().map(|w| w.next).min()
+        };
+
+        for event in done {
+            events.publish(event).await;
+        }
+
+        for wake in outcomes {
+            state.record(wake).await;
+        }
+
+        for w in due {
+            send(socket.as_deref(), &w).await;
+
+            if w.attempt == 1 {
+                let event = Event::WakeSent {
+                    host: w.host,
+                    name: w.name.clone(),
+                };
+
+                events.publish(event).await;
+            }
+        }
+
+        let deadline = next.unwrap_or_else(|| Instant::now() + IDLE);
+
+        tokio::select! {
+            _ = time::sleep_until(deadline) => {}
+            _ = state.notify.notified() => {}
+        }
+    }
+}
+
+/// The outcome of a finished wake, if it's for a configured host.
+fn outcome(w: &Wake, now: Instant, woke: bool) -> Option<history::Wake> {
+    let elapsed = now.saturating_duration_since(w.queued);
+
+    Some(history::Wake {
+        host: w.host?,
+        queued: SystemTime::now()
+            .checked_sub(elapsed)
+            .unwrap_or(SystemTime::UNIX_EPOCH),
+        attempts: w.attempt,
+        woke: woke.then_some(elapsed),
+    })
+}
+
+/// Send the magic packets of a single attempt.
+async fn send(socket: Option<&BroadcastSocket>, w: &Wake) {
+    for mac in &w.macs {
+        let packet = wake_on_lan::packet(*mac, &w.config);
+
+        for &to in &w.targets {
+            let Some(socket) = socket else {
+                tracing::info!("Simulated sending magic packet for {mac} to {to}");
+                continue;
+            };
+
+            if let Err(error) = socket.send(&packet, to).await {
+                tracing::warn!("Sending magic packet for {mac} to {to}: {error}");
+            }
+        }
+    }
+
+    tracing::debug!(
+        attempt = w.attempt,
+        attempts = w.attempts,
+        "Sent magic packets to {}",
+        w.name
+    );
+}
@@ -0,0 +1,437 @@
+//! Minimal reader and writer for ZIP archives, like `.cbz` files.
+//!
+//! Only entries which are stored without compression can be read. Images are
+//! already compressed so this is how comic book archives are commonly built,
+//! and others can be converted with `zip -0`. Archives are written the same
+//! way.
+
+use core::ops::Range;
+
+use std::io::SeekFrom;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+/// Size of the end of central directory record without its comment.
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+/// Size of a central directory header without variable length fields.
+const CENTRAL_DIRECTORY_HEADER_SIZE: usize = 46;
+/// Size of a local file header without variable length fields.
+const LOCAL_FILE_HEADER_SIZE: usize = 30;
+
+/// Compression method for entries which are stored as-is.
+const STORED: u16 = 0;
+
+/// The date 1980-01-01 in MS-DOS format.
+const DOS_EPOCH: u16 = (1 << 5) | 1;
+
+/// An entry in an archive.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The name of the entry.
+    pub name: String,
+    /// The size of the entry when extracted.
+    pub size: u64,
+    method: u16,
+    header_offset: u64,
+}
+
+/// An open archive.
+pub struct Archive {
+    file: File,
+    len: u64,
+    entries: Vec<Entry>,
+}
+
+impl Entry {
+    /// Test if the entry is stored without compression, which is required for
+    /// it to be read.
+    pub fn is_stored(&self) -> bool {
+        self.method == STORED
+    }
+}
+
+impl Archive {
+    /// Open an archive and read its central directory.
+    pub async fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).await?;
+        let len = file.metadata().await?.len();
+
+        if len < END_OF_CENTRAL_DIRECTORY_SIZE as u64 {
+            bail!("not a zip archive");
+        }
+
+        // The end of central directory record is followed by a comment of at
+        // most u16::MAX bytes, so it has to be in this tail.
+        let tail_len = len.min((END_OF_CENTRAL_DIRECTORY_SIZE + u16::MAX as usize) as u64);
+        let mut tail = vec![0; tail_len as usize];
+        file.seek(SeekFrom::End(-(tail_len as i64))).await?;
+        file.read_exact(&mut tail).await?;
+
+        let (count, range) = central_directory(&tail, len)?;
+
+        let mut directory = vec![0; (range.end - range.start) as usize];
+        file.seek(SeekFrom::Start(range.start)).await?;
+        file.read_exact(&mut directory)
+            .await
+            .context("reading central directory")?;
+
+        let entries = entries(&directory, count)?;
+        Ok(Self { file, len, entries })
+    }
+
+    /// Get all entries in the archive, in the order they are stored.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Find an entry by name.
+    ///
+    /// Archives often keep their files in a single top-level directory, so
+    /// this falls back to an entry which is nested in one.
+    pub fn find(&self, name: &str) -> Option<&Entry> {
+        if let Some(e) = self.entries.iter().find(|e| e.name == name) {
+            return Some(e);
+        }
+
+        self.entries
+            .iter()
+            .find(|e| e.name.split_once('/').is_some_and(|(_, rest)| rest == name))
+    }
+
+    /// Read the given range of bytes out of an entry.
+    pub async fn read(&mut self, entry: &Entry, range: Range<u64>) -> Result<Vec<u8>> {
+        if !entry.is_stored() {
+            bail!(
+                "{}: compressed entries are not supported, the archive has to be stored without compression",
+                entry.name
+            );
+        }
+
+        if range.start > range.end || range.end > entry.size {
+            bail!("{}: range out of bounds", entry.name);
+        }
+
+        let mut header = [0; LOCAL_FILE_HEADER_SIZE];
+        self.file.seek(SeekFrom::Start(entry.header_offset)).await?;
+        self.file.read_exact(&mut header).await?;
+
+        let range = data_range(entry, &header, range, self.len)?;
+
+        let mut bytes = vec![0; (range.end - range.start) as usize];
+        self.file.seek(SeekFrom::Start(range.start)).await?;
+        self.file.read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+}
+
+/// Writer for archives where every entry is stored without compression.
+#[derive(Default)]
+pub struct Writer {
+    out: Vec<u8>,
+    directory: Vec<u8>,
+    count: u16,
+}
+
+impl Writer {
+    /// Construct a new empty archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entry to the archive.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let (Ok(size), Ok(name_len), Ok(offset)) = (
+            u32::try_from(data.len()),
+            u16::try_from(name.len()),
+            u32::try_from(self.out.len()),
+        ) else {
+            bail!("{name}: entry is too large, zip64 archives are not supported");
+        };
+
+        let Some(count) = self.count.checked_add(1) else {
+            bail!("too many entries, zip64 archives are not supported");
+        };
+
+        let crc = crc32(data);
+
+        // Version needed to extract, flags, method and modification time,
+        // with the date set to 1980-01-01 which is the earliest one possible.
+        let common = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&10u16.to_le_bytes());
+            out.extend_from_slice(&[0; 6]);
+            out.extend_from_slice(&DOS_EPOCH.to_le_bytes());
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&name_len.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        self.out.extend_from_slice(&LOCAL_FILE_HEADER.to_le_bytes());
+        common(&mut self.out);
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(data);
+
+        self.directory
+            .extend_from_slice(&CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        // Version made by.
+        self.directory.extend_from_slice(&10u16.to_le_bytes());
+        common(&mut self.directory);
+        // Comment length, disk number and attributes.
+        self.directory.extend_from_slice(&[0; 10]);
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name.as_bytes());
+
+        self.count = count;
+        Ok(())
+    }
+
+    /// Finish the archive and return its bytes.
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let (Ok(size), Ok(offset)) = (
+            u32::try_from(self.directory.len()),
+            u32::try_from(self.out.len()),
+        ) else {
+            bail!("archive is too large, zip64 archives are not supported");
+        };
+
+        self.out.extend_from_slice(&self.directory);
+        self.out
+            .extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        // Disk numbers.
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&size.to_le_bytes());
+        self.out.extend_from_slice(&offset.to_le_bytes());
+        // Comment length.
+        self.out.extend_from_slice(&0u16.to_le_bytes());
+        Ok(self.out)
+    }
+}
+
+/// Find the end of central directory record in the tail of an archive which is
+/// `len` bytes long, and return the number of entries and the range of the
+/// central directory.
+fn central_directory(tail: &[u8], len: u64) -> Result<(usize, Range<u64>)> {
+    let mut candidates = (0..=tail.len().saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE)).rev();
+
+    let Some(at) = candidates.find(|&n| {
+        tail.len() >= n + END_OF_CENTRAL_DIRECTORY_SIZE
+            && u32_at(tail, n).is_ok_and(|n| n == END_OF_CENTRAL_DIRECTORY)
+    }) else {
+        bail!("not a zip archive");
+    };
+
+    let eocd = &tail[at..];
+    let count = u16_at(eocd, 10)?;
+    let size = u32_at(eocd, 12)?;
+    let offset = u32_at(eocd, 16)?;
+
+    if count == u16::MAX || offset == u32::MAX {
+        bail!("zip64 archives are not supported");
+    }
+
+    let start = u64::from(offset);
+    let end = start + u64::from(size);
+
+    if end > len {
+        bail!("central directory is out of bounds");
+    }
+
+    Ok((count.into(), start..end))
+}
+
+/// Parse `count` entries out of the central directory.
+fn entries(directory: &[u8], count: usize) -> Result<Vec<Entry>> {
+    let mut entries = Vec::with_capacity(count);
+    let mut rest = directory;
+
+    for _ in 0..count {
+        if rest.len() < CENTRAL_DIRECTORY_HEADER_SIZE
+            || u32_at(rest, 0)? != CENTRAL_DIRECTORY_HEADER
+        {
+            bail!("corrupt central directory");
+        }
+
+        let method = u16_at(rest, 10)?;
+        let size = u32_at(rest, 24)?;
+        let name_len = u16_at(rest, 28)? as usize;
+        let extra_len = u16_at(rest, 30)? as usize;
+        let comment_len = u16_at(rest, 32)? as usize;
+        let header_offset = u32_at(rest, 42)?;
+
+        let end = CENTRAL_DIRECTORY_HEADER_SIZE + name_len + extra_len + comment_len;
+
+        let Some(name) =
+            rest.get(CENTRAL_DIRECTORY_HEADER_SIZE..CENTRAL_DIRECTORY_HEADER_SIZE + name_len)
+        else {
+            bail!("corrupt central directory");
+        };
+
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            size: size.into(),
+            method,
+            header_offset: header_offset.into(),
+        });
+
+        rest = rest.get(end..).unwrap_or_default();
+    }
+
+    Ok(entries)
+}
+
+/// Find where the given range of an entry is stored in an archive which is
+/// `len` bytes long, using the local file header of the entry.
+fn data_range(entry: &Entry, header: &[u8], range: Range<u64>, len: u64) -> Result<Range<u64>> {
+    if u32_at(header, 0)? != LOCAL_FILE_HEADER {
+        bail!("{}: corrupt local file header", entry.name);
+    }
+
+    let name_len = u64::from(u16_at(header, 26)?);
+    let extra_len = u64::from(u16_at(header, 28)?);
+    let data = entry.header_offset + LOCAL_FILE_HEADER_SIZE as u64 + name_len + extra_len;
+
+    // The size of the entry comes from the central directory, so it's checked
+    // against the archive before anything is allocated for it.
+    if data + range.end > len {
+        bail!("{}: data out of bounds", entry.name);
+    }
+
+    Ok(data + range.start..data + range.end)
+}
+
+/// Calculate the CRC-32 checksum of the given bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &b in bytes {
+        crc ^= u32::from(b);
+
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16> {
+    match bytes.get(at..at + 2) {
+        Some(&[a, b]) => Ok(u16::from_le_bytes([a, b])),
+        _ => bail!("unexpected end of archive"),
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32> {
+    match bytes.get(at..at + 4) {
+        Some(&[a, b, c, d]) => Ok(u32::from_le_bytes([a, b, c, d])),
+        _ => bail!("unexpected end of archive"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer.add("001.jpg", b"hello").unwrap();
+        writer.add("002.jpg", b"world!").unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytes = archive();
+        let (count, range) = central_directory(&bytes, bytes.len() as u64).unwrap();
+        let directory = &bytes[range.start as usize..range.end as usize];
+        let entries = entries(directory, count).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "001.jpg");
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(entries[1].name, "002.jpg");
+        assert_eq!(entries[1].size, 6);
+        assert!(entries.iter().all(Entry::is_stored));
+    }
+
+    #[test]
+    fn empty() {
+        assert!(central_directory(&[], 0).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let signature = END_OF_CENTRAL_DIRECTORY.to_le_bytes();
+
+        for n in 0..signature.len() {
+            assert!(central_directory(&signature[..n], n as u64).is_err());
+        }
+
+        // A signature which is not followed by a complete record.
+        let mut bytes = signature.to_vec();
+        bytes.extend_from_slice(&[0; 8]);
+        assert!(central_directory(&bytes, bytes.len() as u64).is_err());
+
+        let bytes = archive();
+        let (count, range) = central_directory(&bytes, bytes.len() as u64).unwrap();
+        let directory = &bytes[range.start as usize..range.end as usize];
+
+        for n in 0..directory.len() {
+            assert!(entries(&directory[..n], count).is_err());
+        }
+    }
+
+    #[test]
+    fn lying_size() {
+        let mut bytes = archive();
+        let at = bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+        bytes[at + 12..at + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(central_directory(&bytes, bytes.len() as u64).is_err());
+
+        let mut bytes = archive();
+        let at = bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+        bytes[at + 16..at + 20].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+        assert!(central_directory(&bytes, bytes.len() as u64).is_err());
+
+        // More entries than the central directory holds.
+        let mut bytes = archive();
+        let at = bytes.len() - END_OF_CENTRAL_DIRECTORY_SIZE;
+        bytes[at + 10..at + 12].copy_from_slice(&3u16.to_le_bytes());
+        let (count, range) = central_directory(&bytes, bytes.len() as u64).unwrap();
+        let directory = &bytes[range.start as usize..range.end as usize];
+        assert!(entries(directory, count).is_err());
+    }
+
+    #[test]
+    fn lying_entry_size() {
+        let bytes = archive();
+        let len = bytes.len() as u64;
+        let (count, range) = central_directory(&bytes, len).unwrap();
+        let directory = &bytes[range.start as usize..range.end as usize];
+        let mut entries = entries(directory, count).unwrap();
+
+        let entry = &mut entries[1];
+        let header = &bytes[entry.header_offset as usize..];
+
+        let data = data_range(entry, header, 0..entry.size, len).unwrap();
+        assert_eq!(&bytes[data.start as usize..data.end as usize], b"world!");
+
+        // The entry claims to be larger than the archive.
+        entry.size = u32::MAX.into();
+        assert!(data_range(entry, header, 0..entry.size, len).is_err());
+
+        // The archive has been truncated in the middle of the entry.
+        entry.size = 6;
+        assert!(data_range(entry, header, 0..entry.size, data.end - 1).is_err());
+    }
+}